@@ -35,7 +35,7 @@ use lib::git::{CategorizedReferenceName, MaybeZeroOid, NonZeroOid, ReferenceName
 use lib::core::effects::Effects;
 pub use lib::core::rewrite::rewrite_hooks::{
     hook_drop_commit_if_empty, hook_post_rewrite, hook_register_extra_post_rewrite_hook,
-    hook_skip_upstream_applied_commit,
+    hook_replace_commit, hook_skip_upstream_applied_commit,
 };
 
 /// Handle Git's `post-checkout` hook.
@@ -542,6 +542,22 @@ pub fn command_main(ctx: CommandContext, args: HookArgs) -> eyre::Result<ExitCod
             hook_register_extra_post_rewrite_hook()?;
         }
 
+        HookSubcommand::ReplaceCommit {
+            original_commit_oid,
+            replacement_commit_oid,
+            parents,
+        } => {
+            let original_commit_oid: NonZeroOid = original_commit_oid.parse()?;
+            let replacement_commit_oid: NonZeroOid = replacement_commit_oid.parse()?;
+            hook_replace_commit(
+                &effects,
+                &git_run_info,
+                original_commit_oid,
+                replacement_commit_oid,
+                &parents,
+            )?;
+        }
+
         HookSubcommand::SkipUpstreamAppliedCommit { commit_oid } => {
             let commit_oid: NonZeroOid = commit_oid.parse()?;
             hook_skip_upstream_applied_commit(&effects, commit_oid)?;
@@ -106,13 +106,54 @@ fn install_tracing(effects: Effects) -> eyre::Result<impl Drop> {
         }
     };
 
+    // If built with the `opentelemetry` feature, export spans via OTLP when an
+    // endpoint is configured, e.g. `OTEL_EXPORTER_OTLP_ENDPOINT=http://localhost:4318`.
+    #[cfg(feature = "opentelemetry")]
+    let (opentelemetry_layer, opentelemetry_guard) =
+        match std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            Some(_) => {
+                // Export each span as it finishes, rather than in batches, so
+                // that no async runtime is required.
+                let tracer = opentelemetry_otlp::new_pipeline()
+                    .tracing()
+                    .with_exporter(opentelemetry_otlp::new_exporter().http())
+                    .with_trace_config(opentelemetry::sdk::trace::config().with_resource(
+                        opentelemetry::sdk::Resource::new(vec![opentelemetry::KeyValue::new(
+                            "service.name",
+                            "git-branchless",
+                        )]),
+                    ))
+                    .install_simple()?;
+                (
+                    Some(tracing_opentelemetry::layer().with_tracer(tracer)),
+                    Some(OpenTelemetryGuard),
+                )
+            }
+            None => (None, None),
+        };
+    #[cfg(not(feature = "opentelemetry"))]
+    let (opentelemetry_layer, opentelemetry_guard) =
+        (None::<tracing_subscriber::layer::Identity>, None::<()>);
+
     tracing_subscriber::registry()
         .with(ErrorLayer::default())
         .with(fmt_layer.with_filter(env_filter))
         .with(profile_layer)
+        .with(opentelemetry_layer)
         .try_init()?;
 
-    Ok(flush_guard)
+    Ok(Box::new((flush_guard, opentelemetry_guard)))
+}
+
+/// Flushes any spans which haven't been exported yet when dropped.
+#[cfg(feature = "opentelemetry")]
+struct OpenTelemetryGuard;
+
+#[cfg(feature = "opentelemetry")]
+impl Drop for OpenTelemetryGuard {
+    fn drop(&mut self) {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
 }
 
 #[instrument]
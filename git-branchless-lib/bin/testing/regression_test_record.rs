@@ -9,7 +9,7 @@ use branchless::core::formatting::Glyphs;
 use branchless::git::{hydrate_tree, process_diff_for_record, FileMode, Repo};
 use bstr::ByteSlice;
 use eyre::Context;
-use scm_record::{File, Section};
+use scm_record::{File, Section, SelectedContents};
 
 fn main() -> eyre::Result<()> {
     let path_to_repo = std::env::var("PATH_TO_REPO")
@@ -68,6 +68,13 @@ fn main() -> eyre::Result<()> {
                         } => {
                             unimplemented!("selecting Section::FileMode");
                         }
+                        Section::Binary {
+                            is_toggled: _,
+                            old_description: _,
+                            new_description: _,
+                        } => {
+                            unimplemented!("selecting Section::Binary");
+                        }
                     }
                 }
             }
@@ -81,6 +88,12 @@ fn main() -> eyre::Result<()> {
                         .get_file_mode()
                         .expect("File mode should have been set");
                     let (selected, _unselected) = file.get_selected_contents();
+                    let selected = match selected {
+                        SelectedContents::Present { contents } => contents,
+                        SelectedContents::Unchanged | SelectedContents::Binary { .. } => {
+                            unimplemented!("selecting binary file contents")
+                        }
+                    };
                     let blob_oid = repo.create_blob_from_contents(selected.as_bytes())?;
                     let file_mode = i32::try_from(new_file_mode).unwrap();
                     let file_mode = FileMode::from(file_mode);
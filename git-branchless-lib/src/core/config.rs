@@ -2,7 +2,7 @@
 
 use std::ffi::OsString;
 use std::fmt::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use cursive::theme::{BaseColor, Effect, Style};
 use cursive::utils::markup::StyledString;
@@ -10,7 +10,10 @@ use eyre::Context;
 use tracing::{instrument, warn};
 
 use crate::core::formatting::StyledStringBuilder;
-use crate::git::{ConfigRead, GitRunInfo, GitRunOpts, Repo};
+use crate::git::{
+    get_default_test_cache_dir, ConfigRead, GitRunInfo, GitRunOpts, Repo, TestExitCodes,
+    TEST_ABORT_EXIT_CODE, TEST_INDETERMINATE_EXIT_CODE,
+};
 
 use super::effects::Effects;
 use super::eventlog::EventTransactionId;
@@ -209,6 +212,109 @@ pub fn get_next_interactive(repo: &Repo) -> eyre::Result<bool> {
         .get_or("branchless.next.interactive", false)
 }
 
+/// If `true`, when advancing with `--newest` or `--oldest`, choose between
+/// ambiguous commits by their author dates, rather than their committer dates.
+#[instrument]
+pub fn get_next_sort_by_author_date(repo: &Repo) -> eyre::Result<bool> {
+    repo.get_readonly_config()?
+        .get_or("branchless.next.sortByAuthorDate", false)
+}
+
+/// The exit code which a test command returns to indicate that the commit
+/// couldn't be tested and should be skipped.
+#[instrument]
+pub fn get_test_skip_exit_code(repo: &Repo) -> eyre::Result<i32> {
+    repo.get_readonly_config()?
+        .get_or("branchless.test.skipExitCode", TEST_INDETERMINATE_EXIT_CODE)
+}
+
+/// The exit code which a test command returns to indicate that testing should
+/// be aborted entirely.
+#[instrument]
+pub fn get_test_abort_exit_code(repo: &Repo) -> eyre::Result<i32> {
+    repo.get_readonly_config()?
+        .get_or("branchless.test.abortExitCode", TEST_ABORT_EXIT_CODE)
+}
+
+/// Additional exit codes which indicate that a test command succeeded, given as
+/// a comma-separated list. These are checked before the built-in handling of
+/// exit codes, so they take precedence over `branchless.test.skipExitCode` and
+/// `branchless.test.abortExitCode`.
+#[instrument]
+pub fn get_test_success_exit_codes(repo: &Repo) -> eyre::Result<Vec<i32>> {
+    get_exit_code_list(repo, "branchless.test.successExitCodes")
+}
+
+/// Additional exit codes which indicate that the commit couldn't be tested and
+/// should be skipped, given as a comma-separated list. These are checked before
+/// the built-in handling of exit codes, so they take precedence over the
+/// success exit code (`0`) and `branchless.test.abortExitCode`.
+#[instrument]
+pub fn get_test_skip_exit_codes(repo: &Repo) -> eyre::Result<Vec<i32>> {
+    get_exit_code_list(repo, "branchless.test.skipExitCodes")
+}
+
+/// The configured exit codes which determine whether the result of a test
+/// command counts as passed, failed, or skipped.
+#[instrument]
+pub fn get_test_exit_codes(repo: &Repo) -> eyre::Result<TestExitCodes> {
+    Ok(TestExitCodes {
+        skip_exit_code: get_test_skip_exit_code(repo)?,
+        success_exit_codes: get_test_success_exit_codes(repo)?,
+        skip_exit_codes: get_test_skip_exit_codes(repo)?,
+    })
+}
+
+fn get_exit_code_list(repo: &Repo, config_key: &str) -> eyre::Result<Vec<i32>> {
+    let value: Option<String> = repo.get_readonly_config()?.get(config_key)?;
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(|exit_code| exit_code.trim())
+        .filter(|exit_code| !exit_code.is_empty())
+        .map(|exit_code| {
+            exit_code.parse::<i32>().wrap_err_with(|| {
+                format!("Invalid exit code in config value {config_key}: {exit_code}")
+            })
+        })
+        .collect()
+}
+
+/// The directory in which the results of test commands are cached. Each
+/// commit's results are stored in a subdirectory named after its tree OID.
+#[instrument]
+pub fn get_test_cache_dir(repo: &Repo) -> eyre::Result<PathBuf> {
+    let cache_dir: Option<String> = repo
+        .get_readonly_config()?
+        .get("branchless.test.cacheDir")?;
+    let cache_dir = match cache_dir {
+        Some(cache_dir) => PathBuf::from(cache_dir),
+        None => get_default_test_cache_dir(repo),
+    };
+    Ok(cache_dir)
+}
+
+/// The directory under which commits are checked out when using the `tmpfs`
+/// test execution strategy. This should ideally be on an in-memory filesystem.
+#[instrument]
+pub fn get_test_tmpfs_dir(repo: &Repo) -> eyre::Result<PathBuf> {
+    let tmpfs_dir: Option<String> = repo
+        .get_readonly_config()?
+        .get("branchless.test.tmpfsDir")?;
+    let tmpfs_dir = match tmpfs_dir {
+        Some(tmpfs_dir) => PathBuf::from(tmpfs_dir),
+        None => {
+            let shm_dir = Path::new("/dev/shm");
+            if shm_dir.is_dir() {
+                shm_dir.to_owned()
+            } else {
+                std::env::temp_dir()
+            }
+        }
+    };
+    Ok(tmpfs_dir)
+}
+
 /// If `true`, show branches pointing to each commit in the smartlog.
 #[instrument]
 pub fn get_commit_descriptors_branches(repo: &Repo) -> eyre::Result<bool> {
@@ -230,6 +336,14 @@ pub fn get_commit_descriptors_relative_time(repo: &Repo) -> eyre::Result<bool> {
         .get_or("branchless.commitDescriptors.relativeTime", true)
 }
 
+/// If `true`, show the cached status of the latest test command for each commit
+/// in the smartlog.
+#[instrument]
+pub fn get_commit_descriptors_test_status(repo: &Repo) -> eyre::Result<bool> {
+    repo.get_readonly_config()?
+        .get_or("branchless.commitDescriptors.testStatus", false)
+}
+
 /// Config key for `get_restack_warn_abandoned`.
 pub const RESTACK_WARN_ABANDONED_CONFIG_KEY: &str = "branchless.restack.warnAbandoned";
 
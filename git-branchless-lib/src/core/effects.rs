@@ -44,6 +44,7 @@ pub enum OperationType {
     RunGitCommand(Arc<String>),
     RunTestOnCommit(Arc<String>),
     RunTests(Arc<String>),
+    RunTestSetup(Arc<String>),
     SortCommits,
     SyncCommits,
     UpdateCommitGraph,
@@ -82,6 +83,9 @@ impl ToString for OperationType {
                 return format!("Running Git command: {}", &command)
             }
             OperationType::RunTests(command) => return format!("Running command: {command}"),
+            OperationType::RunTestSetup(command) => {
+                return format!("Running setup command: {command}")
+            }
             OperationType::RunTestOnCommit(commit) => return format!("Waiting to run on {commit}"),
             OperationType::SortCommits => "Sorting commits",
             OperationType::SyncCommits => "Syncing commit stacks",
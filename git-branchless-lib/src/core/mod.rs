@@ -7,6 +7,7 @@ pub mod effects;
 pub mod eventlog;
 pub mod formatting;
 pub mod gc;
+pub mod navigation_history;
 pub mod node_descriptors;
 pub mod repo_ext;
 pub mod rewrite;
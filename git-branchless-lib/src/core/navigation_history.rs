@@ -0,0 +1,265 @@
+//! Record the positions which the user navigated between.
+//!
+//! The navigation commands (such as `git next`, `git prev`, and `git switch`)
+//! record each checkout here, so that `git branchless back` and `git
+//! branchless forward` can move through the previous positions, like the
+//! history of a web browser.
+
+use eyre::Context;
+use tracing::instrument;
+
+use crate::git::{NonZeroOid, ReferenceName};
+
+/// The maximum number of positions to keep in the navigation history. Older
+/// positions are discarded.
+pub const MAX_NAVIGATION_HISTORY_LENGTH: i64 = 100;
+
+/// A position which the user navigated to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NavigationPosition {
+    /// The commit which was checked out.
+    pub commit_oid: NonZeroOid,
+
+    /// The reference which `HEAD` pointed to, if it wasn't detached.
+    pub reference_name: Option<ReferenceName>,
+}
+
+/// An entry in the navigation history.
+#[derive(Clone, Debug)]
+pub struct NavigationHistoryEntry {
+    /// The index of this entry in the history. Later entries have higher
+    /// indexes.
+    pub index: i64,
+
+    /// The position which the user navigated to.
+    pub position: NavigationPosition,
+}
+
+/// Which way to move through the navigation history.
+#[derive(Clone, Copy, Debug)]
+pub enum NavigationDirection {
+    /// Move to the position before the current one.
+    Back,
+
+    /// Move to the position after the current one.
+    Forward,
+}
+
+/// Stores the navigation history on disk.
+pub struct NavigationHistoryDb<'conn> {
+    conn: &'conn rusqlite::Connection,
+}
+
+impl std::fmt::Debug for NavigationHistoryDb<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<NavigationHistoryDb>")
+    }
+}
+
+#[instrument]
+fn init_tables(conn: &rusqlite::Connection) -> eyre::Result<()> {
+    conn.execute(
+        "
+CREATE TABLE IF NOT EXISTS navigation_history (
+    entry_index INTEGER NOT NULL PRIMARY KEY,
+    commit_oid TEXT NOT NULL,
+    reference_name TEXT
+)
+",
+        rusqlite::params![],
+    )
+    .wrap_err("Creating `navigation_history` table")?;
+
+    conn.execute(
+        "
+CREATE TABLE IF NOT EXISTS navigation_history_cursor (
+    -- There is only ever a single row in this table.
+    id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+    entry_index INTEGER NOT NULL
+)
+",
+        rusqlite::params![],
+    )
+    .wrap_err("Creating `navigation_history_cursor` table")?;
+
+    Ok(())
+}
+
+impl<'conn> NavigationHistoryDb<'conn> {
+    /// Constructor.
+    #[instrument]
+    pub fn new(conn: &'conn rusqlite::Connection) -> eyre::Result<Self> {
+        init_tables(conn)?;
+        Ok(NavigationHistoryDb { conn })
+    }
+
+    fn get_cursor(&self) -> eyre::Result<Option<i64>> {
+        let mut stmt = self.conn.prepare(
+            "
+SELECT entry_index
+FROM navigation_history_cursor
+WHERE id = 0
+",
+        )?;
+        let mut rows = stmt.query(rusqlite::params![])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get("entry_index")?)),
+            None => Ok(None),
+        }
+    }
+
+    fn get_entry(&self, index: i64) -> eyre::Result<Option<NavigationHistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "
+SELECT commit_oid, reference_name
+FROM navigation_history
+WHERE entry_index = :entry_index
+",
+        )?;
+        let mut rows = stmt.query(rusqlite::named_params! {
+            ":entry_index": index,
+        })?;
+        let row = match rows.next()? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let commit_oid: String = row.get("commit_oid")?;
+        let reference_name: Option<String> = row.get("reference_name")?;
+        Ok(Some(NavigationHistoryEntry {
+            index,
+            position: NavigationPosition {
+                commit_oid: commit_oid.parse()?,
+                reference_name: reference_name.map(ReferenceName::from),
+            },
+        }))
+    }
+
+    fn insert_entry(&self, index: i64, position: &NavigationPosition) -> eyre::Result<()> {
+        let NavigationPosition {
+            commit_oid,
+            reference_name,
+        } = position;
+        self.conn
+            .execute(
+                "
+INSERT INTO navigation_history VALUES (
+    :entry_index,
+    :commit_oid,
+    :reference_name
+)
+",
+                rusqlite::named_params! {
+                    ":entry_index": index,
+                    ":commit_oid": commit_oid.to_string(),
+                    ":reference_name": reference_name.as_ref().map(|name| name.as_str()),
+                },
+            )
+            .wrap_err("Inserting navigation history entry")?;
+        Ok(())
+    }
+
+    fn set_cursor_index(&self, index: i64) -> eyre::Result<()> {
+        self.conn
+            .execute(
+                "
+INSERT OR REPLACE INTO navigation_history_cursor VALUES (0, :entry_index)
+",
+                rusqlite::named_params! {
+                    ":entry_index": index,
+                },
+            )
+            .wrap_err("Updating navigation history cursor")?;
+        Ok(())
+    }
+
+    /// Record that the user navigated from one position to another. The
+    /// positions after the current one (which the user went back from, and
+    /// could go forward to again) are discarded, as in a web browser.
+    #[instrument]
+    pub fn record_navigation(
+        &self,
+        from: &NavigationPosition,
+        to: &NavigationPosition,
+    ) -> eyre::Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let cursor = self.get_cursor()?;
+        self.conn
+            .execute(
+                "
+DELETE FROM navigation_history
+WHERE entry_index > :entry_index
+",
+                rusqlite::named_params! {
+                    ":entry_index": cursor.unwrap_or_default(),
+                },
+            )
+            .wrap_err("Discarding later navigation history entries")?;
+
+        // The user may have moved since the last recorded navigation (such as
+        // with a plain `git checkout`), in which case the position they
+        // navigated from needs to be recorded too.
+        let mut index = cursor.unwrap_or_default();
+        let current_position = match cursor {
+            Some(cursor) => self.get_entry(cursor)?.map(|entry| entry.position),
+            None => None,
+        };
+        if current_position.as_ref() != Some(from) {
+            index += 1;
+            self.insert_entry(index, from)?;
+        }
+        if from != to {
+            index += 1;
+            self.insert_entry(index, to)?;
+        }
+        self.set_cursor_index(index)?;
+
+        self.conn
+            .execute(
+                "
+DELETE FROM navigation_history
+WHERE entry_index <= :entry_index
+",
+                rusqlite::named_params! {
+                    ":entry_index": index - MAX_NAVIGATION_HISTORY_LENGTH,
+                },
+            )
+            .wrap_err("Discarding old navigation history entries")?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Get the entry to move to from `current_position` in the given
+    /// direction, if any. This is usually the entry adjacent to the current
+    /// one. But if the user has since moved without it being recorded (such as
+    /// with a plain `git checkout`), then going back returns to the current
+    /// entry, since that's the last recorded position which they were at. There
+    /// is no entry after the newest one, so going forward from it returns
+    /// `None`. The current position is not changed; call `set_cursor` once the
+    /// entry has been navigated to.
+    #[instrument]
+    pub fn get_adjacent_entry(
+        &self,
+        direction: NavigationDirection,
+        current_position: &NavigationPosition,
+    ) -> eyre::Result<Option<NavigationHistoryEntry>> {
+        let cursor = match self.get_cursor()? {
+            Some(cursor) => cursor,
+            None => return Ok(None),
+        };
+        match direction {
+            NavigationDirection::Back => match self.get_entry(cursor)? {
+                Some(entry) if &entry.position != current_position => Ok(Some(entry)),
+                Some(_) | None => self.get_entry(cursor - 1),
+            },
+            NavigationDirection::Forward => self.get_entry(cursor + 1),
+        }
+    }
+
+    /// Set the current position in the navigation history to the given entry.
+    #[instrument]
+    pub fn set_cursor(&self, entry: &NavigationHistoryEntry) -> eyre::Result<()> {
+        self.set_cursor_index(entry.index)
+    }
+}
@@ -5,6 +5,7 @@
 
 use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
@@ -17,10 +18,13 @@ use tracing::instrument;
 
 use crate::core::config::{
     get_commit_descriptors_branches, get_commit_descriptors_differential_revision,
-    get_commit_descriptors_relative_time,
+    get_commit_descriptors_relative_time, get_commit_descriptors_test_status, get_test_cache_dir,
+    get_test_exit_codes,
 };
+use crate::core::effects::icons;
 use crate::git::{
-    CategorizedReferenceName, Commit, NonZeroOid, ReferenceName, Repo, ResolvedReferenceInfo,
+    read_cached_test_result, read_latest_test_command, CategorizedReferenceName, Commit,
+    NonZeroOid, ReferenceName, Repo, ResolvedReferenceInfo, TestExitCodes, TestStatusCategory,
 };
 
 use super::eventlog::{Event, EventCursor, EventReplayer};
@@ -517,6 +521,72 @@ impl NodeDescriptor for RelativeTimeDescriptor {
     }
 }
 
+/// Display the cached result of running the latest test command (as run by
+/// `git test run`) on the given commit. Commits which haven't been tested with
+/// that command aren't annotated. Results are classified as passed, failed, or
+/// skipped with the configured exit codes, as in `git test show`.
+///
+/// Only results of running the command in the default environment are shown.
+/// Runs which are cached separately (those using `--cwd`, `--clean-env`,
+/// `--env-file`, or `--strategy docker`) aren't looked up.
+#[derive(Debug)]
+pub struct TestStatusDescriptor {
+    cache_dir: PathBuf,
+    command: Option<String>,
+    exit_codes: TestExitCodes,
+}
+
+impl TestStatusDescriptor {
+    /// Constructor.
+    pub fn new(repo: &Repo) -> eyre::Result<Self> {
+        let command = if get_commit_descriptors_test_status(repo)? {
+            read_latest_test_command(repo)
+        } else {
+            None
+        };
+        Ok(TestStatusDescriptor {
+            cache_dir: get_test_cache_dir(repo)?,
+            command,
+            exit_codes: get_test_exit_codes(repo)?,
+        })
+    }
+}
+
+impl NodeDescriptor for TestStatusDescriptor {
+    #[instrument]
+    fn describe_node(
+        &mut self,
+        _glyphs: &Glyphs,
+        object: &NodeObject,
+    ) -> eyre::Result<Option<StyledString>> {
+        let command = match &self.command {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+        let commit = match object {
+            NodeObject::Commit { commit } => commit,
+            NodeObject::GarbageCollected { oid: _ } => return Ok(None),
+        };
+
+        let test_result = match read_cached_test_result(&self.cache_dir, commit, command) {
+            Some(test_result) => test_result,
+            None => return Ok(None),
+        };
+        let result = match test_result.get_category(&self.exit_codes) {
+            TestStatusCategory::Passed => {
+                StyledString::styled(icons::CHECKMARK, BaseColor::Green.light())
+            }
+            TestStatusCategory::Skipped => {
+                StyledString::styled(icons::EXCLAMATION, BaseColor::Yellow.light())
+            }
+            TestStatusCategory::Failed => {
+                StyledString::styled(icons::CROSS, BaseColor::Red.light())
+            }
+        };
+        Ok(Some(result))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::ops::{Add, Sub};
@@ -150,24 +150,20 @@ impl ToString for RebaseCommand {
                     .join(" ")
             ),
             RebaseCommand::Replace {
-                commit_oid: _,
+                commit_oid,
                 replacement_commit_oid,
                 parents,
             } => {
                 let parents = parents
                     .iter()
                     .map(|parent| match parent {
-                        OidOrLabel::Oid(parent_oid) => format!("-p {parent_oid}"),
+                        OidOrLabel::Oid(parent_oid) => parent_oid.to_string(),
                         OidOrLabel::Label(parent_label) => {
-                            format!("-p refs/rewritten/{parent_label}")
+                            format!("refs/rewritten/{parent_label}")
                         }
                     })
                     .join(" ");
-                // FIXME: Untested, this probably doesn't work. Currently,
-                // on-disk rebases for merges with replacement commits are not
-                // generated by any command, so this code path is never
-                // triggered.
-                format!("exec git show -s --format=%B {replacement_commit_oid} | git commit-tree {parents} {replacement_commit_oid}^{{tree}}")
+                format!("exec git branchless hook-replace-commit {commit_oid} {replacement_commit_oid} {parents}")
             }
             RebaseCommand::Break => "break".to_string(),
             RebaseCommand::RegisterExtraPostRewriteHook => {
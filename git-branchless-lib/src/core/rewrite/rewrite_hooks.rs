@@ -471,6 +471,81 @@ pub fn hook_drop_commit_if_empty(
     Ok(())
 }
 
+/// For rebases, replace a commit with the contents of another commit without
+/// attempting to apply it as a patch, and record it in the `rewritten-list`.
+pub fn hook_replace_commit(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    original_commit_oid: NonZeroOid,
+    replacement_commit_oid: NonZeroOid,
+    parents: &[String],
+) -> eyre::Result<()> {
+    let repo = Repo::from_current_dir()?;
+    let replacement_commit = repo.find_commit_or_fail(replacement_commit_oid)?;
+    let replacement_message = replacement_commit.get_message_raw();
+    let replacement_message = replacement_message.to_str().with_context(|| {
+        eyre::eyre!(
+            "Could not decode commit message for replacement commit: {:?}",
+            replacement_commit
+        )
+    })?;
+
+    let parents = {
+        let mut result = Vec::new();
+        for parent in parents {
+            match repo.revparse_single_commit(parent)? {
+                Some(parent_commit) => result.push(parent_commit),
+                None => eyre::bail!("Could not resolve parent commit: {parent}"),
+            }
+        }
+        result
+    };
+    let rebased_commit_oid = repo
+        .create_commit(
+            None,
+            &replacement_commit.get_author(),
+            &replacement_commit.get_committer(),
+            replacement_message,
+            &replacement_commit.get_tree()?,
+            parents.iter().collect(),
+        )
+        .wrap_err("Applying replacement commit")?;
+
+    // Update the index and working copy as well as `HEAD`, so that the
+    // subsequent rebase commands apply on top of the replacement commit.
+    git_run_info.run_silent(
+        &repo,
+        None,
+        &["reset", "--hard", &rebased_commit_oid.to_string()],
+        Default::default(),
+    )?;
+
+    writeln!(
+        effects.get_output_stream(),
+        "Replaced commit: {}",
+        effects.get_glyphs().render(
+            repo.friendly_describe_commit_from_oid(effects.get_glyphs(), rebased_commit_oid)?
+        )?
+    )?;
+
+    if let Some(orig_head_reference) = repo.find_reference(&"ORIG_HEAD".into())? {
+        let resolved_orig_head = repo.resolve_reference(&orig_head_reference)?;
+        if resolved_orig_head.oid == Some(original_commit_oid) {
+            save_updated_head_oid(&repo, rebased_commit_oid)?;
+        }
+    }
+    add_rewritten_list_entries(
+        &repo.get_tempfile_dir(),
+        &repo.get_rebase_state_dir_path().join("rewritten-list"),
+        &[(
+            original_commit_oid,
+            MaybeZeroOid::NonZero(rebased_commit_oid),
+        )],
+    )?;
+
+    Ok(())
+}
+
 /// For rebases, if a commit is known to have been applied upstream, skip it
 /// without attempting to apply it.
 pub fn hook_skip_upstream_applied_commit(
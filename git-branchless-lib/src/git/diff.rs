@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+use cursive::theme::{BaseColor, Effect};
+use cursive::utils::markup::StyledString;
 use eyre::Context;
 use itertools::Itertools;
 use scm_record::{ChangeType, File, Section, SectionChangedLine};
@@ -14,6 +16,29 @@ pub struct Diff<'repo> {
     pub(super) inner: git2::Diff<'repo>,
 }
 
+impl Diff<'_> {
+    /// Render this diff as a patch, in the style of `git diff`, with
+    /// additions and removals highlighted.
+    pub fn friendly_describe(&self) -> eyre::Result<StyledString> {
+        let mut result = StyledString::new();
+        self.inner
+            .print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+                let content = String::from_utf8_lossy(line.content());
+                match line.origin() {
+                    '+' => result.append_styled(format!("+{content}"), BaseColor::Green.dark()),
+                    '-' => result.append_styled(format!("-{content}"), BaseColor::Red.dark()),
+                    ' ' => result.append_plain(format!(" {content}")),
+                    'F' => result.append_styled(content, Effect::Bold),
+                    'H' => result.append_styled(content, BaseColor::Cyan.dark()),
+                    _ => result.append_plain(content),
+                }
+                true
+            })
+            .wrap_err("Printing diff")?;
+        Ok(result)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct GitHunk {
     old_start: usize,
@@ -111,9 +136,27 @@ pub fn process_diff_for_record(repo: &Repo, diff: &Diff) -> eyre::Result<Vec<Fil
             continue;
         }
 
+        let describe_binary_blob = |oid| -> eyre::Result<Option<Cow<'static, str>>> {
+            let oid = MaybeZeroOid::from(oid);
+            match oid {
+                MaybeZeroOid::Zero => Ok(None),
+                MaybeZeroOid::NonZero(oid) => {
+                    let description = match repo.find_blob(oid)? {
+                        Some(blob) => format!("{oid} ({} bytes)", blob.get_content().len()),
+                        None => oid.to_string(),
+                    };
+                    Ok(Some(Cow::Owned(description)))
+                }
+            }
+        };
+
         let hunks = match content {
             DeltaFileContent::Binary => {
-                result.push(File::binary(Cow::Owned(path)));
+                result.push(File::binary(
+                    Cow::Owned(path),
+                    describe_binary_blob(old_oid)?,
+                    describe_binary_blob(new_oid)?,
+                ));
                 continue;
             }
             DeltaFileContent::Hunks(mut hunks) => {
@@ -152,14 +195,22 @@ pub fn process_diff_for_record(repo: &Repo, diff: &Diff) -> eyre::Result<Vec<Fil
         let before_lines = match get_lines_from_blob(old_oid)? {
             Some(lines) => lines,
             None => {
-                result.push(File::binary(Cow::Owned(path)));
+                result.push(File::binary(
+                    Cow::Owned(path),
+                    describe_binary_blob(old_oid)?,
+                    describe_binary_blob(new_oid)?,
+                ));
                 continue;
             }
         };
         let after_lines = match get_lines_from_blob(new_oid)? {
             Some(lines) => lines,
             None => {
-                result.push(File::binary(Cow::Owned(path)));
+                result.push(File::binary(
+                    Cow::Owned(path),
+                    describe_binary_blob(old_oid)?,
+                    describe_binary_blob(new_oid)?,
+                ));
                 continue;
             }
         };
@@ -30,8 +30,10 @@ pub use run::{GitRunInfo, GitRunOpts, GitRunResult};
 pub use snapshot::{WorkingCopyChangesType, WorkingCopySnapshot};
 pub use status::{FileMode, FileStatus, StatusEntry};
 pub use test::{
-    get_latest_test_command_path, get_test_locks_dir, get_test_tree_dir, get_test_worktrees_dir,
-    make_test_command_slug, SerializedNonZeroOid, SerializedTestResult, TEST_ABORT_EXIT_CODE,
-    TEST_INDETERMINATE_EXIT_CODE, TEST_SUCCESS_EXIT_CODE,
+    get_default_test_cache_dir, get_latest_test_command_path, get_test_locks_dir,
+    get_test_setup_output_path, get_test_tree_dir, get_test_worktrees_dir, make_test_command_slug,
+    read_cached_test_result, read_latest_test_command, SerializedNonZeroOid, SerializedTestResult,
+    TestExitCodes, TestStatusCategory, TEST_ABORT_EXIT_CODE, TEST_INDETERMINATE_EXIT_CODE,
+    TEST_RESULT_SCHEMA_VERSION, TEST_SUCCESS_EXIT_CODE, TEST_TIMEOUT_EXIT_CODE,
 };
 pub use tree::{dehydrate_tree, get_changed_paths_between_trees, hydrate_tree, Tree};
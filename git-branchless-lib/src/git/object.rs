@@ -131,6 +131,13 @@ impl<'repo> Commit<'repo> {
         }
     }
 
+    /// Determine whether this commit has a signature (such as a GPG or SSH
+    /// signature) attached.
+    #[instrument]
+    pub fn is_signed(&self) -> bool {
+        self.inner.header_field_bytes("gpgsig").is_ok()
+    }
+
     /// Get the OID of the `Tree` object associated with this commit.
     #[instrument]
     pub fn get_tree_oid(&self) -> MaybeZeroOid {
@@ -1,6 +1,7 @@
 use std::convert::{TryFrom, TryInto};
 use std::ffi::{OsStr, OsString};
 use std::fmt::Display;
+use std::path::Path;
 use std::str::FromStr;
 
 use eyre::Context;
@@ -19,6 +20,22 @@ impl NonZeroOid {
     pub fn as_bytes(&self) -> &[u8] {
         self.inner.as_bytes()
     }
+
+    /// Compute the OID that `contents` would have as a blob (as with `git
+    /// hash-object`), without writing it to a repository.
+    pub fn hash_blob(contents: &[u8]) -> eyre::Result<Self> {
+        let oid = git2::Oid::hash_object(git2::ObjectType::Blob, contents)?;
+        Ok(make_non_zero_oid(oid))
+    }
+
+    /// Compute the OID that the contents of the file at `path` would have as a
+    /// blob (as with `git hash-object`), without writing it to a repository.
+    /// The file is read incrementally, rather than all at once.
+    #[instrument]
+    pub fn hash_blob_file(path: &Path) -> eyre::Result<Self> {
+        let oid = git2::Oid::hash_file(git2::ObjectType::Blob, path)?;
+        Ok(make_non_zero_oid(oid))
+    }
 }
 
 impl std::fmt::Debug for NonZeroOid {
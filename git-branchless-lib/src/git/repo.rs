@@ -118,6 +118,13 @@ pub enum Error {
         onto: NonZeroOid,
     },
 
+    #[error("could not merge commit {their_commit} into {our_commit}: {source}")]
+    MergeCommits {
+        source: git2::Error,
+        our_commit: NonZeroOid,
+        their_commit: NonZeroOid,
+    },
+
     #[error("could not fast-cherry-pick commit {commit} onto {onto}: {source}")]
     CherryPickFast {
         source: git2::Error,
@@ -1200,6 +1207,20 @@ impl Repo {
         Ok(Index { inner: index })
     }
 
+    /// Merge two commits in memory and return the resulting index.
+    #[instrument]
+    pub fn merge_commits(&self, our_commit: &Commit, their_commit: &Commit) -> Result<Index> {
+        let index = self
+            .inner
+            .merge_commits(&our_commit.inner, &their_commit.inner, None)
+            .map_err(|err| Error::MergeCommits {
+                source: err,
+                our_commit: our_commit.get_oid(),
+                their_commit: their_commit.get_oid(),
+            })?;
+        Ok(Index { inner: index })
+    }
+
     /// Cherry-pick a commit in memory and return the resulting tree.
     ///
     /// The `libgit2` routines operate on entire `Index`es, which contain one
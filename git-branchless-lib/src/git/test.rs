@@ -5,7 +5,7 @@
 //! Regrettably, this adds `serde` as a new dependency to `git-branchless-lib`,
 //! which will increase build times.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
@@ -38,9 +38,33 @@ pub const TEST_INDETERMINATE_EXIT_CODE: i32 = 125;
 /// shouldn't be too confusing in practice.
 pub const TEST_ABORT_EXIT_CODE: i32 = 127;
 
+/// The exit status recorded for a test command which was killed because it
+/// exceeded its timeout. This matches the exit status used by the coreutils
+/// `timeout` command in the same situation.
+pub const TEST_TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// The version of the format of `SerializedTestResult`. Cached results written
+/// with a different version are ignored, so this should be incremented whenever
+/// the meaning of a stored result changes.
+pub const TEST_RESULT_SCHEMA_VERSION: u32 = 1;
+
 /// Convert a command string into a string that's safe to use as a filename.
+///
+/// Each escaped character is replaced by a distinct two-character sequence
+/// starting with `_` (and `_` itself is escaped), so distinct commands always
+/// map to distinct slugs.
 pub fn make_test_command_slug(command: String) -> String {
-    command.replace(['/', ' ', '\n'], "__")
+    let mut slug = String::with_capacity(command.len());
+    for c in command.chars() {
+        match c {
+            ' ' => slug.push_str("__"),
+            '_' => slug.push_str("_u"),
+            '/' => slug.push_str("_s"),
+            '\n' => slug.push_str("_n"),
+            c => slug.push(c),
+        }
+    }
+    slug
 }
 
 /// A version of `NonZeroOid` that can be serialized and deserialized. This
@@ -74,6 +98,86 @@ pub struct SerializedTestResult {
     pub snapshot_tree_oid: Option<SerializedNonZeroOid>,
     #[serde(default)]
     pub interactive: bool,
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub attempts: Option<usize>,
+    #[serde(default)]
+    pub timestamp: Option<f64>,
+    #[serde(default)]
+    pub combined_output: bool,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    #[serde(default)]
+    pub skip_reason: Option<String>,
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+impl SerializedTestResult {
+    /// Get whether this result counts as passed, failed, or skipped, according
+    /// to the configured `exit_codes`. Results for commands which timed out
+    /// always count as failed.
+    pub fn get_category(&self, exit_codes: &TestExitCodes) -> TestStatusCategory {
+        match self.timeout_seconds {
+            Some(_) => TestStatusCategory::Failed,
+            None => exit_codes.categorize(self.exit_code),
+        }
+    }
+}
+
+/// Whether a test result counts as passed, failed, or skipped when tallying
+/// the results of a test run.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TestStatusCategory {
+    /// The test command succeeded.
+    Passed,
+
+    /// The test command failed, was aborted, or timed out.
+    Failed,
+
+    /// The test command couldn't be run to completion, reported an
+    /// indeterminate result, or wasn't run at all.
+    Skipped,
+}
+
+/// The configured exit codes which determine how the exit code of a test
+/// command is interpreted.
+#[derive(Clone, Debug)]
+pub struct TestExitCodes {
+    /// The exit code which indicates that the commit should be skipped
+    /// (`branchless.test.skipExitCode`).
+    pub skip_exit_code: i32,
+
+    /// Additional exit codes which indicate success
+    /// (`branchless.test.successExitCodes`).
+    pub success_exit_codes: Vec<i32>,
+
+    /// Additional exit codes which indicate that the commit should be skipped
+    /// (`branchless.test.skipExitCodes`).
+    pub skip_exit_codes: Vec<i32>,
+}
+
+impl TestExitCodes {
+    /// Get whether a test command which exited with `exit_code` counts as
+    /// passed, failed, or skipped. The configured lists of exit codes take
+    /// precedence over the built-in handling.
+    pub fn categorize(&self, exit_code: i32) -> TestStatusCategory {
+        let Self {
+            skip_exit_code,
+            success_exit_codes,
+            skip_exit_codes,
+        } = self;
+        if skip_exit_codes.contains(&exit_code) {
+            TestStatusCategory::Skipped
+        } else if exit_code == TEST_SUCCESS_EXIT_CODE || success_exit_codes.contains(&exit_code) {
+            TestStatusCategory::Passed
+        } else if exit_code == *skip_exit_code {
+            TestStatusCategory::Skipped
+        } else {
+            TestStatusCategory::Failed
+        }
+    }
 }
 
 /// Get the directory where the results of running tests are stored.
@@ -81,13 +185,19 @@ fn get_test_dir(repo: &Repo) -> PathBuf {
     repo.get_path().join("branchless").join("test")
 }
 
-/// Get the directory where the result of tests for a particular commit are
-/// stored. Tests are keyed by tree OID, not commit OID, so that they can be
-/// cached based on the contents of the commit, rather than its specific commit
-/// hash. This means that we can cache the results of tests for commits that
-/// have been amended or rebased.
-pub fn get_test_tree_dir(repo: &Repo, commit: &Commit) -> PathBuf {
-    get_test_dir(repo).join(commit.get_tree_oid().to_string())
+/// Get the default directory where the results of tests are cached, if the
+/// user has not configured a different one.
+pub fn get_default_test_cache_dir(repo: &Repo) -> PathBuf {
+    get_test_dir(repo)
+}
+
+/// Get the directory inside `cache_dir` where the result of tests for a
+/// particular commit are stored. Tests are keyed by tree OID, not commit OID,
+/// so that they can be cached based on the contents of the commit, rather than
+/// its specific commit hash. This means that we can cache the results of tests
+/// for commits that have been amended or rebased.
+pub fn get_test_tree_dir(cache_dir: &Path, commit: &Commit) -> PathBuf {
+    cache_dir.join(commit.get_tree_oid().to_string())
 }
 
 /// Get the directory where the locks for running tests are stored.
@@ -104,3 +214,36 @@ pub fn get_test_worktrees_dir(repo: &Repo) -> PathBuf {
 pub fn get_latest_test_command_path(repo: &Repo) -> PathBuf {
     get_test_dir(repo).join("latest-command")
 }
+
+/// Read the latest test command which was run, if any.
+pub fn read_latest_test_command(repo: &Repo) -> Option<String> {
+    let latest_command_path = get_latest_test_command_path(repo);
+    let latest_command = std::fs::read_to_string(latest_command_path).ok()?;
+    Some(latest_command)
+}
+
+/// Read the cached result of running `command` on the given commit, if any.
+/// Results which were recorded for a different command or with a different
+/// `TEST_RESULT_SCHEMA_VERSION` are ignored, as are results which are still
+/// being written.
+pub fn read_cached_test_result(
+    cache_dir: &Path,
+    commit: &Commit,
+    command: &str,
+) -> Option<SerializedTestResult> {
+    let result_path = get_test_tree_dir(cache_dir, commit)
+        .join(make_test_command_slug(command.to_owned()))
+        .join("result");
+    let result_contents = std::fs::read_to_string(result_path).ok()?;
+    let result: SerializedTestResult = serde_json::from_str(&result_contents).ok()?;
+    if result.command != command || result.schema_version != TEST_RESULT_SCHEMA_VERSION {
+        return None;
+    }
+    Some(result)
+}
+
+/// Get the path to the file where the output of the latest test setup command
+/// is stored.
+pub fn get_test_setup_output_path(repo: &Repo) -> PathBuf {
+    get_test_dir(repo).join("setup")
+}
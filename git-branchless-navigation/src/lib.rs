@@ -26,18 +26,19 @@ use lib::util::ExitCode;
 use tracing::{instrument, warn};
 
 use git_branchless_opts::{SwitchOptions, TraverseCommitsOptions};
-use git_branchless_revset::resolve_default_smartlog_commits;
+use git_branchless_revset::{resolve_commits, resolve_default_smartlog_commits};
 use git_branchless_smartlog::make_smartlog_graph;
-use lib::core::config::get_next_interactive;
-use lib::core::dag::{sorted_commit_set, CommitSet, Dag};
+use lib::core::config::{get_next_interactive, get_next_sort_by_author_date};
+use lib::core::dag::{sorted_commit_set, union_all, CommitSet, Dag};
 use lib::core::effects::Effects;
 use lib::core::eventlog::{EventLogDb, EventReplayer};
 use lib::core::formatting::Pluralize;
+use lib::core::navigation_history::{NavigationDirection, NavigationHistoryDb, NavigationPosition};
 use lib::core::node_descriptors::{
     BranchesDescriptor, CommitMessageDescriptor, CommitOidDescriptor,
     DifferentialRevisionDescriptor, NodeDescriptor, Redactor, RelativeTimeDescriptor,
 };
-use lib::git::{GitRunInfo, NonZeroOid, Repo};
+use lib::git::{BranchType, GitRunInfo, NonZeroOid, ReferenceName, Repo, ResolvedReferenceInfo};
 
 use crate::prompt::prompt_select_commit;
 
@@ -81,9 +82,21 @@ pub enum Towards {
     /// When encountering multiple children, select the oldest one.
     Oldest,
 
+    /// Like `Newest`, but compare the author dates of the children rather
+    /// than their committer dates.
+    NewestByAuthorDate,
+
+    /// Like `Oldest`, but compare the author dates of the children rather
+    /// than their committer dates.
+    OldestByAuthorDate,
+
     /// When encountering multiple children, interactively prompt for
     /// which one to advance to.
     Interactive,
+
+    /// Only advance to commits on the way to the given commit, stopping once
+    /// it's reached.
+    Target(NonZeroOid),
 }
 
 #[instrument(skip(commit_descriptors))]
@@ -96,6 +109,7 @@ fn advance(
     command: Command,
     distance: Distance,
     towards: Option<Towards>,
+    parent_oid: Option<NonZeroOid>,
 ) -> eyre::Result<Option<NonZeroOid>> {
     let towards = match towards {
         Some(towards) => Some(towards),
@@ -130,6 +144,13 @@ fn advance(
                     Ok(nearest_descendant_branches)
                 };
 
+                let stack_tops = || -> eyre::Result<CommitSet> {
+                    let descendant_commits = dag.query_descendants(child_commits()?)?;
+                    let descendant_commits = dag.filter_visible_commits(descendant_commits)?;
+                    let stack_tops = dag.query_heads(descendant_commits)?;
+                    Ok(stack_tops)
+                };
+
                 let children = match distance {
                     Distance::AllTheWay {
                         move_by_branches: false,
@@ -141,11 +162,21 @@ fn advance(
 
                     Distance::AllTheWay {
                         move_by_branches: true,
-                    }
-                    | Distance::NumCommits {
+                    } => descendant_branches()?,
+
+                    Distance::NumCommits {
                         amount: _,
                         move_by_branches: true,
-                    } => descendant_branches()?,
+                    } => {
+                        // If there are no more branches, then go to the top
+                        // of the stack instead, rather than nowhere.
+                        let descendant_branches = descendant_branches()?;
+                        if dag.set_is_empty(&descendant_branches)? {
+                            stack_tops()?
+                        } else {
+                            descendant_branches
+                        }
+                    }
                 };
 
                 sorted_commit_set(repo, dag, &children)?
@@ -195,9 +226,46 @@ fn advance(
                     } => ancestor_branches()?,
                 };
 
+                let parents = match parent_oid {
+                    // Only consider the commits reachable via the selected
+                    // parent of the original commit.
+                    Some(parent_oid) => {
+                        parents.intersection(&dag.query_ancestors(CommitSet::from(parent_oid))?)
+                    }
+                    None => parents,
+                };
+
                 sorted_commit_set(repo, dag, &parents)?
             }
         };
+        let candidate_commits = match towards {
+            Some(Towards::Target(target_oid)) => {
+                let mut result = Vec::new();
+                for candidate_commit in candidate_commits {
+                    let is_towards_target = match command {
+                        Command::Next => {
+                            dag.query_is_ancestor(candidate_commit.get_oid(), target_oid)?
+                        }
+                        Command::Prev => {
+                            dag.query_is_ancestor(target_oid, candidate_commit.get_oid())?
+                        }
+                    };
+                    if is_towards_target {
+                        result.push(candidate_commit);
+                    }
+                }
+                result
+            }
+            Some(Towards::NewestByAuthorDate | Towards::OldestByAuthorDate) => {
+                let mut candidate_commits = candidate_commits;
+                candidate_commits
+                    .sort_by_key(|commit| (commit.get_author().get_time(), commit.get_oid()));
+                candidate_commits
+            }
+            Some(Towards::Newest | Towards::Oldest | Towards::Interactive) | None => {
+                candidate_commits
+            }
+        };
 
         match distance {
             Distance::NumCommits {
@@ -255,20 +323,46 @@ fn advance(
                     // treat the operation as a failure. Otherwise, assume that
                     // the user just meant to go as many commits as possible.
                     return Ok(None);
-                } else {
-                    break;
                 }
+
+                if let Distance::NumCommits {
+                    amount,
+                    move_by_branches,
+                } = distance
+                {
+                    let direction = match command {
+                        Command::Next => "forward",
+                        Command::Prev => "back",
+                    };
+                    let unit = if move_by_branches {
+                        "branches"
+                    } else {
+                        "commits"
+                    };
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Moved {direction} {i} of {amount} requested {unit}.",
+                    )?;
+                }
+                break;
             }
 
             (_, [only_child]) => only_child.get_oid(),
-            (Some(Towards::Newest), [.., newest_child]) => newest_child.get_oid(),
-            (Some(Towards::Oldest), [oldest_child, ..]) => oldest_child.get_oid(),
+            (Some(Towards::Newest | Towards::NewestByAuthorDate), [.., newest_child]) => {
+                newest_child.get_oid()
+            }
+            (Some(Towards::Oldest | Towards::OldestByAuthorDate), [oldest_child, ..]) => {
+                oldest_child.get_oid()
+            }
             (Some(Towards::Interactive), [_, _, ..]) => {
                 match prompt_select_commit(
+                    effects,
+                    repo,
                     Some(&header),
                     "",
                     candidate_commits,
                     commit_descriptors,
+                    true,
                 )? {
                     Some(oid) => oid,
                     None => {
@@ -276,7 +370,7 @@ fn advance(
                     }
                 }
             }
-            (None, [_, _, ..]) => {
+            (None | Some(Towards::Target(_)), [_, _, ..]) => {
                 writeln!(effects.get_output_stream(), "{header}")?;
                 for (j, child) in (0..).zip(candidate_commits.iter()) {
                     let descriptor = if j == 0 {
@@ -319,12 +413,17 @@ pub fn traverse_commits(
         move_by_branches,
         oldest,
         newest,
+        author_date,
         interactive,
+        target,
+        parent,
+        create_branch,
         merge,
         force,
-    } = *options;
+    } = options;
 
-    let distance = match (all_the_way, num_commits) {
+    let move_by_branches = *move_by_branches;
+    let distance = match (*all_the_way, *num_commits) {
         (false, None) => Distance::NumCommits {
             amount: 1,
             move_by_branches,
@@ -342,22 +441,13 @@ pub fn traverse_commits(
         }
     };
 
-    let towards = match (oldest, newest, interactive) {
-        (false, false, false) => None,
-        (true, false, false) => Some(Towards::Oldest),
-        (false, true, false) => Some(Towards::Newest),
-        (false, false, true) => Some(Towards::Interactive),
-        (_, _, _) => {
-            eyre::bail!("Only one of --oldest, --newest, and --interactive can be set")
-        }
-    };
-
     let now = SystemTime::now();
     let repo = Repo::from_current_dir()?;
     let head_info = repo.get_head_info()?;
     let references_snapshot = repo.get_references_snapshot()?;
     let conn = repo.get_db_conn()?;
     let event_log_db = EventLogDb::new(&conn)?;
+    let navigation_history_db = NavigationHistoryDb::new(&conn)?;
     let event_tx_id = event_log_db.make_transaction_id(
         now,
         match command {
@@ -367,7 +457,7 @@ pub fn traverse_commits(
     )?;
     let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
     let event_cursor = event_replayer.make_default_cursor();
-    let dag = Dag::open_and_sync(
+    let mut dag = Dag::open_and_sync(
         effects,
         &repo,
         &event_replayer,
@@ -382,6 +472,116 @@ pub fn traverse_commits(
         }
     };
 
+    let target_oid = match target {
+        None => None,
+        Some(target) => {
+            let commit_sets = match resolve_commits(
+                effects,
+                &repo,
+                &mut dag,
+                &[target.clone()],
+                &Default::default(),
+            ) {
+                Ok(commit_sets) => commit_sets,
+                Err(err) => {
+                    err.describe(effects)?;
+                    return Ok(ExitCode(1));
+                }
+            };
+            let target_oids = dag.commit_set_to_vec(&union_all(&commit_sets))?;
+            let target_oid = match target_oids.as_slice() {
+                [target_oid] => *target_oid,
+                target_oids => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "The target {} must resolve to exactly one commit, but it resolved to {}.",
+                        target,
+                        Pluralize {
+                            determiner: None,
+                            amount: target_oids.len(),
+                            unit: ("commit", "commits"),
+                        },
+                    )?;
+                    return Ok(ExitCode(1));
+                }
+            };
+            let (is_reachable, relation) = match command {
+                Command::Next => (dag.query_is_ancestor(head_oid, target_oid)?, "a descendant"),
+                Command::Prev => (dag.query_is_ancestor(target_oid, head_oid)?, "an ancestor"),
+            };
+            if !is_reachable {
+                writeln!(
+                    effects.get_output_stream(),
+                    "The target {} is not {relation} of the current commit.",
+                    effects.get_glyphs().render(
+                        repo.friendly_describe_commit_from_oid(effects.get_glyphs(), target_oid)?
+                    )?,
+                )?;
+                return Ok(ExitCode(1));
+            }
+            Some(target_oid)
+        }
+    };
+
+    let parent_oid = match (command, parent) {
+        (_, None) => None,
+        (Command::Next, Some(_)) => {
+            writeln!(
+                effects.get_output_stream(),
+                "The --parent option can only be used with `git prev`.",
+            )?;
+            return Ok(ExitCode(1));
+        }
+        (Command::Prev, Some(parent)) => {
+            let parent_oids = repo.find_commit_or_fail(head_oid)?.get_parent_oids();
+            match parent.checked_sub(1).and_then(|i| parent_oids.get(i)) {
+                Some(parent_oid) => Some(*parent_oid),
+                None => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "The current commit has no parent with index {parent} (it has {}).",
+                        Pluralize {
+                            determiner: None,
+                            amount: parent_oids.len(),
+                            unit: ("parent", "parents"),
+                        },
+                    )?;
+                    return Ok(ExitCode(1));
+                }
+            }
+        }
+    };
+
+    if let Some(branch_name) = create_branch {
+        if repo.find_branch(branch_name, BranchType::Local)?.is_some() {
+            writeln!(
+                effects.get_output_stream(),
+                "A branch named {branch_name} already exists.",
+            )?;
+            return Ok(ExitCode(1));
+        }
+    }
+
+    let sort_by_author_date = *author_date || get_next_sort_by_author_date(&repo)?;
+    let towards = match (*oldest, *newest, *interactive, target_oid) {
+        (false, false, false, None) => None,
+        (true, false, false, None) => Some(if sort_by_author_date {
+            Towards::OldestByAuthorDate
+        } else {
+            Towards::Oldest
+        }),
+        (false, true, false, None) => Some(if sort_by_author_date {
+            Towards::NewestByAuthorDate
+        } else {
+            Towards::Newest
+        }),
+        (false, false, true, None) => Some(Towards::Interactive),
+        (false, false, false, Some(target_oid)) => Some(Towards::Target(target_oid)),
+        (_, _, _, _) => {
+            eyre::bail!("Only one of --oldest, --newest, --interactive, and --target can be set")
+        }
+    };
+
     let current_oid = advance(
         effects,
         &repo,
@@ -402,6 +602,7 @@ pub fn traverse_commits(
         command,
         distance,
         towards,
+        parent_oid,
     )?;
     let current_oid = match current_oid {
         None => return Ok(ExitCode(1)),
@@ -409,6 +610,10 @@ pub fn traverse_commits(
     };
 
     let checkout_target: CheckoutTarget = match distance {
+        // The new branch is created at the destination commit, so there's no
+        // need to check out any existing branch there.
+        _ if create_branch.is_some() => CheckoutTarget::Oid(current_oid),
+
         Distance::AllTheWay {
             move_by_branches: false,
         }
@@ -445,15 +650,19 @@ pub fn traverse_commits(
 
     let additional_args = {
         let mut args: Vec<OsString> = Vec::new();
-        if merge {
+        if let Some(branch_name) = create_branch {
+            args.push("-b".into());
+            args.push(branch_name.into());
+        }
+        if *merge {
             args.push("--merge".into());
         }
-        if force {
+        if *force {
             args.push("--force".into())
         }
         args
     };
-    check_out_commit(
+    let exit_code = check_out_commit(
         effects,
         git_run_info,
         &repo,
@@ -464,7 +673,11 @@ pub fn traverse_commits(
             additional_args,
             ..Default::default()
         },
-    )
+    )?;
+    if exit_code.is_success() {
+        record_navigation(&repo, &navigation_history_db, &head_info)?;
+    }
+    Ok(exit_code)
 }
 
 /// Interactively switch to a commit from the smartlog.
@@ -488,6 +701,7 @@ pub fn switch(
     let references_snapshot = repo.get_references_snapshot()?;
     let conn = repo.get_db_conn()?;
     let event_log_db = EventLogDb::new(&conn)?;
+    let navigation_history_db = NavigationHistoryDb::new(&conn)?;
     let event_tx_id = event_log_db.make_transaction_id(now, "checkout")?;
     let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
     let event_cursor = event_replayer.make_default_cursor();
@@ -531,6 +745,8 @@ pub fn switch(
         None => target.clone().map(CheckoutTarget::Unknown),
         Some(initial_query) => {
             match prompt_select_commit(
+                effects,
+                &repo,
                 None,
                 &initial_query,
                 graph.get_commits(),
@@ -546,6 +762,7 @@ pub fn switch(
                     &mut DifferentialRevisionDescriptor::new(&repo, &Redactor::Disabled)?,
                     &mut CommitMessageDescriptor::new(&Redactor::Disabled)?,
                 ],
+                false,
             )? {
                 Some(oid) => Some(CheckoutTarget::Oid(oid)),
                 None => return Ok(ExitCode(1)),
@@ -584,5 +801,122 @@ pub fn switch(
             render_smartlog: true,
         },
     )?;
+    if exit_code.is_success() {
+        record_navigation(&repo, &navigation_history_db, &head_info)?;
+    }
+    Ok(exit_code)
+}
+
+fn get_navigation_position(head_info: &ResolvedReferenceInfo) -> Option<NavigationPosition> {
+    let ResolvedReferenceInfo {
+        oid,
+        reference_name,
+    } = head_info;
+    oid.map(|commit_oid| NavigationPosition {
+        commit_oid,
+        reference_name: reference_name.clone(),
+    })
+}
+
+/// Record the checkout which was just carried out in the navigation history,
+/// so that the user can return to their previous position with `git
+/// branchless back`.
+#[instrument]
+fn record_navigation(
+    repo: &Repo,
+    navigation_history_db: &NavigationHistoryDb,
+    previous_head_info: &ResolvedReferenceInfo,
+) -> eyre::Result<()> {
+    let from = get_navigation_position(previous_head_info);
+    let to = get_navigation_position(&repo.get_head_info()?);
+    if let (Some(from), Some(to)) = (from, to) {
+        navigation_history_db.record_navigation(&from, &to)?;
+    }
+    Ok(())
+}
+
+fn get_reference_commit_oid(
+    repo: &Repo,
+    reference_name: &ReferenceName,
+) -> eyre::Result<Option<NonZeroOid>> {
+    let commit = match repo.find_reference(reference_name)? {
+        Some(reference) => reference.peel_to_commit()?,
+        None => None,
+    };
+    Ok(commit.map(|commit| commit.get_oid()))
+}
+
+/// Move back or forward through the positions which were checked out by the
+/// navigation commands.
+#[instrument]
+pub fn navigate_history(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    direction: NavigationDirection,
+) -> eyre::Result<ExitCode> {
+    let now = SystemTime::now();
+    let repo = Repo::from_current_dir()?;
+    let conn = repo.get_db_conn()?;
+    let event_log_db = EventLogDb::new(&conn)?;
+    let event_tx_id = event_log_db.make_transaction_id(
+        now,
+        match direction {
+            NavigationDirection::Back => "back",
+            NavigationDirection::Forward => "forward",
+        },
+    )?;
+    let navigation_history_db = NavigationHistoryDb::new(&conn)?;
+
+    let entry = match get_navigation_position(&repo.get_head_info()?) {
+        Some(current_position) => {
+            navigation_history_db.get_adjacent_entry(direction, &current_position)?
+        }
+        None => None,
+    };
+    let entry = match entry {
+        Some(entry) => entry,
+        None => {
+            writeln!(
+                effects.get_output_stream(),
+                "{}",
+                effects.get_glyphs().render(StyledString::styled(
+                    match direction {
+                        NavigationDirection::Back =>
+                            "No earlier position in the navigation history.",
+                        NavigationDirection::Forward =>
+                            "No later position in the navigation history.",
+                    },
+                    BaseColor::Yellow.light()
+                ))?
+            )?;
+            return Ok(ExitCode(1));
+        }
+    };
+
+    // Check out the branch again, unless it's since been moved.
+    let NavigationPosition {
+        commit_oid,
+        reference_name,
+    } = &entry.position;
+    let checkout_target = match reference_name {
+        Some(reference_name)
+            if get_reference_commit_oid(&repo, reference_name)? == Some(*commit_oid) =>
+        {
+            CheckoutTarget::Reference(reference_name.clone())
+        }
+        Some(_) | None => CheckoutTarget::Oid(*commit_oid),
+    };
+    let exit_code = check_out_commit(
+        effects,
+        git_run_info,
+        &repo,
+        &event_log_db,
+        event_tx_id,
+        Some(checkout_target),
+        &Default::default(),
+    )?;
+    if exit_code.is_success() {
+        navigation_history_db.set_cursor(&entry)?;
+    }
     Ok(exit_code)
 }
@@ -1,32 +1,52 @@
 //! An interactive prompt to select a commit.
 
+use lib::core::effects::Effects;
 use lib::core::node_descriptors::NodeDescriptor;
-use lib::git::{Commit, NonZeroOid};
+use lib::git::{Commit, NonZeroOid, Repo};
 
 /// Prompt the user to select a commit from the provided list
 /// of commits, and returns the OID of the selected commit.
+///
+/// If `show_diffs` is set, the preview pane for each commit also includes the
+/// diff of that commit against its parent. This is only advisable for short
+/// lists of commits, since the diffs are calculated ahead of time.
 #[cfg(unix)]
 pub fn prompt_select_commit(
+    effects: &Effects,
+    repo: &Repo,
     header: Option<&str>,
     initial_query: &str,
     commits: Vec<Commit>,
     commit_descriptors: &mut [&mut dyn NodeDescriptor],
+    show_diffs: bool,
 ) -> eyre::Result<Option<NonZeroOid>> {
-    skim::prompt_skim(header, initial_query, commits, commit_descriptors)
+    skim::prompt_skim(
+        effects,
+        repo,
+        header,
+        initial_query,
+        commits,
+        commit_descriptors,
+        show_diffs,
+    )
 }
 
 #[cfg(not(unix))]
 pub fn prompt_select_commit(
+    effects: &Effects,
+    repo: &Repo,
     header: Option<&str>,
     initial_query: &str,
     commits: Vec<Commit>,
     commit_descriptors: &mut [&mut dyn NodeDescriptor],
+    show_diffs: bool,
 ) -> eyre::Result<Option<NonZeroOid>> {
     unimplemented!("Non-unix targets are currently unsupported for prompting")
 }
 
 #[cfg(unix)]
 mod skim {
+    use cursive::utils::markup::StyledString;
     use eyre::eyre;
     use std::borrow::Cow;
     use std::convert::TryFrom;
@@ -34,9 +54,10 @@ mod skim {
 
     use itertools::Itertools;
 
-    use lib::core::formatting::Glyphs;
+    use lib::core::effects::Effects;
+    use lib::core::formatting::{Glyphs, StyledStringBuilder};
     use lib::core::node_descriptors::{render_node_descriptors, NodeDescriptor, NodeObject};
-    use lib::git::{Commit, NonZeroOid};
+    use lib::git::{Commit, NonZeroOid, Repo};
 
     use skim::{
         prelude::SkimOptionsBuilder, AnsiString, DisplayContext, ItemPreview, Matches,
@@ -97,8 +118,11 @@ mod skim {
 
     impl CommitSkimItem {
         fn from_descriptors(
+            effects: &Effects,
+            repo: &Repo,
             commit: &Commit,
             commit_descriptors: &mut [&mut dyn NodeDescriptor],
+            show_diffs: bool,
         ) -> eyre::Result<Self> {
             let glyphs = Glyphs::pretty();
             let styled_summary = render_node_descriptors(
@@ -109,20 +133,50 @@ mod skim {
                 commit_descriptors,
             )?;
 
+            let preview = commit.friendly_preview()?;
+            let preview = if show_diffs {
+                match get_commit_diff(effects, repo, commit)? {
+                    Some(diff) => StyledStringBuilder::from_lines(vec![preview, diff]),
+                    None => preview,
+                }
+            } else {
+                preview
+            };
+
             Ok(CommitSkimItem {
                 oid: commit.get_oid(),
                 styled_summary: glyphs.render(styled_summary)?,
-                styled_preview: Glyphs::pretty().render(commit.friendly_preview()?)?,
+                styled_preview: Glyphs::pretty().render(preview)?,
             })
         }
     }
 
+    /// Render the changes introduced by the commit. Returns `None` for merge
+    /// commits, since there's no single parent to compare against.
+    fn get_commit_diff(
+        effects: &Effects,
+        repo: &Repo,
+        commit: &Commit,
+    ) -> eyre::Result<Option<StyledString>> {
+        let parent_tree = match commit.get_parents().as_slice() {
+            [] => None,
+            [only_parent] => Some(only_parent.get_tree()?),
+            [..] => return Ok(None),
+        };
+        let diff =
+            repo.get_diff_between_trees(effects, parent_tree.as_ref(), &commit.get_tree()?, 3)?;
+        Ok(Some(diff.friendly_describe()?))
+    }
+
     #[cfg(unix)]
     pub fn prompt_skim(
+        effects: &Effects,
+        repo: &Repo,
         header: Option<&str>,
         initial_query: &str,
         commits: Vec<Commit>,
         commit_descriptors: &mut [&mut dyn NodeDescriptor],
+        show_diffs: bool,
     ) -> eyre::Result<Option<NonZeroOid>> {
         let options = SkimOptionsBuilder::default()
             .height(Some("100%"))
@@ -137,7 +191,15 @@ mod skim {
 
         let items: Vec<CommitSkimItem> = commits
             .iter()
-            .map(|commit| CommitSkimItem::from_descriptors(commit, commit_descriptors))
+            .map(|commit| {
+                CommitSkimItem::from_descriptors(
+                    effects,
+                    repo,
+                    commit,
+                    commit_descriptors,
+                    show_diffs,
+                )
+            })
             .try_collect()?;
 
         let rx_item = {
@@ -106,18 +106,30 @@ pub struct TraverseCommitsOptions {
     #[clap(action, short = 'a', long = "all")]
     pub all_the_way: bool,
 
-    /// Move the specified number of branches rather than commits.
+    /// Move the specified number of branches rather than commits. When
+    /// moving to the next branch, stop at the top of the stack if there are
+    /// no more branches.
     #[clap(action, short = 'b', long = "branch")]
     pub move_by_branches: bool,
 
-    /// When encountering multiple next commits, choose the oldest.
+    /// When encountering multiple next commits, choose the oldest. By
+    /// default, this is the one with the earliest committer date.
     #[clap(action, short = 'o', long = "oldest")]
     pub oldest: bool,
 
-    /// When encountering multiple next commits, choose the newest.
+    /// When encountering multiple next commits, choose the newest. By
+    /// default, this is the one with the latest committer date.
     #[clap(action, short = 'n', long = "newest", conflicts_with("oldest"))]
     pub newest: bool,
 
+    /// With `--oldest` or `--newest`, compare the author dates of the commits
+    /// rather than their committer dates. This is more meaningful when the
+    /// commits have been rebased, since their committer dates are then all
+    /// recent. Can also be enabled with the `branchless.next.sortByAuthorDate`
+    /// configuration option.
+    #[clap(action, long = "author-date")]
+    pub author_date: bool,
+
     /// When encountering multiple next commits, interactively prompt which to
     /// advance to.
     #[clap(
@@ -129,6 +141,30 @@ pub struct TraverseCommitsOptions {
     )]
     pub interactive: bool,
 
+    /// Move towards the given commit, choosing the next commit on the way to
+    /// it when there are multiple, and stopping once it's reached. The commit
+    /// must be a descendant of the current commit (or an ancestor, for
+    /// `prev`).
+    #[clap(
+        value_parser,
+        long = "target",
+        value_name = "REVSET",
+        conflicts_with_all(&["newest", "oldest", "interactive"])
+    )]
+    pub target: Option<Revset>,
+
+    /// When the current commit is a merge commit, move towards its parent with
+    /// the given index (starting from 1), rather than considering all of its
+    /// parents. Only applies to `prev`.
+    #[clap(value_parser, short = 'p', long = "parent", value_name = "N")]
+    pub parent: Option<usize>,
+
+    /// Create a branch with the given name at the destination commit and
+    /// check it out, rather than leaving `HEAD` detached. The branch must not
+    /// already exist.
+    #[clap(value_parser, short = 'c', long = "create-branch", value_name = "NAME")]
+    pub create_branch: Option<String>,
+
     /// If the local changes conflict with the destination commit, attempt to
     /// merge them.
     #[clap(action, short = 'm', long = "merge")]
@@ -228,6 +264,20 @@ pub enum HookSubcommand {
     /// Internal use.
     RegisterExtraPostRewriteHook,
     /// Internal use.
+    ReplaceCommit {
+        /// The OID of the commit being replaced.
+        #[clap(value_parser)]
+        original_commit_oid: String,
+
+        /// The OID of the commit whose contents should be used instead.
+        #[clap(value_parser)]
+        replacement_commit_oid: String,
+
+        /// The parents of the new commit, as commit OIDs or references.
+        #[clap(value_parser)]
+        parents: Vec<String>,
+    },
+    /// Internal use.
     SkipUpstreamAppliedCommit {
         /// The OID of the commit that was skipped.
         #[clap(value_parser)]
@@ -400,10 +450,18 @@ pub enum Command {
         reparent: bool,
     },
 
+    /// Go back to the previous position in the navigation history (the
+    /// commits checked out by `next`, `prev`, and `switch`).
+    Back,
+
     /// Gather information about recent operations to upload as part of a bug
     /// report.
     BugReport,
 
+    /// Go forward to the next position in the navigation history, after
+    /// using `back`.
+    Forward,
+
     /// Run internal garbage collection.
     Gc,
 
@@ -696,6 +754,20 @@ pub enum TestExecutionStrategy {
     /// invalidating build artifacts in the current directory, or if you want to run tests while
     /// your working copy is dirty.
     Worktree,
+
+    /// Run the tests inside a Docker container created from the image named by
+    /// `branchless.test.dockerImage`. The contents of each commit are copied
+    /// into a fresh directory which is bind-mounted into the container. This is
+    /// useful if you want to test each commit in a clean environment.
+    Docker,
+
+    /// Check out the contents of each commit into a directory under
+    /// `branchless.test.tmpfsDir` (by default, `/dev/shm` if it exists, or the
+    /// system temporary directory otherwise) without creating a full worktree.
+    /// This is useful for large repositories, where creating and updating
+    /// worktrees on disk is slow. The directory is not a Git working copy, so
+    /// fixes can't be applied with this strategy.
+    Tmpfs,
 }
 
 /// How to conduct searches on the commit graph.
@@ -714,6 +786,26 @@ pub enum TestSearchStrategy {
     Binary,
 }
 
+/// How to format the results of `git test run`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum TestOutputFormat {
+    /// Default. Print a human-readable description of each result, followed by
+    /// a summary.
+    Human,
+
+    /// Print a JSON array containing an object for each tested commit, and
+    /// nothing else.
+    Json,
+
+    /// Print the results in the Test Anything Protocol (TAP) format, with one
+    /// test point per commit, and nothing else.
+    Tap,
+
+    /// Print the results as CSV, with a header row followed by one row per
+    /// tested commit, and nothing else.
+    Csv,
+}
+
 /// Arguments which apply to all commands. Used during setup.
 #[derive(Debug, Parser)]
 pub struct GlobalArgs {
@@ -771,13 +863,88 @@ pub enum TestSubcommand {
         /// Options for resolving revset expressions.
         #[clap(flatten)]
         resolve_revset_options: ResolveRevsetOptions,
+
+        /// Only clean the cached results for test commands which failed, such
+        /// as to re-run flaky tests while keeping the passing results.
+        #[clap(long = "failed-only", conflicts_with("passed_only"))]
+        failed_only: bool,
+
+        /// Only clean the cached results for test commands which passed.
+        #[clap(long = "passed-only")]
+        passed_only: bool,
+
+        /// Clean the cached results for all commits, rather than only those in
+        /// the revset. Results which are currently being written are skipped.
+        #[clap(long = "all", conflicts_with_all(&["failed_only", "passed_only"]))]
+        all: bool,
+
+        /// Print which cached results would be cleaned, without cleaning them.
+        #[clap(long = "dry-run")]
+        dry_run: bool,
+
+        /// The directory in which test results are cached. Defaults to the
+        /// value of `branchless.test.cacheDir`, or to a directory inside the
+        /// repository's Git directory if that is not set.
+        #[clap(value_parser, long = "cache-dir", value_name = "PATH")]
+        cache_dir: Option<PathBuf>,
+    },
+
+    /// Show how much disk space is used by the cached test results, testing
+    /// worktrees, and locks.
+    Du {
+        /// The directory in which test results are cached. Defaults to the
+        /// value of `branchless.test.cacheDir`, or to a directory inside the
+        /// repository's Git directory if that is not set.
+        #[clap(value_parser, long = "cache-dir", value_name = "PATH")]
+        cache_dir: Option<PathBuf>,
+    },
+
+    /// Remove the worktrees created by `--strategy worktree` which aren't
+    /// currently being used to run tests.
+    Gc,
+
+    /// Show every cached test result for a set of commits, for all of the
+    /// test commands which have been run on them.
+    Log {
+        /// The set of commits to show the cached test results for.
+        #[clap(value_parser, default_value = "@")]
+        revset: Revset,
+
+        /// Options for resolving revset expressions.
+        #[clap(flatten)]
+        resolve_revset_options: ResolveRevsetOptions,
+
+        /// The directory in which test results are cached. Defaults to the
+        /// value of `branchless.test.cacheDir`, or to a directory inside the
+        /// repository's Git directory if that is not set.
+        #[clap(value_parser, long = "cache-dir", value_name = "PATH")]
+        cache_dir: Option<PathBuf>,
     },
 
     /// Run a given command on a set of commits and present the successes and failures.
+    ///
+    /// The command is run with the following environment variables set:
+    ///
+    /// - `BRANCHLESS_TEST_COMMIT`: the full hash of the commit being tested.
+    /// - `BRANCHLESS_TEST_TREE`: the full hash of the tree of the commit being tested.
+    /// - `BRANCHLESS_TEST_COMMIT_SUBJECT`: the first line of the commit message.
+    /// - `BRANCHLESS_TEST_COMMAND`: the command being run.
+    /// - `BRANCHLESS_TEST_SKIP_REASON_FILE`: a file to which the command can
+    ///   write the reason for skipping the commit, before exiting with the skip
+    ///   exit code.
+    ///
+    /// If `branchless.test.teardown` is set, that command is run in each
+    /// worktree once it has finished running tests (or once in the working
+    /// copy, when using `--strategy working-copy`). Failures of the teardown
+    /// command are ignored.
     Run {
-        /// An ad-hoc command to execute on each commit.
+        /// An ad-hoc command to execute on each commit. May be given multiple
+        /// times to run several commands on each commit in sequence; a commit
+        /// only passes if all of the commands pass. Once a command fails on a
+        /// commit, the remaining commands aren't run on it, unless
+        /// `branchless.test.shortCircuit` is set to `false`.
         #[clap(value_parser, short = 'x', long = "exec")]
-        exec: Option<String>,
+        exec: Vec<String>,
 
         /// The test command alias for the command to execute on each commit. Set with
         /// `git config branchless.test.alias.<name> <command>`.
@@ -792,10 +959,32 @@ pub enum TestSubcommand {
         #[clap(flatten)]
         resolve_revset_options: ResolveRevsetOptions,
 
+        /// Test the commits listed in the given file instead, one commit or
+        /// revset per line. Blank lines and lines starting with `#` are
+        /// ignored. Pass `-` to read the list from stdin.
+        #[clap(
+            value_parser,
+            long = "commits-from",
+            value_name = "PATH",
+            conflicts_with = "revset"
+        )]
+        commits_from: Option<PathBuf>,
+
         /// Show the test output as well.
         #[clap(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
         verbosity: u8,
 
+        /// The number of lines to show at the start and end of the test output
+        /// when it's abbreviated. Defaults to the value of
+        /// `branchless.test.outputContextLines`, or 5.
+        #[clap(long = "context", value_name = "N")]
+        context: Option<usize>,
+
+        /// In the summary, collapse failures with the same exit code and output
+        /// into a single entry listing all of the affected commits.
+        #[clap(action, long = "group")]
+        group: bool,
+
         /// How to execute the tests.
         #[clap(short = 's', long = "strategy")]
         strategy: Option<TestExecutionStrategy>,
@@ -809,18 +998,227 @@ pub enum TestSubcommand {
         #[clap(short = 'b', long = "bisect", conflicts_with("search"))]
         bisect: bool,
 
-        /// Don't read or write to the cache when executing the test commands.
+        /// When searching with `--search` or `--bisect`, create or update the
+        /// given ref (such as `refs/branchless/bisect-bad`) to point to the
+        /// first failing commit.
+        #[clap(long = "bisect-ref", value_name = "REF")]
+        bisect_ref: Option<String>,
+
+        /// When searching with `--search` or `--bisect`, print only a line of
+        /// the form `culprit <full-oid>` for the first failing commit, for
+        /// consumption by scripts.
+        #[clap(long = "porcelain")]
+        porcelain: bool,
+
+        /// Treat the test command as a benchmark: it should print a number as
+        /// the last numeric line of its output, and a commit is considered to
+        /// fail if that number is greater than this threshold. Combine with
+        /// `--search` or `--bisect` to find the commit which introduced a
+        /// performance regression.
+        #[clap(
+            long = "perf-threshold",
+            value_name = "VALUE",
+            conflicts_with("interactive")
+        )]
+        perf_threshold: Option<f64>,
+
+        /// Treat a commit as failed if the output of the test command matches
+        /// the given regular expression, even if the command exited
+        /// successfully.
+        #[clap(value_parser, long = "fail-on-output", value_name = "REGEX")]
+        fail_on_output: Option<String>,
+
+        /// Don't read cached results when executing the test commands. The
+        /// test command is always run, and its fresh result is written to the
+        /// cache.
         #[clap(long = "no-cache")]
         no_cache: bool,
 
+        /// The directory in which test results are cached. Defaults to the
+        /// value of `branchless.test.cacheDir`, or to a directory inside the
+        /// repository's Git directory if that is not set.
+        #[clap(value_parser, long = "cache-dir", value_name = "PATH")]
+        cache_dir: Option<PathBuf>,
+
+        /// Only run the test command on commits which have no cached result or
+        /// whose cached result is a failure. Cached failures are discarded and
+        /// the test command is run again for those commits.
+        #[clap(long = "rerun-failures", conflicts_with("no_cache"))]
+        rerun_failures: bool,
+
         /// Run the test command in the foreground rather than the background so
         /// that the user can interact with it.
         #[clap(short = 'i', long = "interactive")]
         interactive: bool,
 
         /// How many jobs to execute in parallel. The value `0` indicates to use all CPUs.
+        /// By default, this is the number of physical cores, which suits
+        /// CPU-bound test commands. Set `branchless.test.jobsCountMode` to
+        /// `logical` to use the number of logical cores instead, which may be
+        /// faster for IO-bound test commands.
         #[clap(short = 'j', long = "jobs")]
         jobs: Option<usize>,
+
+        /// Kill the test command if it runs for longer than this many seconds
+        /// on a given commit, and mark that commit as having timed out.
+        #[clap(long = "timeout", value_name = "SECONDS")]
+        timeout: Option<u64>,
+
+        /// Limit the address space available to the test command to this many
+        /// bytes on each commit (Linux only). A test command which exceeds the
+        /// limit typically fails to allocate memory and is terminated by a
+        /// signal.
+        #[clap(long = "memory-limit", value_name = "BYTES")]
+        memory_limit: Option<u64>,
+
+        /// If another `git test` process is already testing a commit with the
+        /// same command, wait for it to finish (for up to this many seconds,
+        /// or 600 if not provided) and use its result, rather than reporting
+        /// the commit as already in progress.
+        #[clap(
+            long = "wait",
+            value_name = "SECONDS",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "600"
+        )]
+        wait: Option<u64>,
+
+        /// If the test command fails on a commit, re-run it up to this many
+        /// additional times. The commit is only considered to have failed if
+        /// every attempt fails.
+        #[clap(long = "retries", default_value_t = 0)]
+        retries: usize,
+
+        /// Stop running the test command on further commits once it has failed
+        /// on this many commits. The results for the commits which were already
+        /// tested are still reported.
+        #[clap(
+            long = "max-failures",
+            value_name = "N",
+            conflicts_with_all(&["search", "bisect"])
+        )]
+        max_failures: Option<usize>,
+
+        /// Once the test command fails on a commit, don't run it on any of that
+        /// commit's descendants, and report them as skipped instead.
+        #[clap(
+            long = "skip-descendants-on-failure",
+            conflicts_with_all(&["search", "bisect"])
+        )]
+        skip_descendants_on_failure: bool,
+
+        /// Don't run the test command on empty commits (those which don't
+        /// change the tree of their first parent), and report them as skipped
+        /// instead.
+        #[clap(long = "skip-empty")]
+        skip_empty: bool,
+
+        /// For each merge commit with two parents, also re-create the merge
+        /// from the perspective of each parent (by merging the other parent
+        /// into it) and run the test command on the result. If the parents
+        /// don't merge cleanly, then the checkout is reported as failed.
+        #[clap(
+            long = "test-merge-parents",
+            conflicts_with_all(&["search", "bisect", "skip_descendants_on_failure"])
+        )]
+        test_merge_parents: bool,
+
+        /// Run the test command on the commits in a random order, to help
+        /// surface tests which depend on the order in which they're run. The
+        /// results are still reported in the original order.
+        #[clap(long = "shuffle", conflicts_with_all(&["search", "bisect"]))]
+        shuffle: bool,
+
+        /// The seed to use for `--shuffle`, to reproduce a previous order.
+        /// Implies `--shuffle`.
+        #[clap(
+            long = "shuffle-seed",
+            value_name = "SEED",
+            conflicts_with_all(&["search", "bisect"])
+        )]
+        shuffle_seed: Option<u64>,
+
+        /// How to format the results of the test run.
+        #[clap(long = "format", value_enum, default_value_t = TestOutputFormat::Human)]
+        format: TestOutputFormat,
+
+        /// Write a JUnit-style XML report of the results to the given path, with
+        /// one test case per commit.
+        #[clap(value_parser, long = "junit", value_name = "PATH")]
+        junit: Option<PathBuf>,
+
+        /// Send a desktop notification summarizing the results if the test run
+        /// takes longer than `branchless.test.notifyThreshold` seconds (default
+        /// 30). Can also be enabled with `branchless.test.notify`.
+        #[clap(long = "notify")]
+        notify: bool,
+
+        /// A command to run once before running the test command on any
+        /// commits, such as to download shared fixtures. If it fails, then no
+        /// tests are run. The setup command is always run once in the main
+        /// repository's working copy, even when using `--strategy worktree`
+        /// with multiple jobs; it is not run in each worktree. Its output is
+        /// written to `.git/branchless/test/setup`. Defaults to the value of
+        /// `branchless.test.setup`.
+        #[clap(value_parser, long = "setup", value_name = "COMMAND")]
+        setup: Option<String>,
+
+        /// Capture the stdout and stderr of the test command together in a
+        /// single file, so that their relative ordering is preserved.
+        #[clap(long = "combined-output")]
+        combined_output: bool,
+
+        /// Print the output of the test command to the terminal as it runs, in
+        /// addition to capturing it. Can only be used when running a single job
+        /// at a time.
+        #[clap(long = "stream", conflicts_with("format"))]
+        stream: bool,
+
+        /// Run the test command with a clean environment, rather than the
+        /// environment of the invoking shell. Only the `BRANCHLESS_TEST_*`
+        /// variables and the variables listed in the
+        /// `branchless.test.env.passthrough` config option (by default, `HOME`
+        /// and `PATH`) are set.
+        #[clap(long = "clean-env")]
+        clean_env: bool,
+
+        /// Set the environment variables listed in this file for the test
+        /// command. Each line should be of the form `KEY=VALUE`; blank lines,
+        /// `#` comments, and quoted values are supported.
+        #[clap(value_parser, long = "env-file", value_name = "PATH")]
+        env_file: Option<PathBuf>,
+
+        /// Run the test command in this directory, relative to the root of
+        /// the checked-out commit, rather than in the root itself. If the
+        /// directory doesn't exist in a commit, then the test command isn't
+        /// run for that commit.
+        #[clap(value_parser, long = "cwd", value_name = "PATH")]
+        cwd: Option<PathBuf>,
+
+        /// When using `--strategy worktree`, keep the worktree for each commit
+        /// on which the test command failed, so that it can be inspected
+        /// afterwards. The paths to the kept worktrees are printed in the
+        /// summary. They aren't removed automatically; run `git test gc` to
+        /// remove them.
+        #[clap(action, long = "keep-worktrees")]
+        keep_worktrees: bool,
+
+        /// When using `--strategy worktree`, share this many worktrees between
+        /// the jobs, rather than creating one worktree for each job. Must not
+        /// be greater than the number of jobs. Jobs wait for a free worktree
+        /// before running the test command, but can still read cached results
+        /// in the meantime. This saves disk space on large checkouts.
+        #[clap(value_parser, long = "worktree-count", value_name = "N")]
+        worktree_count: Option<usize>,
+
+        /// Extra arguments to pass to the test command, given after `--`. They
+        /// are appended to the command as positional parameters, so quoting is
+        /// preserved. If the command (or command alias) already references
+        /// `$@` or `$*`, then the arguments are substituted there instead of
+        /// being appended.
+        #[clap(value_parser, last = true, value_name = "ARGS")]
+        extra_args: Vec<String>,
     },
 
     /// Show the results of a set of previous test runs.
@@ -845,6 +1243,67 @@ pub enum TestSubcommand {
         /// Show the test output as well.
         #[clap(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
         verbosity: u8,
+
+        /// The number of lines to show at the start and end of the test output
+        /// when it's abbreviated. Defaults to the value of
+        /// `branchless.test.outputContextLines`, or 5.
+        #[clap(long = "context", value_name = "N")]
+        context: Option<usize>,
+
+        /// Only show the commits whose cached results are failures.
+        #[clap(long = "failed-only", conflicts_with_all(&["passed_only", "skipped_only"]))]
+        failed_only: bool,
+
+        /// Only show the commits whose cached results are passes.
+        #[clap(long = "passed-only", conflicts_with("skipped_only"))]
+        passed_only: bool,
+
+        /// Only show the commits whose cached results indicate that they were
+        /// skipped.
+        #[clap(long = "skipped-only")]
+        skipped_only: bool,
+
+        /// The directory in which test results are cached. Defaults to the
+        /// value of `branchless.test.cacheDir`, or to a directory inside the
+        /// repository's Git directory if that is not set.
+        #[clap(value_parser, long = "cache-dir", value_name = "PATH")]
+        cache_dir: Option<PathBuf>,
+    },
+
+    /// Run two commands on a set of commits and show the commits where their
+    /// results differ.
+    Compare {
+        /// The two ad-hoc commands to compare. Must be provided exactly twice.
+        #[clap(value_parser, short = 'x', long = "exec", required = true)]
+        exec: Vec<String>,
+
+        /// The set of commits to test.
+        #[clap(value_parser, default_value = "stack() | @")]
+        revset: Revset,
+
+        /// Options for resolving revset expressions.
+        #[clap(flatten)]
+        resolve_revset_options: ResolveRevsetOptions,
+
+        /// How to execute the tests.
+        #[clap(short = 's', long = "strategy")]
+        strategy: Option<TestExecutionStrategy>,
+
+        /// Don't read cached results when executing the test commands. The
+        /// test commands are always run, and their fresh results are written
+        /// to the cache.
+        #[clap(long = "no-cache")]
+        no_cache: bool,
+
+        /// The directory in which test results are cached. Defaults to the
+        /// value of `branchless.test.cacheDir`, or to a directory inside the
+        /// repository's Git directory if that is not set.
+        #[clap(value_parser, long = "cache-dir", value_name = "PATH")]
+        cache_dir: Option<PathBuf>,
+
+        /// How many jobs to execute in parallel. The value `0` indicates to use all CPUs.
+        #[clap(short = 'j', long = "jobs")]
+        jobs: Option<usize>,
     },
 
     /// Run a given command on a set of commits and present the successes and failures.
@@ -862,6 +1321,23 @@ pub enum TestSubcommand {
         #[clap(value_parser, short = 'n', long = "dry-run")]
         dry_run: bool,
 
+        /// Interactively select which of the changes produced by the command
+        /// to apply to each commit.
+        #[clap(action, short = 'i', long = "interactive")]
+        interactive: bool,
+
+        /// Print the changes that the command made to each commit before
+        /// applying them. Can be combined with `--dry-run` to only preview the
+        /// changes.
+        #[clap(action, long = "diff")]
+        diff: bool,
+
+        /// Only apply the changes that the command made to paths matching
+        /// this glob pattern, and discard its changes to any other paths. Can
+        /// be provided multiple times.
+        #[clap(value_parser, long = "pathspec", value_name = "GLOB")]
+        pathspec: Vec<String>,
+
         /// The set of commits to test.
         #[clap(value_parser, default_value = "stack()")]
         revset: Revset,
@@ -878,11 +1354,23 @@ pub enum TestSubcommand {
         #[clap(short = 's', long = "strategy")]
         strategy: Option<TestExecutionStrategy>,
 
-        /// Don't read or write to the cache when executing the test commands.
+        /// Don't read cached results when executing the test commands. The
+        /// test command is always run, and its fresh result is written to the
+        /// cache.
         #[clap(long = "no-cache")]
         no_cache: bool,
 
+        /// The directory in which test results are cached. Defaults to the
+        /// value of `branchless.test.cacheDir`, or to a directory inside the
+        /// repository's Git directory if that is not set.
+        #[clap(value_parser, long = "cache-dir", value_name = "PATH")]
+        cache_dir: Option<PathBuf>,
+
         /// How many jobs to execute in parallel. The value `0` indicates to use all CPUs.
+        /// By default, this is the number of physical cores, which suits
+        /// CPU-bound test commands. Set `branchless.test.jobsCountMode` to
+        /// `logical` to use the number of logical cores instead, which may be
+        /// faster for IO-bound test commands.
         #[clap(short = 'j', long = "jobs")]
         jobs: Option<usize>,
 
@@ -36,7 +36,7 @@ use lib::git::{
 };
 use lib::util::ExitCode;
 use rayon::ThreadPoolBuilder;
-use scm_record::{EventSource, RecordError, RecordState, Recorder};
+use scm_record::{EventSource, KeyBindings, RecordError, RecordState, Recorder, SelectedContents};
 use tracing::instrument;
 
 /// Commit changes in the working copy.
@@ -228,10 +228,10 @@ fn record_interactive(
     event_tx_id: EventTransactionId,
     message: Option<&str>,
 ) -> eyre::Result<ExitCode> {
+    let new_tree = snapshot.commit_unstaged.get_tree()?;
     let files = {
         let (effects, _progress) = effects.start_operation(OperationType::CalculateDiff);
         let old_tree = snapshot.commit_stage0.get_tree()?;
-        let new_tree = snapshot.commit_unstaged.get_tree()?;
         let diff = repo.get_diff_between_trees(
             &effects,
             Some(&old_tree),
@@ -243,7 +243,7 @@ fn record_interactive(
     };
     let record_state = RecordState { files };
 
-    let recorder = Recorder::new(record_state, EventSource::Crossterm);
+    let recorder = Recorder::new(record_state, EventSource::Crossterm, KeyBindings::default());
     let result = recorder.run();
     let RecordState { files: result } = match result {
         Ok(result) => result,
@@ -269,18 +269,46 @@ fn record_interactive(
 
     let update_index_script: Vec<UpdateIndexCommand> = result
         .into_iter()
-        .map(|file| -> eyre::Result<UpdateIndexCommand> {
+        .map(|file| -> eyre::Result<Option<UpdateIndexCommand>> {
+            let path = file.path.clone().into_owned();
             let (selected, _unselected) = file.get_selected_contents();
-            let oid = repo.create_blob_from_contents(selected.as_bytes())?;
-            let command = UpdateIndexCommand::Update {
-                path: file.path.clone().into_owned(),
-                stage: Stage::Stage0,
-                // TODO: use `FileMode::BlobExecutable` when appropriate.
-                mode: FileMode::Blob,
-                oid,
+            let command = match selected {
+                SelectedContents::Unchanged => return Ok(None),
+                // The whole change to a binary file was selected, so take the
+                // file as-is from the working copy.
+                SelectedContents::Binary {
+                    old_description: _,
+                    new_description: _,
+                } => match new_tree.get_path(&path)? {
+                    Some(entry) => UpdateIndexCommand::Update {
+                        path,
+                        stage: Stage::Stage0,
+                        mode: entry.get_filemode(),
+                        oid: entry.get_oid(),
+                    },
+                    None => UpdateIndexCommand::Delete { path },
+                },
+                SelectedContents::Present { contents } => {
+                    let oid = repo.create_blob_from_contents(contents.as_bytes())?;
+                    let mode = match file.get_file_mode() {
+                        Some(file_mode) => match FileMode::from(i32::try_from(file_mode)?) {
+                            // Newly-added files have no previous file mode.
+                            FileMode::Unreadable => FileMode::Blob,
+                            file_mode => file_mode,
+                        },
+                        None => FileMode::Blob,
+                    };
+                    UpdateIndexCommand::Update {
+                        path,
+                        stage: Stage::Stage0,
+                        mode,
+                        oid,
+                    }
+                }
             };
-            Ok(command)
+            Ok(Some(command))
         })
+        .flatten_ok()
         .try_collect()?;
     let index = repo.get_index()?;
     update_index(
@@ -1,13 +1,13 @@
 use bstr::ByteSlice;
 use eden_dag::nameset::hints::Hints;
 
+use lib::core::config::{get_test_abort_exit_code, get_test_cache_dir, get_test_skip_exit_code};
 use lib::core::dag::CommitSet;
 use lib::core::eventlog::{EventLogDb, EventReplayer};
 use lib::core::rewrite::find_rewrite_target;
 use lib::git::{
-    get_latest_test_command_path, get_test_tree_dir, Commit, MaybeZeroOid, Repo,
-    SerializedNonZeroOid, SerializedTestResult, TEST_ABORT_EXIT_CODE, TEST_INDETERMINATE_EXIT_CODE,
-    TEST_SUCCESS_EXIT_CODE,
+    get_test_tree_dir, read_latest_test_command, Commit, MaybeZeroOid, Repo, SerializedNonZeroOid,
+    SerializedTestResult, TEST_RESULT_SCHEMA_VERSION, TEST_SUCCESS_EXIT_CODE,
 };
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -522,7 +522,8 @@ fn fn_current(ctx: &mut Context, name: &str, args: &[Expr]) -> EvalResult {
 }
 
 fn read_all_test_results(repo: &Repo, commit: &Commit) -> Option<Vec<SerializedTestResult>> {
-    let commit_test_dir = get_test_tree_dir(repo, commit);
+    let cache_dir = get_test_cache_dir(repo).ok()?;
+    let commit_test_dir = get_test_tree_dir(&cache_dir, commit);
     let mut all_results = Vec::new();
     for dir in std::fs::read_dir(commit_test_dir).ok()? {
         let dir = dir.ok()?;
@@ -530,18 +531,16 @@ fn read_all_test_results(repo: &Repo, commit: &Commit) -> Option<Vec<SerializedT
             let result_path = dir.path().join("result");
             let result_contents = std::fs::read_to_string(result_path).ok()?;
             let result: SerializedTestResult = serde_json::from_str(&result_contents).ok()?;
-            all_results.push(result);
+            // Results written in a different format may not be interpreted
+            // correctly, so treat them as missing.
+            if result.schema_version == TEST_RESULT_SCHEMA_VERSION {
+                all_results.push(result);
+            }
         }
     }
     Some(all_results)
 }
 
-fn read_latest_test_command(repo: &Repo) -> Option<String> {
-    let latest_command_path = get_latest_test_command_path(repo);
-    let latest_command = std::fs::read_to_string(latest_command_path).ok()?;
-    Some(latest_command)
-}
-
 fn eval_test_command_pattern(
     ctx: &mut Context,
     name: &str,
@@ -575,6 +574,13 @@ fn fn_tests_passed(ctx: &mut Context, name: &str, args: &[Expr]) -> EvalResult {
                         head_commit_oid: _,
                         snapshot_tree_oid: _,
                         interactive: _,
+                        timeout_seconds: _,
+                        attempts: _,
+                        timestamp: _,
+                        duration_ms: _,
+                        skip_reason: _,
+                        combined_output: _,
+                        schema_version: _,
                     } = test_result;
                     exit_code == TEST_SUCCESS_EXIT_CODE && pattern.matches_text(&command)
                 });
@@ -586,6 +592,8 @@ fn fn_tests_passed(ctx: &mut Context, name: &str, args: &[Expr]) -> EvalResult {
 #[instrument]
 fn fn_tests_failed(ctx: &mut Context, name: &str, args: &[Expr]) -> EvalResult {
     let pattern = eval_test_command_pattern(ctx, name, args)?;
+    let skip_exit_code = get_test_skip_exit_code(ctx.repo).map_err(EvalError::OtherError)?;
+    let abort_exit_code = get_test_abort_exit_code(ctx.repo).map_err(EvalError::OtherError)?;
     make_pattern_matcher(
         ctx,
         name,
@@ -601,10 +609,17 @@ fn fn_tests_failed(ctx: &mut Context, name: &str, args: &[Expr]) -> EvalResult {
                         head_commit_oid: _,
                         snapshot_tree_oid: _,
                         interactive: _,
+                        timeout_seconds: _,
+                        attempts: _,
+                        timestamp: _,
+                        duration_ms: _,
+                        skip_reason: _,
+                        combined_output: _,
+                        schema_version: _,
                     } = test_result;
                     exit_code != TEST_SUCCESS_EXIT_CODE
-                        && exit_code != TEST_INDETERMINATE_EXIT_CODE
-                        && exit_code != TEST_ABORT_EXIT_CODE
+                        && exit_code != skip_exit_code
+                        && exit_code != abort_exit_code
                         && pattern.matches_text(&command)
                 });
             Ok(result)
@@ -630,6 +645,13 @@ fn fn_tests_fixable(ctx: &mut Context, name: &str, args: &[Expr]) -> EvalResult
                         head_commit_oid: _,
                         snapshot_tree_oid,
                         interactive: _,
+                        timeout_seconds: _,
+                        attempts: _,
+                        timestamp: _,
+                        duration_ms: _,
+                        skip_reason: _,
+                        combined_output: _,
+                        schema_version: _,
                     } = test_result;
                     exit_code == TEST_SUCCESS_EXIT_CODE
                         && pattern.matches_text(&command)
@@ -34,7 +34,7 @@ use lib::core::formatting::Pluralize;
 use lib::core::node_descriptors::{
     BranchesDescriptor, CommitMessageDescriptor, CommitOidDescriptor,
     DifferentialRevisionDescriptor, ObsolescenceExplanationDescriptor, Redactor,
-    RelativeTimeDescriptor,
+    RelativeTimeDescriptor, TestStatusDescriptor,
 };
 use lib::git::{GitRunInfo, Repo};
 
@@ -819,6 +819,7 @@ pub fn smartlog(
         references_snapshot.head_oid,
         &mut [
             &mut CommitOidDescriptor::new(true)?,
+            &mut TestStatusDescriptor::new(&repo)?,
             &mut RelativeTimeDescriptor::new(&repo, SystemTime::now())?,
             &mut ObsolescenceExplanationDescriptor::new(
                 &event_replayer,
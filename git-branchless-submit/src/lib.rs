@@ -193,28 +193,46 @@ fn submit(
     };
 
     let raw_test_options = RawTestOptions {
-        exec: Some("<dummy>".to_string()),
+        exec: vec!["<dummy>".to_string()],
         command: None,
         dry_run: false,
         strategy: execution_strategy,
         search: None,
         bisect: false,
         no_cache: true,
+        cache_dir: None,
         interactive: false,
         jobs: None,
         verbosity: Verbosity::None,
         apply_fixes: false,
+        limits: Default::default(),
+        scheduling: Default::default(),
+        process: Default::default(),
+        outcome: Default::default(),
+        report: Default::default(),
+        fix_selection: Default::default(),
     };
     let ResolvedTestOptions {
         command: _,
+        shell_command: _,
         execution_strategy,
         search_strategy: _,
         is_dry_run: _,
-        use_cache: _,
+        cache_mode: _,
+        cache_dir: _,
+        worktrees_dir: _,
         is_interactive: _,
         num_jobs,
         verbosity: _,
         fix_options: _,
+        limits: _,
+        scheduling: _,
+        process: _,
+        hooks: _,
+        outcome: _,
+        storage: _,
+        report: _,
+        fix_selection: _,
     } = {
         let now = SystemTime::now();
         let event_tx_id =
@@ -9,8 +9,8 @@ use std::time::SystemTime;
 
 use git_branchless_opts::Revset;
 use git_branchless_test::{
-    run_tests, FixInfo, ResolvedTestOptions, TestResults, TestStatus, TestingAbortedError,
-    Verbosity,
+    read_test_output, run_tests, FixInfo, ResolvedTestOptions, TestCacheMode, TestResults,
+    TestStatus, TestingAbortedError, Verbosity,
 };
 use itertools::Itertools;
 use lazy_static::lazy_static;
@@ -22,7 +22,10 @@ use lib::core::rewrite::{
     execute_rebase_plan, BuildRebasePlanError, BuildRebasePlanOptions, ExecuteRebasePlanOptions,
     ExecuteRebasePlanResult, RebasePlanBuilder, RebasePlanPermissions, RepoResource,
 };
-use lib::git::{Commit, GitRunInfo, MaybeZeroOid, NonZeroOid, Repo, RepoError};
+use lib::git::{
+    get_default_test_cache_dir, get_test_worktrees_dir, Commit, GitRunInfo, MaybeZeroOid,
+    NonZeroOid, Repo, RepoError,
+};
 use lib::util::ExitCode;
 use rayon::ThreadPoolBuilder;
 use regex::bytes::Regex;
@@ -288,18 +291,28 @@ impl Forge for PhabricatorForge<'_> {
             self.repo,
             self.event_log_db,
             event_tx_id,
-            self.revset,
             &commits,
             &ResolvedTestOptions {
+                shell_command: command.clone(),
                 command,
                 execution_strategy: *execution_strategy,
                 search_strategy: None,
                 is_dry_run: false,
-                use_cache: false,
+                cache_mode: TestCacheMode::Disabled,
+                cache_dir: get_default_test_cache_dir(self.repo),
+                worktrees_dir: get_test_worktrees_dir(self.repo),
                 is_interactive: false,
                 num_jobs: *num_jobs,
                 verbosity: Verbosity::None,
                 fix_options: Some((execute_options.clone(), permissions.clone())),
+                limits: Default::default(),
+                scheduling: Default::default(),
+                process: Default::default(),
+                hooks: Default::default(),
+                outcome: Default::default(),
+                storage: Default::default(),
+                report: Default::default(),
+                fix_selection: Default::default(),
             },
         )
         .map_err(|err| Error::ExecuteArcDiff { source: err })?
@@ -308,6 +321,8 @@ impl Forge for PhabricatorForge<'_> {
             search_bounds: _,
             test_outputs,
             testing_aborted_error,
+            stopped_after_failures: _,
+            interrupted,
         } = test_results;
         if let Some(testing_aborted_error) = testing_aborted_error {
             let TestingAbortedError {
@@ -324,6 +339,13 @@ impl Forge for PhabricatorForge<'_> {
             )?;
             return Ok(Err(ExitCode(1)));
         }
+        if interrupted {
+            writeln!(
+                self.effects.get_output_stream(),
+                "Uploading was interrupted"
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
 
         let rebase_plan = {
             let mut builder = RebasePlanBuilder::new(self.dag, permissions);
@@ -331,12 +353,17 @@ impl Forge for PhabricatorForge<'_> {
                 let head_commit_oid = match test_output.test_status {
                     test_status @ (TestStatus::CheckoutFailed
                     | TestStatus::SpawnTestFailed(_)
-                    | TestStatus::TerminatedBySignal
+                    | TestStatus::TerminatedBySignal { .. }
                     | TestStatus::AlreadyInProgress
                     | TestStatus::ReadCacheFailed(_)
                     | TestStatus::Indeterminate { .. }
+                    | TestStatus::SkippedDueToAncestor { .. }
+                    | TestStatus::SkippedEmpty
+                    | TestStatus::MergeParentConflicted
                     | TestStatus::Abort { .. }
-                    | TestStatus::Failed { .. }) => {
+                    | TestStatus::Timeout { .. }
+                    | TestStatus::Failed { .. }
+                    | TestStatus::PerfThresholdExceeded { .. }) => {
                         let commit = self.repo.find_commit_or_fail(commit_oid)?;
                         writeln!(
                             self.effects.get_output_stream(),
@@ -347,9 +374,11 @@ impl Forge for PhabricatorForge<'_> {
                                 false
                             )?)?,
                         )?;
-                        let stdout = std::fs::read_to_string(&test_output.stdout_path)?;
+                        let stdout =
+                            String::from_utf8(read_test_output(&test_output.stdout_path)?)?;
                         write!(self.effects.get_output_stream(), "Stdout:\n{stdout}")?;
-                        let stderr = std::fs::read_to_string(&test_output.stderr_path)?;
+                        let stderr =
+                            String::from_utf8(read_test_output(&test_output.stderr_path)?)?;
                         write!(self.effects.get_output_stream(), "Stderr:\n{stderr}")?;
                         return Ok(Err(ExitCode(1)));
                     }
@@ -361,6 +390,7 @@ impl Forge for PhabricatorForge<'_> {
                                 snapshot_tree_oid: _,
                             },
                         interactive: _,
+                        attempts: _,
                     } => head_commit_oid,
                 };
 
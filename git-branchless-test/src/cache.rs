@@ -0,0 +1,701 @@
+//! Management of the test cache and testing worktrees: cleaning, reporting
+//! disk usage, evicting old results, and garbage-collecting worktrees.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use eyre::WrapErr;
+use fslock::LockFile;
+use lib::core::config::get_test_exit_codes;
+use lib::core::dag::{sorted_commit_set, CommitSet, Dag};
+use lib::core::effects::Effects;
+use lib::core::eventlog::{EventLogDb, EventReplayer};
+use lib::core::formatting::Pluralize;
+use lib::core::repo_ext::RepoExt;
+use lib::git::{
+    get_test_locks_dir, get_test_tree_dir, ConfigRead, GitRunInfo, GitRunResult, MaybeZeroOid,
+    NonZeroOid, Repo,
+};
+use lib::util::ExitCode;
+use tracing::{debug, instrument};
+
+use git_branchless_opts::{ResolveRevsetOptions, Revset, TestExecutionStrategy};
+use git_branchless_revset::resolve_commits;
+
+use crate::log::read_cached_test_results;
+use crate::worker::WorkerId;
+use crate::{
+    get_working_directory_lock_path, prune_deduped_test_output, resolve_test_cache_dir,
+    resolve_test_worktrees_dir, TestStatusCategory,
+};
+
+/// Delete cached test output for the commits in `revset` (or for all commits,
+/// if `all` is set).
+#[instrument]
+pub fn subcommand_clean(
+    effects: &Effects,
+    revset: Revset,
+    resolve_revset_options: &ResolveRevsetOptions,
+    failed_only: bool,
+    passed_only: bool,
+    all: bool,
+    dry_run: bool,
+    cache_dir: Option<&Path>,
+) -> eyre::Result<ExitCode> {
+    let repo = Repo::from_current_dir()?;
+    if all {
+        let cache_dir = resolve_test_cache_dir(effects, &repo, cache_dir)?;
+        return clean_all_test_results(effects, &cache_dir, dry_run);
+    }
+
+    let conn = repo.get_db_conn()?;
+    let event_log_db = EventLogDb::new(&conn)?;
+    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
+    let event_cursor = event_replayer.make_default_cursor();
+    let references_snapshot = repo.get_references_snapshot()?;
+    let mut dag = Dag::open_and_sync(
+        effects,
+        &repo,
+        &event_replayer,
+        event_cursor,
+        &references_snapshot,
+    )?;
+
+    let commit_set =
+        match resolve_commits(effects, &repo, &mut dag, &[revset], resolve_revset_options) {
+            Ok(mut commit_sets) => commit_sets.pop().unwrap(),
+            Err(err) => {
+                err.describe(effects)?;
+                return Ok(ExitCode(1));
+            }
+        };
+
+    let cache_dir = resolve_test_cache_dir(effects, &repo, cache_dir)?;
+    let exit_codes = get_test_exit_codes(&repo)?;
+    let cleaning = if dry_run { "Would clean" } else { "Cleaning" };
+    let mut num_cleaned_commits = 0;
+    for commit in sorted_commit_set(&repo, &dag, &commit_set)? {
+        let tree_dir = get_test_tree_dir(&cache_dir, &commit);
+        if failed_only || passed_only {
+            // Only remove the directories for the matching commands, since
+            // the tree directory holds the results for all commands.
+            let mut command_dirs = Vec::new();
+            for (result_path, result) in read_cached_test_results(&tree_dir)? {
+                let category = match result {
+                    Ok(result) => result.get_category(&exit_codes),
+                    Err(err) => {
+                        debug!(?err, ?result_path, "Not cleaning unreadable test result");
+                        continue;
+                    }
+                };
+                let is_matching = match category {
+                    TestStatusCategory::Passed => passed_only,
+                    TestStatusCategory::Failed => failed_only,
+                    TestStatusCategory::Skipped => false,
+                };
+                if is_matching {
+                    if let Some(command_dir) = result_path.parent() {
+                        command_dirs.push(command_dir.to_owned());
+                    }
+                }
+            }
+
+            if command_dirs.is_empty() {
+                writeln!(
+                    effects.get_output_stream(),
+                    "Nothing to clean for {}",
+                    effects
+                        .get_glyphs()
+                        .render(commit.friendly_describe(effects.get_glyphs())?)?,
+                )?;
+                continue;
+            }
+            writeln!(
+                effects.get_output_stream(),
+                "{cleaning} {} results for {}",
+                if failed_only { "failed" } else { "passed" },
+                effects
+                    .get_glyphs()
+                    .render(commit.friendly_describe(effects.get_glyphs())?)?,
+            )?;
+            if !dry_run {
+                for command_dir in command_dirs {
+                    std::fs::remove_dir_all(&command_dir)
+                        .with_context(|| format!("Cleaning test dir: {command_dir:?}"))?;
+                }
+            }
+            num_cleaned_commits += 1;
+        } else if tree_dir.exists() {
+            writeln!(
+                effects.get_output_stream(),
+                "{cleaning} results for {}",
+                effects
+                    .get_glyphs()
+                    .render(commit.friendly_describe(effects.get_glyphs())?)?,
+            )?;
+            if !dry_run {
+                std::fs::remove_dir_all(&tree_dir)
+                    .with_context(|| format!("Cleaning test dir: {tree_dir:?}"))?;
+            }
+            num_cleaned_commits += 1;
+        } else {
+            writeln!(
+                effects.get_output_stream(),
+                "Nothing to clean for {}",
+                effects
+                    .get_glyphs()
+                    .render(commit.friendly_describe(effects.get_glyphs())?)?,
+            )?;
+        }
+    }
+    if !dry_run {
+        prune_deduped_test_output(&cache_dir)?;
+    }
+    print_num_cleaned(effects, num_cleaned_commits, dry_run)?;
+    Ok(ExitCode(0))
+}
+
+/// Delete the cached test output for every commit tree in `cache_dir`, except
+/// for the trees which have a test command currently running on them.
+#[instrument]
+fn clean_all_test_results(
+    effects: &Effects,
+    cache_dir: &Path,
+    dry_run: bool,
+) -> eyre::Result<ExitCode> {
+    // The cache directory may also contain the worktrees and locks (which is
+    // the case by default), so only remove the directories named after trees.
+    let mut tree_dirs = Vec::new();
+    if cache_dir.exists() {
+        for entry in std::fs::read_dir(cache_dir)
+            .wrap_err_with(|| format!("Reading test cache dir: {cache_dir:?}"))?
+        {
+            let entry = entry?;
+            let tree_oid: Option<NonZeroOid> = entry
+                .file_name()
+                .to_str()
+                .and_then(|file_name| file_name.parse().ok());
+            if let Some(tree_oid) = tree_oid {
+                tree_dirs.push((tree_oid, entry.path()));
+            }
+        }
+    }
+    tree_dirs.sort();
+
+    let cleaning = if dry_run { "Would clean" } else { "Cleaning" };
+    let mut num_cleaned_trees = 0;
+    for (tree_oid, tree_dir) in tree_dirs {
+        // Hold the lock for each command while removing the tree directory,
+        // so that a result can't be removed while it's being written.
+        let _lock_files = match lock_test_tree_dir(&tree_dir)? {
+            Some(lock_files) => lock_files,
+            None => {
+                writeln!(
+                    effects.get_output_stream(),
+                    "Skipping results which are in use for tree {tree_oid}"
+                )?;
+                continue;
+            }
+        };
+
+        writeln!(
+            effects.get_output_stream(),
+            "{cleaning} results for tree {tree_oid}"
+        )?;
+        if !dry_run {
+            std::fs::remove_dir_all(&tree_dir)
+                .with_context(|| format!("Cleaning test dir: {tree_dir:?}"))?;
+        }
+        num_cleaned_trees += 1;
+    }
+    if !dry_run {
+        prune_deduped_test_output(cache_dir)?;
+    }
+    print_num_cleaned(effects, num_cleaned_trees, dry_run)?;
+    Ok(ExitCode(0))
+}
+
+/// Take the lock for each test command whose results are cached in
+/// `tree_dir`. Returns `None` if any of the locks is held by a running test
+/// command, in which case the directory shouldn't be removed.
+fn lock_test_tree_dir(tree_dir: &Path) -> eyre::Result<Option<Vec<LockFile>>> {
+    let mut lock_files = Vec::new();
+    for entry in std::fs::read_dir(tree_dir)
+        .wrap_err_with(|| format!("Reading test tree dir: {tree_dir:?}"))?
+    {
+        let lock_path = entry?.path().join("pid.lock");
+        if !lock_path.exists() {
+            continue;
+        }
+        let mut lock_file = LockFile::open(&lock_path)
+            .wrap_err_with(|| format!("Opening lock file {lock_path:?}"))?;
+        if !lock_file
+            .try_lock_with_pid()
+            .wrap_err_with(|| format!("Locking file {lock_path:?}"))?
+        {
+            return Ok(None);
+        }
+        lock_files.push(lock_file);
+    }
+    Ok(Some(lock_files))
+}
+
+fn print_num_cleaned(effects: &Effects, num_cleaned: usize, dry_run: bool) -> eyre::Result<()> {
+    writeln!(
+        effects.get_output_stream(),
+        "{} {}.",
+        if dry_run { "Would clean" } else { "Cleaned" },
+        Pluralize {
+            determiner: None,
+            amount: num_cleaned,
+            unit: ("cached test result", "cached test results")
+        }
+    )?;
+    Ok(())
+}
+
+/// The number of largest cached commit trees to show in `git test du`.
+const NUM_LARGEST_CACHED_TREES: usize = 5;
+
+/// Report the disk space used by the test cache, broken down into the cached
+/// results, the testing worktrees, and the locks.
+#[instrument]
+pub(crate) fn subcommand_du(effects: &Effects, cache_dir: Option<&Path>) -> eyre::Result<ExitCode> {
+    let repo = Repo::from_current_dir()?;
+    let cache_dir = resolve_test_cache_dir(effects, &repo, cache_dir)?;
+
+    // The cache directory may also contain the worktrees and locks (which is
+    // the case by default), so only count the directories named after trees.
+    let mut seen_files = HashSet::new();
+    let mut tree_dir_sizes = Vec::new();
+    if cache_dir.exists() {
+        for entry in std::fs::read_dir(&cache_dir)
+            .wrap_err_with(|| format!("Reading test cache dir: {cache_dir:?}"))?
+        {
+            let entry = entry?;
+            let tree_oid: Option<NonZeroOid> = entry
+                .file_name()
+                .to_str()
+                .and_then(|file_name| file_name.parse().ok());
+            if let Some(tree_oid) = tree_oid {
+                tree_dir_sizes.push((tree_oid, get_dir_size(&entry.path(), &mut seen_files)));
+            }
+        }
+    }
+    tree_dir_sizes.sort_by(|(lhs_oid, lhs_size), (rhs_oid, rhs_size)| {
+        rhs_size.cmp(lhs_size).then_with(|| lhs_oid.cmp(rhs_oid))
+    });
+    let cache_size: u64 = tree_dir_sizes.iter().map(|(_, size)| size).sum();
+
+    let worktrees_dir = resolve_test_worktrees_dir(effects, &repo)?;
+    let num_worktrees = match std::fs::read_dir(&worktrees_dir) {
+        Ok(entries) => entries.count(),
+        Err(_) => 0,
+    };
+    let worktrees_size = get_dir_size(&worktrees_dir, &mut seen_files);
+    let locks_size = get_dir_size(&get_test_locks_dir(&repo), &mut seen_files);
+
+    writeln!(
+        effects.get_output_stream(),
+        "Cached test results: {}, {}",
+        Pluralize {
+            determiner: None,
+            amount: tree_dir_sizes.len(),
+            unit: ("commit tree", "commit trees"),
+        },
+        format_size(cache_size),
+    )?;
+    for (tree_oid, size) in tree_dir_sizes.iter().take(NUM_LARGEST_CACHED_TREES) {
+        writeln!(
+            effects.get_output_stream(),
+            "{} {tree_oid}: {}",
+            effects.get_glyphs().bullet_point,
+            format_size(*size),
+        )?;
+    }
+    writeln!(
+        effects.get_output_stream(),
+        "Testing worktrees: {}, {} (remove unused worktrees with: git test gc)",
+        Pluralize {
+            determiner: None,
+            amount: num_worktrees,
+            unit: ("worktree", "worktrees"),
+        },
+        format_size(worktrees_size),
+    )?;
+    writeln!(
+        effects.get_output_stream(),
+        "Locks: {}",
+        format_size(locks_size)
+    )?;
+    writeln!(
+        effects.get_output_stream(),
+        "Total: {}",
+        format_size(cache_size + worktrees_size + locks_size)
+    )?;
+    Ok(ExitCode(0))
+}
+
+/// The limits on the size of the test cache, beyond which the least recently
+/// tested commit trees are evicted at the end of `git test run`.
+#[derive(Debug)]
+pub(crate) struct TestCacheLimits {
+    /// The maximum total size of the cached results, in bytes.
+    max_size: Option<u64>,
+
+    /// The maximum time since a commit tree was last tested.
+    max_age: Option<Duration>,
+}
+
+/// Read the configured `TestCacheLimits`.
+#[instrument]
+pub(crate) fn get_test_cache_limits(
+    effects: &Effects,
+    repo: &Repo,
+) -> eyre::Result<Result<TestCacheLimits, ExitCode>> {
+    let config = repo.get_readonly_config()?;
+
+    let max_size_config_key = "branchless.test.cacheMaxSize";
+    let max_size: Option<String> = config.get(max_size_config_key)?;
+    let max_size = match max_size {
+        None => None,
+        Some(max_size) => match parse_size(&max_size) {
+            Some(max_size) => Some(max_size),
+            None => {
+                writeln!(
+                    effects.get_output_stream(),
+                    "Invalid value for config value for {max_size_config_key} ({max_size}): expected a number of bytes, optionally followed by k, m, or g"
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+        },
+    };
+
+    let max_age_config_key = "branchless.test.cacheMaxAge";
+    let max_age_days: Option<i32> = config.get(max_age_config_key)?;
+    let max_age = match max_age_days {
+        None => None,
+        Some(max_age_days) => match u64::try_from(max_age_days) {
+            Ok(max_age_days) => Some(Duration::from_secs(max_age_days * 24 * 60 * 60)),
+            Err(err) => {
+                writeln!(
+                    effects.get_output_stream(),
+                    "Invalid value for config value for {max_age_config_key} ({max_age_days}): {err}"
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+        },
+    };
+
+    Ok(Ok(TestCacheLimits { max_size, max_age }))
+}
+
+/// Parse a size in bytes, which may have a `k`, `m`, or `g` suffix (as in Git
+/// configuration values).
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (number, multiplier) = match size.chars().last()?.to_ascii_lowercase() {
+        'k' => (&size[..size.len() - 1], 1024),
+        'm' => (&size[..size.len() - 1], 1024 * 1024),
+        'g' => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Remove the cached results for the least recently tested commit trees until
+/// the cache is within the given limits. The trees of the commits in
+/// `commit_set` and of the draft commits are never evicted. Returns the
+/// evicted trees and their sizes.
+#[instrument]
+pub(crate) fn evict_test_cache(
+    repo: &Repo,
+    dag: &Dag,
+    cache_dir: &Path,
+    commit_set: &CommitSet,
+    cache_limits: &TestCacheLimits,
+) -> eyre::Result<Vec<(NonZeroOid, u64)>> {
+    let TestCacheLimits { max_size, max_age } = cache_limits;
+    if (max_size.is_none() && max_age.is_none()) || !cache_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut protected_tree_oids = HashSet::new();
+    let working_set = commit_set.union(dag.query_draft_commits()?);
+    for commit_oid in dag.commit_set_to_vec(&working_set)? {
+        if let Some(commit) = repo.find_commit(commit_oid)? {
+            protected_tree_oids.insert(commit.get_tree_oid());
+        }
+    }
+
+    let mut seen_files = HashSet::new();
+    let mut total_size = 0;
+    let mut candidates = Vec::new();
+    for entry in std::fs::read_dir(cache_dir)
+        .wrap_err_with(|| format!("Reading test cache dir: {cache_dir:?}"))?
+    {
+        let entry = entry?;
+        let tree_oid: Option<NonZeroOid> = entry
+            .file_name()
+            .to_str()
+            .and_then(|file_name| file_name.parse().ok());
+        let tree_oid = match tree_oid {
+            Some(tree_oid) => tree_oid,
+            None => continue,
+        };
+        let tree_dir = entry.path();
+        let size = get_dir_size(&tree_dir, &mut seen_files);
+        total_size += size;
+        if protected_tree_oids.contains(&MaybeZeroOid::NonZero(tree_oid)) {
+            continue;
+        }
+        candidates.push((get_last_tested_time(&tree_dir), tree_oid, tree_dir, size));
+    }
+    // Evict the least recently tested trees first.
+    candidates.sort();
+
+    let now = SystemTime::now();
+    let mut evicted_trees = Vec::new();
+    for (last_tested_time, tree_oid, tree_dir, size) in candidates {
+        let is_too_old = match (max_age, now.duration_since(last_tested_time)) {
+            (Some(max_age), Ok(age)) => age >= *max_age,
+            (Some(_), Err(_)) | (None, _) => false,
+        };
+        let is_too_large = match max_size {
+            Some(max_size) => total_size > *max_size,
+            None => false,
+        };
+        if !is_too_old && !is_too_large {
+            continue;
+        }
+
+        // Take the same locks as `git test clean --all`, so that results
+        // which are being written by a concurrent test run aren't evicted.
+        let _lock_files = match lock_test_tree_dir(&tree_dir)? {
+            Some(lock_files) => lock_files,
+            None => continue,
+        };
+        std::fs::remove_dir_all(&tree_dir)
+            .wrap_err_with(|| format!("Evicting test cache dir: {tree_dir:?}"))?;
+        total_size -= size;
+        evicted_trees.push((tree_oid, size));
+    }
+    if !evicted_trees.is_empty() {
+        prune_deduped_test_output(cache_dir)?;
+    }
+    Ok(evicted_trees)
+}
+
+/// Get the last time that a test command finished running on the tree whose
+/// results are cached in `tree_dir`, according to the modification times of
+/// its result files.
+fn get_last_tested_time(tree_dir: &Path) -> SystemTime {
+    let result_modified_times = std::fs::read_dir(tree_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| std::fs::metadata(entry.path().join("result")).ok())
+        .filter_map(|metadata| metadata.modified().ok());
+    result_modified_times
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Print the commit trees which were evicted by `evict_test_cache`.
+pub(crate) fn print_evicted_trees(
+    effects: &Effects,
+    evicted_trees: &[(NonZeroOid, u64)],
+) -> eyre::Result<()> {
+    if evicted_trees.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(
+        effects.get_output_stream(),
+        "Evicted {} from the test cache, reclaiming {}:",
+        Pluralize {
+            determiner: None,
+            amount: evicted_trees.len(),
+            unit: ("commit tree", "commit trees"),
+        },
+        format_size(evicted_trees.iter().map(|(_, size)| size).sum()),
+    )?;
+    for (tree_oid, _size) in evicted_trees {
+        writeln!(
+            effects.get_output_stream(),
+            "{} {tree_oid}",
+            effects.get_glyphs().bullet_point,
+        )?;
+    }
+    Ok(())
+}
+
+/// Remove the testing worktrees which aren't in use by a running test command,
+/// and prune their administrative files from the repository.
+#[instrument]
+pub(crate) fn subcommand_gc(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+) -> eyre::Result<ExitCode> {
+    let repo = Repo::from_current_dir()?;
+    let worktrees_dir = resolve_test_worktrees_dir(effects, &repo)?;
+
+    let mut worktree_dirs = Vec::new();
+    let mut kept_worktree_dirs = Vec::new();
+    if worktrees_dir.exists() {
+        for entry in std::fs::read_dir(&worktrees_dir)
+            .wrap_err_with(|| format!("Reading worktrees dir: {worktrees_dir:?}"))?
+        {
+            let entry = entry?;
+            let worker_id: Option<WorkerId> = entry
+                .file_name()
+                .to_str()
+                .and_then(|file_name| file_name.strip_prefix("testing-worktree-"))
+                .and_then(|worker_id| worker_id.parse().ok());
+            if let Some(worker_id) = worker_id {
+                worktree_dirs.push((worker_id, entry.path()));
+            } else if entry
+                .file_name()
+                .to_str()
+                .map_or(false, |file_name| file_name.starts_with("kept-worktree-"))
+            {
+                kept_worktree_dirs.push(entry.path());
+            }
+        }
+    }
+    worktree_dirs.sort();
+    kept_worktree_dirs.sort();
+
+    let test_lock_dir_path = get_test_locks_dir(&repo);
+    std::fs::create_dir_all(&test_lock_dir_path)
+        .wrap_err_with(|| format!("Creating test lock dir path: {test_lock_dir_path:?}"))?;
+    let mut num_removed_worktrees = 0;
+    let mut num_reclaimed_bytes = 0;
+    for (worker_id, worktree_dir) in worktree_dirs {
+        // Hold the same lock as `prepare_working_directory` while removing the
+        // worktree, so that it can't be removed out from under a running job.
+        let lock_path =
+            get_working_directory_lock_path(&repo, TestExecutionStrategy::Worktree, worker_id);
+        let mut lock_file = LockFile::open(&lock_path)
+            .wrap_err_with(|| format!("Opening worktree lock at {lock_path:?}"))?;
+        if !lock_file
+            .try_lock_with_pid()
+            .wrap_err_with(|| format!("Locking worktree with {lock_path:?}"))?
+        {
+            writeln!(
+                effects.get_output_stream(),
+                "Skipping testing worktree which is in use: {}",
+                worktree_dir.display(),
+            )?;
+            continue;
+        }
+
+        num_reclaimed_bytes += get_dir_size(&worktree_dir, &mut HashSet::new());
+        std::fs::remove_dir_all(&worktree_dir)
+            .wrap_err_with(|| format!("Removing testing worktree: {worktree_dir:?}"))?;
+        lock_file
+            .unlock()
+            .wrap_err_with(|| format!("Unlocking worktree lock at {lock_path:?}"))?;
+        num_removed_worktrees += 1;
+    }
+
+    // Worktrees kept by `--keep-worktrees` aren't used by any worker, so they
+    // can be removed without taking a lock.
+    for kept_worktree_dir in kept_worktree_dirs {
+        num_reclaimed_bytes += get_dir_size(&kept_worktree_dir, &mut HashSet::new());
+        std::fs::remove_dir_all(&kept_worktree_dir)
+            .wrap_err_with(|| format!("Removing kept testing worktree: {kept_worktree_dir:?}"))?;
+        num_removed_worktrees += 1;
+    }
+
+    // Remove Git's administrative files for the deleted worktrees.
+    let GitRunResult {
+        exit_code: _,
+        stdout: _,
+        stderr: _,
+    } = git_run_info
+        .run_silent(&repo, None, &["worktree", "prune"], Default::default())
+        .wrap_err("Pruning worktrees")?;
+
+    if num_removed_worktrees == 0 {
+        writeln!(
+            effects.get_output_stream(),
+            "No testing worktrees to remove."
+        )?;
+    } else {
+        writeln!(
+            effects.get_output_stream(),
+            "Removed {}, reclaiming {}.",
+            Pluralize {
+                determiner: None,
+                amount: num_removed_worktrees,
+                unit: ("testing worktree", "testing worktrees"),
+            },
+            format_size(num_reclaimed_bytes),
+        )?;
+    }
+    Ok(ExitCode(0))
+}
+
+/// Get the total size of the files in the given directory, not following
+/// symlinks. Files which can't be read are ignored. A file with several hard
+/// links (such as output deduplicated with `branchless.test.dedupeOutput`) is
+/// only counted the first time it's encountered, as recorded by its device and
+/// inode numbers in `seen_files`.
+fn get_dir_size(path: &Path, seen_files: &mut HashSet<(u64, u64)>) -> u64 {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if metadata.nlink() > 1 && !seen_files.insert((metadata.dev(), metadata.ino())) {
+                return 0;
+            }
+        }
+        return metadata.len();
+    }
+    match std::fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| get_dir_size(&entry.path(), seen_files))
+            .sum(),
+        Err(_) => 0,
+    }
+}
+
+/// Format a number of bytes for display, such as `1.5 MiB`.
+fn format_size(num_bytes: u64) -> String {
+    if num_bytes < 1024 {
+        return format!("{num_bytes} B");
+    }
+    let mut size = num_bytes as f64 / 1024.0;
+    for unit in ["KiB", "MiB", "GiB"] {
+        if size < 1024.0 {
+            return format!("{size:.1} {unit}");
+        }
+        size /= 1024.0;
+    }
+    format!("{size:.1} TiB")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0"), Some(0));
+        assert_eq!(parse_size("123"), Some(123));
+        assert_eq!(parse_size("2k"), Some(2 * 1024));
+        assert_eq!(parse_size("3M"), Some(3 * 1024 * 1024));
+        assert_eq!(parse_size("1g"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("k"), None);
+        assert_eq!(parse_size("1.5g"), None);
+    }
+}
@@ -0,0 +1,78 @@
+//! Limit the amount of test command output which is written to the cache, so
+//! that a misbehaving test command can't fill up the disk.
+
+use std::io::Write;
+
+/// The marker appended to the output once the limit has been reached.
+const TRUNCATION_MARKER: &[u8] = b"\n<output truncated>\n";
+
+/// Forwards writes to the inner writer until `limit` bytes have been written,
+/// after which the rest of the output is discarded and a truncation marker is
+/// written in its place.
+#[derive(Debug)]
+pub(crate) struct CappedWriter<W> {
+    inner: W,
+    limit: Option<u64>,
+    num_bytes_written: u64,
+    is_truncated: bool,
+}
+
+impl<W: Write> CappedWriter<W> {
+    /// Constructor. If `limit` is `None`, then all output is written.
+    pub fn new(inner: W, limit: Option<u64>) -> Self {
+        Self {
+            inner,
+            limit,
+            num_bytes_written: 0,
+            is_truncated: false,
+        }
+    }
+}
+
+impl<W: Write> Write for CappedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return self.inner.write(buf),
+        };
+
+        let num_bytes_remaining = limit.saturating_sub(self.num_bytes_written);
+        let num_bytes_to_write = usize::try_from(num_bytes_remaining)
+            .unwrap_or(usize::MAX)
+            .min(buf.len());
+        self.inner.write_all(&buf[..num_bytes_to_write])?;
+        self.num_bytes_written += num_bytes_to_write as u64;
+        if num_bytes_to_write < buf.len() && !self.is_truncated {
+            self.inner.write_all(TRUNCATION_MARKER)?;
+            self.is_truncated = true;
+        }
+
+        // Report the discarded bytes as written, so that callers keep
+        // draining the output of the test command.
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capped_writer() -> std::io::Result<()> {
+        let mut writer = CappedWriter::new(Vec::new(), Some(5));
+        writer.write_all(b"abc")?;
+        writer.write_all(b"defgh")?;
+        writer.write_all(b"ijk")?;
+        assert_eq!(writer.inner, b"abcde\n<output truncated>\n");
+
+        let mut writer = CappedWriter::new(Vec::new(), None);
+        writer.write_all(b"abcdefgh")?;
+        assert_eq!(writer.inner, b"abcdefgh");
+
+        Ok(())
+    }
+}
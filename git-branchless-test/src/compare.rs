@@ -0,0 +1,207 @@
+//! Comparison of the results of two test commands on the same commits.
+
+use std::fmt::Write;
+use std::time::{Instant, SystemTime};
+
+use cursive::theme::Effect;
+use lib::core::dag::{sorted_commit_set, Dag};
+use lib::core::effects::Effects;
+use lib::core::eventlog::{EventLogDb, EventReplayer};
+use lib::core::formatting::{Pluralize, StyledStringBuilder};
+use lib::core::repo_ext::RepoExt;
+use lib::git::{GitRunInfo, Repo};
+use lib::util::ExitCode;
+use tracing::instrument;
+
+use git_branchless_opts::{ResolveRevsetOptions, Revset};
+use git_branchless_revset::resolve_commits;
+
+use crate::{
+    print_summary, run_tests_with_abort_trap, RawTestOptions, ResolvedTestOptions,
+    TestStatusCategory,
+};
+
+/// Run both of the commands in `options` on the commits in `revset`, and print
+/// the commits on which their results differ.
+#[instrument]
+pub(crate) fn subcommand_compare(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    options: &RawTestOptions,
+    revset: Revset,
+    resolve_revset_options: &ResolveRevsetOptions,
+) -> eyre::Result<ExitCode> {
+    let (command_a, command_b) = match options.exec.as_slice() {
+        [command_a, command_b] => (command_a, command_b),
+        _ => {
+            writeln!(
+                effects.get_output_stream(),
+                "Exactly two commands must be provided with --exec to compare."
+            )?;
+            return Ok(ExitCode(1));
+        }
+    };
+
+    let now = SystemTime::now();
+    let repo = Repo::from_current_dir()?;
+    let conn = repo.get_db_conn()?;
+    let event_log_db = EventLogDb::new(&conn)?;
+    let event_tx_id = event_log_db.make_transaction_id(now, "test compare")?;
+    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
+    let event_cursor = event_replayer.make_default_cursor();
+    let references_snapshot = repo.get_references_snapshot()?;
+    let mut dag = Dag::open_and_sync(
+        effects,
+        &repo,
+        &event_replayer,
+        event_cursor,
+        &references_snapshot,
+    )?;
+
+    let commit_set = match resolve_commits(
+        effects,
+        &repo,
+        &mut dag,
+        &[revset.clone()],
+        resolve_revset_options,
+    ) {
+        Ok(mut commit_sets) => commit_sets.pop().unwrap(),
+        Err(err) => {
+            err.describe(effects)?;
+            return Ok(ExitCode(1));
+        }
+    };
+    let commits = sorted_commit_set(&repo, &dag, &commit_set)?;
+
+    let mut all_test_results = Vec::new();
+    for command in [command_a, command_b] {
+        let options = RawTestOptions {
+            exec: vec![command.clone()],
+            ..options.clone()
+        };
+        let options = match ResolvedTestOptions::resolve(
+            now,
+            effects,
+            &dag,
+            &repo,
+            event_tx_id,
+            &commit_set,
+            None,
+            &options,
+        )? {
+            Ok(options) => options,
+            Err(exit_code) => return Ok(exit_code),
+        };
+
+        let start_time = Instant::now();
+        let test_results = match run_tests_with_abort_trap(
+            now,
+            effects,
+            git_run_info,
+            &dag,
+            &repo,
+            &event_log_db,
+            event_tx_id,
+            commits.clone(),
+            &options,
+        )? {
+            Ok(test_results) => test_results,
+            Err(exit_code) => return Ok(exit_code),
+        };
+        if test_results.testing_aborted_error.is_some() || test_results.interrupted {
+            return print_summary(
+                effects,
+                &dag,
+                &repo,
+                &revset,
+                &test_results,
+                &options,
+                start_time.elapsed(),
+            );
+        }
+        all_test_results.push(test_results);
+    }
+    let (results_a, results_b) = (&all_test_results[0], &all_test_results[1]);
+
+    for (label, command) in [("A", command_a), ("B", command_b)] {
+        writeln!(
+            effects.get_output_stream(),
+            "Command {label}: {}",
+            effects.get_glyphs().render(
+                StyledStringBuilder::new()
+                    .append_styled(command, Effect::Bold)
+                    .build()
+            )?,
+        )?;
+    }
+
+    let mut differing_commits = Vec::new();
+    for commit in &commits {
+        let commit_oid = commit.get_oid();
+        let (test_output_a, test_output_b) = match (
+            results_a.test_outputs.get(&commit_oid),
+            results_b.test_outputs.get(&commit_oid),
+        ) {
+            (Some(test_output_a), Some(test_output_b)) => (test_output_a, test_output_b),
+            _ => continue,
+        };
+        // Only compare the overall outcome (passed, failed, or skipped), so
+        // that e.g. a cached and a fresh result can still be equal.
+        if test_output_a.test_status.get_category() != test_output_b.test_status.get_category() {
+            differing_commits.push((commit, test_output_a, test_output_b));
+        }
+    }
+
+    if differing_commits.is_empty() {
+        writeln!(
+            effects.get_output_stream(),
+            "No differences in results on {}.",
+            Pluralize {
+                determiner: None,
+                amount: commits.len(),
+                unit: ("commit", "commits")
+            },
+        )?;
+        return Ok(ExitCode(0));
+    }
+
+    writeln!(
+        effects.get_output_stream(),
+        "Results differ on {} of {}:",
+        differing_commits.len(),
+        Pluralize {
+            determiner: None,
+            amount: commits.len(),
+            unit: ("commit", "commits")
+        },
+    )?;
+    writeln!(effects.get_output_stream(), "A B")?;
+    for (commit, test_output_a, test_output_b) in differing_commits {
+        let status_a = &test_output_a.test_status;
+        let status_b = &test_output_b.test_status;
+        let is_pass_fail = [status_a, status_b]
+            .iter()
+            .all(|status| status.get_category() != TestStatusCategory::Skipped);
+        let description = commit.friendly_describe(effects.get_glyphs())?;
+        writeln!(
+            effects.get_output_stream(),
+            "{}",
+            effects.get_glyphs().render(
+                StyledStringBuilder::new()
+                    .append_styled(status_a.get_icon(), status_a.get_style())
+                    .append_plain(" ")
+                    .append_styled(status_b.get_icon(), status_b.get_style())
+                    .append_plain(" ")
+                    .append(if is_pass_fail {
+                        StyledStringBuilder::new()
+                            .append_styled(description.source().to_owned(), Effect::Bold)
+                            .build()
+                    } else {
+                        description
+                    })
+                    .build()
+            )?
+        )?;
+    }
+    Ok(ExitCode(1))
+}
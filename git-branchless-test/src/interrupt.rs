@@ -0,0 +1,76 @@
+//! Handle `SIGINT` (Ctrl-C) while running tests, so that testing can be
+//! stopped early without discarding the results which were already produced.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether `SIGINT` was received since the current `InterruptGuard` was
+/// installed.
+pub(crate) fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// While alive, records `SIGINT` instead of letting it terminate the process.
+/// The previous handler is restored when dropped. This should only be
+/// installed by the `git test` entry points, not by library callers, which may
+/// have their own handling for `SIGINT`.
+#[derive(Debug)]
+pub(crate) struct InterruptGuard {
+    #[cfg(unix)]
+    previous_handler: Option<libc::sighandler_t>,
+}
+
+#[cfg(unix)]
+extern "C" fn handle_sigint(_signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        // The user pressed Ctrl-C a second time, so they don't want to wait
+        // for the in-progress commands to finish. Exit immediately.
+        //
+        // SAFETY: `signal` and `raise` are async-signal-safe.
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::raise(libc::SIGINT);
+        }
+    }
+}
+
+impl InterruptGuard {
+    #[cfg(unix)]
+    pub fn install() -> Self {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        // SAFETY: `handle_sigint` only calls async-signal-safe functions.
+        let previous_handler = unsafe {
+            libc::signal(
+                libc::SIGINT,
+                handle_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            )
+        };
+        Self {
+            previous_handler: if previous_handler == libc::SIG_ERR {
+                None
+            } else {
+                Some(previous_handler)
+            },
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn install() -> Self {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        Self {}
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        #[cfg(unix)]
+        if let Some(previous_handler) = self.previous_handler {
+            // SAFETY: restores the handler which was installed before.
+            unsafe {
+                libc::signal(libc::SIGINT, previous_handler);
+            }
+        }
+    }
+}
@@ -11,24 +11,42 @@
 )]
 #![allow(clippy::too_many_arguments, clippy::blocks_in_if_conditions)]
 
+mod cache;
+mod capped_writer;
+mod compare;
+mod interrupt;
+mod log;
+mod notify;
+mod report;
 mod worker;
 
-use std::collections::{HashMap, HashSet};
+pub use cache::subcommand_clean;
+pub use lib::git::TestStatusCategory;
+
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::fmt::Write as _;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Read, Write as _};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
 
 use bstr::ByteSlice;
 use clap::ValueEnum;
-use crossbeam::channel::{Receiver, RecvError};
+use crossbeam::channel::{Receiver, RecvError, RecvTimeoutError};
 use cursive::theme::{BaseColor, Effect, Style};
 use cursive::utils::markup::StyledString;
 
 use eyre::WrapErr;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use fslock::LockFile;
 use git_branchless_invoke::CommandContext;
 use indexmap::IndexMap;
@@ -36,37 +54,56 @@ use itertools::Itertools;
 use lazy_static::lazy_static;
 use lib::core::check_out::CheckOutCommitOptions;
 use lib::core::config::{
-    get_hint_enabled, get_hint_string, get_restack_preserve_timestamps,
-    print_hint_suppression_notice, Hint,
+    get_hint_enabled, get_hint_string, get_restack_preserve_timestamps, get_test_abort_exit_code,
+    get_test_cache_dir, get_test_skip_exit_code, get_test_skip_exit_codes,
+    get_test_success_exit_codes, get_test_tmpfs_dir, print_hint_suppression_notice, Hint,
 };
 use lib::core::dag::{sorted_commit_set, CommitSet, Dag};
-use lib::core::effects::{icons, Effects, OperationIcon, OperationType};
+use lib::core::effects::{icons, Effects, OperationIcon, OperationType, ProgressHandle};
 use lib::core::eventlog::{EventLogDb, EventReplayer, EventTransactionId};
 use lib::core::formatting::{Glyphs, Pluralize, StyledStringBuilder};
 use lib::core::repo_ext::RepoExt;
 use lib::core::rewrite::{
     execute_rebase_plan, BuildRebasePlanOptions, ExecuteRebasePlanOptions, ExecuteRebasePlanResult,
-    RebaseCommand, RebasePlan, RebasePlanBuilder, RebasePlanPermissions, RepoResource,
+    MergeConflictRemediation, RebaseCommand, RebasePlan, RebasePlanBuilder, RebasePlanPermissions,
+    RepoResource,
 };
 use lib::git::{
-    get_latest_test_command_path, get_test_locks_dir, get_test_tree_dir, get_test_worktrees_dir,
-    make_test_command_slug, Commit, ConfigRead, GitRunInfo, GitRunResult, MaybeZeroOid, NonZeroOid,
-    Repo, SerializedNonZeroOid, SerializedTestResult, WorkingCopyChangesType, TEST_ABORT_EXIT_CODE,
-    TEST_INDETERMINATE_EXIT_CODE, TEST_SUCCESS_EXIT_CODE,
+    get_changed_paths_between_trees, get_default_test_cache_dir, get_latest_test_command_path,
+    get_test_locks_dir, get_test_setup_output_path, get_test_tree_dir, get_test_worktrees_dir,
+    hydrate_tree, make_test_command_slug, process_diff_for_record, Commit, ConfigRead, FileMode,
+    GitRunInfo, GitRunOpts, GitRunResult, MaybeZeroOid, NonZeroOid, Reference, Repo,
+    SerializedNonZeroOid, SerializedTestResult, WorkingCopyChangesType, TEST_ABORT_EXIT_CODE,
+    TEST_INDETERMINATE_EXIT_CODE, TEST_RESULT_SCHEMA_VERSION, TEST_SUCCESS_EXIT_CODE,
+    TEST_TIMEOUT_EXIT_CODE,
 };
 use lib::util::{get_sh, ExitCode};
+use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
+use regex::Regex;
 use scm_bisect::search;
+use scm_record::{
+    EventSource, File as RecordFile, KeyBindings, RecordError, RecordState, Recorder,
+    SelectedContents,
+};
 use tempfile::TempDir;
 use thiserror::Error;
 use tracing::{debug, info, instrument, warn};
 
 use git_branchless_opts::{
-    MoveOptions, ResolveRevsetOptions, Revset, TestArgs, TestExecutionStrategy, TestSearchStrategy,
-    TestSubcommand,
+    MoveOptions, ResolveRevsetOptions, Revset, TestArgs, TestExecutionStrategy, TestOutputFormat,
+    TestSearchStrategy, TestSubcommand,
 };
 use git_branchless_revset::resolve_commits;
 
+use crate::cache::{
+    evict_test_cache, get_test_cache_limits, print_evicted_trees, subcommand_du, subcommand_gc,
+};
+use crate::capped_writer::CappedWriter;
+use crate::compare::subcommand_compare;
+use crate::interrupt::{is_interrupted, InterruptGuard};
+use crate::log::subcommand_log;
+use crate::notify::DEFAULT_NOTIFY_THRESHOLD_SECONDS;
 use crate::worker::{worker, JobResult, WorkQueue, WorkerId};
 
 lazy_static! {
@@ -78,6 +115,52 @@ lazy_static! {
         Style::merge(&[BaseColor::Yellow.light().into(), Effect::Bold.into()]);
 }
 
+/// Test commands which finish faster than this don't have their duration
+/// displayed, to avoid cluttering the output.
+const MIN_DISPLAYED_DURATION: Duration = Duration::from_secs(1);
+
+/// The maximum number of commits to list as the slowest in the summary.
+const NUM_SLOWEST_COMMITS: usize = 3;
+
+/// The default number of lines to show at the start and end of abbreviated
+/// test output.
+pub const DEFAULT_TEST_OUTPUT_CONTEXT_LINES: usize = 5;
+
+/// The largest accepted value for the number of lines of context, to guard
+/// against values which were probably set by mistake.
+const MAX_TEST_OUTPUT_CONTEXT_LINES: usize = 10_000;
+
+/// The environment variables passed through to the test command with
+/// `--clean-env`, if `branchless.test.env.passthrough` is not set.
+const DEFAULT_ENV_PASSTHROUGH: &str = "HOME PATH";
+
+/// The exit code reported for a commit whose test command succeeded, but whose
+/// output matched the pattern given by `--fail-on-output`.
+const FAIL_ON_OUTPUT_EXIT_CODE: i32 = 1;
+
+/// Format a duration for display, such as `1.2s` or `3m42s`.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else if seconds < 60 * 60 {
+        format!("{}m{}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h{}m", seconds / (60 * 60), (seconds / 60) % 60)
+    }
+}
+
+/// Get the duration of the test command to display alongside its result, if
+/// any. Durations aren't displayed for timeouts, since the description of the
+/// status already includes it.
+fn get_displayed_duration(test_output: &TestOutput) -> Option<Duration> {
+    match (&test_output.test_status, test_output.duration) {
+        (TestStatus::Timeout { .. }, _) | (_, None) => None,
+        (_, Some(duration)) if duration < MIN_DISPLAYED_DURATION => None,
+        (_, Some(duration)) => Some(duration),
+    }
+}
+
 /// How verbose of output to produce.
 #[derive(Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Verbosity {
@@ -103,10 +186,11 @@ impl From<u8> for Verbosity {
 
 /// The options for testing before they've assumed default values or been
 /// validated.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RawTestOptions {
-    /// The command to execute, if any.
-    pub exec: Option<String>,
+    /// The commands to execute, if any. Only `git test run` accepts more than
+    /// one command.
+    pub exec: Vec<String>,
 
     /// The command alias to execute, if any.
     pub command: Option<String>,
@@ -125,9 +209,14 @@ pub struct RawTestOptions {
     /// Shorthand for the binary search strategy.
     pub bisect: bool,
 
-    /// Don't read or write to the cache when executing the test commands.
+    /// Don't read cached results when executing the test commands. The fresh
+    /// results are still written to the cache.
     pub no_cache: bool,
 
+    /// The directory in which test results are cached, overriding
+    /// `branchless.test.cacheDir`.
+    pub cache_dir: Option<PathBuf>,
+
     /// Whether to run interactively.
     pub interactive: bool,
 
@@ -140,6 +229,282 @@ pub struct RawTestOptions {
     /// Whether to amend commits with the changes produced by the executed
     /// command.
     pub apply_fixes: bool,
+
+    /// Limits on running the test command on each commit.
+    pub limits: TestLimits,
+
+    /// Which commits to test, and in which order.
+    pub scheduling: TestScheduling,
+
+    /// How to run the test command on each commit.
+    pub process: RawTestProcessOptions,
+
+    /// How to interpret the results of the test command.
+    pub outcome: RawTestOutcomeOptions,
+
+    /// How to report the results of the test run.
+    pub report: RawTestReportOptions,
+
+    /// Which changes produced by the executed command to amend into each
+    /// commit, if `apply_fixes` is set.
+    pub fix_selection: RawTestFixSelection,
+}
+
+/// Limits on running the test command on each commit.
+#[derive(Clone, Debug, Default)]
+pub struct TestLimits {
+    /// The maximum amount of time to let the test command run on each commit
+    /// before killing it.
+    pub timeout: Option<Duration>,
+
+    /// The maximum number of bytes of address space which the test command
+    /// may use on each commit (Linux only).
+    pub memory_limit: Option<u64>,
+
+    /// The maximum amount of time to wait for another process which is
+    /// already testing a commit to finish, if any.
+    pub lock_wait_timeout: Option<Duration>,
+
+    /// The number of times to re-run the test command on a commit if it fails.
+    pub retries: usize,
+}
+
+/// Which commits to test, and in which order.
+#[derive(Clone, Debug, Default)]
+pub struct TestScheduling {
+    /// Only run the test command on commits which don't have a cached passing
+    /// result.
+    pub rerun_failures: bool,
+
+    /// The number of failing commits after which to stop testing further
+    /// commits.
+    pub max_failures: Option<usize>,
+
+    /// Whether to skip the descendants of commits on which the test command
+    /// failed.
+    pub skip_descendants_on_failure: bool,
+
+    /// Whether to skip commits which don't change their (first) parent's tree.
+    pub skip_empty: bool,
+
+    /// Whether to also test each merge commit from the perspective of each of
+    /// its parents.
+    pub test_merge_parents: bool,
+
+    /// If set, run the test command on the commits in a random order, using
+    /// this seed.
+    pub shuffle_seed: Option<u64>,
+}
+
+/// How to run the test command on each commit, before the options have been
+/// validated.
+#[derive(Clone, Debug, Default)]
+pub struct RawTestProcessOptions {
+    /// Extra arguments to pass to the test command as positional parameters.
+    pub extra_args: Vec<String>,
+
+    /// A command to run once before testing any commits.
+    pub setup: Option<String>,
+
+    /// Whether to run the test command with only an allowlist of environment
+    /// variables passed through from the environment of this process.
+    pub clean_env: bool,
+
+    /// A file of `KEY=VALUE` lines with environment variables to set for the
+    /// test command.
+    pub env_file: Option<PathBuf>,
+
+    /// The directory in which to run the test command, relative to the root
+    /// of the checked-out commit.
+    pub cwd: Option<PathBuf>,
+
+    /// Whether to capture the stdout and stderr of the test command together
+    /// in a single file.
+    pub combined_output: bool,
+
+    /// Whether to print the output of the test command to the terminal as it
+    /// runs.
+    pub stream: bool,
+
+    /// Whether to keep the worktree for each commit on which the test command
+    /// failed, rather than reusing it for the next commit.
+    pub keep_worktrees: bool,
+
+    /// The number of worktrees to share between the jobs, if fewer than the
+    /// number of jobs.
+    pub worktree_count: Option<usize>,
+}
+
+/// How to interpret the results of the test command, before the options have
+/// been validated.
+#[derive(Clone, Debug, Default)]
+pub struct RawTestOutcomeOptions {
+    /// The threshold above which the measurement printed by the test command
+    /// is considered a failure.
+    pub perf_threshold: Option<f64>,
+
+    /// A regular expression which, if it matches the output of a successful
+    /// test command, causes the commit to be treated as failed.
+    pub fail_on_output: Option<String>,
+}
+
+/// How to report the results of the test run, before the options have been
+/// validated.
+#[derive(Clone, Debug)]
+pub struct RawTestReportOptions {
+    /// The ref to point to the first failing commit found by the search.
+    pub bisect_ref: Option<String>,
+
+    /// Whether to print only the first failing commit found by the search, in
+    /// a machine-readable format.
+    pub porcelain: bool,
+
+    /// The number of lines to show at the start and end of abbreviated test
+    /// output.
+    pub context: Option<usize>,
+
+    /// Whether to collapse identical failures into a single entry in the
+    /// summary.
+    pub group: bool,
+
+    /// How to format the results of the test run.
+    pub format: TestOutputFormat,
+
+    /// The path to write a JUnit XML report to, if any.
+    pub junit_path: Option<PathBuf>,
+
+    /// Whether to send a desktop notification when a long test run finishes.
+    pub notify: bool,
+}
+
+impl Default for RawTestReportOptions {
+    fn default() -> Self {
+        Self {
+            bisect_ref: None,
+            porcelain: false,
+            context: None,
+            group: false,
+            format: TestOutputFormat::Human,
+            junit_path: None,
+            notify: false,
+        }
+    }
+}
+
+/// Which changes produced by the executed command to amend into each commit,
+/// before the options have been validated.
+#[derive(Clone, Debug, Default)]
+pub struct RawTestFixSelection {
+    /// Whether to interactively select which changes to amend into each
+    /// commit.
+    pub interactive: bool,
+
+    /// Whether to print the changes before amending them into each commit.
+    pub show_diff: bool,
+
+    /// If non-empty, only amend the changes to paths matching one of these
+    /// glob patterns into each commit.
+    pub pathspecs: Vec<String>,
+}
+
+/// Determine the directory in which to cache test results. If the directory
+/// can't be written to, then print an error and fall back to the default cache
+/// directory inside the Git directory.
+fn resolve_test_cache_dir(
+    effects: &Effects,
+    repo: &Repo,
+    cache_dir: Option<&Path>,
+) -> eyre::Result<PathBuf> {
+    let cache_dir = match cache_dir {
+        Some(cache_dir) => cache_dir.to_owned(),
+        None => get_test_cache_dir(repo)?,
+    };
+    let default_cache_dir = get_default_test_cache_dir(repo);
+    if cache_dir == default_cache_dir {
+        return Ok(cache_dir);
+    }
+
+    let check_writable = || -> std::io::Result<()> {
+        std::fs::create_dir_all(&cache_dir)?;
+        tempfile::tempfile_in(&cache_dir)?;
+        Ok(())
+    };
+    match check_writable() {
+        Ok(()) => Ok(cache_dir),
+        Err(err) => {
+            writeln!(
+                effects.get_output_stream(),
+                "The test cache directory {} is not writable: {err}",
+                cache_dir.display(),
+            )?;
+            writeln!(
+                effects.get_output_stream(),
+                "Falling back to the default test cache directory: {}",
+                default_cache_dir.display(),
+            )?;
+            Ok(default_cache_dir)
+        }
+    }
+}
+
+/// Determine the directory under which to create the testing worktrees. If the
+/// directory configured with `branchless.test.worktreeDir` can't be used, then
+/// print a warning and fall back to the default directory inside the Git
+/// directory.
+fn resolve_test_worktrees_dir(effects: &Effects, repo: &Repo) -> eyre::Result<PathBuf> {
+    let default_worktrees_dir = get_test_worktrees_dir(repo);
+    let worktrees_dir: Option<PathBuf> = repo
+        .get_readonly_config()?
+        .get("branchless.test.worktreeDir")?;
+    let worktrees_dir = match worktrees_dir {
+        Some(worktrees_dir) if worktrees_dir != default_worktrees_dir => worktrees_dir,
+        Some(_) | None => return Ok(default_worktrees_dir),
+    };
+
+    let check_usable = || -> Result<(), String> {
+        std::fs::create_dir_all(&worktrees_dir)
+            .and_then(|()| tempfile::tempfile_in(&worktrees_dir))
+            .map_err(|err| format!("is not writable: {err}"))?;
+
+        // A worktree nested inside another working copy, such as a submodule,
+        // would show up as untracked files there. The default directory is
+        // inside the Git directory, which isn't part of any working copy.
+        let canonical_worktrees_dir = worktrees_dir
+            .canonicalize()
+            .map_err(|err| format!("could not be resolved: {err}"))?;
+        let is_in_git_dir = match repo.get_path().canonicalize() {
+            Ok(git_dir) => canonical_worktrees_dir.starts_with(git_dir),
+            Err(_) => false,
+        };
+        if !is_in_git_dir {
+            if let Some(working_copy_dir) = canonical_worktrees_dir
+                .ancestors()
+                .find(|ancestor| ancestor.join(".git").exists())
+            {
+                return Err(format!(
+                    "is inside the working copy at {}",
+                    working_copy_dir.display()
+                ));
+            }
+        }
+        Ok(())
+    };
+    match check_usable() {
+        Ok(()) => Ok(worktrees_dir),
+        Err(reason) => {
+            writeln!(
+                effects.get_output_stream(),
+                "The test worktree directory {} {reason}",
+                worktrees_dir.display(),
+            )?;
+            writeln!(
+                effects.get_output_stream(),
+                "Falling back to the default test worktree directory: {}",
+                default_worktrees_dir.display(),
+            )?;
+            Ok(default_worktrees_dir)
+        }
+    }
 }
 
 fn resolve_test_command_alias(
@@ -203,14 +568,152 @@ To run a specific command alias, run: git test run -c <alias>",
 #[derive(Debug)]
 pub struct ResolvedTestOptions {
     pub command: String,
+    pub shell_command: String,
     pub execution_strategy: TestExecutionStrategy,
     pub search_strategy: Option<TestSearchStrategy>,
     pub is_dry_run: bool,
-    pub use_cache: bool,
+    pub cache_mode: TestCacheMode,
+    pub cache_dir: PathBuf,
+    pub worktrees_dir: PathBuf,
     pub is_interactive: bool,
     pub num_jobs: usize,
     pub verbosity: Verbosity,
     pub fix_options: Option<(ExecuteRebasePlanOptions, RebasePlanPermissions)>,
+    pub limits: TestLimits,
+    pub scheduling: TestScheduling,
+    pub process: TestProcessOptions,
+    pub hooks: TestHooks,
+    pub outcome: TestOutcomeOptions,
+    pub storage: TestOutputStorage,
+    pub report: TestReportOptions,
+    pub fix_selection: TestFixSelection,
+}
+
+/// How to use the cache of test results.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TestCacheMode {
+    /// Read cached results, and cache fresh results.
+    ReadWrite,
+
+    /// Don't read cached results, but still cache fresh results (`--no-cache`).
+    WriteOnly,
+
+    /// Neither read nor write cached results.
+    Disabled,
+}
+
+impl TestCacheMode {
+    /// Whether cached results should be used instead of running the test
+    /// command.
+    pub fn should_read(self) -> bool {
+        match self {
+            TestCacheMode::ReadWrite => true,
+            TestCacheMode::WriteOnly | TestCacheMode::Disabled => false,
+        }
+    }
+
+    /// Whether fresh results should be written to the cache.
+    pub fn should_write(self) -> bool {
+        match self {
+            TestCacheMode::ReadWrite | TestCacheMode::WriteOnly => true,
+            TestCacheMode::Disabled => false,
+        }
+    }
+}
+
+/// The values from a `RawTestProcessOptions` but with defaults provided.
+#[allow(missing_docs)]
+#[derive(Debug, Default)]
+pub struct TestProcessOptions {
+    pub extra_args: Vec<String>,
+    pub docker_image: Option<String>,
+    pub env_passthrough: Option<Vec<String>>,
+    pub env_file_vars: Vec<(String, String)>,
+    pub cwd: Option<PathBuf>,
+    pub combined_output: bool,
+    pub stream: bool,
+    pub keep_worktrees: bool,
+    pub worktree_count: Option<usize>,
+}
+
+/// Commands to run around the test command, configured with
+/// `branchless.test.setup`, `branchless.test.teardown` and
+/// `branchless.test.onResult`.
+#[allow(missing_docs)]
+#[derive(Debug, Default)]
+pub struct TestHooks {
+    pub setup: Option<String>,
+    pub teardown: Option<String>,
+    pub on_result: Option<String>,
+}
+
+/// The values from a `RawTestOutcomeOptions` but with defaults provided, along
+/// with the configured exit codes.
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub struct TestOutcomeOptions {
+    pub perf_threshold: Option<f64>,
+    pub fail_on_output: Option<Regex>,
+    pub skip_exit_code: i32,
+    pub abort_exit_code: i32,
+    pub success_exit_codes: Vec<i32>,
+    pub skip_exit_codes: Vec<i32>,
+}
+
+impl Default for TestOutcomeOptions {
+    fn default() -> Self {
+        Self {
+            perf_threshold: None,
+            fail_on_output: None,
+            skip_exit_code: TEST_INDETERMINATE_EXIT_CODE,
+            abort_exit_code: TEST_ABORT_EXIT_CODE,
+            success_exit_codes: Vec::new(),
+            skip_exit_codes: Vec::new(),
+        }
+    }
+}
+
+/// How to store the output of the test command in the cache.
+#[allow(missing_docs)]
+#[derive(Debug, Default)]
+pub struct TestOutputStorage {
+    pub max_output_bytes: Option<u64>,
+    pub compress_output: bool,
+    pub dedupe_output: bool,
+}
+
+/// The values from a `RawTestReportOptions` but with defaults provided.
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub struct TestReportOptions {
+    pub bisect_ref: Option<String>,
+    pub output_context_lines: usize,
+    pub group_failures: bool,
+    pub format: TestOutputFormat,
+    pub junit_path: Option<PathBuf>,
+    pub notify_threshold: Option<Duration>,
+}
+
+impl Default for TestReportOptions {
+    fn default() -> Self {
+        Self {
+            bisect_ref: None,
+            output_context_lines: DEFAULT_TEST_OUTPUT_CONTEXT_LINES,
+            group_failures: false,
+            format: TestOutputFormat::Human,
+            junit_path: None,
+            notify_threshold: None,
+        }
+    }
+}
+
+/// The values from a `RawTestFixSelection` but with defaults provided.
+#[allow(missing_docs)]
+#[derive(Debug, Default)]
+pub struct TestFixSelection {
+    pub interactive: bool,
+    pub show_diff: bool,
+    pub pathspecs: Vec<glob::Pattern>,
 }
 
 impl ResolvedTestOptions {
@@ -234,14 +737,50 @@ impl ResolvedTestOptions {
             search,
             bisect,
             no_cache,
+            cache_dir,
             interactive,
             jobs,
             verbosity,
             apply_fixes,
+            limits,
+            scheduling,
+            process:
+                RawTestProcessOptions {
+                    extra_args,
+                    setup,
+                    clean_env,
+                    env_file,
+                    cwd,
+                    combined_output,
+                    stream,
+                    keep_worktrees,
+                    worktree_count,
+                },
+            outcome:
+                RawTestOutcomeOptions {
+                    perf_threshold,
+                    fail_on_output,
+                },
+            report:
+                RawTestReportOptions {
+                    bisect_ref,
+                    porcelain,
+                    context,
+                    group,
+                    format,
+                    junit_path,
+                    notify,
+                },
+            fix_selection:
+                RawTestFixSelection {
+                    interactive: interactive_fixes,
+                    show_diff: show_fix_diff,
+                    pathspecs: fix_pathspecs,
+                },
         } = options;
-        let resolved_command = match (command, command_alias) {
-            (Some(command), None) => command.to_owned(),
-            (None, None) => match (interactive, std::env::var("SHELL")) {
+        let resolved_command = match (command.as_slice(), command_alias) {
+            ([command], None) => command.to_owned(),
+            ([], None) => match (interactive, std::env::var("SHELL")) {
                 (true, Ok(shell)) => shell,
                 _ => match resolve_test_command_alias(effects, repo, None)? {
                     Ok(command) => command,
@@ -250,7 +789,7 @@ impl ResolvedTestOptions {
                     }
                 },
             },
-            (None, Some(command_alias)) => {
+            ([], Some(command_alias)) => {
                 match resolve_test_command_alias(effects, repo, Some(command_alias))? {
                     Ok(command) => command,
                     Err(exit_code) => {
@@ -258,11 +797,20 @@ impl ResolvedTestOptions {
                     }
                 }
             }
-            (Some(command), Some(command_alias)) => unreachable!(
+            ([_, _, ..], None) => {
+                writeln!(
+                    effects.get_output_stream(),
+                    "BUG: Expected a single command to resolve, but got: {command:?}"
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+            ([_, ..], Some(command_alias)) => unreachable!(
                 "Command ({:?}) and command alias ({:?}) are conflicting options",
                 command, command_alias
             ),
         };
+        let (resolved_command, resolved_shell_command) =
+            make_test_shell_command(resolved_command, extra_args);
         let configured_execution_strategy = match strategy {
             Some(strategy) => *strategy,
             None => {
@@ -312,14 +860,22 @@ impl ResolvedTestOptions {
                 (Some(TestExecutionStrategy::WorkingCopy), interactive) => {
                     (1, TestExecutionStrategy::WorkingCopy, interactive)
                 }
-                (Some(TestExecutionStrategy::Worktree), true) => {
-                    (1, TestExecutionStrategy::Worktree, true)
-                }
-                (Some(TestExecutionStrategy::Worktree), false) => (
-                    configured_jobs.unwrap_or(1),
-                    TestExecutionStrategy::Worktree,
+                (
+                    Some(
+                        strategy @ (TestExecutionStrategy::Worktree
+                        | TestExecutionStrategy::Docker
+                        | TestExecutionStrategy::Tmpfs),
+                    ),
+                    true,
+                ) => (1, *strategy, true),
+                (
+                    Some(
+                        strategy @ (TestExecutionStrategy::Worktree
+                        | TestExecutionStrategy::Docker
+                        | TestExecutionStrategy::Tmpfs),
+                    ),
                     false,
-                ),
+                ) => (configured_jobs.unwrap_or(1), *strategy, false),
                 (None, true) => (1, configured_execution_strategy, true),
                 (None, false) => (
                     configured_jobs.unwrap_or(1),
@@ -342,6 +898,9 @@ The --jobs option cannot be used with the --interactive option."
                     None | Some(TestExecutionStrategy::Worktree) => {
                         (*jobs, TestExecutionStrategy::Worktree, false)
                     }
+                    Some(
+                        strategy @ (TestExecutionStrategy::Docker | TestExecutionStrategy::Tmpfs),
+                    ) => (*jobs, *strategy, false),
                     Some(TestExecutionStrategy::WorkingCopy) => {
                         writeln!(
                             effects.get_output_stream(),
@@ -354,2280 +913,5962 @@ The --jobs option can only be used with --strategy worktree, but --strategy work
             }
         };
 
-        if resolved_interactive != *interactive {
-            writeln!(effects.get_output_stream(),
-            "\
-BUG: Expected resolved_interactive ({resolved_interactive:?}) to match interactive ({interactive:?}). If it doesn't match, then multiple interactive jobs might inadvertently be launched in parallel."
+        if resolved_interactive && limits.timeout.is_some() {
+            writeln!(
+                effects.get_output_stream(),
+                "\
+The --timeout option cannot be used with the --interactive option."
             )?;
             return Ok(Err(ExitCode(1)));
         }
 
-        let resolved_num_jobs = if resolved_num_jobs == 0 {
-            num_cpus::get_physical()
-        } else {
-            resolved_num_jobs
-        };
-        assert!(resolved_num_jobs > 0);
+        if limits.memory_limit.is_some() && !cfg!(target_os = "linux") {
+            writeln!(
+                effects.get_output_stream(),
+                "The --memory-limit option is only supported on Linux."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
 
-        let fix_options = if *apply_fixes {
-            let move_options = match move_options {
-                Some(move_options) => move_options,
-                None => {
-                    writeln!(effects.get_output_stream(), "BUG: fixes were requested to be applied, but no `BuildRebasePlanOptions` were provided.")?;
+        if resolved_interactive && limits.retries > 0 {
+            writeln!(
+                effects.get_output_stream(),
+                "\
+The --retries option cannot be used with the --interactive option."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+
+        let notify_config_key = "branchless.test.notify";
+        let notify_threshold = if *notify || config.get_or(notify_config_key, false)? {
+            let notify_threshold_config_key = "branchless.test.notifyThreshold";
+            let configured_threshold: i32 = config.get_or(
+                notify_threshold_config_key,
+                DEFAULT_NOTIFY_THRESHOLD_SECONDS,
+            )?;
+            match u64::try_from(configured_threshold) {
+                Ok(threshold) => Some(Duration::from_secs(threshold)),
+                Err(err) => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Invalid value for config value for {notify_threshold_config_key} ({configured_threshold}): {err}"
+                    )?;
                     return Ok(Err(ExitCode(1)));
                 }
-            };
-            let MoveOptions {
-                force_rewrite_public_commits,
-                force_in_memory: _,
-                force_on_disk,
-                detect_duplicate_commits_via_patch_id,
-                resolve_merge_conflicts,
-                dump_rebase_constraints,
-                dump_rebase_plan,
-            } = move_options;
-
-            let force_in_memory = true;
-            if *force_on_disk {
-                writeln!(
-                    effects.get_output_stream(),
-                    "The --on-disk option cannot be provided for fixes. Use the --in-memory option instead."
-                )?;
-                return Ok(Err(ExitCode(1)));
             }
-
-            let build_options = BuildRebasePlanOptions {
-                force_rewrite_public_commits: *force_rewrite_public_commits,
-                dump_rebase_constraints: *dump_rebase_constraints,
-                dump_rebase_plan: *dump_rebase_plan,
-                detect_duplicate_commits_via_patch_id: *detect_duplicate_commits_via_patch_id,
-            };
-            let execute_options = ExecuteRebasePlanOptions {
-                now,
-                event_tx_id,
-                preserve_timestamps: get_restack_preserve_timestamps(repo)?,
-                force_in_memory,
-                force_on_disk: *force_on_disk,
-                resolve_merge_conflicts: *resolve_merge_conflicts,
-                check_out_commit_options: CheckOutCommitOptions {
-                    render_smartlog: false,
-                    ..Default::default()
-                },
-            };
-            let permissions =
-                match RebasePlanPermissions::verify_rewrite_set(dag, build_options, commits)? {
-                    Ok(permissions) => permissions,
-                    Err(err) => {
-                        err.describe(effects, repo, dag)?;
-                        return Ok(Err(ExitCode(1)));
-                    }
-                };
-            Some((execute_options, permissions))
         } else {
             None
         };
 
-        let resolved_search_strategy = if *bisect {
-            Some(TestSearchStrategy::Binary)
-        } else {
-            *search
+        let setup_command = match setup {
+            Some(setup) => Some(setup.clone()),
+            None => config.get("branchless.test.setup")?,
         };
 
-        let resolved_test_options = ResolvedTestOptions {
-            command: resolved_command,
-            execution_strategy: resolved_execution_strategy,
-            search_strategy: resolved_search_strategy,
-            use_cache: !no_cache,
-            is_dry_run: *dry_run,
-            is_interactive: resolved_interactive,
-            num_jobs: resolved_num_jobs,
-            verbosity: *verbosity,
-            fix_options,
+        let teardown_command: Option<String> = config.get("branchless.test.teardown")?;
+        let on_result_command: Option<String> = config.get("branchless.test.onResult")?;
+
+        let env_passthrough = if *clean_env {
+            let passthrough: Option<String> = config.get("branchless.test.env.passthrough")?;
+            let passthrough = passthrough.as_deref().unwrap_or(DEFAULT_ENV_PASSTHROUGH);
+            Some(
+                passthrough
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.to_owned())
+                    .collect_vec(),
+            )
+        } else {
+            None
         };
-        debug!(?resolved_test_options, "Resolved test options");
-        Ok(Ok(resolved_test_options))
-    }
 
-    fn make_command_slug(&self) -> String {
-        make_test_command_slug(self.command.clone())
+        let env_file_vars = match env_file {
+            None => Vec::new(),
+            Some(env_file) => match std::fs::read_to_string(env_file) {
+                Ok(contents) => match parse_env_file(&contents) {
+                    Ok(env_file_vars) => env_file_vars,
+                    Err(err) => {
+                        writeln!(
+                            effects.get_output_stream(),
+                            "Invalid environment file {}: {err}",
+                            env_file.display()
+                        )?;
+                        return Ok(Err(ExitCode(1)));
+                    }
+                },
+                Err(err) => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Could not read environment file {}: {err}",
+                        env_file.display()
+                    )?;
+                    return Ok(Err(ExitCode(1)));
+                }
+            },
+        };
+
+        if let Some(cwd) = cwd {
+            if !cwd
+                .components()
+                .all(|component| matches!(component, std::path::Component::Normal(_)))
+            {
+                writeln!(
+                    effects.get_output_stream(),
+                    "Invalid value for --cwd ({}): must be a relative path inside the repository, without `..` components",
+                    cwd.display()
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+        }
+
+        let cache_dir = resolve_test_cache_dir(effects, repo, cache_dir.as_deref())?;
+        let worktrees_dir = match resolved_execution_strategy {
+            TestExecutionStrategy::Worktree => resolve_test_worktrees_dir(effects, repo)?,
+            TestExecutionStrategy::WorkingCopy
+            | TestExecutionStrategy::Docker
+            | TestExecutionStrategy::Tmpfs => get_test_worktrees_dir(repo),
+        };
+
+        let docker_image = match resolved_execution_strategy {
+            TestExecutionStrategy::WorkingCopy | TestExecutionStrategy::Worktree => None,
+            TestExecutionStrategy::Tmpfs => {
+                if *apply_fixes {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "\
+Fixes cannot be applied when using --strategy tmpfs, since the test command is not run in a Git working copy."
+                    )?;
+                    return Ok(Err(ExitCode(1)));
+                }
+                None
+            }
+            TestExecutionStrategy::Docker => {
+                if resolved_interactive {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "\
+The --interactive option cannot be used with --strategy docker."
+                    )?;
+                    return Ok(Err(ExitCode(1)));
+                }
+                if *apply_fixes {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "\
+Fixes cannot be applied when using --strategy docker, since the test command is not run in a Git working copy."
+                    )?;
+                    return Ok(Err(ExitCode(1)));
+                }
+
+                let docker_image_config_key = "branchless.test.dockerImage";
+                let docker_image: Option<String> = config.get(docker_image_config_key)?;
+                match docker_image {
+                    Some(docker_image) => Some(docker_image),
+                    None => {
+                        writeln!(
+                            effects.get_output_stream(),
+                            "\
+The docker execution strategy requires an image to run the tests in. Set one with: git config {docker_image_config_key} <image>"
+                        )?;
+                        return Ok(Err(ExitCode(1)));
+                    }
+                }
+            }
+        };
+
+        let skip_exit_code = get_test_skip_exit_code(repo)?;
+        let abort_exit_code = get_test_abort_exit_code(repo)?;
+        for (config_key, exit_code) in [
+            ("branchless.test.skipExitCode", skip_exit_code),
+            ("branchless.test.abortExitCode", abort_exit_code),
+        ] {
+            if exit_code == TEST_SUCCESS_EXIT_CODE {
+                writeln!(
+                    effects.get_output_stream(),
+                    "Invalid value for config value {config_key} ({exit_code}): it cannot be the same as the success exit code."
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+        }
+        if skip_exit_code == abort_exit_code {
+            writeln!(
+                effects.get_output_stream(),
+                "Invalid values for config values branchless.test.skipExitCode and branchless.test.abortExitCode ({skip_exit_code}): they cannot be the same."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+        let success_exit_codes = get_test_success_exit_codes(repo)?;
+        let skip_exit_codes = get_test_skip_exit_codes(repo)?;
+        if let Some(exit_code) = success_exit_codes
+            .iter()
+            .find(|exit_code| skip_exit_codes.contains(exit_code))
+        {
+            writeln!(
+                effects.get_output_stream(),
+                "Invalid values for config values branchless.test.successExitCodes and branchless.test.skipExitCodes ({exit_code}): an exit code cannot be in both."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+
+        if resolved_interactive != *interactive {
+            writeln!(effects.get_output_stream(),
+            "\
+BUG: Expected resolved_interactive ({resolved_interactive:?}) to match interactive ({interactive:?}). If it doesn't match, then multiple interactive jobs might inadvertently be launched in parallel."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+
+        let resolved_num_jobs = if resolved_num_jobs == 0 {
+            let jobs_count_mode_config_key = "branchless.test.jobsCountMode";
+            let jobs_count_mode: Option<String> = config.get(jobs_count_mode_config_key)?;
+            match jobs_count_mode.as_deref() {
+                None | Some("physical") => num_cpus::get_physical(),
+                Some("logical") => num_cpus::get(),
+                Some(jobs_count_mode) => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Invalid value for config value {jobs_count_mode_config_key}: {jobs_count_mode}"
+                    )?;
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Expected one of: physical, logical"
+                    )?;
+                    return Ok(Err(ExitCode(1)));
+                }
+            }
+        } else {
+            resolved_num_jobs
+        };
+        assert!(resolved_num_jobs > 0);
+
+        let output_context_lines_config_key = "branchless.test.outputContextLines";
+        let output_context_lines = match context {
+            Some(context) => *context,
+            None => {
+                let configured_context: Option<i32> =
+                    config.get(output_context_lines_config_key)?;
+                match configured_context {
+                    None => DEFAULT_TEST_OUTPUT_CONTEXT_LINES,
+                    Some(configured_context) => match usize::try_from(configured_context) {
+                        Ok(configured_context) => configured_context,
+                        Err(err) => {
+                            writeln!(
+                                effects.get_output_stream(),
+                                "Invalid value for config value for {output_context_lines_config_key} ({configured_context}): {err}"
+                            )?;
+                            return Ok(Err(ExitCode(1)));
+                        }
+                    },
+                }
+            }
+        };
+        if output_context_lines > MAX_TEST_OUTPUT_CONTEXT_LINES {
+            writeln!(
+                effects.get_output_stream(),
+                "The number of lines of context for the test output ({output_context_lines}) cannot be more than {MAX_TEST_OUTPUT_CONTEXT_LINES}."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+
+        let max_output_bytes_config_key = "branchless.test.maxOutputBytes";
+        let max_output_bytes: Option<i32> = config.get(max_output_bytes_config_key)?;
+        let max_output_bytes = match max_output_bytes {
+            None => None,
+            Some(max_output_bytes) => match u64::try_from(max_output_bytes) {
+                Ok(max_output_bytes) => Some(max_output_bytes),
+                Err(err) => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Invalid value for config value for {max_output_bytes_config_key} ({max_output_bytes}): {err}"
+                    )?;
+                    return Ok(Err(ExitCode(1)));
+                }
+            },
+        };
+        let compress_output = config.get_or("branchless.test.compressOutput", false)?;
+        let dedupe_output = config.get_or("branchless.test.dedupeOutput", false)?;
+
+        if *keep_worktrees
+            && !matches!(resolved_execution_strategy, TestExecutionStrategy::Worktree)
+        {
+            writeln!(
+                effects.get_output_stream(),
+                "The --keep-worktrees option can only be used with --strategy worktree."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+
+        let worktree_count = match worktree_count {
+            None => None,
+            Some(_) if !matches!(resolved_execution_strategy, TestExecutionStrategy::Worktree) => {
+                writeln!(
+                    effects.get_output_stream(),
+                    "The --worktree-count option can only be used with --strategy worktree."
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+            Some(worktree_count) if *worktree_count == 0 || *worktree_count > resolved_num_jobs => {
+                writeln!(
+                    effects.get_output_stream(),
+                    "\
+The --worktree-count option must be between 1 and the number of jobs ({resolved_num_jobs}), but got: {worktree_count}"
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+            // Each job already has its own worktree.
+            Some(worktree_count) if *worktree_count == resolved_num_jobs => None,
+            Some(worktree_count) => Some(*worktree_count),
+        };
+
+        if *stream && resolved_num_jobs > 1 {
+            writeln!(
+                effects.get_output_stream(),
+                "\
+The --stream option cannot be used with multiple jobs ({resolved_num_jobs} were requested), since the output of the jobs would be interleaved."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+
+        let fix_pathspecs = {
+            let mut result = Vec::new();
+            for pathspec in fix_pathspecs {
+                match glob::Pattern::new(pathspec) {
+                    Ok(pattern) => result.push(pattern),
+                    Err(err) => {
+                        writeln!(
+                            effects.get_output_stream(),
+                            "Invalid --pathspec pattern {pathspec:?}: {err}"
+                        )?;
+                        return Ok(Err(ExitCode(1)));
+                    }
+                }
+            }
+            result
+        };
+
+        let fix_options = if *apply_fixes {
+            let move_options = match move_options {
+                Some(move_options) => move_options,
+                None => {
+                    writeln!(effects.get_output_stream(), "BUG: fixes were requested to be applied, but no `BuildRebasePlanOptions` were provided.")?;
+                    return Ok(Err(ExitCode(1)));
+                }
+            };
+            let MoveOptions {
+                force_rewrite_public_commits,
+                force_in_memory,
+                force_on_disk,
+                detect_duplicate_commits_via_patch_id,
+                resolve_merge_conflicts,
+                dump_rebase_constraints,
+                dump_rebase_plan,
+            } = move_options;
+
+            // Apply fixes in-memory by default, since an on-disk rebase
+            // checks out each commit in turn. Fall back to an on-disk rebase if
+            // it was requested explicitly, or if merge conflicts should be
+            // resolved.
+            let force_in_memory = *force_in_memory || !(*force_on_disk || *resolve_merge_conflicts);
+
+            let build_options = BuildRebasePlanOptions {
+                force_rewrite_public_commits: *force_rewrite_public_commits,
+                dump_rebase_constraints: *dump_rebase_constraints,
+                dump_rebase_plan: *dump_rebase_plan,
+                detect_duplicate_commits_via_patch_id: *detect_duplicate_commits_via_patch_id,
+            };
+            let execute_options = ExecuteRebasePlanOptions {
+                now,
+                event_tx_id,
+                preserve_timestamps: get_restack_preserve_timestamps(repo)?,
+                force_in_memory,
+                force_on_disk: *force_on_disk,
+                resolve_merge_conflicts: *resolve_merge_conflicts,
+                check_out_commit_options: CheckOutCommitOptions {
+                    render_smartlog: false,
+                    ..Default::default()
+                },
+            };
+            let permissions =
+                match RebasePlanPermissions::verify_rewrite_set(dag, build_options, commits)? {
+                    Ok(permissions) => permissions,
+                    Err(err) => {
+                        err.describe(effects, repo, dag)?;
+                        return Ok(Err(ExitCode(1)));
+                    }
+                };
+            Some((execute_options, permissions))
+        } else {
+            None
+        };
+
+        let resolved_search_strategy = if *bisect {
+            Some(TestSearchStrategy::Binary)
+        } else {
+            *search
+        };
+        if *porcelain && (resolved_search_strategy.is_none() || *format != TestOutputFormat::Human)
+        {
+            writeln!(
+                effects.get_output_stream(),
+                "The --porcelain option can only be used with --search or --bisect, and not with --format."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+        if let Some(bisect_ref) = bisect_ref {
+            if resolved_search_strategy.is_none() {
+                writeln!(
+                    effects.get_output_stream(),
+                    "The --bisect-ref option can only be used with --search or --bisect."
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+            if !Reference::is_valid_name(bisect_ref) {
+                writeln!(
+                    effects.get_output_stream(),
+                    "Invalid ref name for --bisect-ref: {bisect_ref}"
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+        }
+        if let Some(perf_threshold) = perf_threshold {
+            if !perf_threshold.is_finite() {
+                writeln!(
+                    effects.get_output_stream(),
+                    "The --perf-threshold value must be a finite number, but got: {perf_threshold}"
+                )?;
+                return Ok(Err(ExitCode(1)));
+            }
+        }
+        let fail_on_output = match fail_on_output {
+            Some(fail_on_output) => match Regex::new(fail_on_output) {
+                Ok(fail_on_output) => Some(fail_on_output),
+                Err(err) => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Invalid regular expression for --fail-on-output: {err}"
+                    )?;
+                    return Ok(Err(ExitCode(1)));
+                }
+            },
+            None => None,
+        };
+
+        let resolved_test_options = ResolvedTestOptions {
+            command: resolved_command,
+            shell_command: resolved_shell_command,
+            execution_strategy: resolved_execution_strategy,
+            search_strategy: resolved_search_strategy,
+            is_dry_run: *dry_run,
+            cache_mode: if *no_cache {
+                TestCacheMode::WriteOnly
+            } else {
+                TestCacheMode::ReadWrite
+            },
+            cache_dir,
+            worktrees_dir,
+            is_interactive: resolved_interactive,
+            num_jobs: resolved_num_jobs,
+            verbosity: *verbosity,
+            fix_options,
+            limits: limits.clone(),
+            scheduling: scheduling.clone(),
+            process: TestProcessOptions {
+                extra_args: extra_args.clone(),
+                docker_image,
+                env_passthrough,
+                env_file_vars,
+                cwd: cwd.clone(),
+                combined_output: *combined_output,
+                stream: *stream,
+                keep_worktrees: *keep_worktrees,
+                worktree_count,
+            },
+            hooks: TestHooks {
+                setup: setup_command,
+                teardown: teardown_command,
+                on_result: on_result_command,
+            },
+            outcome: TestOutcomeOptions {
+                perf_threshold: *perf_threshold,
+                fail_on_output,
+                skip_exit_code,
+                abort_exit_code,
+                success_exit_codes,
+                skip_exit_codes,
+            },
+            storage: TestOutputStorage {
+                max_output_bytes,
+                compress_output,
+                dedupe_output,
+            },
+            report: TestReportOptions {
+                bisect_ref: bisect_ref.clone(),
+                output_context_lines,
+                group_failures: *group,
+                format: *format,
+                junit_path: junit_path.clone(),
+                notify_threshold,
+            },
+            fix_selection: TestFixSelection {
+                interactive: *interactive_fixes,
+                show_diff: *show_fix_diff,
+                pathspecs: fix_pathspecs,
+            },
+        };
+        debug!(?resolved_test_options, "Resolved test options");
+        Ok(Ok(resolved_test_options))
+    }
+
+    fn make_command_slug(&self) -> eyre::Result<String> {
+        // Results from running the command in a different directory or
+        // environment aren't interchangeable, so cache them separately.
+        let mut key = self.command.clone();
+        if let Some(cwd) = &self.process.cwd {
+            write!(key, " (in {})", cwd.display()).unwrap();
+        }
+        if let Some(docker_image) = &self.process.docker_image {
+            write!(key, " (with docker image {docker_image})").unwrap();
+        }
+        if let Some(env_passthrough) = &self.process.env_passthrough {
+            write!(key, " (clean env, passing {})", env_passthrough.join(",")).unwrap();
+        }
+        if !self.process.env_file_vars.is_empty() {
+            // The variables may be long or secret, so only include a digest of
+            // them.
+            let env_file_vars = self
+                .process
+                .env_file_vars
+                .iter()
+                .map(|(name, value)| format!("{name}={value}\0"))
+                .collect::<String>();
+            let env_file_vars_oid = NonZeroOid::hash_blob(env_file_vars.as_bytes())?;
+            write!(key, " (with env {env_file_vars_oid})").unwrap();
+        }
+        Ok(make_test_command_slug(key))
+    }
+}
+
+/// Combine the resolved test command with the extra arguments given after
+/// `--`. Returns the command to display and to identify cached results by,
+/// which includes the escaped extra arguments, and the command to pass to `sh
+/// -c`, which receives the extra arguments as positional parameters.
+///
+/// If the command already references `$@` or `$*` (e.g. a command alias which
+/// forwards its arguments), then the extra arguments are substituted there
+/// rather than also being appended to the end of the command.
+fn make_test_shell_command(command: String, extra_args: &[String]) -> (String, String) {
+    if extra_args.is_empty() {
+        return (command.clone(), command);
+    }
+
+    let display_command = std::iter::once(command.clone())
+        .chain(extra_args.iter().map(shell_escape))
+        .join(" ");
+    let shell_command = if command.contains("$@") || command.contains("$*") {
+        command
+    } else {
+        format!(r#"{command} "$@""#)
+    };
+    (display_command, shell_command)
+}
+
+/// Parse the contents of a dotenv-style environment file into a list of
+/// variables. Each non-empty line which isn't a `#` comment should be of the
+/// form `KEY=VALUE`, optionally preceded by `export`. Values may be wrapped in
+/// single quotes (taken literally) or double quotes (supporting `\n`, `\"`
+/// and `\\` escapes); unquoted values end at the first ` #` comment.
+fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut result = Vec::new();
+    for (line_num, line) in contents.lines().enumerate() {
+        let line_num = line_num + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => return Err(format!("line {line_num}: expected KEY=VALUE")),
+        };
+        let is_valid_key = !key.is_empty()
+            && !key.starts_with(|c: char| c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_valid_key {
+            return Err(format!("line {line_num}: invalid variable name {key:?}"));
+        }
+
+        let value = if let Some(value) = value.strip_prefix('\'') {
+            match value.strip_suffix('\'') {
+                Some(value) => value.to_owned(),
+                None => return Err(format!("line {line_num}: unterminated single quote")),
+            }
+        } else if let Some(value) = value.strip_prefix('"') {
+            let mut unescaped = String::with_capacity(value.len());
+            let mut chars = value.chars();
+            let mut is_terminated = false;
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => {
+                        is_terminated = true;
+                        break;
+                    }
+                    '\\' => match chars.next() {
+                        Some('n') => unescaped.push('\n'),
+                        Some(c) => unescaped.push(c),
+                        None => break,
+                    },
+                    c => unescaped.push(c),
+                }
+            }
+            if !is_terminated {
+                return Err(format!("line {line_num}: unterminated double quote"));
+            }
+            if !chars.as_str().is_empty() {
+                return Err(format!(
+                    "line {line_num}: unexpected text after closing quote"
+                ));
+            }
+            unescaped
+        } else {
+            match value.find(" #") {
+                Some(index) => value[..index].trim_end().to_owned(),
+                None => value.to_owned(),
+            }
+        };
+        result.push((key.to_owned(), value));
+    }
+    Ok(result)
+}
+
+/// `test` command.
+#[instrument]
+pub fn command_main(ctx: CommandContext, args: TestArgs) -> eyre::Result<ExitCode> {
+    let CommandContext {
+        effects,
+        git_run_info,
+    } = ctx;
+    let TestArgs { subcommand } = args;
+    match subcommand {
+        TestSubcommand::Clean {
+            revset,
+            resolve_revset_options,
+            failed_only,
+            passed_only,
+            all,
+            dry_run,
+            cache_dir,
+        } => subcommand_clean(
+            &effects,
+            revset,
+            &resolve_revset_options,
+            failed_only,
+            passed_only,
+            all,
+            dry_run,
+            cache_dir.as_deref(),
+        ),
+
+        TestSubcommand::Du { cache_dir } => subcommand_du(&effects, cache_dir.as_deref()),
+
+        TestSubcommand::Gc => subcommand_gc(&effects, &git_run_info),
+
+        TestSubcommand::Log {
+            revset,
+            resolve_revset_options,
+            cache_dir,
+        } => subcommand_log(
+            &effects,
+            revset,
+            &resolve_revset_options,
+            cache_dir.as_deref(),
+        ),
+
+        TestSubcommand::Run {
+            exec: command,
+            command: command_alias,
+            revset,
+            resolve_revset_options,
+            commits_from,
+            verbosity,
+            context,
+            group,
+            strategy,
+            search,
+            bisect,
+            bisect_ref,
+            porcelain,
+            perf_threshold,
+            fail_on_output,
+            no_cache,
+            cache_dir,
+            rerun_failures,
+            interactive,
+            jobs,
+            timeout,
+            memory_limit,
+            wait,
+            retries,
+            max_failures,
+            skip_descendants_on_failure,
+            skip_empty,
+            test_merge_parents,
+            shuffle,
+            shuffle_seed,
+            format,
+            junit,
+            notify,
+            setup,
+            combined_output,
+            stream,
+            clean_env,
+            env_file,
+            cwd,
+            keep_worktrees,
+            worktree_count,
+            extra_args,
+        } => subcommand_run(
+            &effects,
+            &git_run_info,
+            &RawTestOptions {
+                exec: command,
+                command: command_alias,
+                dry_run: false,
+                strategy,
+                search,
+                bisect,
+                no_cache,
+                cache_dir,
+                interactive,
+                jobs,
+                verbosity: Verbosity::from(verbosity),
+                apply_fixes: false,
+                limits: TestLimits {
+                    timeout: timeout.map(Duration::from_secs),
+                    memory_limit,
+                    lock_wait_timeout: wait.map(Duration::from_secs),
+                    retries,
+                },
+                scheduling: TestScheduling {
+                    rerun_failures,
+                    max_failures,
+                    skip_descendants_on_failure,
+                    skip_empty,
+                    test_merge_parents,
+                    shuffle_seed: if shuffle || shuffle_seed.is_some() {
+                        Some(shuffle_seed.unwrap_or_else(|| fastrand::u64(..)))
+                    } else {
+                        None
+                    },
+                },
+                process: RawTestProcessOptions {
+                    extra_args,
+                    setup,
+                    clean_env,
+                    env_file,
+                    cwd,
+                    combined_output,
+                    stream,
+                    keep_worktrees,
+                    worktree_count,
+                },
+                outcome: RawTestOutcomeOptions {
+                    perf_threshold,
+                    fail_on_output,
+                },
+                report: RawTestReportOptions {
+                    bisect_ref,
+                    porcelain,
+                    context,
+                    group,
+                    format,
+                    junit_path: junit,
+                    notify,
+                },
+                fix_selection: Default::default(),
+            },
+            revset,
+            &resolve_revset_options,
+            commits_from.as_deref(),
+            None,
+        ),
+
+        TestSubcommand::Show {
+            exec: command,
+            command: command_alias,
+            revset,
+            resolve_revset_options,
+            verbosity,
+            context,
+            failed_only,
+            passed_only,
+            skipped_only,
+            cache_dir,
+        } => subcommand_show(
+            &effects,
+            &RawTestOptions {
+                exec: command.into_iter().collect(),
+                command: command_alias,
+                dry_run: false,
+                strategy: None,
+                search: None,
+                bisect: false,
+                no_cache: false,
+                cache_dir,
+                interactive: false,
+                jobs: None,
+                verbosity: Verbosity::from(verbosity),
+                apply_fixes: false,
+                limits: Default::default(),
+                scheduling: Default::default(),
+                process: Default::default(),
+                outcome: Default::default(),
+                report: RawTestReportOptions {
+                    context,
+                    ..Default::default()
+                },
+                fix_selection: Default::default(),
+            },
+            revset,
+            &resolve_revset_options,
+            failed_only,
+            passed_only,
+            skipped_only,
+        ),
+
+        TestSubcommand::Compare {
+            exec,
+            revset,
+            resolve_revset_options,
+            strategy,
+            no_cache,
+            cache_dir,
+            jobs,
+        } => subcommand_compare(
+            &effects,
+            &git_run_info,
+            &RawTestOptions {
+                exec,
+                command: None,
+                dry_run: false,
+                strategy,
+                search: None,
+                bisect: false,
+                no_cache,
+                cache_dir,
+                interactive: false,
+                jobs,
+                verbosity: Verbosity::None,
+                apply_fixes: false,
+                limits: Default::default(),
+                scheduling: Default::default(),
+                process: Default::default(),
+                outcome: Default::default(),
+                report: Default::default(),
+                fix_selection: Default::default(),
+            },
+            revset,
+            &resolve_revset_options,
+        ),
+
+        TestSubcommand::Fix {
+            exec: command,
+            command: command_alias,
+            dry_run,
+            interactive: interactive_fixes,
+            diff: show_fix_diff,
+            pathspec: fix_pathspecs,
+            revset,
+            resolve_revset_options,
+            verbosity,
+            strategy,
+            no_cache,
+            cache_dir,
+            jobs,
+            move_options,
+        } => subcommand_run(
+            &effects,
+            &git_run_info,
+            &RawTestOptions {
+                exec: command.into_iter().collect(),
+                command: command_alias,
+                dry_run,
+                strategy,
+                search: None,
+                bisect: false,
+                no_cache,
+                cache_dir,
+                interactive: false,
+                jobs,
+                verbosity: Verbosity::from(verbosity),
+                apply_fixes: true,
+                limits: Default::default(),
+                scheduling: Default::default(),
+                process: Default::default(),
+                outcome: Default::default(),
+                report: Default::default(),
+                fix_selection: RawTestFixSelection {
+                    interactive: interactive_fixes,
+                    show_diff: show_fix_diff,
+                    pathspecs: fix_pathspecs,
+                },
+            },
+            revset,
+            &resolve_revset_options,
+            None,
+            Some(&move_options),
+        ),
+    }
+}
+
+/// Run the command provided in `options` on each of the commits in `revset`.
+#[instrument]
+fn subcommand_run(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    options: &RawTestOptions,
+    revset: Revset,
+    resolve_revset_options: &ResolveRevsetOptions,
+    commits_from: Option<&Path>,
+    move_options: Option<&MoveOptions>,
+) -> eyre::Result<ExitCode> {
+    let now = SystemTime::now();
+    let start_time = Instant::now();
+    let repo = Repo::from_current_dir()?;
+    let conn = repo.get_db_conn()?;
+    let event_log_db = EventLogDb::new(&conn)?;
+    let event_tx_id = event_log_db.make_transaction_id(now, "test run")?;
+    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
+    let event_cursor = event_replayer.make_default_cursor();
+    let references_snapshot = repo.get_references_snapshot()?;
+    let mut dag = Dag::open_and_sync(
+        effects,
+        &repo,
+        &event_replayer,
+        event_cursor,
+        &references_snapshot,
+    )?;
+
+    let (revset, commit_set) = match commits_from {
+        Some(path) => {
+            match resolve_commits_from_file(effects, &repo, &mut dag, path, resolve_revset_options)?
+            {
+                Ok(result) => result,
+                Err(exit_code) => return Ok(exit_code),
+            }
+        }
+        None => match resolve_commits(
+            effects,
+            &repo,
+            &mut dag,
+            &[revset.clone()],
+            resolve_revset_options,
+        ) {
+            Ok(mut commit_sets) => (revset, commit_sets.pop().unwrap()),
+            Err(err) => {
+                err.describe(effects)?;
+                return Ok(ExitCode(1));
+            }
+        },
+    };
+
+    // Stop testing gracefully on Ctrl-C, so that the results which were
+    // already produced are still reported. In interactive mode, Ctrl-C is
+    // handled by the user's shell instead.
+    let _interrupt_guard = if options.interactive {
+        None
+    } else {
+        Some(InterruptGuard::install())
+    };
+
+    let porcelain = options.report.porcelain;
+    if options.exec.len() > 1 {
+        return run_multiple_commands(
+            now,
+            effects,
+            git_run_info,
+            &dag,
+            &repo,
+            &event_log_db,
+            event_tx_id,
+            &revset,
+            &commit_set,
+            move_options,
+            options,
+        );
+    }
+
+    let options = match ResolvedTestOptions::resolve(
+        now,
+        effects,
+        &dag,
+        &repo,
+        event_tx_id,
+        &commit_set,
+        move_options,
+        options,
+    )? {
+        Ok(options) => options,
+        Err(exit_code) => return Ok(exit_code),
+    };
+    let cache_limits = match get_test_cache_limits(effects, &repo)? {
+        Ok(cache_limits) => cache_limits,
+        Err(exit_code) => return Ok(exit_code),
+    };
+
+    // When producing machine-readable output, make sure that nothing else is
+    // written to stdout.
+    let run_effects = match options.report.format {
+        TestOutputFormat::Human if porcelain => effects.suppress(),
+        TestOutputFormat::Human => effects.clone(),
+        TestOutputFormat::Json | TestOutputFormat::Tap | TestOutputFormat::Csv => {
+            effects.suppress()
+        }
+    };
+
+    let test_results = match run_tests_on_commits(
+        &run_effects,
+        git_run_info,
+        &dag,
+        &repo,
+        &event_log_db,
+        event_tx_id,
+        &commit_set,
+        &options,
+    )? {
+        Ok(test_results) => test_results,
+        Err(exit_code) => return Ok(exit_code),
+    };
+
+    if let Some(junit_path) = &options.report.junit_path {
+        report::write_junit(junit_path, &options.command, &test_results)?;
+    }
+
+    if let Some(notify_threshold) = options.report.notify_threshold {
+        if start_time.elapsed() >= notify_threshold {
+            notify::notify_test_results(&options.command, &test_results);
+        }
+    }
+
+    let evicted_trees = if options.cache_mode.should_write() {
+        evict_test_cache(&repo, &dag, &options.cache_dir, &commit_set, &cache_limits)?
+    } else {
+        Vec::new()
+    };
+
+    match options.report.format {
+        TestOutputFormat::Human => {}
+        TestOutputFormat::Json => {
+            report::write_json(effects, &test_results)?;
+            return Ok(get_exit_code(
+                &test_results,
+                options.search_strategy.is_some(),
+            ));
+        }
+        TestOutputFormat::Tap => {
+            report::write_tap(effects, &repo, &test_results)?;
+            return Ok(get_exit_code(
+                &test_results,
+                options.search_strategy.is_some(),
+            ));
+        }
+        TestOutputFormat::Csv => {
+            report::write_csv(effects, &repo, &test_results)?;
+            return Ok(get_exit_code(
+                &test_results,
+                options.search_strategy.is_some(),
+            ));
+        }
+    }
+
+    // In porcelain mode, the summary is suppressed, but still produced for its
+    // side effects (such as updating the `--bisect-ref`).
+    let exit_code = print_summary(
+        &run_effects,
+        &dag,
+        &repo,
+        &revset,
+        &test_results,
+        &options,
+        start_time.elapsed(),
+    )?;
+    if porcelain {
+        report::write_porcelain(effects, &test_results)?;
+    }
+    print_evicted_trees(&run_effects, &evicted_trees)?;
+    if !exit_code.is_success() {
+        return Ok(exit_code);
+    }
+
+    if let Some((execute_options, permissions)) = &options.fix_options {
+        let exit_code = apply_fixes(
+            effects,
+            git_run_info,
+            &mut dag,
+            &repo,
+            &event_log_db,
+            execute_options,
+            permissions.clone(),
+            options.is_dry_run,
+            &options.fix_selection,
+            &options.command,
+            &test_results,
+        )?;
+        if !exit_code.is_success() {
+            return Ok(exit_code);
+        }
+    }
+
+    Ok(ExitCode(0))
+}
+
+/// Read a list of commits from `path` (or from stdin, if `path` is `-`), with
+/// one commit or revset per line, and resolve them into a single commit set.
+/// Also returns an equivalent revset, for use in hints.
+#[instrument]
+fn resolve_commits_from_file(
+    effects: &Effects,
+    repo: &Repo,
+    dag: &mut Dag,
+    path: &Path,
+    resolve_revset_options: &ResolveRevsetOptions,
+) -> eyre::Result<Result<(Revset, CommitSet), ExitCode>> {
+    let is_stdin = path == Path::new("-");
+    let source = if is_stdin {
+        "stdin".to_string()
+    } else {
+        path.display().to_string()
+    };
+    let contents = if is_stdin {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map(|_| contents)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    let contents = match contents {
+        Ok(contents) => contents,
+        Err(err) => {
+            writeln!(
+                effects.get_error_stream(),
+                "Could not read commits from {source}: {err}"
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+    };
+
+    let mut specs = Vec::new();
+    let mut commit_set = CommitSet::empty();
+    for (line_num, line) in contents.lines().enumerate() {
+        let line_num = line_num + 1;
+        let spec = line.trim();
+        if spec.is_empty() || spec.starts_with('#') {
+            continue;
+        }
+
+        let commits = match resolve_commits(
+            effects,
+            repo,
+            dag,
+            &[Revset(spec.to_string())],
+            resolve_revset_options,
+        ) {
+            Ok(mut commit_sets) => commit_sets.pop().unwrap(),
+            Err(err) => {
+                writeln!(
+                    effects.get_error_stream(),
+                    "Invalid commit on line {line_num} of {source}: {spec}"
+                )?;
+                err.describe(effects)?;
+                return Ok(Err(ExitCode(1)));
+            }
+        };
+        if dag.set_is_empty(&commits)? {
+            writeln!(
+                effects.get_error_stream(),
+                "No commits matched on line {line_num} of {source}: {spec}"
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+        commit_set = commit_set.union(&commits);
+        specs.push(spec.to_string());
+    }
+    Ok(Ok((Revset(specs.join(" + ")), commit_set)))
+}
+
+/// Run the test command in `options` on each of the commits in `commit_set`
+/// and return the results, without printing a summary. This is the entry point
+/// for running tests programmatically; `git test run` is a wrapper around it.
+/// Progress is still reported via `effects`; pass `effects.suppress()` to hide
+/// it.
+///
+/// The provided `options` can be obtained from `ResolvedTestOptions::resolve`,
+/// using the same `event_tx_id`. Unlike `git test run`, this doesn't install a
+/// handler for `SIGINT`, so the caller remains responsible for handling it.
+#[instrument]
+pub fn run_tests_on_commits(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    dag: &Dag,
+    repo: &Repo,
+    event_log_db: &EventLogDb,
+    event_tx_id: EventTransactionId,
+    commit_set: &CommitSet,
+    options: &ResolvedTestOptions,
+) -> eyre::Result<Result<TestResults, ExitCode>> {
+    let commits = sorted_commit_set(repo, dag, commit_set)?;
+    run_tests_with_abort_trap(
+        SystemTime::now(),
+        effects,
+        git_run_info,
+        dag,
+        repo,
+        event_log_db,
+        event_tx_id,
+        commits,
+        options,
+    )
+}
+
+/// Run the test command on `commits`, guarding the run with an abort trap (see
+/// `set_abort_trap`).
+#[instrument]
+fn run_tests_with_abort_trap<'repo>(
+    now: SystemTime,
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    dag: &Dag,
+    repo: &'repo Repo,
+    event_log_db: &EventLogDb,
+    event_tx_id: EventTransactionId,
+    commits: Vec<Commit<'repo>>,
+    options: &ResolvedTestOptions,
+) -> eyre::Result<Result<TestResults, ExitCode>> {
+    let abort_trap = match set_abort_trap(
+        now,
+        effects,
+        git_run_info,
+        repo,
+        event_log_db,
+        event_tx_id,
+        options.execution_strategy,
+    )? {
+        Ok(abort_trap) => abort_trap,
+        Err(exit_code) => return Ok(Err(exit_code)),
+    };
+
+    let commits = if options.scheduling.rerun_failures {
+        filter_previously_failed_commits(repo, commits, options)?
+    } else {
+        commits
+    };
+    if options.is_interactive && options.cache_mode.should_read() {
+        print_interactive_resume_point(effects, &commits, options)?;
+    }
+    let test_results: Result<_, _> = {
+        // Don't draw progress over the output of the test command.
+        let effects = if options.is_interactive || options.process.stream {
+            effects.suppress()
+        } else {
+            effects.clone()
+        };
+        run_tests(
+            &effects,
+            git_run_info,
+            dag,
+            repo,
+            event_log_db,
+            event_tx_id,
+            &commits,
+            options,
+        )
+    };
+    let abort_trap_exit_code = clear_abort_trap(effects, git_run_info, event_tx_id, abort_trap)?;
+    if !abort_trap_exit_code.is_success() {
+        return Ok(Err(abort_trap_exit_code));
+    }
+
+    test_results
+}
+
+/// Run each of several commands on the commits in `commit_set` in turn,
+/// printing the summary for each command, followed by the aggregated results.
+/// A commit passes only if every command passed on it.
+#[instrument]
+fn run_multiple_commands(
+    now: SystemTime,
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    dag: &Dag,
+    repo: &Repo,
+    event_log_db: &EventLogDb,
+    event_tx_id: EventTransactionId,
+    revset: &Revset,
+    commit_set: &CommitSet,
+    move_options: Option<&MoveOptions>,
+    options: &RawTestOptions,
+) -> eyre::Result<ExitCode> {
+    if options.search.is_some()
+        || options.bisect
+        || options.report.format != TestOutputFormat::Human
+        || options.report.junit_path.is_some()
+        || options.report.notify
+    {
+        writeln!(
+            effects.get_output_stream(),
+            "The --exec option can only be provided once when using --search, --bisect, --format, --junit, or --notify."
+        )?;
+        return Ok(ExitCode(1));
+    }
+
+    let short_circuit = repo
+        .get_readonly_config()?
+        .get_or("branchless.test.shortCircuit", true)?;
+    let commits = sorted_commit_set(repo, dag, commit_set)?;
+    let mut failed_commit_oids: HashSet<NonZeroOid> = HashSet::new();
+    let mut all_test_results = Vec::new();
+    for command in &options.exec {
+        let options = RawTestOptions {
+            exec: vec![command.clone()],
+            ..options.clone()
+        };
+        let options = match ResolvedTestOptions::resolve(
+            now,
+            effects,
+            dag,
+            repo,
+            event_tx_id,
+            commit_set,
+            move_options,
+            &options,
+        )? {
+            Ok(options) => options,
+            Err(exit_code) => return Ok(exit_code),
+        };
+
+        let command_commits = commits
+            .iter()
+            .filter(|commit| !(short_circuit && failed_commit_oids.contains(&commit.get_oid())))
+            .cloned()
+            .collect_vec();
+        let start_time = Instant::now();
+        let test_results = match run_tests_with_abort_trap(
+            now,
+            effects,
+            git_run_info,
+            dag,
+            repo,
+            event_log_db,
+            event_tx_id,
+            command_commits,
+            &options,
+        )? {
+            Ok(test_results) => test_results,
+            Err(exit_code) => return Ok(exit_code),
+        };
+        let exit_code = print_summary(
+            effects,
+            dag,
+            repo,
+            revset,
+            &test_results,
+            &options,
+            start_time.elapsed(),
+        )?;
+        if test_results.testing_aborted_error.is_some()
+            || test_results.stopped_after_failures.is_some()
+            || test_results.interrupted
+        {
+            return Ok(exit_code);
+        }
+
+        for (commit_oid, test_output) in &test_results.test_outputs {
+            match test_output.test_status.get_category() {
+                TestStatusCategory::Failed => {
+                    failed_commit_oids.insert(*commit_oid);
+                }
+                TestStatusCategory::Passed | TestStatusCategory::Skipped => {}
+            }
+        }
+        all_test_results.push(test_results);
+    }
+
+    let mut num_passed = 0;
+    let mut num_failed = 0;
+    let mut num_skipped = 0;
+    for commit in &commits {
+        let commit_oid = commit.get_oid();
+        if failed_commit_oids.contains(&commit_oid) {
+            num_failed += 1;
+        } else if all_test_results.iter().all(|test_results| {
+            // If there's no result for this commit, then it was filtered out
+            // by `--rerun-failures` because it already passed.
+            match test_results.test_outputs.get(&commit_oid) {
+                Some(test_output) => {
+                    test_output.test_status.get_category() == TestStatusCategory::Passed
+                }
+                None => true,
+            }
+        }) {
+            num_passed += 1;
+        } else {
+            num_skipped += 1;
+        }
+    }
+
+    writeln!(
+        effects.get_output_stream(),
+        "Ran {} on {}:",
+        Pluralize {
+            determiner: None,
+            amount: options.exec.len(),
+            unit: ("command", "commands")
+        },
+        Pluralize {
+            determiner: None,
+            amount: commits.len(),
+            unit: ("commit", "commits")
+        },
+    )?;
+    writeln!(
+        effects.get_output_stream(),
+        "{}",
+        render_test_counts(effects, num_passed, num_failed, num_skipped)?
+    )?;
+
+    if num_passed == commits.len() {
+        Ok(ExitCode(0))
+    } else {
+        Ok(ExitCode(1))
+    }
+}
+
+/// Filter `commits` down to those which don't have a cached result, or whose
+/// cached result indicates that the test command failed. (When
+/// `options.scheduling.rerun_failures` is set, `make_test_files` discards cached
+/// failures, so both cases are reported as `NotCached`.)
+#[instrument]
+fn filter_previously_failed_commits<'repo>(
+    repo: &'repo Repo,
+    commits: Vec<Commit<'repo>>,
+    options: &ResolvedTestOptions,
+) -> eyre::Result<Vec<Commit<'repo>>> {
+    let mut result = Vec::new();
+    for commit in commits {
+        match make_test_files(&commit, options)? {
+            TestFilesResult::NotCached(_) => result.push(commit),
+            TestFilesResult::Cached(_) => {}
+        }
+    }
+    Ok(result)
+}
+
+/// If an earlier interactive run was interrupted partway through `commits`,
+/// tell the user which commit testing will resume from. The verdicts for the
+/// earlier commits were already cached by `test_commit`, so they won't be
+/// prompted for again.
+#[instrument]
+fn print_interactive_resume_point(
+    effects: &Effects,
+    commits: &[Commit],
+    options: &ResolvedTestOptions,
+) -> eyre::Result<()> {
+    for (i, commit) in commits.iter().enumerate() {
+        match make_test_files(commit, options)? {
+            TestFilesResult::Cached(_) => {}
+            TestFilesResult::NotCached(_) => {
+                if i > 0 {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Resuming interactive testing from commit: {} (to start over, re-run with --no-cache)",
+                        effects
+                            .get_glyphs()
+                            .render(commit.friendly_describe(effects.get_glyphs())?)?
+                    )?;
+                }
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Try to take the lock in `lock_file` (opened from `lock_path`), recording
+/// the current PID in it.
+///
+/// The lock is released by the OS when its owner exits, but a process spawned
+/// by the owner (such as a daemon started by the test command) can inherit the
+/// lock's file descriptor and keep it locked indefinitely. If the PID recorded
+/// in the lock file belongs to a process which has exited, the lock is stale,
+/// so replace the lock file with a new one and lock that instead.
+#[instrument]
+fn try_lock_reclaiming_stale(lock_file: &mut LockFile, lock_path: &Path) -> eyre::Result<bool> {
+    if lock_file.try_lock_with_pid()? {
+        return Ok(true);
+    }
+
+    let owner_pid = match std::fs::read_to_string(lock_path)
+        .ok()
+        .and_then(|contents| contents.trim().parse::<u32>().ok())
+    {
+        Some(owner_pid) => owner_pid,
+        None => return Ok(false),
+    };
+    if is_process_alive(owner_pid) {
+        return Ok(false);
+    }
+
+    info!(
+        ?lock_path,
+        ?owner_pid,
+        "Reclaiming stale lock from dead process"
+    );
+    std::fs::remove_file(lock_path)
+        .wrap_err_with(|| format!("Removing stale lock file {lock_path:?}"))?;
+    *lock_file = LockFile::open(lock_path)?;
+    Ok(lock_file.try_lock_with_pid()?)
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) => pid,
+        Err(_) => return false,
+    };
+    // SAFETY: `kill` has no memory-safety preconditions, and sending signal 0
+    // only checks whether the process exists.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        true
+    } else {
+        // `EPERM` means that the process exists, but belongs to another user.
+        std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+    }
+}
+
+/// Without a portable way to check, assume that the process is still alive, so
+/// that a lock is never reclaimed from a running process.
+#[cfg(not(unix))]
+fn is_process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[must_use]
+#[derive(Debug)]
+struct AbortTrap {
+    is_active: bool,
+}
+
+/// Ensure that no commit operation is currently underway (such as a merge or
+/// rebase), and start a rebase.  In the event that the test invocation is
+/// interrupted, this will prevent the user from starting another commit
+/// operation without first running `git rebase --abort` to get back to their
+/// original commit.
+#[instrument]
+fn set_abort_trap(
+    now: SystemTime,
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    repo: &Repo,
+    event_log_db: &EventLogDb,
+    event_tx_id: EventTransactionId,
+    strategy: TestExecutionStrategy,
+) -> eyre::Result<Result<AbortTrap, ExitCode>> {
+    match strategy {
+        TestExecutionStrategy::Worktree
+        | TestExecutionStrategy::Docker
+        | TestExecutionStrategy::Tmpfs => return Ok(Ok(AbortTrap { is_active: false })),
+        TestExecutionStrategy::WorkingCopy => {}
+    }
+
+    if let Some(operation_type) = repo.get_current_operation_type() {
+        writeln!(
+            effects.get_output_stream(),
+            "A {operation_type} operation is already in progress."
+        )?;
+        writeln!(
+            effects.get_output_stream(),
+            "Run git {operation_type} --continue or git {operation_type} --abort to resolve it and proceed."
+        )?;
+        return Ok(Err(ExitCode(1)));
+    }
+
+    let head_info = repo.get_head_info()?;
+    let head_oid = match head_info.oid {
+        Some(head_oid) => head_oid,
+        None => {
+            writeln!(
+                effects.get_output_stream(),
+                "No commit is currently checked out; cannot start on-disk rebase."
+            )?;
+            writeln!(
+                effects.get_output_stream(),
+                "Check out a commit and try again."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+    };
+
+    let rebase_plan = RebasePlan {
+        first_dest_oid: head_oid,
+        commands: vec![RebaseCommand::Break],
+    };
+    match execute_rebase_plan(
+        effects,
+        git_run_info,
+        repo,
+        event_log_db,
+        &rebase_plan,
+        &ExecuteRebasePlanOptions {
+            now,
+            event_tx_id,
+            preserve_timestamps: true,
+            force_in_memory: false,
+            force_on_disk: true,
+            resolve_merge_conflicts: false,
+            check_out_commit_options: CheckOutCommitOptions {
+                render_smartlog: false,
+                ..Default::default()
+            },
+        },
+    )? {
+        ExecuteRebasePlanResult::Succeeded { rewritten_oids: _ } => {
+            // Do nothing.
+        }
+        ExecuteRebasePlanResult::DeclinedToMerge { failed_merge_info } => {
+            writeln!(
+                effects.get_output_stream(),
+                "BUG: Encountered unexpected merge failure: {failed_merge_info:?}"
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+        ExecuteRebasePlanResult::Failed { exit_code } => {
+            return Ok(Err(exit_code));
+        }
+    }
+
+    Ok(Ok(AbortTrap { is_active: true }))
+}
+
+#[instrument]
+fn clear_abort_trap(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    event_tx_id: EventTransactionId,
+    abort_trap: AbortTrap,
+) -> eyre::Result<ExitCode> {
+    let AbortTrap { is_active } = abort_trap;
+    if !is_active {
+        return Ok(ExitCode(0));
+    }
+
+    let exit_code = git_run_info.run(effects, Some(event_tx_id), &["rebase", "--abort"])?;
+    if !exit_code.is_success() {
+        writeln!(
+            effects.get_output_stream(),
+            "{}",
+            effects.get_glyphs().render(
+                StyledStringBuilder::new()
+                    .append_styled(
+                        "Error: Could not abort running commands with `git rebase --abort`.",
+                        BaseColor::Red.light()
+                    )
+                    .build()
+            )?
+        )?;
+    }
+    Ok(exit_code)
+}
+
+/// The result of running a test.
+#[derive(Debug)]
+pub struct TestOutput {
+    /// Only used for `--no-cache` invocations, to ensure that we don't clobber
+    /// an existing cached entry.
+    pub temp_dir: Option<TempDir>,
+
+    /// The path to the file containing the serialized test result information
+    /// (such as the exit code).
+    pub result_path: PathBuf,
+
+    /// The path to the file containing the stdout of the test command.
+    pub stdout_path: PathBuf,
+
+    /// The path to the file containing the stderr of the test command. If the
+    /// stdout and stderr were captured together, then this is the same as
+    /// `stdout_path`.
+    pub stderr_path: PathBuf,
+
+    /// The resulting status of the test.
+    pub test_status: TestStatus,
+
+    /// How long the test command took to run, if it was actually run (rather
+    /// than, for example, having its result read from the cache).
+    pub duration: Option<Duration>,
+
+    /// Whether the test command was interrupted by the user before it
+    /// finished. In that case, the result doesn't reflect the commit, so it
+    /// isn't cached or reported.
+    pub interrupted: bool,
+}
+
+/// The possible results of attempting to run a test.
+#[derive(Clone, Debug)]
+pub enum TestStatus {
+    /// Attempting to set up the working directory for the repository failed.
+    CheckoutFailed,
+
+    /// Invoking the test command failed.
+    SpawnTestFailed(String),
+
+    /// The test command was invoked successfully, but was terminated by a signal, rather than
+    /// returning an exit code normally.
+    TerminatedBySignal {
+        /// The number of the signal which terminated the command.
+        signal: i32,
+    },
+
+    /// The test command did not finish before the timeout elapsed, so it was
+    /// killed.
+    Timeout {
+        /// The timeout which was exceeded.
+        duration: Duration,
+    },
+
+    /// It appears that some other process is already running the test for a commit with the given
+    /// tree. (If that process crashed, then the test may need to be re-run.)
+    AlreadyInProgress,
+
+    /// Attempting to read cached data failed.
+    ReadCacheFailed(String),
+
+    /// The test command indicated that the commit should be skipped for testing.
+    Indeterminate {
+        /// The exit code of the command.
+        exit_code: i32,
+
+        /// The reason for skipping the commit, if the command wrote one to the
+        /// file named by `BRANCHLESS_TEST_SKIP_REASON_FILE`.
+        reason: Option<String>,
+    },
+
+    /// The test command wasn't run because it failed on an ancestor of the
+    /// commit (when using `--skip-descendants-on-failure`).
+    SkippedDueToAncestor {
+        /// The ancestor commit on which the test command failed.
+        ancestor_oid: NonZeroOid,
+    },
+
+    /// The test command wasn't run because the commit doesn't change its
+    /// (first) parent's tree (when using `--skip-empty`).
+    SkippedEmpty,
+
+    /// The test command wasn't run because the commit is a re-creation of a
+    /// merge from the perspective of one of its parents (when using
+    /// `--test-merge-parents`), and merging the other parent conflicted.
+    MergeParentConflicted,
+
+    /// The test command indicated that the process should be aborted entirely.
+    Abort {
+        /// The exit code of the command.
+        exit_code: i32,
+    },
+
+    /// The test failed and returned the provided (non-zero) exit code.
+    Failed {
+        /// Whether or not the result was cached (indicating that we didn't
+        /// actually re-run the test).
+        cached: bool,
+
+        /// The exit code of the process.
+        exit_code: i32,
+
+        /// Whether the test was run interactively (the user executed the
+        /// command via `--interactive`).
+        interactive: bool,
+
+        /// How many times the test command was run before giving up.
+        attempts: usize,
+
+        /// Why the commit was considered to have failed, if not only because
+        /// of its exit code (such as when its output matched the pattern
+        /// given by `--fail-on-output`).
+        reason: Option<String>,
+    },
+
+    /// The test command returned a successful exit code, but the measurement
+    /// which it printed was above the threshold given by `--perf-threshold`.
+    PerfThresholdExceeded {
+        /// Whether or not the result was cached (indicating that we didn't
+        /// actually re-run the test).
+        cached: bool,
+
+        /// The measurement parsed from the last numeric line of the test
+        /// command's output.
+        measurement: f64,
+
+        /// The threshold which the measurement exceeded.
+        threshold: f64,
+    },
+
+    /// The test passed and returned a successful exit code.
+    Passed {
+        /// Whether or not the result was cached (indicating that we didn't
+        /// actually re-run the test).
+        cached: bool,
+
+        /// Information about the working copy state after running the test command.
+        fix_info: FixInfo,
+
+        /// Whether the test was run interactively (the user executed the
+        /// command via `--interactive`).
+        interactive: bool,
+
+        /// How many times the test command was run before it passed. If this is
+        /// more than one, then the test is flaky.
+        attempts: usize,
+    },
+}
+
+/// Information about the working copy state after running the test command.
+#[derive(Clone, Debug)]
+pub struct FixInfo {
+    /// The resulting commit which was checked out as `HEAD`. This is usually
+    /// the same commit as was being tested, but the test command could amend or
+    /// switch to a different commit.
+    pub head_commit_oid: Option<NonZeroOid>,
+
+    /// The resulting contents of the working copy after the command was
+    /// executed (if taking a working copy snapshot succeeded and there were no
+    /// merge conflicts, etc.).
+    pub snapshot_tree_oid: Option<NonZeroOid>,
+}
+
+impl TestStatus {
+    /// Get whether this status counts as passed, failed, or skipped.
+    pub fn get_category(&self) -> TestStatusCategory {
+        match self {
+            TestStatus::CheckoutFailed
+            | TestStatus::SpawnTestFailed(_)
+            | TestStatus::AlreadyInProgress
+            | TestStatus::ReadCacheFailed(_)
+            | TestStatus::TerminatedBySignal { .. }
+            | TestStatus::Indeterminate { .. }
+            | TestStatus::SkippedDueToAncestor { .. }
+            | TestStatus::SkippedEmpty
+            | TestStatus::MergeParentConflicted => TestStatusCategory::Skipped,
+            TestStatus::Failed { .. }
+            | TestStatus::PerfThresholdExceeded { .. }
+            | TestStatus::Timeout { .. }
+            | TestStatus::Abort { .. } => TestStatusCategory::Failed,
+            TestStatus::Passed { .. } => TestStatusCategory::Passed,
+        }
+    }
+
+    #[instrument]
+    fn get_icon(&self) -> &'static str {
+        match self.get_category() {
+            TestStatusCategory::Skipped => icons::EXCLAMATION,
+            TestStatusCategory::Failed => icons::CROSS,
+            TestStatusCategory::Passed => icons::CHECKMARK,
+        }
+    }
+
+    #[instrument]
+    fn get_style(&self) -> Style {
+        match self.get_category() {
+            TestStatusCategory::Skipped => *STYLE_SKIPPED,
+            TestStatusCategory::Failed => *STYLE_FAILURE,
+            TestStatusCategory::Passed => *STYLE_SUCCESS,
+        }
+    }
+
+    /// Get a stable, machine-readable name for this status.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            TestStatus::CheckoutFailed => "checkout_failed",
+            TestStatus::SpawnTestFailed(_) => "spawn_test_failed",
+            TestStatus::TerminatedBySignal { .. } => "terminated_by_signal",
+            TestStatus::Timeout { .. } => "timeout",
+            TestStatus::AlreadyInProgress => "already_in_progress",
+            TestStatus::ReadCacheFailed(_) => "read_cache_failed",
+            TestStatus::Indeterminate { .. } => "indeterminate",
+            TestStatus::SkippedDueToAncestor { .. } => "skipped_due_to_ancestor",
+            TestStatus::SkippedEmpty => "skipped_empty",
+            TestStatus::MergeParentConflicted => "merge_parent_conflicted",
+            TestStatus::Abort { .. } => "abort",
+            TestStatus::Failed { .. } => "failed",
+            TestStatus::PerfThresholdExceeded { .. } => "perf_threshold_exceeded",
+            TestStatus::Passed { .. } => "passed",
+        }
+    }
+
+    /// Get the exit code of the test command, if it ran to completion.
+    pub fn get_exit_code(&self) -> Option<i32> {
+        match self {
+            TestStatus::CheckoutFailed
+            | TestStatus::SpawnTestFailed(_)
+            | TestStatus::TerminatedBySignal { .. }
+            | TestStatus::Timeout { .. }
+            | TestStatus::AlreadyInProgress
+            | TestStatus::ReadCacheFailed(_)
+            | TestStatus::SkippedDueToAncestor { .. }
+            | TestStatus::SkippedEmpty
+            | TestStatus::MergeParentConflicted => None,
+            TestStatus::Indeterminate { exit_code, .. }
+            | TestStatus::Abort { exit_code }
+            | TestStatus::Failed { exit_code, .. } => Some(*exit_code),
+            TestStatus::PerfThresholdExceeded { .. } | TestStatus::Passed { .. } => {
+                Some(TEST_SUCCESS_EXIT_CODE)
+            }
+        }
+    }
+
+    /// Whether this result was read from the cache, rather than produced by
+    /// running the test command.
+    pub fn is_cached(&self) -> bool {
+        match self {
+            TestStatus::CheckoutFailed
+            | TestStatus::SpawnTestFailed(_)
+            | TestStatus::TerminatedBySignal { .. }
+            | TestStatus::Timeout { .. }
+            | TestStatus::AlreadyInProgress
+            | TestStatus::ReadCacheFailed(_)
+            | TestStatus::Indeterminate { .. }
+            | TestStatus::SkippedDueToAncestor { .. }
+            | TestStatus::SkippedEmpty
+            | TestStatus::MergeParentConflicted
+            | TestStatus::Abort { .. } => false,
+            TestStatus::Failed { cached, .. }
+            | TestStatus::PerfThresholdExceeded { cached, .. }
+            | TestStatus::Passed { cached, .. } => *cached,
+        }
+    }
+
+    /// Produce a friendly description of the test status.
+    #[instrument]
+    pub fn describe(
+        &self,
+        glyphs: &Glyphs,
+        commit: &Commit,
+        apply_fixes: bool,
+    ) -> eyre::Result<StyledString> {
+        let description = match self {
+            TestStatus::CheckoutFailed => StyledStringBuilder::new()
+                .append_styled("Failed to check out: ", self.get_style())
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::SpawnTestFailed(err) => StyledStringBuilder::new()
+                .append_styled(
+                    format!("Failed to spawn command: {err}: "),
+                    self.get_style(),
+                )
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::TerminatedBySignal { signal } => StyledStringBuilder::new()
+                .append_styled(
+                    match get_signal_name(*signal) {
+                        Some(signal_name) => {
+                            format!("Command terminated by signal {signal_name} ({signal}): ")
+                        }
+                        None => format!("Command terminated by signal {signal}: "),
+                    },
+                    self.get_style(),
+                )
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::Timeout { duration } => StyledStringBuilder::new()
+                .append_styled(
+                    format!("Timed out after {}s: ", duration.as_secs()),
+                    self.get_style(),
+                )
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::AlreadyInProgress => StyledStringBuilder::new()
+                .append_styled("Command already in progress? ", self.get_style())
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::ReadCacheFailed(_) => StyledStringBuilder::new()
+                .append_styled("Could not read cached command result: ", self.get_style())
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::Indeterminate { exit_code, reason } => StyledStringBuilder::new()
+                .append_styled(
+                    match reason {
+                        Some(reason) => format!(
+                            "Exit code indicated to skip this commit (exit code {exit_code}, reason: {reason}): "
+                        ),
+                        None => format!(
+                            "Exit code indicated to skip this commit (exit code {exit_code}): "
+                        ),
+                    },
+                    self.get_style(),
+                )
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::SkippedDueToAncestor { ancestor_oid: _ } => StyledStringBuilder::new()
+                .append_styled("Skipped because an ancestor failed: ", self.get_style())
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::SkippedEmpty => StyledStringBuilder::new()
+                .append_styled("Skipped empty commit: ", self.get_style())
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::MergeParentConflicted => StyledStringBuilder::new()
+                .append_styled("Skipped conflicting merge: ", self.get_style())
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::Abort { exit_code } => StyledStringBuilder::new()
+                .append_styled(
+                    format!("Exit code indicated to abort command (exit code {exit_code}): "),
+                    self.get_style(),
+                )
+                .append(commit.friendly_describe(glyphs)?)
+                .build(),
+
+            TestStatus::Failed {
+                cached,
+                interactive,
+                exit_code,
+                attempts,
+                reason,
+            } => {
+                let mut descriptors = Vec::new();
+                if *cached {
+                    descriptors.push("cached".to_string());
+                }
+                descriptors.push(format!("exit code {exit_code}"));
+                if let Some(reason) = reason {
+                    descriptors.push(reason.clone());
+                }
+                if *attempts > 1 {
+                    descriptors.push(format!("{attempts} attempts"));
+                }
+                if *interactive {
+                    descriptors.push("interactive".to_string());
+                }
+                let descriptors = descriptors.join(", ");
+                StyledStringBuilder::new()
+                    .append_styled(format!("Failed ({descriptors}): "), self.get_style())
+                    .append(commit.friendly_describe(glyphs)?)
+                    .build()
+            }
+
+            TestStatus::PerfThresholdExceeded {
+                cached,
+                measurement,
+                threshold,
+            } => {
+                let cached = if *cached { "cached, " } else { "" };
+                StyledStringBuilder::new()
+                    .append_styled(
+                        format!(
+                            "Failed ({cached}measurement {measurement} exceeded threshold {threshold}): "
+                        ),
+                        self.get_style(),
+                    )
+                    .append(commit.friendly_describe(glyphs)?)
+                    .build()
+            }
+
+            TestStatus::Passed {
+                cached,
+                interactive,
+                fix_info:
+                    FixInfo {
+                        head_commit_oid: _,
+                        snapshot_tree_oid,
+                    },
+                attempts,
+            } => {
+                let mut descriptors = Vec::new();
+                if *cached {
+                    descriptors.push("cached".to_string());
+                }
+                if *attempts > 1 {
+                    descriptors.push("flaky".to_string());
+                    descriptors.push(format!("{attempts} attempts"));
+                }
+                match (snapshot_tree_oid, commit.get_tree_oid()) {
+                    (Some(snapshot_tree_oid), MaybeZeroOid::NonZero(original_tree_oid)) => {
+                        if *snapshot_tree_oid != original_tree_oid {
+                            descriptors.push(if apply_fixes {
+                                "fixed".to_string()
+                            } else {
+                                "fixable".to_string()
+                            });
+                        }
+                    }
+                    (None, _) | (_, MaybeZeroOid::Zero) => {}
+                }
+                if *interactive {
+                    descriptors.push("interactive".to_string());
+                }
+                let descriptors = if descriptors.is_empty() {
+                    "".to_string()
+                } else {
+                    format!(" ({})", descriptors.join(", "))
+                };
+                StyledStringBuilder::new()
+                    .append_styled(format!("Passed{descriptors}: "), self.get_style())
+                    .append(commit.friendly_describe(glyphs)?)
+                    .build()
+            }
+        };
+        Ok(description)
+    }
+}
+
+/// An error produced when testing is aborted due to a certain commit.
+#[derive(Debug)]
+pub struct TestingAbortedError {
+    /// The commit which aborted testing.
+    pub commit_oid: NonZeroOid,
+
+    /// The exit code of the test command when run on that commit.
+    pub exit_code: i32,
+}
+
+/// The extension of test output files written with
+/// `branchless.test.compressOutput`.
+const COMPRESSED_OUTPUT_EXTENSION: &str = "gz";
+
+/// Open the test output at `path`, decompressing it if it was compressed.
+fn open_test_output(path: &Path) -> std::io::Result<Box<dyn Read>> {
+    let file = File::open(path)?;
+    if path
+        .extension()
+        .map_or(false, |extension| extension == COMPRESSED_OUTPUT_EXTENSION)
+    {
+        Ok(Box::new(GzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Read the entire test output at `path`, decompressing it if it was
+/// compressed.
+pub fn read_test_output(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    open_test_output(path)?.read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// Compress the test output at `path` into a file of the same name with a
+/// `.gz` extension, and remove the original. Returns the path to the
+/// compressed file.
+fn compress_test_output(path: &Path) -> eyre::Result<PathBuf> {
+    let compressed_path = path.with_extension(COMPRESSED_OUTPUT_EXTENSION);
+    let mut input = File::open(path).wrap_err_with(|| format!("Opening output file {path:?}"))?;
+    let output = File::create(&compressed_path)
+        .wrap_err_with(|| format!("Opening compressed output file {compressed_path:?}"))?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    std::io::copy(&mut input, &mut encoder)
+        .and_then(|_| encoder.finish())
+        .wrap_err_with(|| format!("Compressing output file {path:?}"))?;
+    std::fs::remove_file(path).wrap_err_with(|| format!("Removing output file {path:?}"))?;
+    Ok(compressed_path)
+}
+
+/// The name of the directory inside the test cache directory where output
+/// deduplicated with `branchless.test.dedupeOutput` is stored.
+const DEDUPED_OUTPUT_DIR_NAME: &str = "outputs";
+
+/// Replace the test output at `path` with a hard link to a file with the same
+/// contents in the content-addressed store under `cache_dir`, adding it to the
+/// store if it's not already present. Commits which produce identical output
+/// then share a single copy of it on disk.
+///
+/// Outputs are keyed by their Git blob OID (as computed by `git hash-object`),
+/// which is stable across versions and is computed without reading the whole
+/// output into memory.
+#[instrument]
+fn dedupe_test_output(cache_dir: &Path, path: &Path) -> eyre::Result<()> {
+    let metadata =
+        std::fs::metadata(path).wrap_err_with(|| format!("Reading output file {path:?}"))?;
+    if metadata.len() == 0 {
+        return Ok(());
+    }
+
+    let store_dir = cache_dir.join(DEDUPED_OUTPUT_DIR_NAME);
+    let store_path = {
+        let blob_oid = NonZeroOid::hash_blob_file(path)
+            .wrap_err_with(|| format!("Hashing output file {path:?}"))?;
+        store_dir.join(blob_oid.to_string())
+    };
+    std::fs::create_dir_all(&store_dir)
+        .wrap_err_with(|| format!("Creating output store dir {store_dir:?}"))?;
+    match std::fs::hard_link(path, &store_path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            std::fs::remove_file(path)
+                .wrap_err_with(|| format!("Removing output file {path:?}"))?;
+            std::fs::hard_link(&store_path, path)
+                .wrap_err_with(|| format!("Linking output file {path:?} to {store_path:?}"))?;
+        }
+        Err(err) => {
+            return Err(err)
+                .wrap_err_with(|| format!("Linking output file {path:?} to {store_path:?}"))
+        }
+    }
+    Ok(())
+}
+
+/// Remove the deduplicated output in `cache_dir` which is no longer linked to
+/// from any cached test result.
+#[instrument]
+fn prune_deduped_test_output(cache_dir: &Path) -> eyre::Result<()> {
+    let store_dir = cache_dir.join(DEDUPED_OUTPUT_DIR_NAME);
+    if !store_dir.exists() {
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(&store_dir)
+        .wrap_err_with(|| format!("Reading output store dir {store_dir:?}"))?
+    {
+        let entry = entry?;
+        #[cfg(unix)]
+        let is_unused = {
+            use std::os::unix::fs::MetadataExt;
+            entry.metadata()?.nlink() <= 1
+        };
+        // The number of links to a file isn't available on other platforms,
+        // so the stored output is kept.
+        #[cfg(not(unix))]
+        let is_unused = false;
+        if is_unused {
+            let path = entry.path();
+            std::fs::remove_file(&path)
+                .wrap_err_with(|| format!("Removing unused output file {path:?}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the path of the cached test output which would be written to `path`,
+/// which has a `.gz` extension if the output was compressed.
+fn get_cached_test_output_path(path: PathBuf) -> PathBuf {
+    let compressed_path = path.with_extension(COMPRESSED_OUTPUT_EXTENSION);
+    if compressed_path.exists() {
+        compressed_path
+    } else {
+        path
+    }
+}
+
+/// Get the lines of the file at `path` to display, replacing the middle of the
+/// file with a `<N more lines>` message unless the full output was requested.
+///
+/// The file is streamed rather than read into memory all at once, and only the
+/// first and last `context_lines` lines are retained, so that this is safe to
+/// call on very large output files.
+fn abbreviate_lines(path: &Path, verbosity: Verbosity, context_lines: usize) -> Vec<StyledString> {
+    let should_show_all_lines = match verbosity {
+        Verbosity::None => return Vec::new(),
+        Verbosity::PartialOutput => false,
+        Verbosity::FullOutput => true,
+    };
+
+    let read_failed = || {
+        vec![StyledStringBuilder::new()
+            .append_plain("<failed to read file>")
+            .build()]
+    };
+    let file = match open_test_output(path) {
+        Ok(file) => file,
+        Err(_) => return read_failed(),
+    };
+
+    let mut num_lines: usize = 0;
+    let mut head_lines = Vec::new();
+    let mut tail_lines = VecDeque::new();
+    for line in BufReader::new(file).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return read_failed(),
+        };
+        num_lines += 1;
+        if should_show_all_lines || head_lines.len() < context_lines {
+            head_lines.push(line);
+        } else {
+            tail_lines.push_back(line);
+            if tail_lines.len() > context_lines {
+                tail_lines.pop_front();
+            }
+        }
+    }
+
+    let num_missing_lines = num_lines.saturating_sub(2 * context_lines);
+    let lines: Vec<String> = if num_lines == 0 {
+        vec!["<no output>".to_string()]
+    } else if num_missing_lines == 0 || should_show_all_lines {
+        head_lines.into_iter().chain(tail_lines).collect()
+    } else {
+        head_lines
+            .into_iter()
+            .chain(std::iter::once(format!("<{num_missing_lines} more lines>")))
+            .chain(tail_lines)
+            .collect()
+    };
+    lines
+        .into_iter()
+        .map(|line| StyledStringBuilder::new().append_plain(line).build())
+        .collect()
+}
+
+impl TestOutput {
+    #[instrument]
+    fn describe(
+        &self,
+        effects: &Effects,
+        commit: &Commit,
+        apply_fixes: bool,
+        verbosity: Verbosity,
+        context_lines: usize,
+    ) -> eyre::Result<StyledString> {
+        let description = StyledStringBuilder::new()
+            .append_styled(self.test_status.get_icon(), self.test_status.get_style())
+            .append_plain(" ")
+            .append(
+                self.test_status
+                    .describe(effects.get_glyphs(), commit, apply_fixes)?,
+            );
+        let description = match get_displayed_duration(self) {
+            Some(duration) => description.append_plain(format!(" ({})", format_duration(duration))),
+            None => description,
+        }
+        .build();
+
+        // The command was never run on a commit skipped due to its ancestor,
+        // for being empty, or for being a conflicting merge, so there's no
+        // output to show.
+        if verbosity == Verbosity::None
+            || matches!(
+                self.test_status,
+                TestStatus::SkippedDueToAncestor { .. }
+                    | TestStatus::SkippedEmpty
+                    | TestStatus::MergeParentConflicted
+            )
+        {
+            return Ok(StyledStringBuilder::from_lines(vec![description]));
+        }
+
+        let interactive = match self.test_status {
+            TestStatus::CheckoutFailed
+            | TestStatus::SpawnTestFailed(_)
+            | TestStatus::TerminatedBySignal { .. }
+            | TestStatus::Timeout { .. }
+            | TestStatus::AlreadyInProgress
+            | TestStatus::ReadCacheFailed(_)
+            | TestStatus::Indeterminate { .. }
+            | TestStatus::SkippedDueToAncestor { .. }
+            | TestStatus::SkippedEmpty
+            | TestStatus::MergeParentConflicted
+            | TestStatus::PerfThresholdExceeded { .. }
+            | TestStatus::Abort { .. } => false,
+            TestStatus::Failed { interactive, .. } | TestStatus::Passed { interactive, .. } => {
+                interactive
+            }
+        };
+
+        if self.stdout_path == self.stderr_path {
+            let mut lines = vec![description];
+            if !interactive {
+                lines.push(
+                    StyledStringBuilder::new()
+                        .append_styled("Output: ", Effect::Bold)
+                        .append_plain(self.stdout_path.to_string_lossy())
+                        .build(),
+                );
+                lines.extend(abbreviate_lines(
+                    &self.stdout_path,
+                    verbosity,
+                    context_lines,
+                ));
+            }
+            return Ok(StyledStringBuilder::from_lines(lines));
+        }
+
+        let stdout_lines = {
+            let mut lines = Vec::new();
+            if !interactive {
+                lines.push(
+                    StyledStringBuilder::new()
+                        .append_styled("Stdout: ", Effect::Bold)
+                        .append_plain(self.stdout_path.to_string_lossy())
+                        .build(),
+                );
+                lines.extend(abbreviate_lines(
+                    &self.stdout_path,
+                    verbosity,
+                    context_lines,
+                ));
+            }
+            lines
+        };
+        let stderr_lines = {
+            let mut lines = Vec::new();
+            if !interactive {
+                lines.push(
+                    StyledStringBuilder::new()
+                        .append_styled("Stderr: ", Effect::Bold)
+                        .append_plain(self.stderr_path.to_string_lossy())
+                        .build(),
+                );
+                lines.extend(abbreviate_lines(
+                    &self.stderr_path,
+                    verbosity,
+                    context_lines,
+                ));
+            }
+            lines
+        };
+
+        Ok(StyledStringBuilder::from_lines(
+            [
+                &[description],
+                stdout_lines.as_slice(),
+                stderr_lines.as_slice(),
+            ]
+            .concat(),
+        ))
     }
 }
 
-/// `test` command.
-#[instrument]
-pub fn command_main(ctx: CommandContext, args: TestArgs) -> eyre::Result<ExitCode> {
-    let CommandContext {
-        effects,
-        git_run_info,
-    } = ctx;
-    let TestArgs { subcommand } = args;
-    match subcommand {
-        TestSubcommand::Clean {
-            revset,
-            resolve_revset_options,
-        } => subcommand_clean(&effects, revset, &resolve_revset_options),
+fn shell_escape(s: impl AsRef<str>) -> String {
+    let s = s.as_ref();
+    let mut escaped = String::new();
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str(r#"\""#),
+            '\\' => escaped.push_str(r#"\\\\"#),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct TestJob {
+    commit_oid: NonZeroOid,
+    operation_type: OperationType,
+}
+
+#[derive(Debug, Error)]
+enum SearchGraphError {
+    #[error(transparent)]
+    Dag(#[from] eden_dag::Error),
 
-        TestSubcommand::Run {
-            exec: command,
-            command: command_alias,
-            revset,
-            resolve_revset_options,
-            verbosity,
-            strategy,
-            search,
-            bisect,
-            no_cache,
-            interactive,
-            jobs,
-        } => subcommand_run(
-            &effects,
-            &git_run_info,
-            &RawTestOptions {
-                exec: command,
-                command: command_alias,
-                dry_run: false,
-                strategy,
-                search,
-                bisect,
-                no_cache,
-                interactive,
-                jobs,
-                verbosity: Verbosity::from(verbosity),
-                apply_fixes: false,
-            },
-            revset,
-            &resolve_revset_options,
-            None,
-        ),
+    #[error(transparent)]
+    Other(#[from] eyre::Error),
+}
 
-        TestSubcommand::Show {
-            exec: command,
-            command: command_alias,
-            revset,
-            resolve_revset_options,
-            verbosity,
-        } => subcommand_show(
-            &effects,
-            &RawTestOptions {
-                exec: command,
-                command: command_alias,
-                dry_run: false,
-                strategy: None,
-                search: None,
-                bisect: false,
-                no_cache: false,
-                interactive: false,
-                jobs: None,
-                verbosity: Verbosity::from(verbosity),
-                apply_fixes: false,
-            },
-            revset,
-            &resolve_revset_options,
-        ),
+#[derive(Clone, Debug)]
+struct SearchGraph<'a> {
+    dag: &'a Dag,
+    commit_set: CommitSet,
+}
 
-        TestSubcommand::Fix {
-            exec: command,
-            command: command_alias,
-            dry_run,
-            revset,
-            resolve_revset_options,
-            verbosity,
-            strategy,
-            no_cache,
-            jobs,
-            move_options,
-        } => subcommand_run(
-            &effects,
-            &git_run_info,
-            &RawTestOptions {
-                exec: command,
-                command: command_alias,
-                dry_run,
-                strategy,
-                search: None,
-                bisect: false,
-                no_cache,
-                interactive: false,
-                jobs,
-                verbosity: Verbosity::from(verbosity),
-                apply_fixes: true,
-            },
-            revset,
-            &resolve_revset_options,
-            Some(&move_options),
-        ),
+impl<'a> search::SearchGraph for SearchGraph<'a> {
+    type Node = NonZeroOid;
+    type Error = SearchGraphError;
+
+    #[instrument]
+    fn ancestors(&self, node: Self::Node) -> Result<HashSet<Self::Node>, Self::Error> {
+        let ancestors = self.dag.query_ancestors(CommitSet::from(node))?;
+        let ancestors = ancestors.intersection(&self.commit_set);
+        let ancestors = self.dag.commit_set_to_vec(&ancestors)?;
+        Ok(ancestors.into_iter().collect())
+    }
+
+    #[instrument]
+    fn descendants(&self, node: Self::Node) -> Result<HashSet<Self::Node>, Self::Error> {
+        let descendants = self.dag.query_descendants(CommitSet::from(node))?;
+        let descendants = descendants.intersection(&self.commit_set);
+        let descendants = self.dag.commit_set_to_vec(&descendants)?;
+        Ok(descendants.into_iter().collect())
     }
 }
 
-/// Run the command provided in `options` on each of the commits in `revset`.
+/// The results of running all tests.
+#[derive(Debug)]
+pub struct TestResults {
+    /// If a search strategy was provided, the calculated bounds on the input
+    /// commit set.
+    pub search_bounds: search::Bounds<NonZeroOid>,
+
+    /// The test output for each commit.
+    pub test_outputs: IndexMap<NonZeroOid, TestOutput>,
+
+    /// If testing was aborted, the corresponding error.
+    pub testing_aborted_error: Option<TestingAbortedError>,
+
+    /// If testing was stopped early because the maximum number of failures
+    /// was reached, that number of failures.
+    pub stopped_after_failures: Option<usize>,
+
+    /// Whether testing was stopped early because the user interrupted it
+    /// (e.g. with Ctrl-C).
+    pub interrupted: bool,
+}
+
+/// Run tests on the provided set of commits.
 #[instrument]
-fn subcommand_run(
+pub fn run_tests<'a>(
     effects: &Effects,
     git_run_info: &GitRunInfo,
-    options: &RawTestOptions,
-    revset: Revset,
-    resolve_revset_options: &ResolveRevsetOptions,
-    move_options: Option<&MoveOptions>,
-) -> eyre::Result<ExitCode> {
-    let now = SystemTime::now();
-    let repo = Repo::from_current_dir()?;
-    let conn = repo.get_db_conn()?;
-    let event_log_db = EventLogDb::new(&conn)?;
-    let event_tx_id = event_log_db.make_transaction_id(now, "test run")?;
-    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
-    let event_cursor = event_replayer.make_default_cursor();
-    let references_snapshot = repo.get_references_snapshot()?;
-    let mut dag = Dag::open_and_sync(
-        effects,
-        &repo,
-        &event_replayer,
-        event_cursor,
-        &references_snapshot,
-    )?;
+    dag: &Dag,
+    repo: &Repo,
+    event_log_db: &EventLogDb,
+    event_tx_id: EventTransactionId,
+    commits: &[Commit],
+    options: &ResolvedTestOptions,
+) -> eyre::Result<Result<TestResults, ExitCode>> {
+    let ResolvedTestOptions {
+        command,
+        shell_command: _, // Used in `test_commit`.
+        execution_strategy,
+        search_strategy,
+        is_dry_run: _, // Used only in `apply_fixes`.
+        cache_mode: _, // Used only in `make_test_files`.
+        cache_dir: _,  // Used only in `make_test_files`.
+        worktrees_dir,
+        is_interactive: _, // Used in `test_commit`.
+        num_jobs,
+        verbosity: _,   // Verbosity used by caller to print results.
+        fix_options: _, // Whether to apply fixes is checked by `test_commit`, after the working directory is set up.
+        limits: _,      // Used in `run_test` and `test_commit`.
+        scheduling:
+            TestScheduling {
+                rerun_failures: _, // Used by caller to filter `commits`.
+                max_failures,
+                skip_descendants_on_failure,
+                skip_empty,
+                test_merge_parents,
+                shuffle_seed,
+            },
+        process: _, // Used in `run_test` and `test_commit`.
+        hooks:
+            TestHooks {
+                setup: setup_command,
+                teardown: teardown_command,
+                on_result: on_result_command,
+            },
+        outcome: _,       // Used in `test_commit`.
+        storage: _,       // Used in `test_commit`.
+        report: _,        // Used by caller to print results.
+        fix_selection: _, // Used only in `apply_fixes`.
+    } = &options;
 
-    let commit_set = match resolve_commits(
-        effects,
-        &repo,
-        &mut dag,
-        &[revset.clone()],
-        resolve_revset_options,
-    ) {
-        Ok(mut commit_sets) => commit_sets.pop().unwrap(),
-        Err(err) => {
-            err.describe(effects)?;
-            return Ok(ExitCode(1));
+    let shell_path = match get_sh() {
+        Some(shell_path) => shell_path,
+        None => {
+            writeln!(
+                effects.get_output_stream(),
+                "{}",
+                effects.get_glyphs().render(
+                    StyledStringBuilder::new()
+                        .append_styled(
+                            "Error: Could not determine path to shell.",
+                            BaseColor::Red.light()
+                        )
+                        .build()
+                )?
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+    };
+
+    if let Some(strategy_value) = execution_strategy.to_possible_value() {
+        writeln!(
+            effects.get_output_stream(),
+            "Using command execution strategy: {}",
+            effects.get_glyphs().render(
+                StyledStringBuilder::new()
+                    .append_styled(strategy_value.get_name(), Effect::Bold)
+                    .build()
+            )?,
+        )?;
+    }
+
+    if let Some(strategy_value) = search_strategy.and_then(|opt| opt.to_possible_value()) {
+        writeln!(
+            effects.get_output_stream(),
+            "Using test search strategy: {}",
+            effects.get_glyphs().render(
+                StyledStringBuilder::new()
+                    .append_styled(strategy_value.get_name(), Effect::Bold)
+                    .build()
+            )?,
+        )?;
+    }
+    if let Some(shuffle_seed) = shuffle_seed {
+        writeln!(
+            effects.get_output_stream(),
+            "Shuffling commits with seed: {}",
+            effects.get_glyphs().render(
+                StyledStringBuilder::new()
+                    .append_styled(shuffle_seed.to_string(), Effect::Bold)
+                    .build()
+            )?,
+        )?;
+    }
+    let search_strategy = match search_strategy {
+        None => None,
+        Some(TestSearchStrategy::Linear) => Some(search::Strategy::Linear),
+        Some(TestSearchStrategy::Reverse) => Some(search::Strategy::LinearReverse),
+        Some(TestSearchStrategy::Binary) => Some(search::Strategy::Binary),
+    };
+
+    let latest_test_command_path = get_latest_test_command_path(repo);
+    if let Some(parent) = latest_test_command_path.parent() {
+        if let Err(err) = std::fs::create_dir_all(parent) {
+            warn!(
+                ?err,
+                ?latest_test_command_path,
+                "Failed to create containing directory for latest test command"
+            );
         }
-    };
+    }
+    if let Err(err) = std::fs::write(&latest_test_command_path, command) {
+        warn!(
+            ?err,
+            ?latest_test_command_path,
+            "Failed to write latest test command to disk"
+        );
+    }
 
-    let options = match ResolvedTestOptions::resolve(
-        now,
-        effects,
-        &dag,
-        &repo,
-        event_tx_id,
-        &commit_set,
-        move_options,
-        options,
-    )? {
-        Ok(options) => options,
-        Err(exit_code) => return Ok(exit_code),
+    // Empty commits are reported as skipped without ever being scheduled.
+    let empty_commit_oids: HashSet<NonZeroOid> = if *skip_empty {
+        commits
+            .iter()
+            .filter(|commit| is_empty_commit(commit))
+            .map(|commit| commit.get_oid())
+            .collect()
+    } else {
+        Default::default()
     };
+    let all_commits = commits;
+    let commits = all_commits
+        .iter()
+        .filter(|commit| !empty_commit_oids.contains(&commit.get_oid()))
+        .collect_vec();
+
+    // With `--test-merge-parents`, each merge commit is followed by
+    // re-creations of the merge from the perspective of each of its parents.
+    // These aren't part of the commit graph, so they're only scheduled as jobs,
+    // and left out of the search graph.
+    let mut merge_parent_commits: HashMap<NonZeroOid, Vec<Commit>> = HashMap::new();
+    let mut conflicted_commit_oids: HashSet<NonZeroOid> = HashSet::new();
+    if *test_merge_parents {
+        for commit in &commits {
+            let mut commit_merge_parent_commits = Vec::new();
+            for (merge_parent_commit, conflicted) in make_merge_parent_commits(repo, commit)? {
+                if conflicted {
+                    conflicted_commit_oids.insert(merge_parent_commit.get_oid());
+                }
+                commit_merge_parent_commits.push(merge_parent_commit);
+            }
+            if !commit_merge_parent_commits.is_empty() {
+                merge_parent_commits.insert(commit.get_oid(), commit_merge_parent_commits);
+            }
+        }
+    }
+    let job_commits = commits
+        .iter()
+        .copied()
+        .flat_map(|commit| {
+            std::iter::once(commit).chain(
+                merge_parent_commits
+                    .get(&commit.get_oid())
+                    .into_iter()
+                    .flatten(),
+            )
+        })
+        .filter(|commit| !conflicted_commit_oids.contains(&commit.get_oid()))
+        .collect_vec();
 
-    let abort_trap = match set_abort_trap(
-        now,
-        effects,
-        git_run_info,
-        &repo,
-        &event_log_db,
-        event_tx_id,
-        options.execution_strategy,
-    )? {
-        Ok(abort_trap) => abort_trap,
-        Err(exit_code) => return Ok(exit_code),
-    };
+    if let Some(setup_command) = setup_command {
+        if let Err(exit_code) = run_setup_command(effects, repo, &shell_path, setup_command)? {
+            return Ok(Err(exit_code));
+        }
+    }
 
-    let commits = sorted_commit_set(&repo, &dag, &commit_set)?;
-    let test_results: Result<_, _> = {
-        let effects = if options.is_interactive {
-            effects.suppress()
+    let EventLoopOutput {
+        search,
+        test_outputs: test_outputs_unordered,
+        testing_aborted_error,
+        stopped_after_failures,
+        interrupted,
+    } = {
+        let (effects, progress) =
+            effects.start_operation(OperationType::RunTests(Arc::new(command.clone())));
+        progress.notify_progress(0, job_commits.len());
+        let commit_jobs = {
+            let mut results = IndexMap::new();
+            for commit in &job_commits {
+                // Create the progress entries in the multiprogress meter without starting them.
+                // They'll be resumed later in the loop below.
+                let commit_description = effects
+                    .get_glyphs()
+                    .render(commit.friendly_describe(effects.get_glyphs())?)?;
+                let operation_type =
+                    OperationType::RunTestOnCommit(Arc::new(commit_description.clone()));
+                let (_effects, progress) = effects.start_operation(operation_type.clone());
+                progress.notify_status(
+                    OperationIcon::InProgress,
+                    format!("Waiting to run on {commit_description}"),
+                );
+                results.insert(
+                    commit.get_oid(),
+                    TestJob {
+                        commit_oid: commit.get_oid(),
+                        operation_type,
+                    },
+                );
+            }
+            if let Some(shuffle_seed) = shuffle_seed {
+                // Jobs are scheduled in the order of `commit_jobs`, so this
+                // only affects the execution order. The results are put back
+                // into the input order below.
+                let mut jobs = results.into_iter().collect_vec();
+                fastrand::Rng::with_seed(*shuffle_seed).shuffle(&mut jobs);
+                results = jobs.into_iter().collect();
+            }
+            results
+        };
+
+        let graph = SearchGraph {
+            dag,
+            commit_set: commits.iter().map(|c| c.get_oid()).collect(),
+        };
+        let skip_descendants_graph = if *skip_descendants_on_failure {
+            Some(graph.clone())
         } else {
-            effects.clone()
+            None
         };
-        run_tests(
-            &effects,
-            git_run_info,
-            &dag,
-            &repo,
-            &event_log_db,
-            event_tx_id,
-            &revset,
-            &commits,
-            &options,
-        )
-    };
-    let abort_trap_exit_code = clear_abort_trap(effects, git_run_info, event_tx_id, abort_trap)?;
-    if !abort_trap_exit_code.is_success() {
-        return Ok(abort_trap_exit_code);
-    }
+        let search = search::Search::new(graph, commits.iter().map(|c| c.get_oid()));
 
-    let test_results = match test_results? {
-        Ok(test_results) => test_results,
-        Err(exit_code) => return Ok(exit_code),
-    };
+        let work_queue = WorkQueue::new();
+        let repo_dir = repo.get_path();
+        crossbeam::thread::scope(|scope| -> eyre::Result<_> {
+            let (result_tx, result_rx) = crossbeam::channel::unbounded();
+            let workers: HashMap<WorkerId, crossbeam::thread::ScopedJoinHandle<()>> = {
+                let mut result = HashMap::new();
+                for worker_id in 1..=*num_jobs {
+                    let effects = &effects;
+                    let progress = &progress;
+                    let shell_path = &shell_path;
+                    let work_queue = work_queue.clone();
+                    let result_tx = result_tx.clone();
+                    let setup = move || -> eyre::Result<Repo> {
+                        let repo = Repo::from_dir(repo_dir)?;
+                        Ok(repo)
+                    };
+                    let f = move |job: TestJob, repo: &Repo| -> eyre::Result<TestOutput> {
+                        let TestJob {
+                            commit_oid,
+                            operation_type,
+                        } = job;
+                        let commit = repo.find_commit_or_fail(commit_oid)?;
+                        run_test(
+                            effects,
+                            operation_type,
+                            git_run_info,
+                            shell_path,
+                            repo,
+                            event_tx_id,
+                            options,
+                            worker_id,
+                            &commit,
+                        )
+                    };
+                    let teardown = move |repo: &Repo| {
+                        if let Some(teardown_command) = teardown_command {
+                            run_teardown_command(
+                                repo,
+                                worktrees_dir,
+                                shell_path,
+                                *execution_strategy,
+                                worker_id,
+                                teardown_command,
+                            );
+                        }
+                        match execution_strategy {
+                            TestExecutionStrategy::WorkingCopy
+                            | TestExecutionStrategy::Worktree
+                            | TestExecutionStrategy::Docker => {}
+                            TestExecutionStrategy::Tmpfs => {
+                                remove_test_tmpfs_worker_dir(repo, worker_id);
+                            }
+                        }
+                    };
+                    result.insert(
+                        worker_id,
+                        scope.spawn(move |_scope| {
+                            worker(
+                                progress, worker_id, work_queue, result_tx, setup, f, teardown,
+                            );
+                            debug!("Exiting spawned thread closure");
+                        }),
+                    );
+                }
+                result
+            };
 
-    let exit_code = print_summary(
-        effects,
-        &dag,
-        &repo,
-        &revset,
-        &options.command,
-        &test_results,
-        options.search_strategy.is_some(),
-        options.fix_options.is_some(),
-        &options.verbosity,
-    )?;
-    if !exit_code.is_success() {
-        return Ok(exit_code);
-    }
+            // We rely on `result_rx.recv()` returning `Err` once all the threads have exited
+            // (whether that reason should be panicking or having finished all work). We have to
+            // drop our local reference to ensure that we don't deadlock, since otherwise there
+            // would still be a live receiver for the sender.
+            drop(result_tx);
 
-    if let Some((execute_options, permissions)) = &options.fix_options {
-        let exit_code = apply_fixes(
-            effects,
-            git_run_info,
-            &mut dag,
-            &repo,
-            &event_log_db,
-            execute_options,
-            permissions.clone(),
-            options.is_dry_run,
-            &options.command,
-            &test_results,
-        )?;
-        if !exit_code.is_success() {
-            return Ok(exit_code);
+            let test_results = event_loop(
+                &progress,
+                repo,
+                &shell_path,
+                on_result_command.as_deref(),
+                commit_jobs,
+                search,
+                search_strategy,
+                *num_jobs,
+                *max_failures,
+                skip_descendants_graph,
+                work_queue.clone(),
+                result_rx,
+            );
+            work_queue.close();
+            let test_results = test_results?;
+
+            if test_results.testing_aborted_error.is_none() && search_strategy.is_none() {
+                debug!("Waiting for workers");
+                progress.notify_status(OperationIcon::InProgress, "Waiting for workers");
+                for (worker_id, worker) in workers {
+                    worker
+                        .join()
+                        .map_err(|_err| eyre::eyre!("Waiting for worker {worker_id} to exit"))?;
+                }
+            }
+
+            debug!("About to return from thread scope");
+            Ok(test_results)
+        })
+        .map_err(|_| eyre::eyre!("Could not spawn workers"))?
+        .wrap_err("Failed waiting on workers")?
+    };
+    debug!("Returned from thread scope");
+
+    // The results may be returned in an arbitrary order if they were produced
+    // in parallel, so recover the input order to produce deterministic output.
+    let test_outputs_ordered: IndexMap<NonZeroOid, TestOutput> = {
+        let mut test_outputs_unordered = test_outputs_unordered;
+        let mut test_outputs_ordered = IndexMap::new();
+        for commit_oid in all_commits
+            .iter()
+            .flat_map(|commit| {
+                std::iter::once(commit).chain(
+                    merge_parent_commits
+                        .get(&commit.get_oid())
+                        .into_iter()
+                        .flatten(),
+                )
+            })
+            .map(|commit| commit.get_oid())
+        {
+            let unscheduled_status = if empty_commit_oids.contains(&commit_oid) {
+                Some(TestStatus::SkippedEmpty)
+            } else if conflicted_commit_oids.contains(&commit_oid) {
+                Some(TestStatus::MergeParentConflicted)
+            } else {
+                None
+            };
+            if let Some(test_status) = unscheduled_status {
+                test_outputs_ordered.insert(
+                    commit_oid,
+                    TestOutput {
+                        temp_dir: None,
+                        result_path: PathBuf::new(),
+                        stdout_path: PathBuf::new(),
+                        stderr_path: PathBuf::new(),
+                        test_status,
+                        duration: None,
+                        interrupted: false,
+                    },
+                );
+                continue;
+            }
+            match test_outputs_unordered.remove(&commit_oid) {
+                Some(result) => {
+                    test_outputs_ordered.insert(commit_oid, result);
+                }
+                None => {
+                    if search_strategy.is_none()
+                        && testing_aborted_error.is_none()
+                        && stopped_after_failures.is_none()
+                        && !interrupted
+                    {
+                        warn!(?commit_oid, "No result was returned for commit");
+                    }
+                }
+            }
         }
-    }
+        if !test_outputs_unordered.is_empty() {
+            warn!(
+                ?test_outputs_unordered,
+                ?commits,
+                "There were extra results for commits not appearing in the input list"
+            );
+        }
+        test_outputs_ordered
+    };
 
-    Ok(ExitCode(0))
+    Ok(Ok(TestResults {
+        search_bounds: match search_strategy {
+            None => Default::default(),
+            Some(search_strategy) => search.search(search_strategy)?.bounds,
+        },
+        test_outputs: test_outputs_ordered,
+        testing_aborted_error,
+        stopped_after_failures,
+        interrupted,
+    }))
 }
 
-#[must_use]
-#[derive(Debug)]
-struct AbortTrap {
-    is_active: bool,
+/// Determine whether the commit doesn't change the tree of its first parent.
+/// Merge commits are compared against their first parent only, so a merge
+/// which brings in no changes is considered empty. Root commits are never
+/// considered empty.
+fn is_empty_commit(commit: &Commit) -> bool {
+    match commit.get_parents().first() {
+        None => false,
+        Some(parent) => parent.get_tree_oid() == commit.get_tree_oid(),
+    }
 }
 
-/// Ensure that no commit operation is currently underway (such as a merge or
-/// rebase), and start a rebase.  In the event that the test invocation is
-/// interrupted, this will prevent the user from starting another commit
-/// operation without first running `git rebase --abort` to get back to their
-/// original commit.
+/// Re-create the provided merge commit from the perspective of each of its
+/// parents, by merging the other parent into that parent in memory. The
+/// resulting commits aren't referenced by anything. Each is returned along with
+/// whether the merge conflicted, in which case it just has the parent's tree
+/// and can't be tested. Only merges with exactly two parents are handled.
+fn make_merge_parent_commits<'repo>(
+    repo: &'repo Repo,
+    commit: &Commit,
+) -> eyre::Result<Vec<(Commit<'repo>, bool)>> {
+    let (first_parent, second_parent) = match commit.get_parent_oids().as_slice() {
+        [first_parent_oid, second_parent_oid] => (
+            repo.find_commit_or_fail(*first_parent_oid)?,
+            repo.find_commit_or_fail(*second_parent_oid)?,
+        ),
+        _ => return Ok(Vec::new()),
+    };
+
+    let summary = commit.get_summary()?;
+    let mut result = Vec::new();
+    for (our_parent, their_parent) in [
+        (&first_parent, &second_parent),
+        (&second_parent, &first_parent),
+    ] {
+        let mut index = repo.merge_commits(our_parent, their_parent)?;
+        let conflicted = index.has_conflicts();
+        let tree = if conflicted {
+            our_parent.get_tree()?
+        } else {
+            let tree_oid = repo.write_index_to_tree(&mut index)?;
+            repo.find_tree_or_fail(tree_oid)?
+        };
+        let message = format!(
+            "{} (from parent {})",
+            summary.to_str_lossy(),
+            our_parent.get_short_oid()?
+        );
+        let merge_parent_commit_oid = repo.create_commit(
+            None,
+            &commit.get_author(),
+            &commit.get_committer(),
+            &message,
+            &tree,
+            vec![our_parent, their_parent],
+        )?;
+        result.push((
+            repo.find_commit_or_fail(merge_parent_commit_oid)?,
+            conflicted,
+        ));
+    }
+    Ok(result)
+}
+
+/// Run the setup command once in the main repository, writing its output to
+/// the setup output file.
 #[instrument]
-fn set_abort_trap(
-    now: SystemTime,
+fn run_setup_command(
     effects: &Effects,
-    git_run_info: &GitRunInfo,
     repo: &Repo,
-    event_log_db: &EventLogDb,
-    event_tx_id: EventTransactionId,
-    strategy: TestExecutionStrategy,
-) -> eyre::Result<Result<AbortTrap, ExitCode>> {
-    match strategy {
-        TestExecutionStrategy::Worktree => return Ok(Ok(AbortTrap { is_active: false })),
-        TestExecutionStrategy::WorkingCopy => {}
-    }
+    shell_path: &Path,
+    setup_command: &str,
+) -> eyre::Result<Result<(), ExitCode>> {
+    let (effects, _progress) = effects.start_operation(OperationType::RunTestSetup(Arc::new(
+        setup_command.to_string(),
+    )));
+
+    let output_path = get_test_setup_output_path(repo);
+    let output_file = File::create(&output_path)
+        .wrap_err_with(|| format!("Opening setup output file {output_path:?}"))?;
+    let error_file = output_file
+        .try_clone()
+        .wrap_err_with(|| format!("Cloning setup output file {output_path:?}"))?;
+    let working_directory = repo
+        .get_working_copy_path()
+        .unwrap_or_else(|| repo.get_path());
+    let exit_status = Command::new(shell_path)
+        .arg("-c")
+        .arg(setup_command)
+        .current_dir(working_directory)
+        .stdin(Stdio::null())
+        .stdout(output_file)
+        .stderr(error_file)
+        .status();
+    let exit_status = match exit_status {
+        Ok(exit_status) => exit_status,
+        Err(err) => {
+            writeln!(
+                effects.get_output_stream(),
+                "Could not run setup command: {}: {err}",
+                effects.get_glyphs().render(
+                    StyledStringBuilder::new()
+                        .append_styled(setup_command, Effect::Bold)
+                        .build()
+                )?,
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+    };
 
-    if let Some(operation_type) = repo.get_current_operation_type() {
+    if !exit_status.success() {
         writeln!(
             effects.get_output_stream(),
-            "A {operation_type} operation is already in progress."
+            "Setup command failed with {}: {}",
+            match exit_status.code() {
+                Some(exit_code) => format!("exit code {exit_code}"),
+                None => "a signal".to_string(),
+            },
+            effects.get_glyphs().render(
+                StyledStringBuilder::new()
+                    .append_styled(setup_command, Effect::Bold)
+                    .build()
+            )?,
         )?;
         writeln!(
             effects.get_output_stream(),
-            "Run git {operation_type} --continue or git {operation_type} --abort to resolve it and proceed."
+            "Setup output: {}",
+            output_path.display()
         )?;
         return Ok(Err(ExitCode(1)));
     }
 
-    let head_info = repo.get_head_info()?;
-    let head_oid = match head_info.oid {
-        Some(head_oid) => head_oid,
-        None => {
-            writeln!(
-                effects.get_output_stream(),
-                "No commit is currently checked out; cannot start on-disk rebase."
-            )?;
-            writeln!(
-                effects.get_output_stream(),
-                "Check out a commit and try again."
-            )?;
-            return Ok(Err(ExitCode(1)));
-        }
-    };
+    Ok(Ok(()))
+}
 
-    let rebase_plan = RebasePlan {
-        first_dest_oid: head_oid,
-        commands: vec![RebaseCommand::Break],
-    };
-    match execute_rebase_plan(
-        effects,
-        git_run_info,
-        repo,
-        event_log_db,
-        &rebase_plan,
-        &ExecuteRebasePlanOptions {
-            now,
-            event_tx_id,
-            preserve_timestamps: true,
-            force_in_memory: false,
-            force_on_disk: true,
-            resolve_merge_conflicts: false,
-            check_out_commit_options: CheckOutCommitOptions {
-                render_smartlog: false,
-                ..Default::default()
-            },
+/// Run the teardown command in the working directory used by the given worker.
+/// Failures are logged, but otherwise ignored.
+#[instrument]
+fn run_teardown_command(
+    repo: &Repo,
+    worktrees_dir: &Path,
+    shell_path: &Path,
+    strategy: TestExecutionStrategy,
+    worker_id: WorkerId,
+    teardown_command: &str,
+) {
+    let working_directory = match strategy {
+        TestExecutionStrategy::WorkingCopy => match repo.get_working_copy_path() {
+            Some(working_copy_path) => working_copy_path.to_owned(),
+            None => return,
         },
-    )? {
-        ExecuteRebasePlanResult::Succeeded { rewritten_oids: _ } => {
-            // Do nothing.
-        }
-        ExecuteRebasePlanResult::DeclinedToMerge { failed_merge_info } => {
-            writeln!(
-                effects.get_output_stream(),
-                "BUG: Encountered unexpected merge failure: {failed_merge_info:?}"
-            )?;
-            return Ok(Err(ExitCode(1)));
+        TestExecutionStrategy::Worktree => get_test_worktree_dir(worktrees_dir, worker_id),
+        TestExecutionStrategy::Docker => get_test_docker_dir(repo, worker_id),
+        TestExecutionStrategy::Tmpfs => match get_test_tmpfs_worker_dir(repo, worker_id) {
+            Ok(tmpfs_dir) => tmpfs_dir,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "Could not determine tmpfs directory for teardown command"
+                );
+                return;
+            }
+        },
+    };
+    if !working_directory.exists() {
+        debug!(
+            ?working_directory,
+            "Not running teardown command since working directory does not exist"
+        );
+        return;
+    }
+
+    let result = Command::new(shell_path)
+        .arg("-c")
+        .arg(teardown_command)
+        .current_dir(&working_directory)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match result {
+        Ok(exit_status) if exit_status.success() => {}
+        Ok(exit_status) => {
+            warn!(?working_directory, ?exit_status, "Teardown command failed");
         }
-        ExecuteRebasePlanResult::Failed { exit_code } => {
-            return Ok(Err(exit_code));
+        Err(err) => {
+            warn!(?working_directory, ?err, "Could not run teardown command");
         }
     }
-
-    Ok(Ok(AbortTrap { is_active: true }))
 }
 
+/// Run the `branchless.test.onResult` command for a commit whose test result
+/// has just arrived. The commit OID and the name of its status are passed as
+/// arguments and in the environment. Failures are logged, but otherwise
+/// ignored.
 #[instrument]
-fn clear_abort_trap(
-    effects: &Effects,
-    git_run_info: &GitRunInfo,
-    event_tx_id: EventTransactionId,
-    abort_trap: AbortTrap,
-) -> eyre::Result<ExitCode> {
-    let AbortTrap { is_active } = abort_trap;
-    if !is_active {
-        return Ok(ExitCode(0));
+fn run_on_result_command(
+    repo: &Repo,
+    shell_path: &Path,
+    on_result_command: &str,
+    commit_oid: NonZeroOid,
+    test_output: &TestOutput,
+) {
+    let working_directory = repo
+        .get_working_copy_path()
+        .unwrap_or_else(|| repo.get_path());
+    let status = test_output.test_status.get_name();
+    let mut command = Command::new(shell_path);
+    command
+        .arg("-c")
+        .arg(on_result_command)
+        .arg("sh")
+        .arg(commit_oid.to_string())
+        .arg(status)
+        .current_dir(working_directory)
+        .env("BRANCHLESS_TEST_COMMIT", commit_oid.to_string())
+        .env("BRANCHLESS_TEST_STATUS", status)
+        .env("BRANCHLESS_TEST_STDOUT_FILE", &test_output.stdout_path)
+        .env("BRANCHLESS_TEST_STDERR_FILE", &test_output.stderr_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(exit_code) = test_output.test_status.get_exit_code() {
+        command.env("BRANCHLESS_TEST_EXIT_CODE", exit_code.to_string());
     }
-
-    let exit_code = git_run_info.run(effects, Some(event_tx_id), &["rebase", "--abort"])?;
-    if !exit_code.is_success() {
-        writeln!(
-            effects.get_output_stream(),
-            "{}",
-            effects.get_glyphs().render(
-                StyledStringBuilder::new()
-                    .append_styled(
-                        "Error: Could not abort running commands with `git rebase --abort`.",
-                        BaseColor::Red.light()
-                    )
-                    .build()
-            )?
-        )?;
+    match command.status() {
+        Ok(exit_status) if exit_status.success() => {}
+        Ok(exit_status) => {
+            warn!(?commit_oid, ?exit_status, "On-result command failed");
+        }
+        Err(err) => {
+            warn!(?commit_oid, ?err, "Could not run on-result command");
+        }
     }
-    Ok(exit_code)
 }
 
-/// The result of running a test.
-#[derive(Debug)]
-pub struct TestOutput {
-    /// Only used for `--no-cache` invocations, to ensure that we don't clobber
-    /// an existing cached entry.
-    pub temp_dir: Option<TempDir>,
-
-    /// The path to the file containing the serialized test result information
-    /// (such as the exit code).
-    pub result_path: PathBuf,
-
-    /// The path to the file containing the stdout of the test command.
-    pub stdout_path: PathBuf,
-
-    /// The path to the file containing the stderr of the test command.
-    pub stderr_path: PathBuf,
-
-    /// The resulting status of the test.
-    pub test_status: TestStatus,
+struct EventLoopOutput<'a> {
+    search: search::Search<SearchGraph<'a>>,
+    test_outputs: HashMap<NonZeroOid, TestOutput>,
+    testing_aborted_error: Option<TestingAbortedError>,
+    stopped_after_failures: Option<usize>,
+    interrupted: bool,
 }
 
-/// The possible results of attempting to run a test.
-#[derive(Clone, Debug)]
-pub enum TestStatus {
-    /// Attempting to set up the working directory for the repository failed.
-    CheckoutFailed,
-
-    /// Invoking the test command failed.
-    SpawnTestFailed(String),
-
-    /// The test command was invoked successfully, but was terminated by a signal, rather than
-    /// returning an exit code normally.
-    TerminatedBySignal,
+/// How often the event loop checks whether the user interrupted testing while
+/// it's waiting for results.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-    /// It appears that some other process is already running the test for a commit with the given
-    /// tree. (If that process crashed, then the test may need to be re-run.)
-    AlreadyInProgress,
+fn event_loop<'a>(
+    progress: &ProgressHandle,
+    repo: &Repo,
+    shell_path: &Path,
+    on_result_command: Option<&str>,
+    commit_jobs: IndexMap<NonZeroOid, TestJob>,
+    mut search: search::Search<SearchGraph<'a>>,
+    search_strategy: Option<search::Strategy>,
+    num_jobs: usize,
+    max_failures: Option<usize>,
+    skip_descendants_graph: Option<SearchGraph<'a>>,
+    work_queue: WorkQueue<TestJob>,
+    result_rx: Receiver<JobResult<TestJob, TestOutput>>,
+) -> eyre::Result<EventLoopOutput<'a>> {
+    #[derive(Debug)]
+    enum ScheduledJob {
+        Scheduled(TestJob),
+        Complete(TestOutput),
+    }
+    let mut scheduled_jobs: HashMap<NonZeroOid, ScheduledJob> = Default::default();
+    let mut testing_aborted_error = None;
+    let mut num_failures = 0;
+    let mut stopped_after_failures = None;
+    let mut interrupted = false;
 
-    /// Attempting to read cached data failed.
-    ReadCacheFailed(String),
+    if search_strategy.is_none() {
+        let jobs_to_schedule = commit_jobs
+            .keys()
+            .map(|commit_oid| commit_jobs[commit_oid].clone())
+            .collect_vec();
+        debug!(
+            ?jobs_to_schedule,
+            "Scheduling all jobs (since no search strategy was specified)"
+        );
+        for job in &jobs_to_schedule {
+            scheduled_jobs.insert(job.commit_oid, ScheduledJob::Scheduled(job.clone()));
+        }
+        work_queue.set(jobs_to_schedule);
+    }
 
-    /// The test command indicated that the commit should be skipped for testing.
-    Indeterminate {
-        /// The exit code of the command.
-        exit_code: i32,
-    },
+    loop {
+        if let Some(err) = &testing_aborted_error {
+            debug!(?err, "Testing aborted");
+            break;
+        }
 
-    /// The test command indicated that the process should be aborted entirely.
-    Abort {
-        /// The exit code of the command.
-        exit_code: i32,
-    },
+        if !interrupted && is_interrupted() {
+            // Don't start any new jobs. The test commands which are already
+            // running also received the interrupt, so wait for them to exit,
+            // but discard their results.
+            let unstarted_jobs = work_queue.clear();
+            debug!(?unstarted_jobs, "Interrupted, no longer scheduling jobs");
+            for job in unstarted_jobs {
+                scheduled_jobs.remove(&job.commit_oid);
+            }
+            interrupted = true;
+        }
 
-    /// The test failed and returned the provided (non-zero) exit code.
-    Failed {
-        /// Whether or not the result was cached (indicating that we didn't
-        /// actually re-run the test).
-        cached: bool,
+        if let Some(search_strategy) = search_strategy.filter(|_| !interrupted) {
+            // Take back the jobs which no worker has started yet, since the
+            // best commits to test may have changed. The jobs which are
+            // already running are kept, and their results are reconciled with
+            // the search once they arrive.
+            let unstarted_commit_oids: HashSet<NonZeroOid> = work_queue
+                .clear()
+                .into_iter()
+                .map(|job| job.commit_oid)
+                .collect();
+            scheduled_jobs = scheduled_jobs
+                .into_iter()
+                .filter_map(|(commit_oid, scheduled_job)| match scheduled_job {
+                    ScheduledJob::Scheduled(_) if unstarted_commit_oids.contains(&commit_oid) => {
+                        None
+                    }
+                    scheduled_job @ (ScheduledJob::Scheduled(_) | ScheduledJob::Complete(_)) => {
+                        Some((commit_oid, scheduled_job))
+                    }
+                })
+                .collect();
+            let pending_commit_oids: HashSet<NonZeroOid> = scheduled_jobs
+                .iter()
+                .filter_map(|(commit_oid, scheduled_job)| match scheduled_job {
+                    ScheduledJob::Scheduled(_) => Some(*commit_oid),
+                    ScheduledJob::Complete(_) => None,
+                })
+                .collect();
 
-        /// The exit code of the process.
-        exit_code: i32,
+            // If the remaining jobs are only testing commits whose results
+            // are already implied, then there's no need to wait for them.
+            let num_remaining = search.search(search_strategy)?.next_to_search.count();
+            if num_remaining == 0 {
+                debug!("Search completed, exiting.");
+                break;
+            }
+            progress.notify_status(
+                OperationIcon::InProgress,
+                format!(
+                    "{}: ~{} remaining",
+                    match search_strategy {
+                        search::Strategy::Linear | search::Strategy::LinearReverse => "Searching",
+                        search::Strategy::Binary => "Bisecting",
+                    },
+                    Pluralize {
+                        determiner: None,
+                        amount: num_remaining,
+                        unit: ("commit", "commits"),
+                    },
+                ),
+            );
 
-        /// Whether the test was run interactively (the user executed the
-        /// command via `--interactive`).
-        interactive: bool,
-    },
+            // Use the idle workers to search the ranges between the commits
+            // which are still being tested, rather than speculating further
+            // into a single range.
+            let solution = search.search_with_pending(search_strategy, &pending_commit_oids)?;
+            let next_to_search = solution
+                .next_to_search
+                .filter(|commit_oid| !scheduled_jobs.contains_key(commit_oid))
+                .take(num_jobs.saturating_sub(pending_commit_oids.len()))
+                .collect_vec();
+            let jobs_to_schedule = next_to_search
+                .into_iter()
+                .map(|commit_oid| commit_jobs[&commit_oid].clone())
+                .collect_vec();
+            debug!(
+                ?search_strategy,
+                ?jobs_to_schedule,
+                "Jobs to schedule for search"
+            );
+            for job in &jobs_to_schedule {
+                if let Some(previous_job) =
+                    scheduled_jobs.insert(job.commit_oid, ScheduledJob::Scheduled(job.clone()))
+                {
+                    warn!(?job, ?previous_job, "Overwriting previously-scheduled job");
+                }
+            }
+            work_queue.set(jobs_to_schedule);
+        }
 
-    /// The test passed and returned a successful exit code.
-    Passed {
-        /// Whether or not the result was cached (indicating that we didn't
-        /// actually re-run the test).
-        cached: bool,
+        let message = {
+            let jobs_in_progress = scheduled_jobs
+                .values()
+                .filter_map(|scheduled_job| match scheduled_job {
+                    ScheduledJob::Scheduled(job) => Some(job),
+                    ScheduledJob::Complete(_) => None,
+                })
+                .collect_vec();
+            if jobs_in_progress.is_empty() {
+                debug!("No more in-progress jobs to wait on, exiting");
+                break;
+            }
 
-        /// Information about the working copy state after running the test command.
-        fix_info: FixInfo,
+            // If there is work to be done, then block on the next result to
+            // be received from a worker. This is okay because we won't
+            // adjust the work queue until we've received the next result,
+            // except to stop scheduling jobs if the user interrupts testing,
+            // which is checked periodically.
+            debug!(?jobs_in_progress, "Event loop waiting for new job result");
+            let result = loop {
+                match result_rx.recv_timeout(INTERRUPT_POLL_INTERVAL) {
+                    Ok(result) => break Some(Ok(result)),
+                    Err(RecvTimeoutError::Disconnected) => break Some(Err(RecvError)),
+                    Err(RecvTimeoutError::Timeout) => {
+                        if !interrupted && is_interrupted() {
+                            break None;
+                        }
+                    }
+                }
+            };
+            debug!(?result, "Event loop got new job result");
+            match result {
+                Some(result) => result,
+                // Go back to the start of the loop to stop scheduling jobs.
+                None => continue,
+            }
+        };
+        let (job, test_output) = match message {
+            Err(RecvError) => {
+                debug!("No more job results could be received because result_rx closed");
+                break;
+            }
 
-        /// Whether the test was run interactively (the user executed the
-        /// command via `--interactive`).
-        interactive: bool,
-    },
-}
+            Ok(JobResult::Cancelled(job)) => {
+                scheduled_jobs.remove(&job.commit_oid);
+                continue;
+            }
 
-/// Information about the working copy state after running the test command.
-#[derive(Clone, Debug)]
-pub struct FixInfo {
-    /// The resulting commit which was checked out as `HEAD`. This is usually
-    /// the same commit as was being tested, but the test command could amend or
-    /// switch to a different commit.
-    pub head_commit_oid: Option<NonZeroOid>,
+            // Results which were completed before the interrupt are kept,
+            // since they've already been cached.
+            Ok(JobResult::Done(job, test_output)) if test_output.interrupted => {
+                scheduled_jobs.remove(&job.commit_oid);
+                continue;
+            }
 
-    /// The resulting contents of the working copy after the command was
-    /// executed (if taking a working copy snapshot succeeded and there were no
-    /// merge conflicts, etc.).
-    pub snapshot_tree_oid: Option<NonZeroOid>,
-}
+            Ok(JobResult::Error(worker_id, job, error_message)) => {
+                let TestJob {
+                    commit_oid,
+                    operation_type: _,
+                } = job;
+                eyre::bail!("Worker {worker_id} failed when processing commit {commit_oid}: {error_message}");
+            }
 
-impl TestStatus {
-    #[instrument]
-    fn get_icon(&self) -> &'static str {
-        match self {
-            TestStatus::CheckoutFailed
-            | TestStatus::SpawnTestFailed(_)
-            | TestStatus::AlreadyInProgress
-            | TestStatus::ReadCacheFailed(_)
-            | TestStatus::TerminatedBySignal
-            | TestStatus::Indeterminate { .. } => icons::EXCLAMATION,
-            TestStatus::Failed { .. } | TestStatus::Abort { .. } => icons::CROSS,
-            TestStatus::Passed { .. } => icons::CHECKMARK,
-        }
-    }
+            Ok(JobResult::Done(job, test_output)) => (job, test_output),
+        };
 
-    #[instrument]
-    fn get_style(&self) -> Style {
-        match self {
+        let TestJob {
+            commit_oid,
+            operation_type: _,
+        } = job;
+        let (maybe_testing_aborted_error, search_status) = match &test_output.test_status {
             TestStatus::CheckoutFailed
             | TestStatus::SpawnTestFailed(_)
+            | TestStatus::TerminatedBySignal { .. }
             | TestStatus::AlreadyInProgress
             | TestStatus::ReadCacheFailed(_)
-            | TestStatus::TerminatedBySignal
-            | TestStatus::Indeterminate { .. } => *STYLE_SKIPPED,
-            TestStatus::Failed { .. } | TestStatus::Abort { .. } => *STYLE_FAILURE,
-            TestStatus::Passed { .. } => *STYLE_SUCCESS,
-        }
-    }
-
-    /// Produce a friendly description of the test status.
-    #[instrument]
-    pub fn describe(
-        &self,
-        glyphs: &Glyphs,
-        commit: &Commit,
-        apply_fixes: bool,
-    ) -> eyre::Result<StyledString> {
-        let description = match self {
-            TestStatus::CheckoutFailed => StyledStringBuilder::new()
-                .append_styled("Failed to check out: ", self.get_style())
-                .append(commit.friendly_describe(glyphs)?)
-                .build(),
-
-            TestStatus::SpawnTestFailed(err) => StyledStringBuilder::new()
-                .append_styled(
-                    format!("Failed to spawn command: {err}: "),
-                    self.get_style(),
-                )
-                .append(commit.friendly_describe(glyphs)?)
-                .build(),
-
-            TestStatus::TerminatedBySignal => StyledStringBuilder::new()
-                .append_styled("Command terminated by signal: ", self.get_style())
-                .append(commit.friendly_describe(glyphs)?)
-                .build(),
-
-            TestStatus::AlreadyInProgress => StyledStringBuilder::new()
-                .append_styled("Command already in progress? ", self.get_style())
-                .append(commit.friendly_describe(glyphs)?)
-                .build(),
-
-            TestStatus::ReadCacheFailed(_) => StyledStringBuilder::new()
-                .append_styled("Could not read cached command result: ", self.get_style())
-                .append(commit.friendly_describe(glyphs)?)
-                .build(),
+            | TestStatus::Indeterminate { .. }
+            | TestStatus::SkippedDueToAncestor { .. }
+            | TestStatus::SkippedEmpty
+            | TestStatus::MergeParentConflicted => (None, search::Status::Indeterminate),
 
-            TestStatus::Indeterminate { exit_code } => StyledStringBuilder::new()
-                .append_styled(
-                    format!("Exit code indicated to skip this commit (exit code {exit_code}): "),
-                    self.get_style(),
-                )
-                .append(commit.friendly_describe(glyphs)?)
-                .build(),
+            TestStatus::Abort { exit_code } => (
+                Some(TestingAbortedError {
+                    commit_oid,
+                    exit_code: *exit_code,
+                }),
+                search::Status::Indeterminate,
+            ),
 
-            TestStatus::Abort { exit_code } => StyledStringBuilder::new()
-                .append_styled(
-                    format!("Exit code indicated to abort command (exit code {exit_code}): "),
-                    self.get_style(),
-                )
-                .append(commit.friendly_describe(glyphs)?)
-                .build(),
+            TestStatus::Timeout { duration: _ } => (None, search::Status::Failure),
 
             TestStatus::Failed {
-                cached,
-                interactive,
-                exit_code,
-            } => {
-                let mut descriptors = Vec::new();
-                if *cached {
-                    descriptors.push("cached".to_string());
-                }
-                descriptors.push(format!("exit code {exit_code}"));
-                if *interactive {
-                    descriptors.push("interactive".to_string());
-                }
-                let descriptors = descriptors.join(", ");
-                StyledStringBuilder::new()
-                    .append_styled(format!("Failed ({descriptors}): "), self.get_style())
-                    .append(commit.friendly_describe(glyphs)?)
-                    .build()
-            }
+                cached: _,
+                interactive: _,
+                exit_code: _,
+                attempts: _,
+                reason: _,
+            } => (None, search::Status::Failure),
+
+            TestStatus::PerfThresholdExceeded {
+                cached: _,
+                measurement: _,
+                threshold: _,
+            } => (None, search::Status::Failure),
 
             TestStatus::Passed {
-                cached,
-                interactive,
-                fix_info:
-                    FixInfo {
-                        head_commit_oid: _,
-                        snapshot_tree_oid,
-                    },
-            } => {
-                let mut descriptors = Vec::new();
-                if *cached {
-                    descriptors.push("cached".to_string());
-                }
-                match (snapshot_tree_oid, commit.get_tree_oid()) {
-                    (Some(snapshot_tree_oid), MaybeZeroOid::NonZero(original_tree_oid)) => {
-                        if *snapshot_tree_oid != original_tree_oid {
-                            descriptors.push(if apply_fixes {
-                                "fixed".to_string()
-                            } else {
-                                "fixable".to_string()
-                            });
-                        }
-                    }
-                    (None, _) | (_, MaybeZeroOid::Zero) => {}
+                cached: _,
+                fix_info: _,
+                interactive: _,
+                attempts: _,
+            } => (None, search::Status::Success),
+        };
+        if search_strategy.is_some() {
+            search.notify(commit_oid, search_status)?;
+        }
+        if let Some(on_result_command) = on_result_command {
+            run_on_result_command(
+                repo,
+                shell_path,
+                on_result_command,
+                commit_oid,
+                &test_output,
+            );
+        }
+        let is_failure = matches!(
+            test_output.test_status,
+            TestStatus::Failed { .. }
+                | TestStatus::PerfThresholdExceeded { .. }
+                | TestStatus::Abort { .. }
+                | TestStatus::Timeout { .. }
+        );
+        if scheduled_jobs
+            .insert(commit_oid, ScheduledJob::Complete(test_output))
+            .is_none()
+        {
+            warn!(
+                ?commit_oid,
+                "Received test result for commit that was not scheduled"
+            );
+        }
+
+        if let Some(err) = maybe_testing_aborted_error {
+            testing_aborted_error = Some(err);
+        }
+
+        if is_failure {
+            num_failures += 1;
+
+            if let Some(graph) = &skip_descendants_graph {
+                let descendant_oids = search::SearchGraph::descendants(graph, commit_oid)?;
+                let (skipped_jobs, remaining_jobs): (Vec<_>, Vec<_>) = work_queue
+                    .clear()
+                    .into_iter()
+                    .partition(|job| descendant_oids.contains(&job.commit_oid));
+                debug!(
+                    ?commit_oid,
+                    ?skipped_jobs,
+                    "Skipping descendants of failed commit"
+                );
+                work_queue.set(remaining_jobs);
+                for job in skipped_jobs {
+                    scheduled_jobs.insert(
+                        job.commit_oid,
+                        ScheduledJob::Complete(TestOutput {
+                            temp_dir: None,
+                            result_path: PathBuf::new(),
+                            stdout_path: PathBuf::new(),
+                            stderr_path: PathBuf::new(),
+                            test_status: TestStatus::SkippedDueToAncestor {
+                                ancestor_oid: commit_oid,
+                            },
+                            duration: None,
+                            interrupted: false,
+                        }),
+                    );
                 }
-                if *interactive {
-                    descriptors.push("interactive".to_string());
+            }
+        }
+        if let Some(max_failures) = max_failures {
+            if stopped_after_failures.is_none() && num_failures >= max_failures {
+                // Don't start any new jobs, but wait for the jobs which are
+                // already in progress to finish.
+                let unstarted_jobs = work_queue.clear();
+                debug!(
+                    ?num_failures,
+                    ?unstarted_jobs,
+                    "Maximum number of failures reached, no longer scheduling jobs"
+                );
+                for job in unstarted_jobs {
+                    scheduled_jobs.remove(&job.commit_oid);
                 }
-                let descriptors = if descriptors.is_empty() {
-                    "".to_string()
-                } else {
-                    format!(" ({})", descriptors.join(", "))
-                };
-                StyledStringBuilder::new()
-                    .append_styled(format!("Passed{descriptors}: "), self.get_style())
-                    .append(commit.friendly_describe(glyphs)?)
-                    .build()
+                stopped_after_failures = Some(num_failures);
             }
-        };
-        Ok(description)
+        }
     }
+
+    let test_outputs = scheduled_jobs
+        .into_iter()
+        .filter_map(|(commit_oid, scheduled_job)| match scheduled_job {
+            ScheduledJob::Scheduled(_) => None,
+            ScheduledJob::Complete(test_output) => Some((commit_oid, test_output)),
+        })
+        .collect();
+    Ok(EventLoopOutput {
+        search,
+        test_outputs,
+        testing_aborted_error,
+        stopped_after_failures,
+        interrupted,
+    })
 }
 
-/// An error produced when testing is aborted due to a certain commit.
-#[derive(Debug)]
-pub struct TestingAbortedError {
-    /// The commit which aborted testing.
-    pub commit_oid: NonZeroOid,
+/// Get a key which is the same for failures with the same exit code and
+/// output, or `None` if the test command didn't fail with an exit code.
+fn get_failure_group_key(test_output: &TestOutput) -> Option<u64> {
+    let exit_code = match test_output.test_status {
+        TestStatus::Failed { exit_code, .. } => exit_code,
+        _ => return None,
+    };
 
-    /// The exit code of the test command when run on that commit.
-    pub exit_code: i32,
+    let mut hasher = DefaultHasher::new();
+    exit_code.hash(&mut hasher);
+    for path in [&test_output.stdout_path, &test_output.stderr_path] {
+        let output = read_test_output(path).unwrap_or_default();
+        output.trim().hash(&mut hasher);
+    }
+    Some(hasher.finish())
 }
 
-impl TestOutput {
-    #[instrument]
-    fn describe(
-        &self,
-        effects: &Effects,
-        commit: &Commit,
-        apply_fixes: bool,
-        verbosity: Verbosity,
-    ) -> eyre::Result<StyledString> {
-        let description = StyledStringBuilder::new()
-            .append_styled(self.test_status.get_icon(), self.test_status.get_style())
-            .append_plain(" ")
-            .append(
-                self.test_status
-                    .describe(effects.get_glyphs(), commit, apply_fixes)?,
-            )
-            .build();
+/// Print a single entry for a group of commits which failed with the same
+/// exit code and output, as determined by `get_failure_group_key`.
+fn print_failure_group(
+    effects: &Effects,
+    repo: &Repo,
+    test_output: &TestOutput,
+    commit_oids: &[NonZeroOid],
+) -> eyre::Result<()> {
+    let exit_code = test_output.test_status.get_exit_code().unwrap_or_default();
+    writeln!(
+        effects.get_output_stream(),
+        "{}",
+        effects.get_glyphs().render(
+            StyledStringBuilder::new()
+                .append_styled(
+                    test_output.test_status.get_icon(),
+                    test_output.test_status.get_style()
+                )
+                .append_plain(" ")
+                .append_styled(
+                    format!(
+                        "Failed (exit code {exit_code}) with identical output on {}:",
+                        Pluralize {
+                            determiner: None,
+                            amount: commit_oids.len(),
+                            unit: ("commit", "commits"),
+                        }
+                    ),
+                    test_output.test_status.get_style()
+                )
+                .build()
+        )?
+    )?;
+    for commit_oid in commit_oids {
+        let commit = repo.find_commit_or_fail(*commit_oid)?;
+        writeln!(
+            effects.get_output_stream(),
+            "{} {}",
+            effects.get_glyphs().bullet_point,
+            effects
+                .get_glyphs()
+                .render(commit.friendly_describe(effects.get_glyphs())?)?
+        )?;
+    }
+    Ok(())
+}
 
-        if verbosity == Verbosity::None {
-            return Ok(StyledStringBuilder::from_lines(vec![description]));
+#[instrument]
+fn print_summary(
+    effects: &Effects,
+    dag: &Dag,
+    repo: &Repo,
+    revset: &Revset,
+    test_results: &TestResults,
+    options: &ResolvedTestOptions,
+    elapsed: Duration,
+) -> eyre::Result<ExitCode> {
+    let command = options.command.as_str();
+    let is_search = options.search_strategy.is_some();
+    let bisect_ref = options.report.bisect_ref.as_deref();
+    let apply_fixes = options.fix_options.is_some();
+    let read_cache = options.cache_mode.should_read();
+    let keep_worktrees = options.process.keep_worktrees;
+    let worktrees_dir = options.worktrees_dir.as_path();
+    let verbosity = &options.verbosity;
+    let output_context_lines = options.report.output_context_lines;
+    let group_failures = options.report.group_failures;
+    let failure_group_keys: HashMap<NonZeroOid, u64> = if group_failures {
+        test_results
+            .test_outputs
+            .iter()
+            .filter_map(|(commit_oid, test_output)| {
+                get_failure_group_key(test_output).map(|key| (*commit_oid, key))
+            })
+            .collect()
+    } else {
+        Default::default()
+    };
+    let mut failure_groups: HashMap<u64, Vec<NonZeroOid>> = HashMap::new();
+    for commit_oid in test_results.test_outputs.keys() {
+        if let Some(key) = failure_group_keys.get(commit_oid) {
+            failure_groups.entry(*key).or_default().push(*commit_oid);
         }
+    }
 
-        fn abbreviate_lines(path: &Path, verbosity: Verbosity) -> Vec<StyledString> {
-            let should_show_all_lines = match verbosity {
-                Verbosity::None => return Vec::new(),
-                Verbosity::PartialOutput => false,
-                Verbosity::FullOutput => true,
-            };
+    let mut num_passed = 0;
+    let mut num_failed = 0;
+    let mut num_skipped = 0;
+    let mut num_cached_results = 0;
+    for (commit_oid, test_output) in &test_results.test_outputs {
+        let failure_group = failure_group_keys
+            .get(commit_oid)
+            .and_then(|key| failure_groups.get(key))
+            .filter(|failure_group| failure_group.len() > 1);
+        match failure_group {
+            Some(failure_group) => {
+                // Print the whole group when its first commit is reached, and
+                // nothing for the rest of its commits.
+                if failure_group.first() == Some(commit_oid) {
+                    print_failure_group(effects, repo, test_output, failure_group)?;
+                }
+            }
+            None => {
+                let commit = repo.find_commit_or_fail(*commit_oid)?;
+                write!(
+                    effects.get_output_stream(),
+                    "{}",
+                    effects.get_glyphs().render(test_output.describe(
+                        effects,
+                        &commit,
+                        apply_fixes,
+                        *verbosity,
+                        output_context_lines,
+                    )?)?
+                )?;
+            }
+        }
+        match test_output.test_status.get_category() {
+            TestStatusCategory::Passed => num_passed += 1,
+            TestStatusCategory::Failed => num_failed += 1,
+            TestStatusCategory::Skipped => num_skipped += 1,
+        }
+        if test_output.test_status.is_cached() {
+            num_cached_results += 1;
+        }
+    }
+
+    writeln!(
+        effects.get_output_stream(),
+        "Ran command on {}: {}:",
+        Pluralize {
+            determiner: None,
+            amount: test_results.test_outputs.len(),
+            unit: ("commit", "commits")
+        },
+        effects.get_glyphs().render(
+            StyledStringBuilder::new()
+                .append_styled(command, Effect::Bold)
+                .build()
+        )?,
+    )?;
+
+    writeln!(
+        effects.get_output_stream(),
+        "{}",
+        render_test_counts(effects, num_passed, num_failed, num_skipped)?
+    )?;
+    if !read_cache {
+        writeln!(
+            effects.get_output_stream(),
+            "Caching was disabled (--no-cache)"
+        )?;
+    } else if num_cached_results > 0 {
+        let num_results = test_results.test_outputs.len();
+        writeln!(
+            effects.get_output_stream(),
+            "{}/{} from cache ({}%)",
+            num_cached_results,
+            Pluralize {
+                determiner: None,
+                amount: num_results,
+                unit: ("result", "results"),
+            },
+            num_cached_results * 100 / num_results,
+        )?;
+    }
+
+    let slowest_test_outputs = {
+        let mut test_outputs = test_results
+            .test_outputs
+            .iter()
+            .filter_map(|(commit_oid, test_output)| {
+                get_displayed_duration(test_output).map(|duration| (*commit_oid, duration))
+            })
+            .collect_vec();
+        test_outputs.sort_by(|(_, lhs), (_, rhs)| rhs.cmp(lhs));
+        test_outputs.truncate(NUM_SLOWEST_COMMITS);
+        test_outputs
+    };
+    if !slowest_test_outputs.is_empty() {
+        let total_duration: Duration = test_results
+            .test_outputs
+            .values()
+            .filter_map(|test_output| test_output.duration)
+            .sum();
+        writeln!(
+            effects.get_output_stream(),
+            "Total time spent running the command: {}",
+            format_duration(total_duration)
+        )?;
+        writeln!(
+            effects.get_output_stream(),
+            "Slowest {}:",
+            if slowest_test_outputs.len() == 1 {
+                "commit"
+            } else {
+                "commits"
+            }
+        )?;
+        for (commit_oid, duration) in slowest_test_outputs {
+            let commit = repo.find_commit_or_fail(commit_oid)?;
+            writeln!(
+                effects.get_output_stream(),
+                "{} {} ({})",
+                effects.get_glyphs().bullet_point,
+                effects
+                    .get_glyphs()
+                    .render(commit.friendly_describe(effects.get_glyphs())?)?,
+                format_duration(duration),
+            )?;
+        }
+    }
 
-            // FIXME: don't read entire file into memory
-            let contents = match std::fs::read_to_string(path) {
-                Ok(contents) => contents,
-                Err(_) => {
-                    return vec![StyledStringBuilder::new()
-                        .append_plain("<failed to read file>")
-                        .build()]
+    if elapsed >= MIN_DISPLAYED_DURATION {
+        let durations = test_results
+            .test_outputs
+            .values()
+            .filter_map(|test_output| test_output.duration)
+            .collect_vec();
+        let mut details = Vec::new();
+        if let Ok(num_durations) = u32::try_from(durations.len()) {
+            if num_durations > 0 {
+                let average_duration = durations.iter().sum::<Duration>() / num_durations;
+                details.push(format!(
+                    "averaging {} per commit tested",
+                    format_duration(average_duration)
+                ));
+            }
+        }
+        if num_cached_results > 0 {
+            details.push(format!(
+                "{} took no time",
+                Pluralize {
+                    determiner: None,
+                    amount: num_cached_results,
+                    unit: ("cached result", "cached results"),
                 }
-            };
+            ));
+        }
+        writeln!(
+            effects.get_output_stream(),
+            "Finished in {}{}",
+            format_duration(elapsed),
+            if details.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", details.join(", "))
+            }
+        )?;
+    }
+
+    if keep_worktrees {
+        let mut kept_worktrees = Vec::new();
+        for (commit_oid, test_output) in &test_results.test_outputs {
+            let kept_worktree_dir = get_kept_test_worktree_dir(worktrees_dir, *commit_oid);
+            match test_output.test_status {
+                TestStatus::Failed { .. }
+                | TestStatus::PerfThresholdExceeded { .. }
+                | TestStatus::TerminatedBySignal { .. }
+                | TestStatus::Timeout { .. }
+                | TestStatus::Abort { .. }
+                    if kept_worktree_dir.exists() =>
+                {
+                    kept_worktrees.push((*commit_oid, kept_worktree_dir));
+                }
+                _ => {}
+            }
+        }
+        if !kept_worktrees.is_empty() {
+            writeln!(
+                effects.get_output_stream(),
+                "Kept testing worktrees for failed commits (run git test gc to remove them):"
+            )?;
+            for (commit_oid, kept_worktree_dir) in kept_worktrees {
+                let commit = repo.find_commit_or_fail(commit_oid)?;
+                writeln!(
+                    effects.get_output_stream(),
+                    "{} {}: {}",
+                    effects.get_glyphs().bullet_point,
+                    effects
+                        .get_glyphs()
+                        .render(commit.friendly_describe(effects.get_glyphs())?)?,
+                    kept_worktree_dir.display(),
+                )?;
+            }
+        }
+    }
+
+    if is_search {
+        let success_commits: CommitSet =
+            test_results.search_bounds.success.iter().copied().collect();
+        let success_commits = sorted_commit_set(repo, dag, &success_commits)?;
+        if success_commits.is_empty() {
+            writeln!(
+                effects.get_output_stream(),
+                "There were no passing commits in the provided set."
+            )?;
+        } else {
+            writeln!(
+                effects.get_output_stream(),
+                "Last passing {commits}:",
+                commits = if success_commits.len() == 1 {
+                    "commit"
+                } else {
+                    "commits"
+                },
+            )?;
+            for commit in success_commits {
+                writeln!(
+                    effects.get_output_stream(),
+                    "{} {}",
+                    effects.get_glyphs().bullet_point,
+                    effects
+                        .get_glyphs()
+                        .render(commit.friendly_describe(effects.get_glyphs())?)?
+                )?;
+            }
+        }
+
+        let failure_commits: CommitSet =
+            test_results.search_bounds.failure.iter().copied().collect();
+        let failure_commits = sorted_commit_set(repo, dag, &failure_commits)?;
+        if failure_commits.is_empty() {
+            writeln!(
+                effects.get_output_stream(),
+                "There were no failing commits in the provided set."
+            )?;
+        } else {
+            writeln!(
+                effects.get_output_stream(),
+                "First failing {commits}:",
+                commits = if failure_commits.len() == 1 {
+                    "commit"
+                } else {
+                    "commits"
+                },
+            )?;
+            for commit in &failure_commits {
+                writeln!(
+                    effects.get_output_stream(),
+                    "{} {}",
+                    effects.get_glyphs().bullet_point,
+                    effects
+                        .get_glyphs()
+                        .render(commit.friendly_describe(effects.get_glyphs())?)?
+                )?;
+            }
+        }
 
-            const NUM_CONTEXT_LINES: usize = 5;
-            let lines = contents.lines().collect_vec();
-            let num_missing_lines = lines.len().saturating_sub(2 * NUM_CONTEXT_LINES);
-            let num_missing_lines_message = format!("<{num_missing_lines} more lines>");
-            let lines = if lines.is_empty() {
-                vec!["<no output>"]
-            } else if num_missing_lines == 0 || should_show_all_lines {
-                lines
-            } else {
-                [
-                    &lines[..NUM_CONTEXT_LINES],
-                    &[num_missing_lines_message.as_str()],
-                    &lines[lines.len() - NUM_CONTEXT_LINES..],
-                ]
-                .concat()
-            };
-            lines
-                .into_iter()
-                .map(|line| StyledStringBuilder::new().append_plain(line).build())
-                .collect()
+        if let (Some(bisect_ref), Some(first_failure_commit)) =
+            (bisect_ref, failure_commits.first())
+        {
+            repo.create_reference(
+                &bisect_ref.into(),
+                first_failure_commit.get_oid(),
+                true,
+                "branchless: recording first failing commit from git test run",
+            )?;
+            writeln!(
+                effects.get_output_stream(),
+                "Updated {bisect_ref} to point to the first failing commit: {}",
+                effects
+                    .get_glyphs()
+                    .render(first_failure_commit.friendly_describe(effects.get_glyphs())?)?
+            )?;
         }
+    }
 
-        let interactive = match self.test_status {
-            TestStatus::CheckoutFailed
-            | TestStatus::SpawnTestFailed(_)
-            | TestStatus::TerminatedBySignal
-            | TestStatus::AlreadyInProgress
-            | TestStatus::ReadCacheFailed(_)
-            | TestStatus::Indeterminate { .. }
-            | TestStatus::Abort { .. } => false,
-            TestStatus::Failed { interactive, .. } | TestStatus::Passed { interactive, .. } => {
-                interactive
+    if num_cached_results > 0 && get_hint_enabled(repo, Hint::CleanCachedTestResults)? {
+        writeln!(
+            effects.get_output_stream(),
+            "{}: there {}",
+            effects.get_glyphs().render(get_hint_string())?,
+            Pluralize {
+                determiner: Some(("was", "were")),
+                amount: num_cached_results,
+                unit: ("cached test result", "cached test results")
             }
-        };
+        )?;
+        writeln!(
+            effects.get_output_stream(),
+            "{}: to clear these cached results, run: git test clean {}",
+            effects.get_glyphs().render(get_hint_string())?,
+            shell_escape(revset.to_string()),
+        )?;
+        print_hint_suppression_notice(effects, Hint::CleanCachedTestResults)?;
+    }
 
-        let stdout_lines = {
-            let mut lines = Vec::new();
-            if !interactive {
-                lines.push(
-                    StyledStringBuilder::new()
-                        .append_styled("Stdout: ", Effect::Bold)
-                        .append_plain(self.stdout_path.to_string_lossy())
-                        .build(),
-                );
-                lines.extend(abbreviate_lines(&self.stdout_path, verbosity));
-            }
-            lines
-        };
-        let stderr_lines = {
-            let mut lines = Vec::new();
-            if !interactive {
-                lines.push(
-                    StyledStringBuilder::new()
-                        .append_styled("Stderr: ", Effect::Bold)
-                        .append_plain(self.stderr_path.to_string_lossy())
-                        .build(),
-                );
-                lines.extend(abbreviate_lines(&self.stderr_path, verbosity));
-            }
-            lines
-        };
+    if let Some(testing_aborted_error) = &test_results.testing_aborted_error {
+        let TestingAbortedError {
+            commit_oid,
+            exit_code,
+        } = testing_aborted_error;
+        let commit = repo.find_commit_or_fail(*commit_oid)?;
+        writeln!(
+            effects.get_output_stream(),
+            "Aborted running commands with exit code {} at commit: {}",
+            exit_code,
+            effects
+                .get_glyphs()
+                .render(commit.friendly_describe(effects.get_glyphs())?)?
+        )?;
+        return Ok(ExitCode(1));
+    }
 
-        Ok(StyledStringBuilder::from_lines(
-            [
-                &[description],
-                stdout_lines.as_slice(),
-                stderr_lines.as_slice(),
-            ]
-            .concat(),
-        ))
+    if let Some(num_failures) = test_results.stopped_after_failures {
+        writeln!(
+            effects.get_output_stream(),
+            "Stopped running commands after {}",
+            Pluralize {
+                determiner: None,
+                amount: num_failures,
+                unit: ("failure", "failures")
+            },
+        )?;
+        return Ok(ExitCode(1));
     }
-}
 
-fn shell_escape(s: impl AsRef<str>) -> String {
-    let s = s.as_ref();
-    let mut escaped = String::new();
-    escaped.push('"');
-    for c in s.chars() {
-        match c {
-            '"' => escaped.push_str(r#"\""#),
-            '\\' => escaped.push_str(r#"\\\\"#),
-            c => escaped.push(c),
-        }
+    if test_results.interrupted {
+        writeln!(
+            effects.get_output_stream(),
+            "Interrupted; stopped running commands"
+        )?;
+        return Ok(ExitCode(1));
     }
-    escaped.push('"');
-    escaped
+
+    Ok(get_exit_code(test_results, is_search))
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-struct TestJob {
-    commit_oid: NonZeroOid,
-    operation_type: OperationType,
+/// Render the line summarizing how many commits passed, failed, and were
+/// skipped.
+fn render_test_counts(
+    effects: &Effects,
+    num_passed: usize,
+    num_failed: usize,
+    num_skipped: usize,
+) -> eyre::Result<String> {
+    let passed = effects.get_glyphs().render(
+        StyledStringBuilder::new()
+            .append_styled(format!("{num_passed} passed"), *STYLE_SUCCESS)
+            .build(),
+    )?;
+    let failed = effects.get_glyphs().render(
+        StyledStringBuilder::new()
+            .append_styled(format!("{num_failed} failed"), *STYLE_FAILURE)
+            .build(),
+    )?;
+    let skipped = effects.get_glyphs().render(
+        StyledStringBuilder::new()
+            .append_styled(format!("{num_skipped} skipped"), *STYLE_SKIPPED)
+            .build(),
+    )?;
+    Ok(format!("{passed}, {failed}, {skipped}"))
 }
 
-#[derive(Debug, Error)]
-enum SearchGraphError {
-    #[error(transparent)]
-    Dag(#[from] eden_dag::Error),
+/// Determine the exit code for the overall test run from its results.
+fn get_exit_code(test_results: &TestResults, is_search: bool) -> ExitCode {
+    if test_results.testing_aborted_error.is_some()
+        || test_results.stopped_after_failures.is_some()
+        || test_results.interrupted
+    {
+        return ExitCode(1);
+    }
+    if is_search {
+        return ExitCode(0);
+    }
 
-    #[error(transparent)]
-    Other(#[from] eyre::Error),
+    // Empty commits skipped with `--skip-empty` were deliberately not tested,
+    // so they don't count against the run.
+    let all_passed = test_results.test_outputs.values().all(|test_output| {
+        matches!(
+            test_output.test_status,
+            TestStatus::Passed { .. } | TestStatus::SkippedEmpty
+        )
+    });
+    if all_passed {
+        ExitCode(0)
+    } else {
+        ExitCode(1)
+    }
 }
 
-#[derive(Debug)]
-struct SearchGraph<'a> {
-    dag: &'a Dag,
-    commit_set: CommitSet,
+/// Discard the changes in the fixed tree to any paths which don't match one of
+/// `pathspecs`, keeping the contents from the original commit for those paths
+/// instead.
+#[instrument]
+fn restrict_fix_to_pathspecs(
+    repo: &Repo,
+    original_commit_oid: NonZeroOid,
+    fixed_tree_oid: NonZeroOid,
+    pathspecs: &[glob::Pattern],
+) -> eyre::Result<NonZeroOid> {
+    let original_tree = repo.find_commit_or_fail(original_commit_oid)?.get_tree()?;
+    let fixed_tree = repo.find_tree_or_fail(fixed_tree_oid)?;
+    let changed_paths =
+        get_changed_paths_between_trees(repo, Some(&original_tree), Some(&fixed_tree))?;
+    let mut entries = HashMap::new();
+    for path in changed_paths {
+        if pathspecs.iter().any(|pattern| pattern.matches_path(&path)) {
+            continue;
+        }
+        let original_entry = original_tree
+            .get_path(&path)?
+            .map(|entry| (entry.get_oid(), entry.get_filemode()));
+        entries.insert(path, original_entry);
+    }
+    let restricted_tree_oid = hydrate_tree(repo, Some(&fixed_tree), entries)?;
+    Ok(restricted_tree_oid)
 }
 
-impl<'a> search::SearchGraph for SearchGraph<'a> {
-    type Node = NonZeroOid;
-    type Error = SearchGraphError;
+/// Let the user choose which of the changes produced by the test command to
+/// keep. Returns the fixed tree for each commit, containing only the selected
+/// changes.
+#[instrument]
+fn select_fixes_interactively(
+    effects: &Effects,
+    repo: &Repo,
+    fixed_tree_oids: Vec<(NonZeroOid, NonZeroOid)>,
+) -> eyre::Result<Result<Vec<(NonZeroOid, NonZeroOid)>, ExitCode>> {
+    // All fixes are presented in a single session, so prefix each file path
+    // with the commit that it belongs to, and remember the original path so
+    // that the selected contents can be written back.
+    let mut files = Vec::new();
+    let mut file_sources = Vec::new();
+    {
+        let (effects, _progress) = effects.start_operation(OperationType::CalculateDiff);
+        for (commit_index, (original_commit_oid, fixed_tree_oid)) in
+            fixed_tree_oids.iter().enumerate()
+        {
+            let original_commit = repo.find_commit_or_fail(*original_commit_oid)?;
+            let original_tree = original_commit.get_tree()?;
+            let fixed_tree = repo.find_tree_or_fail(*fixed_tree_oid)?;
+            let diff = repo.get_diff_between_trees(
+                &effects,
+                Some(&original_tree),
+                &fixed_tree,
+                // We manually add context to the git-record output, so suppress the context lines here.
+                0,
+            )?;
+            let short_oid = original_commit.get_short_oid()?;
+            for file in process_diff_for_record(repo, &diff)? {
+                let path = file.path.clone().into_owned();
+                files.push(RecordFile {
+                    path: Cow::Owned(Path::new(&short_oid).join(&path)),
+                    ..file
+                });
+                file_sources.push((commit_index, path));
+            }
+        }
+    }
 
-    #[instrument]
-    fn ancestors(&self, node: Self::Node) -> Result<HashSet<Self::Node>, Self::Error> {
-        let ancestors = self.dag.query_ancestors(CommitSet::from(node))?;
-        let ancestors = ancestors.intersection(&self.commit_set);
-        let ancestors = self.dag.commit_set_to_vec(&ancestors)?;
-        Ok(ancestors.into_iter().collect())
+    let recorder = Recorder::new(
+        RecordState { files },
+        EventSource::Crossterm,
+        KeyBindings::default(),
+    );
+    let RecordState { files } = match recorder.run() {
+        Ok(result) => result,
+        Err(RecordError::Cancelled) => {
+            writeln!(effects.get_output_stream(), "Aborted.")?;
+            return Ok(Err(ExitCode(1)));
+        }
+        Err(RecordError::Bug(message)) => {
+            writeln!(effects.get_output_stream(), "BUG: {message}")?;
+            writeln!(
+                effects.get_output_stream(),
+                "This is a bug. Please report it."
+            )?;
+            return Ok(Err(ExitCode(1)));
+        }
+        Err(
+            err @ (RecordError::SetUpTerminal(_)
+            | RecordError::CleanUpTerminal(_)
+            | RecordError::ReadInput(_)
+            | RecordError::RenderFrame(_)),
+        ) => {
+            writeln!(effects.get_output_stream(), "Error: {err}")?;
+            return Ok(Err(ExitCode(1)));
+        }
+    };
+
+    let mut entries: Vec<HashMap<PathBuf, Option<(NonZeroOid, FileMode)>>> =
+        vec![Default::default(); fixed_tree_oids.len()];
+    for (file, (commit_index, path)) in files.into_iter().zip(file_sources) {
+        let (_original_commit_oid, fixed_tree_oid) = fixed_tree_oids[commit_index];
+        let fixed_tree = repo.find_tree_or_fail(fixed_tree_oid)?;
+        let fixed_entry = fixed_tree.get_path(&path)?;
+        let (selected, _unselected) = file.get_selected_contents();
+        let entry = match selected {
+            SelectedContents::Unchanged => continue,
+            SelectedContents::Binary {
+                old_description: _,
+                new_description: _,
+            } => fixed_entry.map(|entry| (entry.get_oid(), entry.get_filemode())),
+            SelectedContents::Present { contents } => {
+                let file_mode = match fixed_entry {
+                    Some(entry) => entry.get_filemode(),
+                    None => FileMode::Blob,
+                };
+                let oid = repo.create_blob_from_contents(contents.as_bytes())?;
+                Some((oid, file_mode))
+            }
+        };
+        entries[commit_index].insert(path, entry);
     }
 
-    #[instrument]
-    fn descendants(&self, node: Self::Node) -> Result<HashSet<Self::Node>, Self::Error> {
-        let descendants = self.dag.query_descendants(CommitSet::from(node))?;
-        let descendants = descendants.intersection(&self.commit_set);
-        let descendants = self.dag.commit_set_to_vec(&descendants)?;
-        Ok(descendants.into_iter().collect())
+    let mut result = Vec::new();
+    for ((original_commit_oid, _fixed_tree_oid), entries) in
+        fixed_tree_oids.into_iter().zip(entries)
+    {
+        let original_commit = repo.find_commit_or_fail(original_commit_oid)?;
+        let selected_tree_oid = hydrate_tree(repo, Some(&original_commit.get_tree()?), entries)?;
+        result.push((original_commit_oid, selected_tree_oid));
     }
+    Ok(Ok(result))
 }
 
-/// The results of running all tests.
-#[derive(Debug)]
-pub struct TestResults {
-    /// If a search strategy was provided, the calculated bounds on the input
-    /// commit set.
-    pub search_bounds: search::Bounds<NonZeroOid>,
+#[instrument(skip(permissions))]
+fn apply_fixes(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    dag: &mut Dag,
+    repo: &Repo,
+    event_log_db: &EventLogDb,
+    execute_options: &ExecuteRebasePlanOptions,
+    permissions: RebasePlanPermissions,
+    dry_run: bool,
+    fix_selection: &TestFixSelection,
+    command: &str,
+    test_results: &TestResults,
+) -> eyre::Result<ExitCode> {
+    let TestFixSelection {
+        interactive,
+        show_diff,
+        pathspecs,
+    } = fix_selection;
+    let fixed_tree_oids: Vec<(NonZeroOid, NonZeroOid)> = test_results
+        .test_outputs
+        .iter()
+        .filter_map(|(commit_oid, test_output)| match test_output.test_status {
+            TestStatus::Passed {
+                cached: _,
+                fix_info:
+                    FixInfo {
+                        head_commit_oid: _,
+                        snapshot_tree_oid: Some(snapshot_tree_oid),
+                    },
+                interactive: _,
+                attempts: _,
+            } => Some((*commit_oid, snapshot_tree_oid)),
 
-    /// The test output for each commit.
-    pub test_outputs: IndexMap<NonZeroOid, TestOutput>,
+            TestStatus::Passed {
+                cached: _,
+                fix_info:
+                    FixInfo {
+                        head_commit_oid: _,
+                        snapshot_tree_oid: None,
+                    },
+                interactive: _,
+                attempts: _,
+            }
+            | TestStatus::CheckoutFailed
+            | TestStatus::SpawnTestFailed(_)
+            | TestStatus::TerminatedBySignal { .. }
+            | TestStatus::Timeout { .. }
+            | TestStatus::AlreadyInProgress
+            | TestStatus::ReadCacheFailed(_)
+            | TestStatus::Indeterminate { .. }
+            | TestStatus::SkippedDueToAncestor { .. }
+            | TestStatus::SkippedEmpty
+            | TestStatus::MergeParentConflicted
+            | TestStatus::Failed { .. }
+            | TestStatus::PerfThresholdExceeded { .. }
+            | TestStatus::Abort { .. } => None,
+        })
+        .collect();
+    let fixed_tree_oids: Vec<(NonZeroOid, NonZeroOid)> = if pathspecs.is_empty() {
+        fixed_tree_oids
+    } else {
+        fixed_tree_oids
+            .into_iter()
+            .map(|(original_commit_oid, fixed_tree_oid)| -> eyre::Result<_> {
+                let fixed_tree_oid = restrict_fix_to_pathspecs(
+                    repo,
+                    original_commit_oid,
+                    fixed_tree_oid,
+                    pathspecs,
+                )?;
+                Ok((original_commit_oid, fixed_tree_oid))
+            })
+            .try_collect()?
+    };
+    let fixed_tree_oids = if *interactive && !fixed_tree_oids.is_empty() {
+        match select_fixes_interactively(effects, repo, fixed_tree_oids)? {
+            Ok(fixed_tree_oids) => fixed_tree_oids,
+            Err(exit_code) => return Ok(exit_code),
+        }
+    } else {
+        fixed_tree_oids
+    };
 
-    /// If testing was aborted, the corresponding error.
-    pub testing_aborted_error: Option<TestingAbortedError>,
-}
+    #[derive(Debug)]
+    struct Fix {
+        original_commit_oid: NonZeroOid,
+        original_commit_parent_oids: Vec<NonZeroOid>,
+        fixed_commit_oid: NonZeroOid,
+    }
+    let fixes: Vec<Fix> = {
+        let mut fixes = Vec::new();
+        for (original_commit_oid, fixed_tree_oid) in fixed_tree_oids {
+            let original_commit = repo.find_commit_or_fail(original_commit_oid)?;
+            let original_tree_oid = original_commit.get_tree_oid();
+            let commit_message = original_commit.get_message_raw();
+            let commit_message = commit_message.to_str().with_context(|| {
+                eyre::eyre!(
+                    "Could not decode commit message for commit: {:?}",
+                    original_commit_oid
+                )
+            })?;
+            let parents: Vec<Commit> = original_commit
+                .get_parent_oids()
+                .into_iter()
+                .map(|parent_oid| repo.find_commit_or_fail(parent_oid))
+                .try_collect()?;
+            let fixed_tree = repo.find_tree_or_fail(fixed_tree_oid)?;
+            let fixed_commit_oid = repo.create_commit(
+                None,
+                &original_commit.get_author(),
+                &original_commit.get_committer(),
+                commit_message,
+                &fixed_tree,
+                parents.iter().collect(),
+            )?;
+            if original_commit_oid == fixed_commit_oid {
+                continue;
+            }
 
-/// Run tests on the provided set of commits.
-#[instrument]
-pub fn run_tests<'a>(
-    effects: &Effects,
-    git_run_info: &GitRunInfo,
-    dag: &Dag,
-    repo: &Repo,
-    event_log_db: &EventLogDb,
-    event_tx_id: EventTransactionId,
-    revset: &Revset,
-    commits: &[Commit],
-    options: &ResolvedTestOptions,
-) -> eyre::Result<Result<TestResults, ExitCode>> {
-    let ResolvedTestOptions {
-        command,
-        execution_strategy,
-        search_strategy,
-        use_cache: _,      // Used only in `make_test_files`.
-        is_dry_run: _,     // Used only in `apply_fixes`.
-        is_interactive: _, // Used in `test_commit`.
-        num_jobs,
-        verbosity: _,   // Verbosity used by caller to print results.
-        fix_options: _, // Whether to apply fixes is checked by `test_commit`, after the working directory is set up.
-    } = &options;
+            let fix = Fix {
+                original_commit_oid,
+                original_commit_parent_oids: original_commit.get_parent_oids(),
+                fixed_commit_oid,
+            };
+            debug!(
+                ?fix,
+                ?original_tree_oid,
+                ?fixed_tree_oid,
+                "Generated fix to apply"
+            );
+            fixes.push(fix);
+        }
+        fixes
+    };
 
-    let shell_path = match get_sh() {
-        Some(shell_path) => shell_path,
-        None => {
+    if *show_diff {
+        for fix in &fixes {
+            let Fix {
+                original_commit_oid,
+                original_commit_parent_oids: _,
+                fixed_commit_oid,
+            } = fix;
             writeln!(
                 effects.get_output_stream(),
-                "{}",
-                effects.get_glyphs().render(
-                    StyledStringBuilder::new()
-                        .append_styled(
-                            "Error: Could not determine path to shell.",
-                            BaseColor::Red.light()
-                        )
-                        .build()
-                )?
+                "Changes made to {}:",
+                effects
+                    .get_glyphs()
+                    .render(repo.friendly_describe_commit_from_oid(
+                        effects.get_glyphs(),
+                        *original_commit_oid
+                    )?)?
             )?;
-            return Ok(Err(ExitCode(1)));
+            let GitRunResult {
+                exit_code: _,
+                stdout,
+                stderr: _,
+            } = git_run_info.run_silent(
+                repo,
+                None,
+                &[
+                    "diff",
+                    &original_commit_oid.to_string(),
+                    &fixed_commit_oid.to_string(),
+                ],
+                Default::default(),
+            )?;
+            write!(effects.get_output_stream(), "{}", stdout.to_str_lossy())?;
         }
-    };
-
-    if let Some(strategy_value) = execution_strategy.to_possible_value() {
-        writeln!(
-            effects.get_output_stream(),
-            "Using command execution strategy: {}",
-            effects.get_glyphs().render(
-                StyledStringBuilder::new()
-                    .append_styled(strategy_value.get_name(), Effect::Bold)
-                    .build()
-            )?,
-        )?;
     }
 
-    if let Some(strategy_value) = search_strategy.and_then(|opt| opt.to_possible_value()) {
+    // Signatures can't be carried over to the rewritten commits, since they
+    // cover the original commit contents. Make sure that they aren't stripped
+    // silently.
+    let signed_commits: Vec<Commit> = {
+        let mut result = Vec::new();
+        for fix in &fixes {
+            let original_commit = repo.find_commit_or_fail(fix.original_commit_oid)?;
+            if original_commit.is_signed() {
+                result.push(original_commit);
+            }
+        }
+        result
+    };
+    if !signed_commits.is_empty() {
         writeln!(
             effects.get_output_stream(),
-            "Using test search strategy: {}",
-            effects.get_glyphs().render(
-                StyledStringBuilder::new()
-                    .append_styled(strategy_value.get_name(), Effect::Bold)
-                    .build()
-            )?,
+            "Warning: {} signed, but signatures are not preserved when applying fixes:",
+            Pluralize {
+                determiner: None,
+                amount: signed_commits.len(),
+                unit: ("commit was", "commits were"),
+            }
         )?;
-    }
-    let search_strategy = match search_strategy {
-        None => None,
-        Some(TestSearchStrategy::Linear) => Some(search::Strategy::Linear),
-        Some(TestSearchStrategy::Reverse) => Some(search::Strategy::LinearReverse),
-        Some(TestSearchStrategy::Binary) => Some(search::Strategy::Binary),
-    };
-
-    let latest_test_command_path = get_latest_test_command_path(repo);
-    if let Some(parent) = latest_test_command_path.parent() {
-        if let Err(err) = std::fs::create_dir_all(parent) {
-            warn!(
-                ?err,
-                ?latest_test_command_path,
-                "Failed to create containing directory for latest test command"
-            );
+        for commit in &signed_commits {
+            writeln!(
+                effects.get_output_stream(),
+                "{}",
+                effects
+                    .get_glyphs()
+                    .render(commit.friendly_describe(effects.get_glyphs())?)?
+            )?;
+        }
+        if repo
+            .get_readonly_config()?
+            .get_or("commit.gpgsign", false)?
+        {
+            writeln!(
+                effects.get_output_stream(),
+                "The rewritten commits will not be signed, even though commit.gpgsign is set. Sign them again before pushing them."
+            )?;
         }
     }
-    if let Err(err) = std::fs::write(&latest_test_command_path, command) {
-        warn!(
-            ?err,
-            ?latest_test_command_path,
-            "Failed to write latest test command to disk"
-        );
-    }
-
-    let EventLoopOutput {
-        search,
-        test_outputs: test_outputs_unordered,
-        testing_aborted_error,
-    } = {
-        let (effects, progress) =
-            effects.start_operation(OperationType::RunTests(Arc::new(command.clone())));
-        progress.notify_progress(0, commits.len());
-        let commit_jobs = {
-            let mut results = IndexMap::new();
-            for commit in commits {
-                // Create the progress entries in the multiprogress meter without starting them.
-                // They'll be resumed later in the loop below.
-                let commit_description = effects
-                    .get_glyphs()
-                    .render(commit.friendly_describe(effects.get_glyphs())?)?;
-                let operation_type =
-                    OperationType::RunTestOnCommit(Arc::new(commit_description.clone()));
-                let (_effects, progress) = effects.start_operation(operation_type.clone());
-                progress.notify_status(
-                    OperationIcon::InProgress,
-                    format!("Waiting to run on {commit_description}"),
-                );
-                results.insert(
-                    commit.get_oid(),
-                    TestJob {
-                        commit_oid: commit.get_oid(),
-                        operation_type,
-                    },
-                );
-            }
-            results
-        };
 
-        let graph = SearchGraph {
-            dag,
-            commit_set: commits.iter().map(|c| c.get_oid()).collect(),
-        };
-        let search = search::Search::new(graph, commits.iter().map(|c| c.get_oid()));
-
-        let work_queue = WorkQueue::new();
-        let repo_dir = repo.get_path();
-        crossbeam::thread::scope(|scope| -> eyre::Result<_> {
-            let (result_tx, result_rx) = crossbeam::channel::unbounded();
-            let workers: HashMap<WorkerId, crossbeam::thread::ScopedJoinHandle<()>> = {
-                let mut result = HashMap::new();
-                for worker_id in 1..=*num_jobs {
-                    let effects = &effects;
-                    let progress = &progress;
-                    let shell_path = &shell_path;
-                    let work_queue = work_queue.clone();
-                    let result_tx = result_tx.clone();
-                    let setup = move || -> eyre::Result<Repo> {
-                        let repo = Repo::from_dir(repo_dir)?;
-                        Ok(repo)
-                    };
-                    let f = move |job: TestJob, repo: &Repo| -> eyre::Result<TestOutput> {
-                        let TestJob {
-                            commit_oid,
-                            operation_type,
-                        } = job;
-                        let commit = repo.find_commit_or_fail(commit_oid)?;
-                        run_test(
-                            effects,
-                            operation_type,
-                            git_run_info,
-                            shell_path,
-                            repo,
-                            event_tx_id,
-                            options,
-                            worker_id,
-                            &commit,
-                        )
-                    };
-                    result.insert(
-                        worker_id,
-                        scope.spawn(move |_scope| {
-                            worker(progress, worker_id, work_queue, result_tx, setup, f);
-                            debug!("Exiting spawned thread closure");
-                        }),
-                    );
-                }
-                result
-            };
+    dag.sync_from_oids(
+        effects,
+        repo,
+        CommitSet::empty(),
+        fixes
+            .iter()
+            .map(|fix| {
+                let Fix {
+                    original_commit_oid: _,
+                    original_commit_parent_oids: _,
+                    fixed_commit_oid,
+                } = fix;
+                fixed_commit_oid
+            })
+            .copied()
+            .collect(),
+    )?;
 
-            // We rely on `result_rx.recv()` returning `Err` once all the threads have exited
-            // (whether that reason should be panicking or having finished all work). We have to
-            // drop our local reference to ensure that we don't deadlock, since otherwise there
-            // would still be a live receiver for the sender.
-            drop(result_tx);
+    let rebase_plan = {
+        let mut builder = RebasePlanBuilder::new(dag, permissions);
+        for fix in &fixes {
+            let Fix {
+                original_commit_oid,
+                original_commit_parent_oids,
+                fixed_commit_oid,
+            } = fix;
+            builder.replace_commit(*original_commit_oid, *fixed_commit_oid)?;
+            builder.move_subtree(*original_commit_oid, original_commit_parent_oids.clone())?;
+        }
 
-            let test_results = event_loop(
-                commit_jobs,
-                search,
-                search_strategy,
-                *num_jobs,
-                work_queue.clone(),
-                result_rx,
-            );
-            work_queue.close();
-            let test_results = test_results?;
+        let original_oids: CommitSet = fixes
+            .iter()
+            .map(|fix| {
+                let Fix {
+                    original_commit_oid,
+                    original_commit_parent_oids: _,
+                    fixed_commit_oid: _,
+                } = fix;
+                original_commit_oid
+            })
+            .copied()
+            .collect();
+        let descendant_oids = dag.query_descendants(original_oids.clone())?;
+        let descendant_oids = dag
+            .filter_visible_commits(descendant_oids)?
+            .difference(&original_oids);
+        for descendant_oid in dag.commit_set_to_vec(&descendant_oids)? {
+            let descendant_commit = repo.find_commit_or_fail(descendant_oid)?;
+            builder.replace_commit(descendant_oid, descendant_oid)?;
+            builder.move_subtree(descendant_oid, descendant_commit.get_parent_oids())?;
+        }
 
-            if test_results.testing_aborted_error.is_none() && search_strategy.is_none() {
-                debug!("Waiting for workers");
-                progress.notify_status(OperationIcon::InProgress, "Waiting for workers");
-                for (worker_id, worker) in workers {
-                    worker
-                        .join()
-                        .map_err(|_err| eyre::eyre!("Waiting for worker {worker_id} to exit"))?;
-                }
-            }
+        let thread_pool = ThreadPoolBuilder::new().build()?;
+        let repo_pool = RepoResource::new_pool(repo)?;
+        builder.build(effects, &thread_pool, &repo_pool)?
+    };
 
-            debug!("About to return from thread scope");
-            Ok(test_results)
-        })
-        .map_err(|_| eyre::eyre!("Could not spawn workers"))?
-        .wrap_err("Failed waiting on workers")?
+    let rebase_plan = match rebase_plan {
+        Ok(Some(plan)) => plan,
+        Ok(None) => {
+            writeln!(effects.get_output_stream(), "No commits to fix.")?;
+            return Ok(ExitCode(0));
+        }
+        Err(err) => {
+            err.describe(effects, repo, dag)?;
+            return Ok(ExitCode(1));
+        }
     };
-    debug!("Returned from thread scope");
 
-    // The results may be returned in an arbitrary order if they were produced
-    // in parallel, so recover the input order to produce deterministic output.
-    let test_outputs_ordered: IndexMap<NonZeroOid, TestOutput> = {
-        let mut test_outputs_unordered = test_outputs_unordered;
-        let mut test_outputs_ordered = IndexMap::new();
-        for commit_oid in commits.iter().map(|commit| commit.get_oid()) {
-            match test_outputs_unordered.remove(&commit_oid) {
-                Some(result) => {
-                    test_outputs_ordered.insert(commit_oid, result);
-                }
-                None => {
-                    if search_strategy.is_none() && testing_aborted_error.is_none() {
-                        warn!(?commit_oid, "No result was returned for commit");
-                    }
-                }
+    let rewritten_oids = if dry_run {
+        Default::default()
+    } else {
+        match execute_rebase_plan(
+            effects,
+            git_run_info,
+            repo,
+            event_log_db,
+            &rebase_plan,
+            execute_options,
+        )? {
+            ExecuteRebasePlanResult::Succeeded { rewritten_oids } => rewritten_oids,
+            ExecuteRebasePlanResult::DeclinedToMerge { failed_merge_info } => {
+                // The fixed commits themselves apply cleanly, but their
+                // descendants may conflict with the fixes when rebased on top
+                // of them.
+                failed_merge_info.describe(effects, repo, MergeConflictRemediation::Retry)?;
+                return Ok(ExitCode(1));
             }
+            ExecuteRebasePlanResult::Failed { exit_code } => return Ok(exit_code),
         }
-        if !test_outputs_unordered.is_empty() {
-            warn!(
-                ?test_outputs_unordered,
-                ?commits,
-                "There were extra results for commits not appearing in the input list"
-            );
-        }
-        test_outputs_ordered
     };
+    let rewritten_oids = match rewritten_oids {
+        Some(rewritten_oids) => rewritten_oids,
 
-    Ok(Ok(TestResults {
-        search_bounds: match search_strategy {
-            None => Default::default(),
-            Some(search_strategy) => search.search(search_strategy)?.bounds,
-        },
-        test_outputs: test_outputs_ordered,
-        testing_aborted_error,
-    }))
-}
+        // Can happen during a dry-run or an on-disk rebase; just produce our
+        // rewritten commits which haven't been rebased on top of each other
+        // yet.
+        // FIXME: it should be possible to execute the rebase plan but not
+        // commit the branch moves so that we can preview it.
+        None => fixes
+            .iter()
+            .map(|fix| {
+                let Fix {
+                    original_commit_oid,
+                    original_commit_parent_oids: _,
+                    fixed_commit_oid,
+                } = fix;
+                (
+                    *original_commit_oid,
+                    MaybeZeroOid::NonZero(*fixed_commit_oid),
+                )
+            })
+            .collect(),
+    };
 
-struct EventLoopOutput<'a> {
-    search: search::Search<SearchGraph<'a>>,
-    test_outputs: HashMap<NonZeroOid, TestOutput>,
-    testing_aborted_error: Option<TestingAbortedError>,
-}
+    writeln!(
+        effects.get_output_stream(),
+        "Fixed {} with {}:",
+        Pluralize {
+            determiner: None,
+            amount: fixes.len(),
+            unit: ("commit", "commits")
+        },
+        effects.get_glyphs().render(
+            StyledStringBuilder::new()
+                .append_styled(command, Effect::Bold)
+                .build()
+        )?,
+    )?;
+    for fix in fixes {
+        let Fix {
+            original_commit_oid,
+            original_commit_parent_oids: _,
+            fixed_commit_oid,
+        } = fix;
+        let original_commit = repo.find_commit_or_fail(original_commit_oid)?;
+        let fixed_commit_oid = rewritten_oids
+            .get(&original_commit_oid)
+            .copied()
+            .unwrap_or(MaybeZeroOid::NonZero(fixed_commit_oid));
+        match fixed_commit_oid {
+            MaybeZeroOid::NonZero(fixed_commit_oid) => {
+                let fixed_commit = repo.find_commit_or_fail(fixed_commit_oid)?;
+                writeln!(
+                    effects.get_output_stream(),
+                    "{} -> {}",
+                    effects
+                        .get_glyphs()
+                        .render(original_commit.friendly_describe_oid(effects.get_glyphs())?)?,
+                    effects
+                        .get_glyphs()
+                        .render(fixed_commit.friendly_describe(effects.get_glyphs())?)?
+                )?;
+            }
 
-fn event_loop(
-    commit_jobs: IndexMap<NonZeroOid, TestJob>,
-    mut search: search::Search<SearchGraph>,
-    search_strategy: Option<search::Strategy>,
-    num_jobs: usize,
-    work_queue: WorkQueue<TestJob>,
-    result_rx: Receiver<JobResult<TestJob, TestOutput>>,
-) -> eyre::Result<EventLoopOutput> {
-    #[derive(Debug)]
-    enum ScheduledJob {
-        Scheduled(TestJob),
-        Complete(TestOutput),
+            MaybeZeroOid::Zero => {
+                // Shouldn't happen.
+                writeln!(
+                    effects.get_output_stream(),
+                    "(deleted) {}",
+                    effects
+                        .get_glyphs()
+                        .render(original_commit.friendly_describe_oid(effects.get_glyphs())?)?,
+                )?;
+            }
+        }
     }
-    let mut scheduled_jobs: HashMap<NonZeroOid, ScheduledJob> = Default::default();
-    let mut testing_aborted_error = None;
 
-    if search_strategy.is_none() {
-        let jobs_to_schedule = commit_jobs
-            .keys()
-            .map(|commit_oid| commit_jobs[commit_oid].clone())
-            .collect_vec();
-        debug!(
-            ?jobs_to_schedule,
-            "Scheduling all jobs (since no search strategy was specified)"
-        );
-        for job in &jobs_to_schedule {
-            scheduled_jobs.insert(job.commit_oid, ScheduledJob::Scheduled(job.clone()));
-        }
-        work_queue.set(jobs_to_schedule);
+    if dry_run {
+        writeln!(effects.get_output_stream(), "(This was a dry-run, so no commits were rewritten. Re-run without the --dry-run option to apply fixes.)")?;
     }
 
-    loop {
-        if let Some(err) = &testing_aborted_error {
-            debug!(?err, "Testing aborted");
-            break;
+    Ok(ExitCode(0))
+}
+
+#[instrument]
+fn run_test(
+    effects: &Effects,
+    operation_type: OperationType,
+    git_run_info: &GitRunInfo,
+    shell_path: &Path,
+    repo: &Repo,
+    event_tx_id: EventTransactionId,
+    options: &ResolvedTestOptions,
+    worker_id: WorkerId,
+    commit: &Commit,
+) -> eyre::Result<TestOutput> {
+    let ResolvedTestOptions {
+        command: _,       // Used in `test_commit`.
+        shell_command: _, // Used in `test_commit`.
+        execution_strategy,
+        search_strategy: _, // Caller handles which commits to test.
+        is_dry_run: _,      // Used only in `apply_fixes`.
+        cache_mode: _,      // Used only in `make_test_files`.
+        cache_dir: _,       // Used only in `make_test_files`.
+        worktrees_dir,
+        is_interactive: _, // Used in `test_commit`.
+        num_jobs: _,       // Caller handles job management.
+        verbosity: _,
+        fix_options,
+        limits:
+            TestLimits {
+                timeout: _,      // Used in `test_commit`.
+                memory_limit: _, // Used in `test_commit`.
+                lock_wait_timeout,
+                retries: _, // Used in `test_commit`.
+            },
+        scheduling: _, // Caller handles job management.
+        process:
+            TestProcessOptions {
+                extra_args: _,      // Used in `test_commit`.
+                docker_image: _,    // Used in `test_commit`.
+                env_passthrough: _, // Used in `test_commit`.
+                env_file_vars: _,   // Used in `test_commit`.
+                cwd: _,             // Used in `test_commit`.
+                combined_output: _, // Used in `test_commit`.
+                stream: _,          // Used in `test_commit`.
+                keep_worktrees,
+                worktree_count,
+            },
+        hooks: _,   // Run by `run_tests`.
+        outcome: _, // Used in `test_commit`.
+        storage: _, // Used in `test_commit`.
+        report: _,
+        fix_selection: _, // Used only in `apply_fixes`.
+    } = options;
+    let (effects, progress) = effects.start_operation(operation_type);
+    progress.notify_status(
+        OperationIcon::InProgress,
+        format!(
+            "Preparing {}",
+            effects
+                .get_glyphs()
+                .render(commit.friendly_describe(effects.get_glyphs())?)?
+        ),
+    );
+
+    let test_files_result = match lock_wait_timeout {
+        None => make_test_files(commit, options)?,
+        Some(lock_wait_timeout) => {
+            wait_for_test_files(&effects, &progress, commit, options, *lock_wait_timeout)?
         }
+    };
+    let test_output = match test_files_result {
+        TestFilesResult::Cached(test_output) => test_output,
+        TestFilesResult::NotCached(test_files) => {
+            match prepare_working_directory(
+                git_run_info,
+                repo,
+                event_tx_id,
+                commit,
+                *execution_strategy,
+                worktrees_dir,
+                *worktree_count,
+                worker_id,
+            )? {
+                Err(err) => {
+                    info!(?err, "Failed to prepare working directory for testing");
+                    let TestFiles {
+                        temp_dir,
+                        lock_file: _, // Drop lock.
+                        result_path,
+                        result_file: _,
+                        stdout_path,
+                        stdout_file: _,
+                        stderr_path,
+                        stderr_file: _,
+                    } = test_files;
+                    TestOutput {
+                        temp_dir,
+                        result_path,
+                        stdout_path,
+                        stderr_path,
+                        test_status: TestStatus::CheckoutFailed,
+                        duration: None,
+                        // Waiting for a working directory is abandoned if
+                        // the user interrupts testing.
+                        interrupted: is_interrupted(),
+                    }
+                }
+                Ok(PreparedWorkingDirectory {
+                    lock_file: mut working_directory_lock_file,
+                    path,
+                }) => {
+                    progress.notify_status(
+                        OperationIcon::InProgress,
+                        format!(
+                            "Running on {}",
+                            effects
+                                .get_glyphs()
+                                .render(commit.friendly_describe(effects.get_glyphs())?)?
+                        ),
+                    );
 
-        if let Some(search_strategy) = search_strategy {
-            scheduled_jobs = scheduled_jobs
-                .into_iter()
-                .filter_map(|(commit_oid, scheduled_job)| match scheduled_job {
-                    ScheduledJob::Scheduled(_) => None,
-                    scheduled_job @ ScheduledJob::Complete(_) => Some((commit_oid, scheduled_job)),
-                })
-                .collect();
-
-            let solution = search.search(search_strategy)?;
-            let next_to_search = solution
-                .next_to_search
-                .filter(|commit_oid| {
-                    // At this point, `scheduled_jobs` should only contain completed jobs.
-                    match scheduled_jobs.get(commit_oid) {
-                        Some(ScheduledJob::Complete(_)) => false,
-                        Some(ScheduledJob::Scheduled(_)) => {
-                            warn!(?commit_oid, "Left-over scheduled job; this should have already been filtered out.");
-                            true
-                        }
-                        None => true
+                    let result = test_commit(
+                        &effects,
+                        git_run_info,
+                        repo,
+                        event_tx_id,
+                        test_files,
+                        &path,
+                        shell_path,
+                        options,
+                        worker_id,
+                        commit,
+                    )?;
+                    let is_failure = matches!(
+                        result.test_status,
+                        TestStatus::Failed { .. }
+                            | TestStatus::PerfThresholdExceeded { .. }
+                            | TestStatus::TerminatedBySignal { .. }
+                            | TestStatus::Timeout { .. }
+                            | TestStatus::Abort { .. }
+                    );
+                    if *keep_worktrees && is_failure {
+                        keep_test_worktree(
+                            git_run_info,
+                            repo,
+                            event_tx_id,
+                            worktrees_dir,
+                            &path,
+                            commit,
+                        )?;
                     }
-                })
-                .take(num_jobs)
-                .collect_vec();
-            if next_to_search.is_empty() {
-                debug!("Search completed, exiting.");
-                break;
-            }
-            let jobs_to_schedule = next_to_search
-                .into_iter()
-                .map(|commit_oid| commit_jobs[&commit_oid].clone())
-                .collect_vec();
-            debug!(
-                ?search_strategy,
-                ?jobs_to_schedule,
-                "Jobs to schedule for search"
-            );
-            for job in &jobs_to_schedule {
-                if let Some(previous_job) =
-                    scheduled_jobs.insert(job.commit_oid, ScheduledJob::Scheduled(job.clone()))
-                {
-                    warn!(?job, ?previous_job, "Overwriting previously-scheduled job");
+                    working_directory_lock_file
+                        .unlock()
+                        .wrap_err_with(|| format!("Unlocking working directory at {path:?}"))?;
+                    drop(working_directory_lock_file);
+                    result
                 }
             }
-            work_queue.set(jobs_to_schedule);
         }
+    };
 
-        let message = {
-            let jobs_in_progress = scheduled_jobs
-                .values()
-                .filter_map(|scheduled_job| match scheduled_job {
-                    ScheduledJob::Scheduled(job) => Some(job),
-                    ScheduledJob::Complete(_) => None,
-                })
-                .collect_vec();
-            if jobs_in_progress.is_empty() {
-                debug!("No more in-progress jobs to wait on, exiting");
-                break;
-            }
-
-            // If there is work to be done, then block on the next result to
-            // be received from a worker. This is okay because we won't
-            // adjust the work queue until we've received the next result.
-            // (If we wanted to support cancellation or adjusting the number
-            // of running workers then this wouldn't be sufficient.)
-            debug!(?jobs_in_progress, "Event loop waiting for new job result");
-            let result = result_rx.recv();
-            debug!(?result, "Event loop got new job result");
-            result
-        };
-        let (job, test_output) = match message {
-            Err(RecvError) => {
-                debug!("No more job results could be received because result_rx closed");
-                break;
-            }
-
-            Ok(JobResult::Error(worker_id, job, error_message)) => {
-                let TestJob {
-                    commit_oid,
-                    operation_type: _,
-                } = job;
-                eyre::bail!("Worker {worker_id} failed when processing commit {commit_oid}: {error_message}");
-            }
-
-            Ok(JobResult::Done(job, test_output)) => (job, test_output),
-        };
-
-        let TestJob {
-            commit_oid,
-            operation_type: _,
-        } = job;
-        let (maybe_testing_aborted_error, search_status) = match &test_output.test_status {
+    let description = StyledStringBuilder::new()
+        .append(test_output.test_status.describe(
+            effects.get_glyphs(),
+            commit,
+            fix_options.is_some(),
+        )?)
+        .build();
+    progress.notify_status(
+        match test_output.test_status {
             TestStatus::CheckoutFailed
             | TestStatus::SpawnTestFailed(_)
-            | TestStatus::TerminatedBySignal
             | TestStatus::AlreadyInProgress
             | TestStatus::ReadCacheFailed(_)
-            | TestStatus::Indeterminate { .. } => (None, search::Status::Indeterminate),
+            | TestStatus::Indeterminate { .. }
+            | TestStatus::SkippedDueToAncestor { .. }
+            | TestStatus::SkippedEmpty
+            | TestStatus::MergeParentConflicted => OperationIcon::Warning,
 
-            TestStatus::Abort { exit_code } => (
-                Some(TestingAbortedError {
-                    commit_oid,
-                    exit_code: *exit_code,
-                }),
-                search::Status::Indeterminate,
-            ),
+            TestStatus::TerminatedBySignal { .. }
+            | TestStatus::Timeout { .. }
+            | TestStatus::Failed { .. }
+            | TestStatus::PerfThresholdExceeded { .. }
+            | TestStatus::Abort { .. } => OperationIcon::Failure,
 
-            TestStatus::Failed {
-                cached: _,
-                interactive: _,
-                exit_code: _,
-            } => (None, search::Status::Failure),
+            TestStatus::Passed { .. } => OperationIcon::Success,
+        },
+        effects.get_glyphs().render(description)?,
+    );
+    Ok(test_output)
+}
 
-            TestStatus::Passed {
-                cached: _,
-                fix_info: _,
-                interactive: _,
-            } => (None, search::Status::Success),
-        };
-        if search_strategy.is_some() {
-            search.notify(commit_oid, search_status)?;
-        }
-        if scheduled_jobs
-            .insert(commit_oid, ScheduledJob::Complete(test_output))
-            .is_none()
-        {
+#[derive(Debug)]
+struct TestFiles {
+    temp_dir: Option<TempDir>,
+    lock_file: LockFile,
+    result_path: PathBuf,
+    result_file: File,
+    stdout_path: PathBuf,
+    stdout_file: File,
+    stderr_path: PathBuf,
+    stderr_file: File,
+}
+
+#[derive(Debug)]
+enum TestFilesResult {
+    Cached(TestOutput),
+    NotCached(TestFiles),
+}
+
+#[instrument]
+fn make_test_files(
+    commit: &Commit,
+    options: &ResolvedTestOptions,
+) -> eyre::Result<TestFilesResult> {
+    if !options.cache_mode.should_write() {
+        let temp_dir = tempfile::tempdir().context("Creating temporary directory")?;
+        let lock_path = temp_dir.path().join("pid.lock");
+        let mut lock_file = LockFile::open(&lock_path)
+            .wrap_err_with(|| format!("Opening lock file {lock_path:?}"))?;
+        if !lock_file.try_lock_with_pid()? {
             warn!(
-                ?commit_oid,
-                "Received test result for commit that was not scheduled"
+                ?temp_dir,
+                ?lock_file,
+                "Could not acquire lock despite being in a temporary directory"
             );
         }
 
-        if let Some(err) = maybe_testing_aborted_error {
-            testing_aborted_error = Some(err);
-        }
+        let result_path = temp_dir.path().join("result");
+        let stdout_path = temp_dir.path().join("stdout");
+        let stderr_path = temp_dir.path().join("stderr");
+        let result_file = File::create(&result_path)
+            .wrap_err_with(|| format!("Opening result file {result_path:?}"))?;
+        let stdout_file = File::create(&stdout_path)
+            .wrap_err_with(|| format!("Opening stdout file {stdout_path:?}"))?;
+        let stderr_file = File::create(&stderr_path)
+            .wrap_err_with(|| format!("Opening stderr file {stderr_path:?}"))?;
+        return Ok(TestFilesResult::NotCached(TestFiles {
+            temp_dir: Some(temp_dir),
+            lock_file,
+            result_path,
+            result_file,
+            stdout_path,
+            stdout_file,
+            stderr_path,
+            stderr_file,
+        }));
+    }
+
+    let tree_dir = get_test_tree_dir(&options.cache_dir, commit);
+    std::fs::create_dir_all(&tree_dir)
+        .wrap_err_with(|| format!("Creating tree directory {tree_dir:?}"))?;
+
+    let command_dir = tree_dir.join(options.make_command_slug()?);
+    std::fs::create_dir_all(&command_dir)
+        .wrap_err_with(|| format!("Creating command directory {command_dir:?}"))?;
+
+    let result_path = command_dir.join("result");
+    let stdout_path = command_dir.join("stdout");
+    let stderr_path = command_dir.join("stderr");
+    let lock_path = command_dir.join("pid.lock");
+
+    let mut lock_file =
+        LockFile::open(&lock_path).wrap_err_with(|| format!("Opening lock file {lock_path:?}"))?;
+    if !try_lock_reclaiming_stale(&mut lock_file, &lock_path)
+        .wrap_err_with(|| format!("Locking file {lock_path:?}"))?
+    {
+        return Ok(TestFilesResult::Cached(TestOutput {
+            temp_dir: None,
+            result_path,
+            stdout_path,
+            stderr_path,
+            test_status: TestStatus::AlreadyInProgress,
+            duration: None,
+            interrupted: false,
+        }));
     }
 
-    let test_outputs = scheduled_jobs
-        .into_iter()
-        .filter_map(|(commit_oid, scheduled_job)| match scheduled_job {
-            ScheduledJob::Scheduled(_) => None,
-            ScheduledJob::Complete(test_output) => Some((commit_oid, test_output)),
-        })
-        .collect();
-    Ok(EventLoopOutput {
-        search,
-        test_outputs,
-        testing_aborted_error,
-    })
-}
-
-#[instrument]
-fn print_summary(
-    effects: &Effects,
-    dag: &Dag,
-    repo: &Repo,
-    revset: &Revset,
-    command: &str,
-    test_results: &TestResults,
-    is_search: bool,
-    apply_fixes: bool,
-    verbosity: &Verbosity,
-) -> eyre::Result<ExitCode> {
-    let mut num_passed = 0;
-    let mut num_failed = 0;
-    let mut num_skipped = 0;
-    let mut num_cached_results = 0;
-    for (commit_oid, test_output) in &test_results.test_outputs {
-        let commit = repo.find_commit_or_fail(*commit_oid)?;
-        write!(
-            effects.get_output_stream(),
-            "{}",
-            effects.get_glyphs().render(test_output.describe(
-                effects,
-                &commit,
-                apply_fixes,
-                *verbosity,
-            )?)?
-        )?;
-        match test_output.test_status {
-            TestStatus::CheckoutFailed
-            | TestStatus::SpawnTestFailed(_)
-            | TestStatus::AlreadyInProgress
-            | TestStatus::ReadCacheFailed(_)
-            | TestStatus::TerminatedBySignal
-            | TestStatus::Indeterminate { .. } => num_skipped += 1,
+    let cached_contents = if options.cache_mode.should_read() {
+        std::fs::read_to_string(&result_path).ok()
+    } else {
+        None
+    };
+    if let Some(contents) = cached_contents {
+        // If the file exists but was empty, this indicates that a previous
+        // attempt did not complete successfully. However, we successfully took
+        // the lock, so it should be the case that we are the exclusive writers
+        // to the contents of this directory (i.e. the previous attempt is not
+        // still running), so it's safe to proceed and overwrite these files.
+        if !contents.is_empty() {
+            let serialized_result: Result<SerializedTestResult, _> =
+                serde_json::from_str(&contents);
+            let combined_output = match &serialized_result {
+                Ok(serialized_result) => serialized_result.combined_output,
+                Err(_) => false,
+            };
+            // Don't reuse a result which was recorded for a different command
+            // (e.g. one written by an older version with a colliding slug), or
+            // which was written in a different format.
+            let is_stale = match &serialized_result {
+                Ok(serialized_result) => {
+                    serialized_result.command != options.command
+                        || serialized_result.schema_version != TEST_RESULT_SCHEMA_VERSION
+                }
+                Err(_) => false,
+            };
+            let test_status = match serialized_result {
+                Ok(SerializedTestResult {
+                    command: _,
+                    exit_code: _,
+                    head_commit_oid: _,
+                    snapshot_tree_oid: _,
+                    interactive: _,
+                    timeout_seconds: Some(timeout_seconds),
+                    attempts: _,
+                    timestamp: _,
+                    combined_output: _,
+                    duration_ms: _,
+                    skip_reason: _,
+                    schema_version: _,
+                }) => TestStatus::Timeout {
+                    duration: Duration::from_secs(timeout_seconds),
+                },
 
-            TestStatus::Abort { .. } => {
-                num_failed += 1;
-            }
-            TestStatus::Failed {
-                cached,
-                exit_code: _,
-                interactive: _,
-            } => {
-                num_failed += 1;
-                if cached {
-                    num_cached_results += 1;
+                Ok(SerializedTestResult {
+                    command: _,
+                    exit_code,
+                    head_commit_oid: _,
+                    snapshot_tree_oid: _,
+                    interactive: _,
+                    timeout_seconds: None,
+                    attempts: _,
+                    timestamp: _,
+                    combined_output: _,
+                    duration_ms: _,
+                    skip_reason,
+                    schema_version: _,
+                }) if options.outcome.skip_exit_codes.contains(&exit_code) => {
+                    TestStatus::Indeterminate {
+                        exit_code,
+                        reason: skip_reason,
+                    }
                 }
-            }
-            TestStatus::Passed {
-                cached,
-                fix_info: _,
-                interactive: _,
-            } => {
-                num_passed += 1;
-                if cached {
-                    num_cached_results += 1;
+
+                Ok(SerializedTestResult {
+                    command: _,
+                    exit_code,
+                    head_commit_oid,
+                    snapshot_tree_oid,
+                    interactive,
+                    timeout_seconds: None,
+                    attempts,
+                    timestamp: _,
+                    combined_output: _,
+                    duration_ms: _,
+                    skip_reason: _,
+                    schema_version: _,
+                }) if exit_code == TEST_SUCCESS_EXIT_CODE
+                    || options.outcome.success_exit_codes.contains(&exit_code) =>
+                {
+                    TestStatus::Passed {
+                        cached: true,
+                        fix_info: FixInfo {
+                            head_commit_oid: head_commit_oid.map(|SerializedNonZeroOid(oid)| oid),
+                            snapshot_tree_oid: snapshot_tree_oid
+                                .map(|SerializedNonZeroOid(oid)| oid),
+                        },
+
+                        interactive,
+                        attempts: attempts.unwrap_or(1),
+                    }
                 }
-            }
-        }
-    }
 
-    writeln!(
-        effects.get_output_stream(),
-        "Ran command on {}: {}:",
-        Pluralize {
-            determiner: None,
-            amount: test_results.test_outputs.len(),
-            unit: ("commit", "commits")
-        },
-        effects.get_glyphs().render(
-            StyledStringBuilder::new()
-                .append_styled(command, Effect::Bold)
-                .build()
-        )?,
-    )?;
+                Ok(SerializedTestResult {
+                    command: _,
+                    exit_code,
+                    head_commit_oid: _,
+                    snapshot_tree_oid: _,
+                    interactive: _,
+                    timeout_seconds: None,
+                    attempts: _,
+                    timestamp: _,
+                    combined_output: _,
+                    duration_ms: _,
+                    skip_reason,
+                    schema_version: _,
+                }) if exit_code == options.outcome.skip_exit_code => TestStatus::Indeterminate {
+                    exit_code,
+                    reason: skip_reason,
+                },
 
-    let passed = effects.get_glyphs().render(
-        StyledStringBuilder::new()
-            .append_styled(format!("{num_passed} passed"), *STYLE_SUCCESS)
-            .build(),
-    )?;
-    let failed = effects.get_glyphs().render(
-        StyledStringBuilder::new()
-            .append_styled(format!("{num_failed} failed"), *STYLE_FAILURE)
-            .build(),
-    )?;
-    let skipped = effects.get_glyphs().render(
-        StyledStringBuilder::new()
-            .append_styled(format!("{num_skipped} skipped"), *STYLE_SKIPPED)
-            .build(),
-    )?;
-    writeln!(effects.get_output_stream(), "{passed}, {failed}, {skipped}")?;
+                Ok(SerializedTestResult {
+                    command: _,
+                    exit_code,
+                    head_commit_oid: _,
+                    snapshot_tree_oid: _,
+                    interactive: _,
+                    timeout_seconds: None,
+                    attempts: _,
+                    timestamp: _,
+                    combined_output: _,
+                    duration_ms: _,
+                    skip_reason: _,
+                    schema_version: _,
+                }) if exit_code == options.outcome.abort_exit_code => {
+                    TestStatus::Abort { exit_code }
+                }
 
-    if is_search {
-        let success_commits: CommitSet =
-            test_results.search_bounds.success.iter().copied().collect();
-        let success_commits = sorted_commit_set(repo, dag, &success_commits)?;
-        if success_commits.is_empty() {
-            writeln!(
-                effects.get_output_stream(),
-                "There were no passing commits in the provided set."
-            )?;
-        } else {
-            writeln!(
-                effects.get_output_stream(),
-                "Last passing {commits}:",
-                commits = if success_commits.len() == 1 {
-                    "commit"
-                } else {
-                    "commits"
+                Ok(SerializedTestResult {
+                    command: _,
+                    exit_code,
+                    head_commit_oid: _,
+                    snapshot_tree_oid: _,
+                    interactive,
+                    timeout_seconds: None,
+                    attempts,
+                    timestamp: _,
+                    combined_output: _,
+                    duration_ms: _,
+                    skip_reason: _,
+                    schema_version: _,
+                }) => TestStatus::Failed {
+                    cached: true,
+                    exit_code,
+                    interactive,
+                    attempts: attempts.unwrap_or(1),
+                    reason: None,
                 },
-            )?;
-            for commit in success_commits {
-                writeln!(
-                    effects.get_output_stream(),
-                    "{} {}",
-                    effects.get_glyphs().bullet_point,
-                    effects
-                        .get_glyphs()
-                        .render(commit.friendly_describe(effects.get_glyphs())?)?
-                )?;
-            }
-        }
+                Err(err) => TestStatus::ReadCacheFailed(err.to_string()),
+            };
 
-        let failure_commits: CommitSet =
-            test_results.search_bounds.failure.iter().copied().collect();
-        let failure_commits = sorted_commit_set(repo, dag, &failure_commits)?;
-        if failure_commits.is_empty() {
-            writeln!(
-                effects.get_output_stream(),
-                "There were no failing commits in the provided set."
-            )?;
-        } else {
-            writeln!(
-                effects.get_output_stream(),
-                "First failing {commits}:",
-                commits = if failure_commits.len() == 1 {
-                    "commit"
+            // An interactive abort means that the user stopped testing at this
+            // commit, not that they reached a verdict for it, so prompt for it
+            // again when resuming.
+            let is_interactive_abort =
+                options.is_interactive && matches!(test_status, TestStatus::Abort { .. });
+            let should_rerun = is_interactive_abort
+                || (options.scheduling.rerun_failures
+                    && match test_status {
+                        TestStatus::Failed { .. }
+                        | TestStatus::PerfThresholdExceeded { .. }
+                        | TestStatus::Abort { .. }
+                        | TestStatus::Timeout { .. }
+                        | TestStatus::ReadCacheFailed(_) => true,
+                        TestStatus::CheckoutFailed
+                        | TestStatus::SpawnTestFailed(_)
+                        | TestStatus::TerminatedBySignal { .. }
+                        | TestStatus::AlreadyInProgress
+                        | TestStatus::Indeterminate { .. }
+                        | TestStatus::SkippedDueToAncestor { .. }
+                        | TestStatus::SkippedEmpty
+                        | TestStatus::MergeParentConflicted
+                        | TestStatus::Passed { .. } => false,
+                    });
+            if !should_rerun && !is_stale {
+                let stdout_path = get_cached_test_output_path(stdout_path);
+                let stderr_path = get_cached_test_output_path(stderr_path);
+
+                // The threshold isn't part of the cache key, so re-check the
+                // cached measurement against the current threshold.
+                let test_status =
+                    apply_perf_threshold(test_status, &stdout_path, options.outcome.perf_threshold);
+                let stderr_path = if combined_output {
+                    stdout_path.clone()
                 } else {
-                    "commits"
-                },
-            )?;
-            for commit in failure_commits {
-                writeln!(
-                    effects.get_output_stream(),
-                    "{} {}",
-                    effects.get_glyphs().bullet_point,
-                    effects
-                        .get_glyphs()
-                        .render(commit.friendly_describe(effects.get_glyphs())?)?
-                )?;
-            }
-        }
-    }
-
-    if num_cached_results > 0 && get_hint_enabled(repo, Hint::CleanCachedTestResults)? {
-        writeln!(
-            effects.get_output_stream(),
-            "{}: there {}",
-            effects.get_glyphs().render(get_hint_string())?,
-            Pluralize {
-                determiner: Some(("was", "were")),
-                amount: num_cached_results,
-                unit: ("cached test result", "cached test results")
-            }
-        )?;
-        writeln!(
-            effects.get_output_stream(),
-            "{}: to clear these cached results, run: git test clean {}",
-            effects.get_glyphs().render(get_hint_string())?,
-            shell_escape(revset.to_string()),
-        )?;
-        print_hint_suppression_notice(effects, Hint::CleanCachedTestResults)?;
-    }
-
-    if let Some(testing_aborted_error) = &test_results.testing_aborted_error {
-        let TestingAbortedError {
-            commit_oid,
-            exit_code,
-        } = testing_aborted_error;
-        let commit = repo.find_commit_or_fail(*commit_oid)?;
-        writeln!(
-            effects.get_output_stream(),
-            "Aborted running commands with exit code {} at commit: {}",
-            exit_code,
-            effects
-                .get_glyphs()
-                .render(commit.friendly_describe(effects.get_glyphs())?)?
-        )?;
-        return Ok(ExitCode(1));
+                    stderr_path
+                };
+                let test_status = apply_fail_on_output(
+                    test_status,
+                    &stdout_path,
+                    &stderr_path,
+                    options.outcome.fail_on_output.as_ref(),
+                );
+                return Ok(TestFilesResult::Cached(TestOutput {
+                    temp_dir: None,
+                    result_path,
+                    stdout_path,
+                    stderr_path,
+                    test_status,
+                    duration: None,
+                    interrupted: false,
+                }));
+            }
+        }
     }
 
-    if is_search {
-        Ok(ExitCode(0))
-    } else if num_failed > 0 || num_skipped > 0 {
-        Ok(ExitCode(1))
-    } else {
-        Ok(ExitCode(0))
+    // Remove any output from a previous run, so that compressed output isn't
+    // read in place of the new output, and so that output which is shared
+    // with other commits via `branchless.test.dedupeOutput` is unlinked rather
+    // than truncated.
+    for output_path in [
+        stdout_path.clone(),
+        stderr_path.clone(),
+        stdout_path.with_extension(COMPRESSED_OUTPUT_EXTENSION),
+        stderr_path.with_extension(COMPRESSED_OUTPUT_EXTENSION),
+    ] {
+        match std::fs::remove_file(&output_path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).wrap_err_with(|| format!("Removing output file {output_path:?}"))
+            }
+        }
     }
+
+    let result_file = File::create(&result_path)
+        .wrap_err_with(|| format!("Opening result file {result_path:?}"))?;
+    let stdout_file = File::create(&stdout_path)
+        .wrap_err_with(|| format!("Opening stdout file {stdout_path:?}"))?;
+    let stderr_file = File::create(&stderr_path)
+        .wrap_err_with(|| format!("Opening stderr file {stderr_path:?}"))?;
+    Ok(TestFilesResult::NotCached(TestFiles {
+        temp_dir: None,
+        lock_file,
+        result_path,
+        result_file,
+        stdout_path,
+        stdout_file,
+        stderr_path,
+        stderr_file,
+    }))
 }
 
-#[instrument(skip(permissions))]
-fn apply_fixes(
-    effects: &Effects,
+#[derive(Debug)]
+struct PreparedWorkingDirectory {
+    lock_file: LockFile,
+    path: PathBuf,
+}
+
+#[derive(Debug)]
+enum PrepareWorkingDirectoryError {
+    LockFailed(PathBuf),
+    NoWorkingCopy,
+    CheckoutFailed(NonZeroOid),
+    CreateWorktreeFailed(PathBuf),
+}
+
+#[instrument(fields(commit_oid = %commit.get_oid()))]
+fn prepare_working_directory(
     git_run_info: &GitRunInfo,
-    dag: &mut Dag,
     repo: &Repo,
-    event_log_db: &EventLogDb,
-    execute_options: &ExecuteRebasePlanOptions,
-    permissions: RebasePlanPermissions,
-    dry_run: bool,
-    command: &str,
-    test_results: &TestResults,
-) -> eyre::Result<ExitCode> {
-    let fixed_tree_oids: Vec<(NonZeroOid, NonZeroOid)> = test_results
-        .test_outputs
-        .iter()
-        .filter_map(|(commit_oid, test_output)| match test_output.test_status {
-            TestStatus::Passed {
-                cached: _,
-                fix_info:
-                    FixInfo {
-                        head_commit_oid: _,
-                        snapshot_tree_oid: Some(snapshot_tree_oid),
-                    },
-                interactive: _,
-            } => Some((*commit_oid, snapshot_tree_oid)),
+    event_tx_id: EventTransactionId,
+    commit: &Commit,
+    strategy: TestExecutionStrategy,
+    worktrees_dir: &Path,
+    worktree_count: Option<usize>,
+    worker_id: WorkerId,
+) -> eyre::Result<Result<PreparedWorkingDirectory, PrepareWorkingDirectoryError>> {
+    let test_lock_dir_path = get_test_locks_dir(repo);
+    std::fs::create_dir_all(&test_lock_dir_path)
+        .wrap_err_with(|| format!("Creating test lock dir path: {test_lock_dir_path:?}"))?;
 
-            TestStatus::Passed {
-                cached: _,
-                fix_info:
-                    FixInfo {
-                        head_commit_oid: _,
-                        snapshot_tree_oid: None,
-                    },
-                interactive: _,
+    let (worker_id, lock_file) = match (strategy, worktree_count) {
+        (TestExecutionStrategy::Worktree, Some(worktree_count)) => {
+            match lock_pooled_worktree(repo, worker_id, worktree_count)? {
+                Ok(result) => result,
+                Err(err) => return Ok(Err(err)),
             }
-            | TestStatus::CheckoutFailed
-            | TestStatus::SpawnTestFailed(_)
-            | TestStatus::TerminatedBySignal
-            | TestStatus::AlreadyInProgress
-            | TestStatus::ReadCacheFailed(_)
-            | TestStatus::Indeterminate { .. }
-            | TestStatus::Failed { .. }
-            | TestStatus::Abort { .. } => None,
-        })
-        .collect();
-
-    #[derive(Debug)]
-    struct Fix {
-        original_commit_oid: NonZeroOid,
-        original_commit_parent_oids: Vec<NonZeroOid>,
-        fixed_commit_oid: NonZeroOid,
-    }
-    let fixes: Vec<Fix> = {
-        let mut fixes = Vec::new();
-        for (original_commit_oid, fixed_tree_oid) in fixed_tree_oids {
-            let original_commit = repo.find_commit_or_fail(original_commit_oid)?;
-            let original_tree_oid = original_commit.get_tree_oid();
-            let commit_message = original_commit.get_message_raw();
-            let commit_message = commit_message.to_str().with_context(|| {
-                eyre::eyre!(
-                    "Could not decode commit message for commit: {:?}",
-                    original_commit_oid
-                )
-            })?;
-            let parents: Vec<Commit> = original_commit
-                .get_parent_oids()
-                .into_iter()
-                .map(|parent_oid| repo.find_commit_or_fail(parent_oid))
-                .try_collect()?;
-            let fixed_tree = repo.find_tree_or_fail(fixed_tree_oid)?;
-            let fixed_commit_oid = repo.create_commit(
-                None,
-                &original_commit.get_author(),
-                &original_commit.get_committer(),
-                commit_message,
-                &fixed_tree,
-                parents.iter().collect(),
-            )?;
-            if original_commit_oid == fixed_commit_oid {
-                continue;
+        }
+        _ => {
+            let lock_path = get_working_directory_lock_path(repo, strategy, worker_id);
+            let mut lock_file = LockFile::open(&lock_path)
+                .wrap_err_with(|| format!("Opening working copy lock at {lock_path:?}"))?;
+            if !try_lock_reclaiming_stale(&mut lock_file, &lock_path)
+                .wrap_err_with(|| format!("Locking working copy with {lock_path:?}"))?
+            {
+                return Ok(Err(PrepareWorkingDirectoryError::LockFailed(lock_path)));
             }
-
-            let fix = Fix {
-                original_commit_oid,
-                original_commit_parent_oids: original_commit.get_parent_oids(),
-                fixed_commit_oid,
-            };
-            debug!(
-                ?fix,
-                ?original_tree_oid,
-                ?fixed_tree_oid,
-                "Generated fix to apply"
-            );
-            fixes.push(fix);
+            (worker_id, lock_file)
         }
-        fixes
     };
 
-    dag.sync_from_oids(
-        effects,
-        repo,
-        CommitSet::empty(),
-        fixes
-            .iter()
-            .map(|fix| {
-                let Fix {
-                    original_commit_oid: _,
-                    original_commit_parent_oids: _,
-                    fixed_commit_oid,
-                } = fix;
-                fixed_commit_oid
-            })
-            .copied()
-            .collect(),
-    )?;
+    match strategy {
+        TestExecutionStrategy::WorkingCopy => {
+            let working_copy_path = match repo.get_working_copy_path() {
+                None => return Ok(Err(PrepareWorkingDirectoryError::NoWorkingCopy)),
+                Some(working_copy_path) => working_copy_path.to_owned(),
+            };
 
-    let rebase_plan = {
-        let mut builder = RebasePlanBuilder::new(dag, permissions);
-        for fix in &fixes {
-            let Fix {
-                original_commit_oid,
-                original_commit_parent_oids,
-                fixed_commit_oid,
-            } = fix;
-            builder.replace_commit(*original_commit_oid, *fixed_commit_oid)?;
-            builder.move_subtree(*original_commit_oid, original_commit_parent_oids.clone())?;
+            let GitRunResult { exit_code, stdout: _, stderr: _ } =
+                // Don't show the `git reset` operation among the progress bars,
+                // as we only want to see the testing status.
+                git_run_info.run_silent(
+                    repo,
+                    Some(event_tx_id),
+                    &["reset", "--hard", &commit.get_oid().to_string()],
+                    Default::default()
+                ).context("Checking out commit to prepare working directory")?;
+            if exit_code.is_success() {
+                Ok(Ok(PreparedWorkingDirectory {
+                    lock_file,
+                    path: working_copy_path,
+                }))
+            } else {
+                Ok(Err(PrepareWorkingDirectoryError::CheckoutFailed(
+                    commit.get_oid(),
+                )))
+            }
         }
 
-        let original_oids: CommitSet = fixes
-            .iter()
-            .map(|fix| {
-                let Fix {
-                    original_commit_oid,
-                    original_commit_parent_oids: _,
-                    fixed_commit_oid: _,
-                } = fix;
-                original_commit_oid
-            })
-            .copied()
-            .collect();
-        let descendant_oids = dag.query_descendants(original_oids.clone())?;
-        let descendant_oids = dag
-            .filter_visible_commits(descendant_oids)?
-            .difference(&original_oids);
-        for descendant_oid in dag.commit_set_to_vec(&descendant_oids)? {
-            let descendant_commit = repo.find_commit_or_fail(descendant_oid)?;
-            builder.replace_commit(descendant_oid, descendant_oid)?;
-            builder.move_subtree(descendant_oid, descendant_commit.get_parent_oids())?;
+        TestExecutionStrategy::Worktree => {
+            std::fs::create_dir_all(worktrees_dir)
+                .wrap_err_with(|| format!("Creating worktree parent dir at {worktrees_dir:?}"))?;
+
+            let worktree_dir = get_test_worktree_dir(worktrees_dir, worker_id);
+            let worktree_dir_str = match worktree_dir.to_str() {
+                Some(worktree_dir) => worktree_dir,
+                None => {
+                    return Ok(Err(PrepareWorkingDirectoryError::CreateWorktreeFailed(
+                        worktree_dir,
+                    )));
+                }
+            };
+
+            if !worktree_dir.exists() {
+                let GitRunResult {
+                    exit_code,
+                    stdout: _,
+                    stderr: _,
+                } = git_run_info.run_silent(
+                    repo,
+                    Some(event_tx_id),
+                    &["worktree", "add", worktree_dir_str, "--force", "--detach"],
+                    Default::default(),
+                )?;
+                if !exit_code.is_success() {
+                    return Ok(Err(PrepareWorkingDirectoryError::CreateWorktreeFailed(
+                        worktree_dir,
+                    )));
+                }
+            }
+
+            let GitRunResult {
+                exit_code,
+                stdout: _,
+                stderr: _,
+            } = git_run_info.run_silent(
+                repo,
+                Some(event_tx_id),
+                &[
+                    "-C",
+                    worktree_dir_str,
+                    "checkout",
+                    "--force",
+                    &commit.get_oid().to_string(),
+                ],
+                Default::default(),
+            )?;
+            if !exit_code.is_success() {
+                return Ok(Err(PrepareWorkingDirectoryError::CheckoutFailed(
+                    commit.get_oid(),
+                )));
+            }
+            Ok(Ok(PreparedWorkingDirectory {
+                lock_file,
+                path: worktree_dir,
+            }))
         }
 
-        let thread_pool = ThreadPoolBuilder::new().build()?;
-        let repo_pool = RepoResource::new_pool(repo)?;
-        builder.build(effects, &thread_pool, &repo_pool)?
-    };
-
-    let rebase_plan = match rebase_plan {
-        Ok(Some(plan)) => plan,
-        Ok(None) => {
-            writeln!(effects.get_output_stream(), "No commits to fix.")?;
-            return Ok(ExitCode(0));
-        }
-        Err(err) => {
-            err.describe(effects, repo, dag)?;
-            return Ok(ExitCode(1));
-        }
-    };
+        TestExecutionStrategy::Docker => {
+            // Start from an empty directory each time, so that no state is
+            // carried over from testing a previous commit.
+            let docker_dir = get_test_docker_dir(repo, worker_id);
+            if docker_dir.exists() {
+                std::fs::remove_dir_all(&docker_dir)
+                    .wrap_err_with(|| format!("Removing Docker test dir at {docker_dir:?}"))?;
+            }
+            std::fs::create_dir_all(&docker_dir)
+                .wrap_err_with(|| format!("Creating Docker test dir at {docker_dir:?}"))?;
 
-    let rewritten_oids = if dry_run {
-        Default::default()
-    } else {
-        match execute_rebase_plan(
-            effects,
-            git_run_info,
-            repo,
-            event_log_db,
-            &rebase_plan,
-            execute_options,
-        )? {
-            ExecuteRebasePlanResult::Succeeded { rewritten_oids } => rewritten_oids,
-            ExecuteRebasePlanResult::DeclinedToMerge { failed_merge_info } => {
-                writeln!(effects.get_output_stream(), "BUG: encountered merge conflicts during git test fix, but we should not be applying any patches: {failed_merge_info:?}")?;
-                return Ok(ExitCode(1));
+            let GitRunResult {
+                exit_code,
+                stdout: archive,
+                stderr: _,
+            } = git_run_info.run_silent(
+                repo,
+                Some(event_tx_id),
+                &["archive", "--format=tar", &commit.get_oid().to_string()],
+                Default::default(),
+            )?;
+            if !exit_code.is_success() || !extract_tar_archive(&archive, &docker_dir)? {
+                return Ok(Err(PrepareWorkingDirectoryError::CheckoutFailed(
+                    commit.get_oid(),
+                )));
             }
-            ExecuteRebasePlanResult::Failed { exit_code } => return Ok(exit_code),
+            Ok(Ok(PreparedWorkingDirectory {
+                lock_file,
+                path: docker_dir,
+            }))
         }
-    };
-    let rewritten_oids = match rewritten_oids {
-        Some(rewritten_oids) => rewritten_oids,
-
-        // Can happen during a dry-run; just produce our rewritten commits which
-        // haven't been rebased on top of each other yet.
-        // FIXME: it should be possible to execute the rebase plan but not
-        // commit the branch moves so that we can preview it.
-        None => fixes
-            .iter()
-            .map(|fix| {
-                let Fix {
-                    original_commit_oid,
-                    original_commit_parent_oids: _,
-                    fixed_commit_oid,
-                } = fix;
-                (
-                    *original_commit_oid,
-                    MaybeZeroOid::NonZero(*fixed_commit_oid),
-                )
-            })
-            .collect(),
-    };
 
-    writeln!(
-        effects.get_output_stream(),
-        "Fixed {} with {}:",
-        Pluralize {
-            determiner: None,
-            amount: fixes.len(),
-            unit: ("commit", "commits")
-        },
-        effects.get_glyphs().render(
-            StyledStringBuilder::new()
-                .append_styled(command, Effect::Bold)
-                .build()
-        )?,
-    )?;
-    for fix in fixes {
-        let Fix {
-            original_commit_oid,
-            original_commit_parent_oids: _,
-            fixed_commit_oid,
-        } = fix;
-        let original_commit = repo.find_commit_or_fail(original_commit_oid)?;
-        let fixed_commit_oid = rewritten_oids
-            .get(&original_commit_oid)
-            .copied()
-            .unwrap_or(MaybeZeroOid::NonZero(fixed_commit_oid));
-        match fixed_commit_oid {
-            MaybeZeroOid::NonZero(fixed_commit_oid) => {
-                let fixed_commit = repo.find_commit_or_fail(fixed_commit_oid)?;
-                writeln!(
-                    effects.get_output_stream(),
-                    "{} -> {}",
-                    effects
-                        .get_glyphs()
-                        .render(original_commit.friendly_describe_oid(effects.get_glyphs())?)?,
-                    effects
-                        .get_glyphs()
-                        .render(fixed_commit.friendly_describe(effects.get_glyphs())?)?
-                )?;
+        TestExecutionStrategy::Tmpfs => {
+            let tmpfs_dir = get_test_tmpfs_worker_dir(repo, worker_id)?;
+            if tmpfs_dir.exists() {
+                std::fs::remove_dir_all(&tmpfs_dir)
+                    .wrap_err_with(|| format!("Removing tmpfs test dir at {tmpfs_dir:?}"))?;
             }
+            std::fs::create_dir_all(&tmpfs_dir)
+                .wrap_err_with(|| format!("Creating tmpfs test dir at {tmpfs_dir:?}"))?;
+            let tmpfs_dir_str = match tmpfs_dir.to_str() {
+                Some(tmpfs_dir) => tmpfs_dir,
+                None => {
+                    return Ok(Err(PrepareWorkingDirectoryError::CreateWorktreeFailed(
+                        tmpfs_dir,
+                    )));
+                }
+            };
 
-            MaybeZeroOid::Zero => {
-                // Shouldn't happen.
-                writeln!(
-                    effects.get_output_stream(),
-                    "(deleted) {}",
-                    effects
-                        .get_glyphs()
-                        .render(original_commit.friendly_describe_oid(effects.get_glyphs())?)?,
+            // Use a separate index so that the main repository's index isn't
+            // disturbed.
+            let mut git_run_info = git_run_info.clone();
+            git_run_info.env.insert(
+                "GIT_INDEX_FILE".into(),
+                get_test_tmpfs_index_path(&tmpfs_dir).into(),
+            );
+            for args in [
+                vec!["read-tree".to_string(), commit.get_tree_oid().to_string()],
+                vec![
+                    "checkout-index".to_string(),
+                    "--all".to_string(),
+                    "--force".to_string(),
+                    format!("--prefix={tmpfs_dir_str}/"),
+                ],
+            ] {
+                let GitRunResult {
+                    exit_code,
+                    stdout: _,
+                    stderr: _,
+                } = git_run_info.run_silent(
+                    repo,
+                    Some(event_tx_id),
+                    args.as_slice(),
+                    GitRunOpts {
+                        treat_git_failure_as_error: false,
+                        ..Default::default()
+                    },
                 )?;
+                if !exit_code.is_success() {
+                    return Ok(Err(PrepareWorkingDirectoryError::CheckoutFailed(
+                        commit.get_oid(),
+                    )));
+                }
             }
+            Ok(Ok(PreparedWorkingDirectory {
+                lock_file,
+                path: tmpfs_dir,
+            }))
         }
     }
-
-    if dry_run {
-        writeln!(effects.get_output_stream(), "(This was a dry-run, so no commits were rewritten. Re-run without the --dry-run option to apply fixes.)")?;
-    }
-
-    Ok(ExitCode(0))
 }
 
-#[instrument]
-fn run_test(
-    effects: &Effects,
-    operation_type: OperationType,
-    git_run_info: &GitRunInfo,
-    shell_path: &Path,
+/// How often to check for a free worktree when the jobs are sharing a pool of
+/// worktrees (with `--worktree-count`).
+const WORKTREE_POOL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Lock one of the `worktree_count` worktrees which are shared between the
+/// jobs, waiting until one is free. The worktree with the same ID as the worker
+/// is tried first, so that each worker tends to reuse the same worktree. Returns
+/// the ID of the locked worktree, which is used in place of the worker ID to
+/// locate it.
+fn lock_pooled_worktree(
     repo: &Repo,
-    event_tx_id: EventTransactionId,
-    options: &ResolvedTestOptions,
     worker_id: WorkerId,
-    commit: &Commit,
-) -> eyre::Result<TestOutput> {
-    let ResolvedTestOptions {
-        command: _, // Used in `test_commit`.
-        execution_strategy,
-        search_strategy: _, // Caller handles which commits to test.
-        use_cache: _,       // Used only in `make_test_files`.
-        is_dry_run: _,      // Used only in `apply_fixes`.
-        is_interactive: _,  // Used in `test_commit`.
-        num_jobs: _,        // Caller handles job management.
-        verbosity: _,
-        fix_options,
-    } = options;
-    let (effects, progress) = effects.start_operation(operation_type);
-    progress.notify_status(
-        OperationIcon::InProgress,
-        format!(
-            "Preparing {}",
-            effects
-                .get_glyphs()
-                .render(commit.friendly_describe(effects.get_glyphs())?)?
-        ),
-    );
+    worktree_count: usize,
+) -> eyre::Result<Result<(WorkerId, LockFile), PrepareWorkingDirectoryError>> {
+    let first_worktree_index = (worker_id - 1) % worktree_count;
+    loop {
+        for offset in 0..worktree_count {
+            let worktree_id = (first_worktree_index + offset) % worktree_count + 1;
+            let lock_path =
+                get_working_directory_lock_path(repo, TestExecutionStrategy::Worktree, worktree_id);
+            let mut lock_file = LockFile::open(&lock_path)
+                .wrap_err_with(|| format!("Opening working copy lock at {lock_path:?}"))?;
+            if try_lock_reclaiming_stale(&mut lock_file, &lock_path)
+                .wrap_err_with(|| format!("Locking working copy with {lock_path:?}"))?
+            {
+                return Ok(Ok((worktree_id, lock_file)));
+            }
+        }
 
-    let test_output = match make_test_files(repo, commit, options)? {
-        TestFilesResult::Cached(test_output) => test_output,
-        TestFilesResult::NotCached(test_files) => {
-            match prepare_working_directory(
-                git_run_info,
-                repo,
-                event_tx_id,
-                commit,
-                *execution_strategy,
-                worker_id,
-            )? {
-                Err(err) => {
-                    info!(?err, "Failed to prepare working directory for testing");
-                    let TestFiles {
-                        temp_dir,
-                        lock_file: _, // Drop lock.
-                        result_path,
-                        result_file: _,
-                        stdout_path,
-                        stdout_file: _,
-                        stderr_path,
-                        stderr_file: _,
-                    } = test_files;
-                    TestOutput {
-                        temp_dir,
-                        result_path,
-                        stdout_path,
-                        stderr_path,
-                        test_status: TestStatus::CheckoutFailed,
-                    }
-                }
-                Ok(PreparedWorkingDirectory {
-                    lock_file: mut working_directory_lock_file,
-                    path,
-                }) => {
-                    progress.notify_status(
-                        OperationIcon::InProgress,
-                        format!(
-                            "Running on {}",
-                            effects
-                                .get_glyphs()
-                                .render(commit.friendly_describe(effects.get_glyphs())?)?
-                        ),
-                    );
+        if is_interrupted() {
+            let lock_path =
+                get_working_directory_lock_path(repo, TestExecutionStrategy::Worktree, worker_id);
+            return Ok(Err(PrepareWorkingDirectoryError::LockFailed(lock_path)));
+        }
+        std::thread::sleep(WORKTREE_POOL_POLL_INTERVAL);
+    }
+}
 
-                    let result = test_commit(
-                        &effects,
-                        git_run_info,
-                        repo,
-                        event_tx_id,
-                        test_files,
-                        &path,
-                        shell_path,
-                        options,
-                        commit,
-                    )?;
-                    working_directory_lock_file
-                        .unlock()
-                        .wrap_err_with(|| format!("Unlocking working directory at {path:?}"))?;
-                    drop(working_directory_lock_file);
-                    result
-                }
-            }
+/// Get the path to the lock file which is held while the given worker is using
+/// its working directory for the given execution strategy.
+fn get_working_directory_lock_path(
+    repo: &Repo,
+    strategy: TestExecutionStrategy,
+    worker_id: WorkerId,
+) -> PathBuf {
+    let lock_file_name = match strategy {
+        TestExecutionStrategy::WorkingCopy => "working-copy.lock".to_string(),
+        TestExecutionStrategy::Worktree => {
+            format!("worktree-{worker_id}.lock")
+        }
+        TestExecutionStrategy::Docker => {
+            format!("docker-{worker_id}.lock")
+        }
+        TestExecutionStrategy::Tmpfs => {
+            format!("tmpfs-{worker_id}.lock")
         }
     };
+    get_test_locks_dir(repo).join(lock_file_name)
+}
 
-    let description = StyledStringBuilder::new()
-        .append(test_output.test_status.describe(
-            effects.get_glyphs(),
-            commit,
-            fix_options.is_some(),
-        )?)
-        .build();
-    progress.notify_status(
-        match test_output.test_status {
-            TestStatus::CheckoutFailed
-            | TestStatus::SpawnTestFailed(_)
-            | TestStatus::AlreadyInProgress
-            | TestStatus::ReadCacheFailed(_)
-            | TestStatus::Indeterminate { .. } => OperationIcon::Warning,
-
-            TestStatus::TerminatedBySignal
-            | TestStatus::Failed { .. }
-            | TestStatus::Abort { .. } => OperationIcon::Failure,
-
-            TestStatus::Passed { .. } => OperationIcon::Success,
-        },
-        effects.get_glyphs().render(description)?,
-    );
-    Ok(test_output)
+/// Extract the given tar archive into `dir`. Returns `false` if `tar` could not
+/// extract it.
+fn extract_tar_archive(archive: &[u8], dir: &Path) -> eyre::Result<bool> {
+    let mut child = Command::new("tar")
+        .arg("-x")
+        .arg("-C")
+        .arg(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .wrap_err("Spawning tar to extract commit contents")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(archive)
+            .wrap_err("Writing commit contents to tar")?;
+    }
+    let exit_status = child.wait().wrap_err("Waiting for tar to exit")?;
+    Ok(exit_status.success())
 }
 
-#[derive(Debug)]
-struct TestFiles {
-    temp_dir: Option<TempDir>,
-    lock_file: LockFile,
-    result_path: PathBuf,
-    result_file: File,
-    stdout_path: PathBuf,
-    stdout_file: File,
-    stderr_path: PathBuf,
-    stderr_file: File,
+/// Get the path to the worktree used by the given worker when using the
+/// worktree execution strategy.
+fn get_test_worktree_dir(worktrees_dir: &Path, worker_id: WorkerId) -> PathBuf {
+    worktrees_dir.join(format!("testing-worktree-{worker_id}"))
 }
 
-#[derive(Debug)]
-enum TestFilesResult {
-    Cached(TestOutput),
-    NotCached(TestFiles),
+/// Get the path to the worktree which was kept for inspection after the test
+/// command failed on the given commit.
+fn get_kept_test_worktree_dir(worktrees_dir: &Path, commit_oid: NonZeroOid) -> PathBuf {
+    worktrees_dir.join(format!("kept-worktree-{commit_oid}"))
 }
 
+/// Move the worker's worktree out of the way so that it's kept for inspection,
+/// rather than being reused to test the next commit. The worker creates a fresh
+/// worktree the next time that it needs one.
 #[instrument]
-fn make_test_files(
+fn keep_test_worktree(
+    git_run_info: &GitRunInfo,
     repo: &Repo,
+    event_tx_id: EventTransactionId,
+    worktrees_dir: &Path,
+    worktree_dir: &Path,
     commit: &Commit,
-    options: &ResolvedTestOptions,
-) -> eyre::Result<TestFilesResult> {
-    if !options.use_cache {
-        let temp_dir = tempfile::tempdir().context("Creating temporary directory")?;
-        let lock_path = temp_dir.path().join("pid.lock");
-        let mut lock_file = LockFile::open(&lock_path)
-            .wrap_err_with(|| format!("Opening lock file {lock_path:?}"))?;
-        if !lock_file.try_lock_with_pid()? {
-            warn!(
-                ?temp_dir,
-                ?lock_file,
-                "Could not acquire lock despite being in a temporary directory"
-            );
-        }
-
-        let result_path = temp_dir.path().join("result");
-        let stdout_path = temp_dir.path().join("stdout");
-        let stderr_path = temp_dir.path().join("stderr");
-        let result_file = File::create(&result_path)
-            .wrap_err_with(|| format!("Opening result file {result_path:?}"))?;
-        let stdout_file = File::create(&stdout_path)
-            .wrap_err_with(|| format!("Opening stdout file {stdout_path:?}"))?;
-        let stderr_file = File::create(&stderr_path)
-            .wrap_err_with(|| format!("Opening stderr file {stderr_path:?}"))?;
-        return Ok(TestFilesResult::NotCached(TestFiles {
-            temp_dir: Some(temp_dir),
-            lock_file,
-            result_path,
-            result_file,
-            stdout_path,
-            stdout_file,
-            stderr_path,
-            stderr_file,
-        }));
+) -> eyre::Result<()> {
+    let kept_worktree_dir = get_kept_test_worktree_dir(worktrees_dir, commit.get_oid());
+    if kept_worktree_dir.exists() {
+        // Replace the worktree kept by a previous run for the same commit.
+        std::fs::remove_dir_all(&kept_worktree_dir)
+            .wrap_err_with(|| format!("Removing kept worktree: {kept_worktree_dir:?}"))?;
+        git_run_info
+            .run_silent(
+                repo,
+                Some(event_tx_id),
+                &["worktree", "prune"],
+                Default::default(),
+            )
+            .wrap_err("Pruning worktrees")?;
     }
 
-    let tree_dir = get_test_tree_dir(repo, commit);
-    std::fs::create_dir_all(&tree_dir)
-        .wrap_err_with(|| format!("Creating tree directory {tree_dir:?}"))?;
+    let worktree_dir = worktree_dir.to_string_lossy().into_owned();
+    let kept_worktree_dir = kept_worktree_dir.to_string_lossy().into_owned();
+    git_run_info
+        .run_silent(
+            repo,
+            Some(event_tx_id),
+            &[
+                "worktree",
+                "move",
+                worktree_dir.as_str(),
+                kept_worktree_dir.as_str(),
+            ],
+            Default::default(),
+        )
+        .wrap_err_with(|| format!("Keeping worktree at {kept_worktree_dir:?}"))?;
+    Ok(())
+}
 
-    let command_dir = tree_dir.join(options.make_command_slug());
-    std::fs::create_dir_all(&command_dir)
-        .wrap_err_with(|| format!("Creating command directory {command_dir:?}"))?;
+/// Get the path to the directory which is mounted into the container used by
+/// the given worker when using the Docker execution strategy.
+fn get_test_docker_dir(repo: &Repo, worker_id: WorkerId) -> PathBuf {
+    get_test_worktrees_dir(repo).join(format!("testing-docker-{worker_id}"))
+}
 
-    let result_path = command_dir.join("result");
-    let stdout_path = command_dir.join("stdout");
-    let stderr_path = command_dir.join("stderr");
-    let lock_path = command_dir.join("pid.lock");
+/// Get the path to the directory used by the given worker when using the tmpfs
+/// execution strategy. The directory name includes the process ID, since the
+/// tmpfs directory may be shared between repositories.
+fn get_test_tmpfs_worker_dir(repo: &Repo, worker_id: WorkerId) -> eyre::Result<PathBuf> {
+    let tmpfs_dir = get_test_tmpfs_dir(repo)?;
+    Ok(tmpfs_dir.join(format!(
+        "git-branchless-test-{}-{worker_id}",
+        std::process::id()
+    )))
+}
 
-    let mut lock_file =
-        LockFile::open(&lock_path).wrap_err_with(|| format!("Opening lock file {lock_path:?}"))?;
-    if !lock_file
-        .try_lock_with_pid()
-        .wrap_err_with(|| format!("Locking file {lock_path:?}"))?
-    {
-        return Ok(TestFilesResult::Cached(TestOutput {
-            temp_dir: None,
-            result_path,
-            stdout_path,
-            stderr_path,
-            test_status: TestStatus::AlreadyInProgress,
-        }));
+/// Get the path to the index file used to populate the given tmpfs test
+/// directory.
+fn get_test_tmpfs_index_path(tmpfs_dir: &Path) -> PathBuf {
+    tmpfs_dir.with_extension("index")
+}
+
+/// Remove the tmpfs directory used by the given worker once it's done, so that
+/// it doesn't continue to take up memory. Failures are logged, but otherwise
+/// ignored.
+#[instrument]
+fn remove_test_tmpfs_worker_dir(repo: &Repo, worker_id: WorkerId) {
+    let tmpfs_dir = match get_test_tmpfs_worker_dir(repo, worker_id) {
+        Ok(tmpfs_dir) => tmpfs_dir,
+        Err(err) => {
+            warn!(?err, "Could not determine tmpfs directory to remove");
+            return;
+        }
+    };
+    if let Err(err) = std::fs::remove_dir_all(&tmpfs_dir) {
+        warn!(?err, ?tmpfs_dir, "Could not remove tmpfs test directory");
     }
+    let index_path = get_test_tmpfs_index_path(&tmpfs_dir);
+    if let Err(err) = std::fs::remove_file(&index_path) {
+        warn!(?err, ?index_path, "Could not remove tmpfs test index");
+    }
+}
 
-    if let Ok(contents) = std::fs::read_to_string(&result_path) {
-        // If the file exists but was empty, this indicates that a previous
-        // attempt did not complete successfully. However, we successfully took
-        // the lock, so it should be the case that we are the exclusive writers
-        // to the contents of this directory (i.e. the previous attempt is not
-        // still running), so it's safe to proceed and overwrite these files.
-        if !contents.is_empty() {
-            let serialized_result: Result<SerializedTestResult, _> =
-                serde_json::from_str(&contents);
-            let test_status = match serialized_result {
-                Ok(SerializedTestResult {
-                    command: _,
-                    exit_code: 0,
-                    head_commit_oid,
-                    snapshot_tree_oid,
-                    interactive,
-                }) => TestStatus::Passed {
-                    cached: true,
-                    fix_info: FixInfo {
-                        head_commit_oid: head_commit_oid.map(|SerializedNonZeroOid(oid)| oid),
-                        snapshot_tree_oid: snapshot_tree_oid.map(|SerializedNonZeroOid(oid)| oid),
-                    },
+/// The path at which the commit contents are mounted inside the container when
+/// using the Docker execution strategy.
+const DOCKER_WORKING_DIRECTORY: &str = "/branchless-test";
 
-                    interactive,
-                },
+/// The directory inside the Docker container at which the directory containing
+/// the skip reason file is mounted.
+const DOCKER_SKIP_REASON_DIRECTORY: &str = "/branchless-test-skip-reason";
 
-                Ok(SerializedTestResult {
-                    command: _,
-                    exit_code,
-                    head_commit_oid: _,
-                    snapshot_tree_oid: _,
-                    interactive: _,
-                }) if exit_code == TEST_INDETERMINATE_EXIT_CODE => {
-                    TestStatus::Indeterminate { exit_code }
-                }
+#[instrument(fields(commit_oid = %commit.get_oid()))]
+fn test_commit(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    repo: &Repo,
+    event_tx_id: EventTransactionId,
+    test_files: TestFiles,
+    working_directory: &Path,
+    shell_path: &Path,
+    options: &ResolvedTestOptions,
+    worker_id: WorkerId,
+    commit: &Commit,
+) -> eyre::Result<TestOutput> {
+    let TestFiles {
+        temp_dir,
+        lock_file: _lock_file, // Make sure not to drop lock.
+        result_path,
+        result_file,
+        stdout_path,
+        mut stdout_file,
+        stderr_path,
+        mut stderr_file,
+    } = test_files;
 
-                Ok(SerializedTestResult {
-                    command: _,
-                    exit_code,
-                    head_commit_oid: _,
-                    snapshot_tree_oid: _,
-                    interactive: _,
-                }) if exit_code == TEST_ABORT_EXIT_CODE => TestStatus::Abort { exit_code },
+    // When combining the output streams, write both to the stdout file, using
+    // the same file description so that the output is correctly interleaved.
+    let stderr_path = if options.process.combined_output {
+        stderr_file = stdout_file
+            .try_clone()
+            .wrap_err_with(|| format!("Cloning stdout file {stdout_path:?}"))?;
+        stdout_path.clone()
+    } else {
+        stderr_path
+    };
 
-                Ok(SerializedTestResult {
-                    command: _,
-                    exit_code,
-                    head_commit_oid: _,
-                    snapshot_tree_oid: _,
-                    interactive,
-                }) => TestStatus::Failed {
-                    cached: true,
-                    exit_code,
-                    interactive,
-                },
-                Err(err) => TestStatus::ReadCacheFailed(err.to_string()),
-            };
-            return Ok(TestFilesResult::Cached(TestOutput {
-                temp_dir: None,
+    let skip_reason_path = stdout_path.with_file_name("skip_reason");
+    let mut attempts = 1;
+    let TestAttempt {
+        test_status,
+        exit_code,
+        duration,
+        interrupted,
+    } = loop {
+        let test_attempt = run_test_command(
+            effects,
+            git_run_info,
+            event_tx_id,
+            working_directory,
+            shell_path,
+            options,
+            worker_id,
+            commit,
+            stdout_file,
+            stderr_file,
+            &skip_reason_path,
+            attempts,
+        )?;
+        match test_attempt.test_status {
+            TestStatus::Failed { .. }
+                if attempts <= options.limits.retries && !is_interrupted() =>
+            {
+                debug!(?attempts, ?commit, "Retrying failed test command");
+                // Discard the output of the failed attempt.
+                stdout_file = File::create(&stdout_path)
+                    .wrap_err_with(|| format!("Opening stdout file {stdout_path:?}"))?;
+                stderr_file = if options.process.combined_output {
+                    stdout_file
+                        .try_clone()
+                        .wrap_err_with(|| format!("Cloning stdout file {stdout_path:?}"))?
+                } else {
+                    File::create(&stderr_path)
+                        .wrap_err_with(|| format!("Opening stderr file {stderr_path:?}"))?
+                };
+                attempts += 1;
+            }
+            _ => break test_attempt,
+        }
+    };
+
+    let exit_code = match exit_code {
+        // The result of a command which was interrupted doesn't reflect the
+        // commit, so don't cache it.
+        Some(exit_code) if !interrupted => exit_code,
+        Some(_) | None => {
+            return Ok(TestOutput {
+                temp_dir,
                 result_path,
                 stdout_path,
                 stderr_path,
                 test_status,
-            }));
+                duration,
+                interrupted,
+            });
+        }
+    };
+
+    let (stdout_path, stderr_path) = if options.storage.compress_output {
+        let stdout_path = compress_test_output(&stdout_path)?;
+        let stderr_path = if options.process.combined_output {
+            stdout_path.clone()
+        } else {
+            compress_test_output(&stderr_path)?
+        };
+        (stdout_path, stderr_path)
+    } else {
+        (stdout_path, stderr_path)
+    };
+
+    if options.storage.dedupe_output {
+        dedupe_test_output(&options.cache_dir, &stdout_path)?;
+        if !options.process.combined_output {
+            dedupe_test_output(&options.cache_dir, &stderr_path)?;
         }
     }
 
-    let result_file = File::create(&result_path)
-        .wrap_err_with(|| format!("Opening result file {result_path:?}"))?;
-    let stdout_file = File::create(&stdout_path)
-        .wrap_err_with(|| format!("Opening stdout file {stdout_path:?}"))?;
-    let stderr_file = File::create(&stderr_path)
-        .wrap_err_with(|| format!("Opening stderr file {stderr_path:?}"))?;
-    Ok(TestFilesResult::NotCached(TestFiles {
-        temp_dir: None,
-        lock_file,
+    let fix_info = match &test_status {
+        TestStatus::Passed {
+            cached: _,
+            fix_info,
+            interactive: _,
+            attempts: _,
+        } => Some(fix_info),
+        TestStatus::CheckoutFailed
+        | TestStatus::SpawnTestFailed(_)
+        | TestStatus::TerminatedBySignal { .. }
+        | TestStatus::Timeout { .. }
+        | TestStatus::AlreadyInProgress
+        | TestStatus::ReadCacheFailed(_)
+        | TestStatus::Failed { .. }
+        | TestStatus::PerfThresholdExceeded { .. }
+        | TestStatus::Abort { .. }
+        | TestStatus::Indeterminate { .. }
+        | TestStatus::SkippedDueToAncestor { .. }
+        | TestStatus::SkippedEmpty
+        | TestStatus::MergeParentConflicted => None,
+    };
+    let timeout_seconds = match &test_status {
+        TestStatus::Timeout { duration } => Some(duration.as_secs()),
+        _ => None,
+    };
+    let skip_reason = match &test_status {
+        TestStatus::Indeterminate {
+            exit_code: _,
+            reason,
+        } => reason.clone(),
+        _ => None,
+    };
+    let serialized_test_result = SerializedTestResult {
+        command: options.command.clone(),
+        exit_code,
+        head_commit_oid: fix_info
+            .and_then(|fix_info| fix_info.head_commit_oid.map(SerializedNonZeroOid)),
+        snapshot_tree_oid: fix_info
+            .and_then(|fix_info| fix_info.snapshot_tree_oid.map(SerializedNonZeroOid)),
+        interactive: options.is_interactive,
+        timeout_seconds,
+        attempts: Some(attempts),
+        combined_output: options.process.combined_output,
+        duration_ms: duration.and_then(|duration| u64::try_from(duration.as_millis()).ok()),
+        skip_reason,
+        schema_version: TEST_RESULT_SCHEMA_VERSION,
+        timestamp: Some(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_secs_f64(),
+        ),
+    };
+    serde_json::to_writer_pretty(result_file, &serialized_test_result)
+        .wrap_err_with(|| format!("Writing test status {test_status:?} to {result_path:?}"))?;
+
+    // The result is cached as a plain success, since the threshold may differ
+    // between runs.
+    let test_status =
+        apply_perf_threshold(test_status, &stdout_path, options.outcome.perf_threshold);
+    let test_status = apply_fail_on_output(
+        test_status,
+        &stdout_path,
+        &stderr_path,
+        options.outcome.fail_on_output.as_ref(),
+    );
+    Ok(TestOutput {
+        temp_dir,
         result_path,
-        result_file,
         stdout_path,
-        stdout_file,
-        stderr_path,
-        stderr_file,
-    }))
-}
-
-#[derive(Debug)]
-struct PreparedWorkingDirectory {
-    lock_file: LockFile,
-    path: PathBuf,
+        stderr_path,
+        test_status,
+        duration,
+        interrupted: false,
+    })
 }
 
+/// The outcome of a single attempt at running the test command on a commit.
 #[derive(Debug)]
-enum PrepareWorkingDirectoryError {
-    LockFailed(PathBuf),
-    NoWorkingCopy,
-    CheckoutFailed(NonZeroOid),
-    CreateWorktreeFailed(PathBuf),
+struct TestAttempt {
+    test_status: TestStatus,
+
+    /// The exit code to record in the cache, or `None` if the result should
+    /// not be cached (such as if the command could not be spawned).
+    exit_code: Option<i32>,
+
+    duration: Option<Duration>,
+
+    /// Whether the user interrupted testing while the command was running.
+    interrupted: bool,
 }
 
-#[instrument]
-fn prepare_working_directory(
-    git_run_info: &GitRunInfo,
-    repo: &Repo,
-    event_tx_id: EventTransactionId,
-    commit: &Commit,
-    strategy: TestExecutionStrategy,
-    worker_id: WorkerId,
-) -> eyre::Result<Result<PreparedWorkingDirectory, PrepareWorkingDirectoryError>> {
-    let test_lock_dir_path = get_test_locks_dir(repo);
-    std::fs::create_dir_all(&test_lock_dir_path)
-        .wrap_err_with(|| format!("Creating test lock dir path: {test_lock_dir_path:?}"))?;
+/// If a `--perf-threshold` was provided and the test command succeeded, compare
+/// the measurement which it printed to the threshold. A measurement above the
+/// threshold is treated as a failure, and a missing measurement or unreadable
+/// output means that the result is indeterminate.
+fn apply_perf_threshold(
+    test_status: TestStatus,
+    stdout_path: &Path,
+    perf_threshold: Option<f64>,
+) -> TestStatus {
+    let (cached, threshold) = match (&test_status, perf_threshold) {
+        (TestStatus::Passed { cached, .. }, Some(threshold)) => (*cached, threshold),
+        _ => return test_status,
+    };
 
-    let lock_file_name = match strategy {
-        TestExecutionStrategy::WorkingCopy => "working-copy.lock".to_string(),
-        TestExecutionStrategy::Worktree => {
-            format!("worktree-{worker_id}.lock")
+    let stdout = match read_test_output(stdout_path) {
+        Ok(stdout) => stdout,
+        Err(err) => {
+            warn!(
+                ?err,
+                ?stdout_path,
+                "Could not read test output for --perf-threshold"
+            );
+            return TestStatus::Indeterminate {
+                exit_code: TEST_SUCCESS_EXIT_CODE,
+                reason: Some(format!("could not read output for --perf-threshold: {err}")),
+            };
         }
     };
-    let lock_path = test_lock_dir_path.join(lock_file_name);
-    let mut lock_file = LockFile::open(&lock_path)
-        .wrap_err_with(|| format!("Opening working copy lock at {lock_path:?}"))?;
-    if !lock_file
-        .try_lock_with_pid()
-        .wrap_err_with(|| format!("Locking working copy with {lock_path:?}"))?
-    {
-        return Ok(Err(PrepareWorkingDirectoryError::LockFailed(lock_path)));
+    match parse_perf_measurement(&String::from_utf8_lossy(&stdout)) {
+        Some(measurement) if measurement > threshold => TestStatus::PerfThresholdExceeded {
+            cached,
+            measurement,
+            threshold,
+        },
+        Some(_) => test_status,
+        None => TestStatus::Indeterminate {
+            exit_code: TEST_SUCCESS_EXIT_CODE,
+            reason: Some("no numeric measurement in output for --perf-threshold".to_string()),
+        },
     }
+}
 
-    match strategy {
-        TestExecutionStrategy::WorkingCopy => {
-            let working_copy_path = match repo.get_working_copy_path() {
-                None => return Ok(Err(PrepareWorkingDirectoryError::NoWorkingCopy)),
-                Some(working_copy_path) => working_copy_path.to_owned(),
-            };
+/// If a `--fail-on-output` pattern was provided and the test command
+/// succeeded, search its output for the pattern. A match is treated as a
+/// failure, even though the command's exit code indicated success, as is
+/// output which can't be read.
+fn apply_fail_on_output(
+    test_status: TestStatus,
+    stdout_path: &Path,
+    stderr_path: &Path,
+    fail_on_output: Option<&Regex>,
+) -> TestStatus {
+    let (cached, interactive, attempts, regex) = match (&test_status, fail_on_output) {
+        (
+            TestStatus::Passed {
+                cached,
+                fix_info: _,
+                interactive,
+                attempts,
+            },
+            Some(regex),
+        ) => (*cached, *interactive, *attempts, regex),
+        _ => return test_status,
+    };
 
-            let GitRunResult { exit_code, stdout: _, stderr: _ } =
-                // Don't show the `git reset` operation among the progress bars,
-                // as we only want to see the testing status.
-                git_run_info.run_silent(
-                    repo,
-                    Some(event_tx_id),
-                    &["reset", "--hard", &commit.get_oid().to_string()],
-                    Default::default()
-                ).context("Checking out commit to prepare working directory")?;
-            if exit_code.is_success() {
-                Ok(Ok(PreparedWorkingDirectory {
-                    lock_file,
-                    path: working_copy_path,
-                }))
-            } else {
-                Ok(Err(PrepareWorkingDirectoryError::CheckoutFailed(
-                    commit.get_oid(),
-                )))
+    let mut output_paths = vec![stdout_path];
+    if stderr_path != stdout_path {
+        output_paths.push(stderr_path);
+    }
+    for path in output_paths {
+        // If the output can't be read, then we can't tell whether it matched,
+        // so don't let the commit pass.
+        let reason = match read_test_output(path) {
+            Ok(output) if regex.is_match(&String::from_utf8_lossy(&output)) => {
+                format!("output matched {}", regex.as_str())
             }
-        }
-
-        TestExecutionStrategy::Worktree => {
-            let parent_dir = get_test_worktrees_dir(repo);
-            std::fs::create_dir_all(&parent_dir)
-                .wrap_err_with(|| format!("Creating worktree parent dir at {parent_dir:?}"))?;
-
-            let worktree_dir_name = format!("testing-worktree-{worker_id}");
-            let worktree_dir = parent_dir.join(worktree_dir_name);
-            let worktree_dir_str = match worktree_dir.to_str() {
-                Some(worktree_dir) => worktree_dir,
-                None => {
-                    return Ok(Err(PrepareWorkingDirectoryError::CreateWorktreeFailed(
-                        worktree_dir,
-                    )));
-                }
-            };
-
-            if !worktree_dir.exists() {
-                let GitRunResult {
-                    exit_code,
-                    stdout: _,
-                    stderr: _,
-                } = git_run_info.run_silent(
-                    repo,
-                    Some(event_tx_id),
-                    &["worktree", "add", worktree_dir_str, "--force", "--detach"],
-                    Default::default(),
-                )?;
-                if !exit_code.is_success() {
-                    return Ok(Err(PrepareWorkingDirectoryError::CreateWorktreeFailed(
-                        worktree_dir,
-                    )));
-                }
+            Ok(_) => continue,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    ?path,
+                    "Could not read test output for --fail-on-output"
+                );
+                format!("could not read output for --fail-on-output: {err}")
             }
+        };
+        return TestStatus::Failed {
+            cached,
+            exit_code: FAIL_ON_OUTPUT_EXIT_CODE,
+            interactive,
+            attempts,
+            reason: Some(reason),
+        };
+    }
+    test_status
+}
 
-            let GitRunResult {
-                exit_code,
-                stdout: _,
-                stderr: _,
-            } = git_run_info.run_silent(
-                repo,
-                Some(event_tx_id),
-                &[
-                    "-C",
-                    worktree_dir_str,
-                    "checkout",
-                    "--force",
-                    &commit.get_oid().to_string(),
-                ],
-                Default::default(),
-            )?;
-            if !exit_code.is_success() {
-                return Ok(Err(PrepareWorkingDirectoryError::CheckoutFailed(
-                    commit.get_oid(),
-                )));
-            }
-            Ok(Ok(PreparedWorkingDirectory {
-                lock_file,
-                path: worktree_dir,
-            }))
-        }
+/// Extract the measurement printed by a benchmark command, which is the last
+/// line of its output consisting of a single number.
+fn parse_perf_measurement(output: &str) -> Option<f64> {
+    output.lines().rev().find_map(|line| {
+        line.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|measurement| measurement.is_finite())
+    })
+}
+
+/// Read the reason for skipping a commit which the test command wrote to
+/// `skip_reason_path`, if any, and remove the file so that it doesn't affect
+/// later runs.
+fn read_skip_reason(skip_reason_path: &Path) -> Option<String> {
+    let reason = std::fs::read_to_string(skip_reason_path).ok()?;
+    if let Err(err) = std::fs::remove_file(skip_reason_path) {
+        warn!(?err, ?skip_reason_path, "Could not remove skip reason file");
+    }
+    let reason = reason.trim();
+    if reason.is_empty() {
+        None
+    } else {
+        Some(reason.to_owned())
     }
 }
 
 #[instrument]
-fn test_commit(
+fn run_test_command(
     effects: &Effects,
     git_run_info: &GitRunInfo,
-    repo: &Repo,
     event_tx_id: EventTransactionId,
-    test_files: TestFiles,
     working_directory: &Path,
     shell_path: &Path,
     options: &ResolvedTestOptions,
+    worker_id: WorkerId,
     commit: &Commit,
-) -> eyre::Result<TestOutput> {
-    let TestFiles {
-        temp_dir,
-        lock_file: _lock_file, // Make sure not to drop lock.
-        result_path,
-        result_file,
-        stdout_path,
-        stdout_file,
-        stderr_path,
-        stderr_file,
-    } = test_files;
+    stdout_file: File,
+    stderr_file: File,
+    skip_reason_path: &Path,
+    attempts: usize,
+) -> eyre::Result<TestAttempt> {
+    let (command_directory, docker_working_directory) = match &options.process.cwd {
+        None => (
+            Cow::Borrowed(working_directory),
+            DOCKER_WORKING_DIRECTORY.to_string(),
+        ),
+        Some(cwd) => {
+            let is_dir = match commit.get_tree()?.get_path(cwd)? {
+                Some(entry) => matches!(entry.get_filemode(), FileMode::Tree),
+                None => false,
+            };
+            if !is_dir {
+                return Ok(TestAttempt {
+                    test_status: TestStatus::SpawnTestFailed(format!(
+                        "directory {} does not exist in this commit",
+                        cwd.display()
+                    )),
+                    exit_code: None,
+                    duration: None,
+                    interrupted: false,
+                });
+            }
+            (
+                Cow::Owned(working_directory.join(cwd)),
+                format!("{DOCKER_WORKING_DIRECTORY}/{}", cwd.display()),
+            )
+        }
+    };
 
-    let mut command = Command::new(shell_path);
-    command
-        .arg("-c")
-        .arg(&options.command)
-        .current_dir(working_directory)
-        .env("BRANCHLESS_TEST_COMMIT", commit.get_oid().to_string())
-        .env("BRANCHLESS_TEST_COMMAND", options.command.clone());
+    let mut env_vars = vec![
+        ("BRANCHLESS_TEST_COMMIT", commit.get_oid().to_string()),
+        ("BRANCHLESS_TEST_TREE", commit.get_tree_oid().to_string()),
+        (
+            "BRANCHLESS_TEST_COMMIT_SUBJECT",
+            commit.get_summary()?.to_str_lossy().into_owned(),
+        ),
+        ("BRANCHLESS_TEST_COMMAND", options.command.clone()),
+    ];
+    // The skip reason file doesn't exist yet, so its parent directory is
+    // mounted into the Docker container instead of the file itself.
+    let skip_reason_dir = skip_reason_path.parent().unwrap_or(working_directory);
+    let skip_reason_file_name = skip_reason_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    env_vars.push((
+        "BRANCHLESS_TEST_SKIP_REASON_FILE",
+        match options.process.docker_image {
+            Some(_) => format!("{DOCKER_SKIP_REASON_DIRECTORY}/{skip_reason_file_name}"),
+            None => skip_reason_path.to_string_lossy().into_owned(),
+        },
+    ));
+    let docker_container_name = options
+        .process
+        .docker_image
+        .as_ref()
+        .map(|_| format!("git-branchless-test-{}-{worker_id}", std::process::id()));
+    let mut command = match (&options.process.docker_image, &docker_container_name) {
+        (Some(docker_image), Some(docker_container_name)) => {
+            let mut command = Command::new("docker");
+            command
+                .arg("run")
+                .arg("--rm")
+                .arg("--name")
+                .arg(docker_container_name)
+                .arg("--volume")
+                .arg(format!(
+                    "{}:{DOCKER_WORKING_DIRECTORY}",
+                    working_directory.display()
+                ))
+                .arg("--volume")
+                .arg(format!(
+                    "{}:{DOCKER_SKIP_REASON_DIRECTORY}",
+                    skip_reason_dir.display()
+                ))
+                .arg("--workdir")
+                .arg(docker_working_directory);
+            for (key, value) in &options.process.env_file_vars {
+                command.arg("--env").arg(format!("{key}={value}"));
+            }
+            for (key, value) in &env_vars {
+                command.arg("--env").arg(format!("{key}={value}"));
+            }
+            if let Some(memory_limit) = options.limits.memory_limit {
+                // Limiting the address space of the `docker` client itself
+                // wouldn't constrain the container, so let Docker enforce the
+                // limit instead.
+                command.arg("--memory").arg(memory_limit.to_string());
+            }
+            command
+                .arg(docker_image)
+                .arg("sh")
+                .arg("-c")
+                .arg(&options.shell_command)
+                .arg("sh")
+                .args(&options.process.extra_args);
+            command
+        }
+        _ => {
+            let mut command = Command::new(shell_path);
+            if let Some(env_passthrough) = &options.process.env_passthrough {
+                command.env_clear();
+                for name in env_passthrough {
+                    if let Some(value) = std::env::var_os(name) {
+                        command.env(name, value);
+                    }
+                }
+            }
+            command
+                .envs(options.process.env_file_vars.iter().cloned())
+                .arg("-c")
+                .arg(&options.shell_command)
+                .arg("sh")
+                .args(&options.process.extra_args)
+                .current_dir(command_directory.as_ref())
+                .envs(env_vars.iter().cloned());
+            command
+        }
+    };
 
+    let mut stream_files = None;
     if options.is_interactive {
         let commit_desc = effects
             .get_glyphs()
@@ -2641,12 +6882,14 @@ fn test_commit(
             .get_glyphs()
             .render(StyledString::styled("exit 1", *STYLE_FAILURE))?;
         let skipped = "skipped";
-        let exit125 = effects
-            .get_glyphs()
-            .render(StyledString::styled("exit 125", *STYLE_SKIPPED))?;
-        let exit127 = effects
-            .get_glyphs()
-            .render(StyledString::styled("exit 127", *STYLE_FAILURE))?;
+        let exit_skip = effects.get_glyphs().render(StyledString::styled(
+            format!("exit {}", options.outcome.skip_exit_code),
+            *STYLE_SKIPPED,
+        ))?;
+        let exit_abort = effects.get_glyphs().render(StyledString::styled(
+            format!("exit {}", options.outcome.abort_exit_code),
+            *STYLE_FAILURE,
+        ))?;
 
         // NB: use `println` here instead of
         // `writeln!(effects.get_output_stream(), ...)` because the effects are
@@ -2656,11 +6899,20 @@ fn test_commit(
 You are now at: {commit_desc}
 To mark this commit as {passed},run:   {exit0}
 To mark this commit as {failed}, run:  {exit1}
-To mark this commit as {skipped}, run: {exit125}
-To abort testing entirely, run:      {exit127}",
+To mark this commit as {skipped}, run: {exit_skip}
+To abort testing entirely, run:      {exit_abort}",
         );
         match options.execution_strategy {
-            TestExecutionStrategy::WorkingCopy => {}
+            TestExecutionStrategy::WorkingCopy | TestExecutionStrategy::Docker => {}
+            TestExecutionStrategy::Tmpfs => {
+                let warning = effects
+                    .get_glyphs()
+                    .render(StyledString::styled(
+                        "Warning: You are in a temporary checkout which is not a Git repository. Your changes will be discarded.",
+                        *STYLE_SKIPPED
+                    ))?;
+                println!("{warning}");
+            }
             TestExecutionStrategy::Worktree => {
                 let warning = effects
                     .get_glyphs()
@@ -2674,39 +6926,152 @@ To abort testing entirely, run:      {exit127}",
             }
         }
     } else {
-        command
-            .stdin(Stdio::null())
-            .stdout(stdout_file)
-            .stderr(stderr_file);
+        command.stdin(Stdio::null());
+        if options.process.stream || options.storage.max_output_bytes.is_some() {
+            // The output has to be copied by this process, rather than written
+            // directly to the files by the test command.
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+            let stdout_writer = Arc::new(Mutex::new(CappedWriter::new(
+                stdout_file,
+                options.storage.max_output_bytes,
+            )));
+            // When combining the output streams, both are written through the
+            // same writer, so that the output limit applies to their total.
+            let stderr_writer = if options.process.combined_output {
+                Arc::clone(&stdout_writer)
+            } else {
+                Arc::new(Mutex::new(CappedWriter::new(
+                    stderr_file,
+                    options.storage.max_output_bytes,
+                )))
+            };
+            stream_files = Some((stdout_writer, stderr_writer));
+        } else {
+            command.stdout(stdout_file).stderr(stderr_file);
+        }
+        if options.limits.timeout.is_some() || docker_container_name.is_some() {
+            // Run the command in its own process group so that, if it times
+            // out, we can also kill any processes which it spawned. The
+            // `docker` client is also run in its own process group, so that it
+            // can be killed if the test run is interrupted; otherwise, it would
+            // keep waiting for the container to exit.
+            set_new_process_group(&mut command);
+        }
+    }
+
+    if let Some(memory_limit) = options.limits.memory_limit {
+        if !matches!(options.execution_strategy, TestExecutionStrategy::Docker) {
+            set_memory_limit(&mut command, memory_limit);
+        }
     }
 
-    let exit_code = match command.status() {
-        Ok(status) => status.code(),
+    let start_time = Instant::now();
+    let mut child = match command.spawn() {
+        Ok(child) => child,
         Err(err) => {
-            return Ok(TestOutput {
-                temp_dir,
-                result_path,
-                stdout_path,
-                stderr_path,
+            return Ok(TestAttempt {
                 test_status: TestStatus::SpawnTestFailed(err.to_string()),
+                exit_code: None,
+                duration: None,
+                interrupted: false,
             });
         }
     };
-    let exit_code = match exit_code {
-        Some(exit_code) => exit_code,
-        None => {
-            return Ok(TestOutput {
-                temp_dir,
-                result_path,
-                stdout_path,
-                stderr_path,
-                test_status: TestStatus::TerminatedBySignal,
-            });
+    let mut docker_container_guard = DockerContainerGuard {
+        docker_container_name: docker_container_name.as_deref(),
+    };
+    let tee_threads = match stream_files {
+        None => Vec::new(),
+        Some((stdout_file, stderr_file)) => {
+            let mut tee_threads = Vec::new();
+            if let Some(child_stdout) = child.stdout.take() {
+                tee_threads.push(spawn_tee_thread(
+                    child_stdout,
+                    stdout_file,
+                    options.process.stream.then(std::io::stdout),
+                ));
+            }
+            if let Some(child_stderr) = child.stderr.take() {
+                tee_threads.push(spawn_tee_thread(
+                    child_stderr,
+                    stderr_file,
+                    options.process.stream.then(std::io::stderr),
+                ));
+            }
+            tee_threads
+        }
+    };
+    let (exit_status, timed_out) = match (options.limits.timeout, &docker_container_name) {
+        (None, None) => (
+            Some(child.wait().wrap_err("Waiting for test command to exit")?),
+            None,
+        ),
+        (timeout, _) => match wait_with_timeout(&mut child, timeout)
+            .wrap_err("Waiting for test command to exit")?
+        {
+            Some(exit_status) => (Some(exit_status), None),
+            None => (None, timeout),
+        },
+    };
+    let duration = start_time.elapsed();
+    let interrupted = is_interrupted();
+    if timed_out.is_none() && !interrupted {
+        // The container has already exited and been removed, since the
+        // `docker` client wasn't killed above.
+        docker_container_guard.disarm();
+    }
+    drop(docker_container_guard);
+    let skip_reason = read_skip_reason(skip_reason_path);
+    for tee_thread in tee_threads {
+        match tee_thread.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => warn!(?err, "Failed to stream test command output"),
+            Err(_) => warn!("Thread streaming test command output panicked"),
         }
+    }
+    let exit_code = match exit_status {
+        Some(exit_status) => match exit_status.code() {
+            Some(exit_code) => exit_code,
+            None => {
+                return Ok(TestAttempt {
+                    test_status: TestStatus::TerminatedBySignal {
+                        signal: get_terminating_signal(&exit_status),
+                    },
+                    exit_code: None,
+                    duration: Some(duration),
+                    interrupted,
+                });
+            }
+        },
+        None => TEST_TIMEOUT_EXIT_CODE,
     };
-    let test_status = match exit_code {
-        TEST_SUCCESS_EXIT_CODE => {
-            let fix_info = {
+    let test_status = match (timed_out, exit_code) {
+        (Some(duration), _) => TestStatus::Timeout { duration },
+
+        // The configured lists of exit codes take precedence over the
+        // built-in handling below.
+        (None, exit_code) if options.outcome.skip_exit_codes.contains(&exit_code) => {
+            TestStatus::Indeterminate {
+                exit_code,
+                reason: skip_reason,
+            }
+        }
+
+        (None, exit_code)
+            if exit_code == TEST_SUCCESS_EXIT_CODE
+                || options.outcome.success_exit_codes.contains(&exit_code) =>
+        {
+            let fix_info = if matches!(
+                options.execution_strategy,
+                TestExecutionStrategy::Docker | TestExecutionStrategy::Tmpfs
+            ) {
+                // The Docker and tmpfs test directories aren't Git working
+                // copies, so there are no fixes to detect.
+                FixInfo {
+                    head_commit_oid: None,
+                    snapshot_tree_oid: None,
+                }
+            } else {
                 let repo = Repo::from_dir(working_directory)?;
                 let (head_commit_oid, snapshot) = {
                     let index = repo.get_index()?;
@@ -2740,57 +7105,304 @@ To abort testing entirely, run:      {exit127}",
                     snapshot_tree_oid,
                 }
             };
-            TestStatus::Passed {
-                cached: false,
-                fix_info,
-                interactive: options.is_interactive,
+            TestStatus::Passed {
+                cached: false,
+                fix_info,
+                interactive: options.is_interactive,
+                attempts,
+            }
+        }
+
+        (None, exit_code) if exit_code == options.outcome.skip_exit_code => {
+            TestStatus::Indeterminate {
+                exit_code,
+                reason: skip_reason,
+            }
+        }
+        (None, exit_code) if exit_code == options.outcome.abort_exit_code => {
+            TestStatus::Abort { exit_code }
+        }
+
+        (None, exit_code) => TestStatus::Failed {
+            cached: false,
+            exit_code,
+            interactive: options.is_interactive,
+            attempts,
+            reason: None,
+        },
+    };
+
+    Ok(TestAttempt {
+        test_status,
+        exit_code: Some(exit_code),
+        duration: Some(duration),
+        interrupted,
+    })
+}
+
+/// Get the signal which terminated the process, given that it did not exit
+/// with an exit code.
+#[cfg(unix)]
+fn get_terminating_signal(exit_status: &ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    exit_status.signal().unwrap_or_default()
+}
+
+/// On non-Unix platforms, processes always exit with an exit code, so there is
+/// no terminating signal.
+#[cfg(not(unix))]
+fn get_terminating_signal(_exit_status: &ExitStatus) -> i32 {
+    0
+}
+
+/// Get the conventional name of the given signal (e.g. `SIGKILL`), if known.
+#[cfg(unix)]
+fn get_signal_name(signal: i32) -> Option<&'static str> {
+    let signal_name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        _ => return None,
+    };
+    Some(signal_name)
+}
+
+#[cfg(not(unix))]
+fn get_signal_name(_signal: i32) -> Option<&'static str> {
+    None
+}
+
+/// Copy everything read from `input` to `file` (and to `terminal`, if
+/// provided) on a background thread, until `input` is closed. Each chunk is
+/// written to `file` while holding its lock, so that chunks from other threads
+/// sharing the same writer aren't interleaved with it.
+fn spawn_tee_thread(
+    mut input: impl Read + Send + 'static,
+    file: Arc<Mutex<CappedWriter<File>>>,
+    mut terminal: Option<impl std::io::Write + Send + 'static>,
+) -> JoinHandle<std::io::Result<()>> {
+    std::thread::spawn(move || {
+        let mut buf = [0; 8192];
+        loop {
+            let num_bytes = match input.read(&mut buf) {
+                Ok(0) => break,
+                Ok(num_bytes) => num_bytes,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            file.lock().unwrap().write_all(&buf[..num_bytes])?;
+            if let Some(terminal) = &mut terminal {
+                terminal.write_all(&buf[..num_bytes])?;
+                terminal.flush()?;
             }
         }
+        Ok(())
+    })
+}
 
-        exit_code @ TEST_INDETERMINATE_EXIT_CODE => TestStatus::Indeterminate { exit_code },
-        exit_code @ TEST_ABORT_EXIT_CODE => TestStatus::Abort { exit_code },
+/// How often to check whether another process has finished testing a commit,
+/// when waiting for it with `--wait`.
+const LOCK_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
-        exit_code => TestStatus::Failed {
-            cached: false,
-            exit_code,
-            interactive: options.is_interactive,
-        },
-    };
+/// Like `make_test_files`, but if another process is already testing `commit`,
+/// wait up to `timeout` for it to finish (and then use its cached result)
+/// rather than immediately reporting the commit as already in progress.
+#[instrument]
+fn wait_for_test_files(
+    effects: &Effects,
+    progress: &ProgressHandle,
+    commit: &Commit,
+    options: &ResolvedTestOptions,
+    timeout: Duration,
+) -> eyre::Result<TestFilesResult> {
+    let start_time = Instant::now();
+    loop {
+        let test_files_result = make_test_files(commit, options)?;
+        let is_in_progress = matches!(
+            test_files_result,
+            TestFilesResult::Cached(TestOutput {
+                test_status: TestStatus::AlreadyInProgress,
+                ..
+            })
+        );
+        let elapsed = start_time.elapsed();
+        if !is_in_progress || elapsed >= timeout {
+            return Ok(test_files_result);
+        }
 
-    let fix_info = match &test_status {
-        TestStatus::Passed {
-            cached: _,
-            fix_info,
-            interactive: _,
-        } => Some(fix_info),
-        TestStatus::CheckoutFailed
-        | TestStatus::SpawnTestFailed(_)
-        | TestStatus::TerminatedBySignal
-        | TestStatus::AlreadyInProgress
-        | TestStatus::ReadCacheFailed(_)
-        | TestStatus::Failed { .. }
-        | TestStatus::Abort { .. }
-        | TestStatus::Indeterminate { .. } => None,
-    };
-    let serialized_test_result = SerializedTestResult {
-        command: options.command.clone(),
-        exit_code,
-        head_commit_oid: fix_info
-            .and_then(|fix_info| fix_info.head_commit_oid.map(SerializedNonZeroOid)),
-        snapshot_tree_oid: fix_info
-            .and_then(|fix_info| fix_info.snapshot_tree_oid.map(SerializedNonZeroOid)),
-        interactive: options.is_interactive,
+        progress.notify_status(
+            OperationIcon::InProgress,
+            format!(
+                "Waiting for another process to finish testing {} ({}s elapsed)",
+                effects
+                    .get_glyphs()
+                    .render(commit.friendly_describe(effects.get_glyphs())?)?,
+                elapsed.as_secs(),
+            ),
+        );
+        std::thread::sleep(LOCK_WAIT_POLL_INTERVAL);
+    }
+}
+
+/// How often to check whether a test command with a timeout has exited.
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Wait for the child process to exit. If it hasn't exited by the time
+/// `timeout` has elapsed (if any), kill it (along with its process group) and
+/// return `None`. The child must have been placed in its own process group with
+/// `set_new_process_group`.
+#[instrument]
+fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> std::io::Result<Option<ExitStatus>> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        if let Some(exit_status) = child.try_wait()? {
+            return Ok(Some(exit_status));
+        }
+        if is_interrupted() {
+            // The child is in its own process group, so it didn't receive the
+            // interrupt from the terminal.
+            kill_process_group(child)?;
+            return Ok(Some(child.wait()?));
+        }
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            kill_process_group(child)?;
+            // Reap the killed process so that it doesn't become a zombie.
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(TIMEOUT_POLL_INTERVAL);
+    }
+}
+
+/// Kills the named Docker container, if any, when dropped. Killing the `docker`
+/// client doesn't stop the container itself, so this makes sure that the
+/// container is stopped however the test command's attempt ends, such as when
+/// it times out, testing is interrupted, or an error is returned early.
+#[derive(Debug)]
+struct DockerContainerGuard<'a> {
+    docker_container_name: Option<&'a str>,
+}
+
+impl DockerContainerGuard<'_> {
+    /// Don't kill the container, because it's known to have exited.
+    fn disarm(&mut self) {
+        self.docker_container_name = None;
+    }
+}
+
+impl Drop for DockerContainerGuard<'_> {
+    fn drop(&mut self) {
+        let docker_container_name = match self.docker_container_name {
+            Some(docker_container_name) => docker_container_name,
+            None => return,
+        };
+        let result = Command::new("docker")
+            .arg("kill")
+            .arg(docker_container_name)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if let Err(err) = result {
+            warn!(
+                ?err,
+                ?docker_container_name,
+                "Could not kill Docker container"
+            );
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_new_process_group(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: `setpgid` is async-signal-safe, so it's safe to call between
+    // `fork` and `exec`.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setpgid(0, 0) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn set_new_process_group(_command: &mut Command) {}
+
+/// Limit the address space of the spawned process to `memory_limit` bytes.
+/// (`RLIMIT_RSS` has no effect on modern Linux kernels, so `RLIMIT_AS` is used
+/// instead.)
+#[cfg(target_os = "linux")]
+fn set_memory_limit(command: &mut Command, memory_limit: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let memory_limit = libc::rlim_t::try_from(memory_limit).unwrap_or(libc::RLIM_INFINITY);
+    let limit = libc::rlimit {
+        rlim_cur: memory_limit,
+        rlim_max: memory_limit,
     };
-    serde_json::to_writer_pretty(result_file, &serialized_test_result)
-        .wrap_err_with(|| format!("Writing test status {test_status:?} to {result_path:?}"))?;
+    // SAFETY: `setrlimit` is async-signal-safe, so it's safe to call between
+    // `fork` and `exec`.
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+}
 
-    Ok(TestOutput {
-        temp_dir,
-        result_path,
-        stdout_path,
-        stderr_path,
-        test_status,
-    })
+/// The `--memory-limit` option is rejected on other platforms when the options
+/// are resolved.
+#[cfg(not(target_os = "linux"))]
+fn set_memory_limit(_command: &mut Command, _memory_limit: u64) {}
+
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) -> std::io::Result<()> {
+    let pgid = libc::pid_t::try_from(child.id())
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+    // SAFETY: `kill` has no memory-safety preconditions. The child was placed
+    // into its own process group (with the same ID as its PID) by
+    // `set_new_process_group`, so this only signals the test command and its
+    // descendants.
+    if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            // The process group has already exited.
+            Some(libc::ESRCH) => Ok(()),
+            _ => Err(err),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) -> std::io::Result<()> {
+    child.kill()
 }
 
 /// Show test output for the command provided in `options` for each of the
@@ -2801,6 +7413,9 @@ fn subcommand_show(
     options: &RawTestOptions,
     revset: Revset,
     resolve_revset_options: &ResolveRevsetOptions,
+    failed_only: bool,
+    passed_only: bool,
+    skipped_only: bool,
 ) -> eyre::Result<ExitCode> {
     let now = SystemTime::now();
     let repo = Repo::from_current_dir()?;
@@ -2844,8 +7459,40 @@ fn subcommand_show(
     };
 
     let commits = sorted_commit_set(&repo, &dag, &commit_set)?;
-    for commit in commits {
-        let test_files = make_test_files(&repo, &commit, &options)?;
+    let test_files = {
+        // Reading the cached results is independent for each commit, so read
+        // them in parallel and then print them in the original order.
+        let commit_oids = commits.iter().map(|commit| commit.get_oid()).collect_vec();
+        let thread_pool = ThreadPoolBuilder::new().build()?;
+        let repo_pool = RepoResource::new_pool(&repo)?;
+        thread_pool.install(|| {
+            commit_oids
+                .into_par_iter()
+                .map(|commit_oid| -> eyre::Result<TestFilesResult> {
+                    let repo = repo_pool.try_create()?;
+                    let commit = repo.find_commit_or_fail(commit_oid)?;
+                    make_test_files(&commit, &options)
+                })
+                .collect::<eyre::Result<Vec<_>>>()
+        })?
+    };
+    let is_filtered = failed_only || passed_only || skipped_only;
+    for (commit, test_files) in commits.into_iter().zip(test_files) {
+        let is_shown = match &test_files {
+            TestFilesResult::NotCached(_) => !is_filtered,
+            TestFilesResult::Cached(test_output) => {
+                !is_filtered
+                    || match test_output.test_status.get_category() {
+                        TestStatusCategory::Skipped => skipped_only,
+                        TestStatusCategory::Failed => failed_only,
+                        TestStatusCategory::Passed => passed_only,
+                    }
+            }
+        };
+        if !is_shown {
+            continue;
+        }
+
         match test_files {
             TestFilesResult::NotCached(_) => {
                 writeln!(
@@ -2864,7 +7511,8 @@ fn subcommand_show(
                         effects,
                         &commit,
                         false,
-                        options.verbosity
+                        options.verbosity,
+                        options.report.output_context_lines,
                     )?)?,
                 )?;
             }
@@ -2896,72 +7544,6 @@ fn subcommand_show(
     Ok(ExitCode(0))
 }
 
-/// Delete cached test output for the commits in `revset`.
-#[instrument]
-pub fn subcommand_clean(
-    effects: &Effects,
-    revset: Revset,
-    resolve_revset_options: &ResolveRevsetOptions,
-) -> eyre::Result<ExitCode> {
-    let repo = Repo::from_current_dir()?;
-    let conn = repo.get_db_conn()?;
-    let event_log_db = EventLogDb::new(&conn)?;
-    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
-    let event_cursor = event_replayer.make_default_cursor();
-    let references_snapshot = repo.get_references_snapshot()?;
-    let mut dag = Dag::open_and_sync(
-        effects,
-        &repo,
-        &event_replayer,
-        event_cursor,
-        &references_snapshot,
-    )?;
-
-    let commit_set =
-        match resolve_commits(effects, &repo, &mut dag, &[revset], resolve_revset_options) {
-            Ok(mut commit_sets) => commit_sets.pop().unwrap(),
-            Err(err) => {
-                err.describe(effects)?;
-                return Ok(ExitCode(1));
-            }
-        };
-
-    let mut num_cleaned_commits = 0;
-    for commit in sorted_commit_set(&repo, &dag, &commit_set)? {
-        let tree_dir = get_test_tree_dir(&repo, &commit);
-        if tree_dir.exists() {
-            writeln!(
-                effects.get_output_stream(),
-                "Cleaning results for {}",
-                effects
-                    .get_glyphs()
-                    .render(commit.friendly_describe(effects.get_glyphs())?)?,
-            )?;
-            std::fs::remove_dir_all(&tree_dir)
-                .with_context(|| format!("Cleaning test dir: {tree_dir:?}"))?;
-            num_cleaned_commits += 1;
-        } else {
-            writeln!(
-                effects.get_output_stream(),
-                "Nothing to clean for {}",
-                effects
-                    .get_glyphs()
-                    .render(commit.friendly_describe(effects.get_glyphs())?)?,
-            )?;
-        }
-    }
-    writeln!(
-        effects.get_output_stream(),
-        "Cleaned {}.",
-        Pluralize {
-            determiner: None,
-            amount: num_cleaned_commits,
-            unit: ("cached test result", "cached test results")
-        }
-    )?;
-    Ok(ExitCode(0))
-}
-
 #[cfg(test)]
 mod tests {
     use git_branchless_testing::make_git;
@@ -2988,6 +7570,8 @@ mod tests {
             event_tx_id,
             &head_commit,
             TestExecutionStrategy::WorkingCopy,
+            &get_test_worktrees_dir(&repo),
+            None,
             worker_id,
         )?
         .unwrap();
@@ -2998,6 +7582,8 @@ mod tests {
                 event_tx_id,
                 &head_commit,
                 TestExecutionStrategy::WorkingCopy,
+                &get_test_worktrees_dir(&repo),
+                None,
                 worker_id
             )?,
             Err(PrepareWorkingDirectoryError::LockFailed(_))
@@ -3009,6 +7595,8 @@ mod tests {
             event_tx_id,
             &head_commit,
             TestExecutionStrategy::Worktree,
+            &get_test_worktrees_dir(&repo),
+            None,
             worker_id,
         )?
         .unwrap();
@@ -3019,6 +7607,8 @@ mod tests {
                 event_tx_id,
                 &head_commit,
                 TestExecutionStrategy::Worktree,
+                &get_test_worktrees_dir(&repo),
+                None,
                 worker_id
             )?,
             Err(PrepareWorkingDirectoryError::LockFailed(_))
@@ -3026,4 +7616,228 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_run_tests_on_commits() -> eyre::Result<()> {
+        let git = make_git()?;
+        git.init_repo()?;
+        let test1_oid = git.commit_file("test1", 1)?;
+        let test2_oid = git.commit_file("test2", 2)?;
+
+        let effects = Effects::new_suppress_for_test(Glyphs::text());
+        let git_run_info = git.get_git_run_info();
+        let repo = git.get_repo()?;
+        let conn = repo.get_db_conn()?;
+        let event_log_db = EventLogDb::new(&conn)?;
+        let event_replayer = EventReplayer::from_event_log_db(&effects, &repo, &event_log_db)?;
+        let event_cursor = event_replayer.make_default_cursor();
+        let references_snapshot = repo.get_references_snapshot()?;
+        let dag = Dag::open_and_sync(
+            &effects,
+            &repo,
+            &event_replayer,
+            event_cursor,
+            &references_snapshot,
+        )?;
+        let event_tx_id = event_log_db.make_transaction_id(SystemTime::now(), "test")?;
+
+        let command = "test -f test2.txt".to_string();
+        let options = ResolvedTestOptions {
+            shell_command: command.clone(),
+            command,
+            execution_strategy: TestExecutionStrategy::Worktree,
+            search_strategy: None,
+            is_dry_run: false,
+            cache_mode: TestCacheMode::ReadWrite,
+            cache_dir: get_default_test_cache_dir(&repo),
+            worktrees_dir: get_test_worktrees_dir(&repo),
+            is_interactive: false,
+            num_jobs: 1,
+            verbosity: Verbosity::None,
+            fix_options: None,
+            limits: Default::default(),
+            scheduling: Default::default(),
+            process: Default::default(),
+            hooks: Default::default(),
+            outcome: Default::default(),
+            storage: Default::default(),
+            report: Default::default(),
+            fix_selection: Default::default(),
+        };
+
+        let test_results = run_tests_on_commits(
+            &effects,
+            &git_run_info,
+            &dag,
+            &repo,
+            &event_log_db,
+            event_tx_id,
+            &[test1_oid, test2_oid].into_iter().collect(),
+            &options,
+        )?
+        .unwrap();
+        let statuses = test_results
+            .test_outputs
+            .iter()
+            .map(|(commit_oid, test_output)| (*commit_oid, test_output.test_status.get_category()))
+            .collect_vec();
+        assert_eq!(
+            statuses,
+            vec![
+                (test1_oid, TestStatusCategory::Failed),
+                (test2_oid, TestStatusCategory::Passed),
+            ]
+        );
+        assert!(test_results.testing_aborted_error.is_none());
+        assert!(!test_results.interrupted);
+
+        let test_results = run_tests_on_commits(
+            &effects,
+            &git_run_info,
+            &dag,
+            &repo,
+            &event_log_db,
+            event_tx_id,
+            &CommitSet::empty(),
+            &options,
+        )?
+        .unwrap();
+        assert!(test_results.test_outputs.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_make_command_slug_docker_image() -> eyre::Result<()> {
+        let make_options = |execution_strategy, docker_image: Option<&str>| {
+            let command = "exit 0".to_string();
+            ResolvedTestOptions {
+                shell_command: command.clone(),
+                command,
+                execution_strategy,
+                search_strategy: None,
+                is_dry_run: false,
+                cache_mode: TestCacheMode::ReadWrite,
+                cache_dir: PathBuf::new(),
+                worktrees_dir: PathBuf::new(),
+                is_interactive: false,
+                num_jobs: 1,
+                verbosity: Verbosity::None,
+                fix_options: None,
+                limits: Default::default(),
+                scheduling: Default::default(),
+                process: TestProcessOptions {
+                    docker_image: docker_image.map(|image| image.to_owned()),
+                    ..Default::default()
+                },
+                hooks: Default::default(),
+                outcome: Default::default(),
+                storage: Default::default(),
+                report: Default::default(),
+                fix_selection: Default::default(),
+            }
+        };
+
+        let host_slug = make_options(TestExecutionStrategy::Worktree, None).make_command_slug()?;
+        let foo_slug =
+            make_options(TestExecutionStrategy::Docker, Some("foo")).make_command_slug()?;
+        let bar_slug =
+            make_options(TestExecutionStrategy::Docker, Some("bar")).make_command_slug()?;
+        assert_eq!(host_slug, "exit__0");
+        assert_ne!(foo_slug, host_slug);
+        assert_ne!(foo_slug, bar_slug);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_abbreviate_lines_large_file() -> eyre::Result<()> {
+        use std::io::Write;
+
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("stdout");
+        {
+            let mut file = std::io::BufWriter::new(File::create(&path)?);
+            for i in 1..=1_000_000 {
+                writeln!(file, "line {i}")?;
+            }
+        }
+
+        let render = |lines: Vec<StyledString>| -> Vec<String> {
+            lines
+                .into_iter()
+                .map(|line| line.source().to_owned())
+                .collect()
+        };
+        assert_eq!(
+            render(abbreviate_lines(&path, Verbosity::PartialOutput, 2)),
+            vec![
+                "line 1",
+                "line 2",
+                "<999996 more lines>",
+                "line 999999",
+                "line 1000000",
+            ]
+        );
+        assert_eq!(
+            render(abbreviate_lines(&path, Verbosity::PartialOutput, 0)),
+            vec!["<1000000 more lines>"]
+        );
+        assert!(abbreviate_lines(&path, Verbosity::None, 2).is_empty());
+
+        let short_path = temp_dir.path().join("stderr");
+        std::fs::write(&short_path, "line 1\nline 2\nline 3\n")?;
+        assert_eq!(
+            render(abbreviate_lines(&short_path, Verbosity::PartialOutput, 2)),
+            vec!["line 1", "line 2", "line 3"]
+        );
+
+        let empty_path = temp_dir.path().join("empty");
+        std::fs::write(&empty_path, "")?;
+        assert_eq!(
+            render(abbreviate_lines(&empty_path, Verbosity::PartialOutput, 2)),
+            vec!["<no output>"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let contents = r#"
+# A comment.
+FOO=bar
+export BAZ = qux # trailing comment
+EMPTY=
+SINGLE='$literal # not a comment'
+DOUBLE="line 1\nsays \"hi\""
+"#;
+        assert_eq!(
+            parse_env_file(contents),
+            Ok(vec![
+                ("FOO".to_string(), "bar".to_string()),
+                ("BAZ".to_string(), "qux".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+                ("SINGLE".to_string(), "$literal # not a comment".to_string()),
+                ("DOUBLE".to_string(), "line 1\nsays \"hi\"".to_string()),
+            ])
+        );
+
+        assert_eq!(
+            parse_env_file("FOO=bar\nnot a variable\n"),
+            Err("line 2: expected KEY=VALUE".to_string())
+        );
+        assert_eq!(
+            parse_env_file("1FOO=bar"),
+            Err(r#"line 1: invalid variable name "1FOO""#.to_string())
+        );
+        assert_eq!(
+            parse_env_file(r#"FOO="bar"#),
+            Err("line 1: unterminated double quote".to_string())
+        );
+        assert_eq!(
+            parse_env_file(r#"FOO="bar" baz"#),
+            Err("line 1: unexpected text after closing quote".to_string())
+        );
+    }
 }
@@ -0,0 +1,203 @@
+//! The history of cached test results for a commit, across all test commands.
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use cursive::theme::Effect;
+use eyre::WrapErr;
+use lib::core::config::get_test_exit_codes;
+use lib::core::dag::{sorted_commit_set, Dag};
+use lib::core::effects::{icons, Effects};
+use lib::core::eventlog::{EventLogDb, EventReplayer};
+use lib::core::formatting::StyledStringBuilder;
+use lib::core::node_descriptors::RelativeTimeDescriptor;
+use lib::core::repo_ext::RepoExt;
+use lib::git::{get_test_tree_dir, Repo, SerializedTestResult, TEST_RESULT_SCHEMA_VERSION};
+use lib::util::ExitCode;
+use tracing::{debug, instrument};
+
+use git_branchless_opts::{ResolveRevsetOptions, Revset};
+use git_branchless_revset::resolve_commits;
+
+use crate::{
+    resolve_test_cache_dir, TestStatusCategory, STYLE_FAILURE, STYLE_SKIPPED, STYLE_SUCCESS,
+};
+
+/// Show all of the cached test results for the commits in `revset`, across all
+/// test commands.
+#[instrument]
+pub(crate) fn subcommand_log(
+    effects: &Effects,
+    revset: Revset,
+    resolve_revset_options: &ResolveRevsetOptions,
+    cache_dir: Option<&Path>,
+) -> eyre::Result<ExitCode> {
+    let now = SystemTime::now();
+    let repo = Repo::from_current_dir()?;
+    let conn = repo.get_db_conn()?;
+    let event_log_db = EventLogDb::new(&conn)?;
+    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
+    let event_cursor = event_replayer.make_default_cursor();
+    let references_snapshot = repo.get_references_snapshot()?;
+    let mut dag = Dag::open_and_sync(
+        effects,
+        &repo,
+        &event_replayer,
+        event_cursor,
+        &references_snapshot,
+    )?;
+
+    let commit_set =
+        match resolve_commits(effects, &repo, &mut dag, &[revset], resolve_revset_options) {
+            Ok(mut commit_sets) => commit_sets.pop().unwrap(),
+            Err(err) => {
+                err.describe(effects)?;
+                return Ok(ExitCode(1));
+            }
+        };
+
+    let cache_dir = resolve_test_cache_dir(effects, &repo, cache_dir)?;
+    let exit_codes = get_test_exit_codes(&repo)?;
+    for commit in sorted_commit_set(&repo, &dag, &commit_set)? {
+        let commit_description = effects
+            .get_glyphs()
+            .render(commit.friendly_describe(effects.get_glyphs())?)?;
+        let tree_dir = get_test_tree_dir(&cache_dir, &commit);
+        let results = read_cached_test_results(&tree_dir)?;
+        if results.is_empty() {
+            writeln!(
+                effects.get_output_stream(),
+                "No cached test results for {commit_description}"
+            )?;
+            continue;
+        }
+
+        writeln!(
+            effects.get_output_stream(),
+            "Cached test results for {commit_description}:"
+        )?;
+        for (result_path, result) in results {
+            let result = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "{}",
+                        effects.get_glyphs().render(
+                            StyledStringBuilder::new()
+                                .append_styled(
+                                    format!(
+                                        "{} Could not read cached result at {}: {err}",
+                                        icons::EXCLAMATION,
+                                        result_path.display()
+                                    ),
+                                    *STYLE_SKIPPED
+                                )
+                                .build()
+                        )?
+                    )?;
+                    continue;
+                }
+            };
+
+            let (icon, style) = match result.get_category(&exit_codes) {
+                TestStatusCategory::Skipped => (icons::EXCLAMATION, *STYLE_SKIPPED),
+                TestStatusCategory::Passed => (icons::CHECKMARK, *STYLE_SUCCESS),
+                TestStatusCategory::Failed => (icons::CROSS, *STYLE_FAILURE),
+            };
+            let SerializedTestResult {
+                command,
+                exit_code,
+                head_commit_oid: _,
+                snapshot_tree_oid: _,
+                interactive: _,
+                timeout_seconds: _,
+                attempts: _,
+                timestamp,
+                combined_output: _,
+                duration_ms: _,
+                skip_reason: _,
+                schema_version: _,
+            } = result;
+            let time_description = match timestamp {
+                Some(timestamp) => {
+                    let time = SystemTime::UNIX_EPOCH + Duration::from_secs_f64(timestamp);
+                    format!(
+                        "{} ago",
+                        RelativeTimeDescriptor::describe_time_delta(now, time)?
+                    )
+                }
+                None => "unknown time".to_string(),
+            };
+            writeln!(
+                effects.get_output_stream(),
+                "{}",
+                effects.get_glyphs().render(
+                    StyledStringBuilder::new()
+                        .append_styled(format!("{icon} exit code {exit_code}"), style)
+                        .append_plain(format!(" ({time_description}): "))
+                        .append_styled(command, Effect::Bold)
+                        .build()
+                )?
+            )?;
+        }
+    }
+
+    Ok(ExitCode(0))
+}
+
+/// Read all of the cached test results in the given tree directory, ordered
+/// from oldest to newest. Results which are still in progress, or which were
+/// written with a different `TEST_RESULT_SCHEMA_VERSION`, are skipped.
+#[instrument]
+pub(crate) fn read_cached_test_results(
+    tree_dir: &Path,
+) -> eyre::Result<Vec<(PathBuf, Result<SerializedTestResult, serde_json::Error>)>> {
+    let entries = match std::fs::read_dir(tree_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| format!("Reading tree directory {tree_dir:?}"))
+        }
+    };
+
+    let mut results = Vec::new();
+    for entry in entries {
+        let entry = entry.wrap_err_with(|| format!("Reading tree directory {tree_dir:?}"))?;
+        let result_path = entry.path().join("result");
+        let contents = match std::fs::read_to_string(&result_path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                debug!(?err, ?result_path, "Could not read cached test result");
+                continue;
+            }
+        };
+        if contents.is_empty() {
+            continue;
+        }
+        let result: Result<SerializedTestResult, _> = serde_json::from_str(&contents);
+        if let Ok(result) = &result {
+            if result.schema_version != TEST_RESULT_SCHEMA_VERSION {
+                debug!(
+                    ?result_path,
+                    schema_version = result.schema_version,
+                    "Skipping cached test result with different schema version"
+                );
+                continue;
+            }
+        }
+        results.push((result_path, result));
+    }
+
+    results.sort_by(|(lhs_path, lhs), (rhs_path, rhs)| {
+        let lhs_timestamp = lhs.as_ref().ok().and_then(|result| result.timestamp);
+        let rhs_timestamp = rhs.as_ref().ok().and_then(|result| result.timestamp);
+        lhs_timestamp
+            .partial_cmp(&rhs_timestamp)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| lhs_path.cmp(rhs_path))
+    });
+    Ok(results)
+}
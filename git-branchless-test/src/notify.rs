@@ -0,0 +1,74 @@
+//! Desktop notifications for when a long-running test run finishes.
+
+use std::process::{Command, Stdio};
+
+use tracing::{instrument, warn};
+
+use crate::{TestResults, TestStatusCategory};
+
+/// The default minimum duration of a test run, in seconds, before a
+/// notification is sent.
+pub(crate) const DEFAULT_NOTIFY_THRESHOLD_SECONDS: i32 = 30;
+
+/// Send a desktop notification summarizing the results of the test run. If no
+/// notification backend is available, this does nothing.
+#[instrument]
+pub(crate) fn notify_test_results(command: &str, test_results: &TestResults) {
+    let mut num_passed = 0;
+    let mut num_failed = 0;
+    let mut num_skipped = 0;
+    for test_output in test_results.test_outputs.values() {
+        match test_output.test_status.get_category() {
+            TestStatusCategory::Passed => num_passed += 1,
+            TestStatusCategory::Failed => num_failed += 1,
+            TestStatusCategory::Skipped => num_skipped += 1,
+        }
+    }
+
+    let title = format!("git test run: {command}");
+    let body = format!("{num_passed} passed, {num_failed} failed, {num_skipped} skipped");
+    let mut notify_command = match make_notify_command(&title, &body) {
+        Some(notify_command) => notify_command,
+        None => return,
+    };
+    let result = notify_command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match result {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!(?status, "Notification command exited unsuccessfully"),
+        Err(err) => warn!(?err, "Could not run notification command"),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn make_notify_command(title: &str, body: &str) -> Option<Command> {
+    fn quote(s: &str) -> String {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        quote(body),
+        quote(title)
+    ));
+    Some(command)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn make_notify_command(title: &str, body: &str) -> Option<Command> {
+    let mut command = Command::new("notify-send");
+    command
+        .arg("--app-name=git-branchless")
+        .arg(title)
+        .arg(body);
+    Some(command)
+}
+
+#[cfg(not(unix))]
+fn make_notify_command(_title: &str, _body: &str) -> Option<Command> {
+    None
+}
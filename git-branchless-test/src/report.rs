@@ -0,0 +1,277 @@
+//! Machine-readable reports of the results of a test run, for consumption by
+//! other tools.
+
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use bstr::ByteSlice;
+use eyre::WrapErr;
+use itertools::Itertools;
+use lib::core::effects::Effects;
+use lib::git::{Repo, SerializedNonZeroOid};
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::{read_test_output, TestOutput, TestResults, TestStatusCategory};
+
+/// The JSON representation of the result of testing a single commit.
+#[derive(Debug, Serialize)]
+struct JsonTestOutput<'a> {
+    commit_oid: SerializedNonZeroOid,
+    status: &'static str,
+    exit_code: Option<i32>,
+    cached: bool,
+    duration_ms: Option<u64>,
+    stdout: &'a Path,
+    stderr: &'a Path,
+}
+
+/// Write the results of the test run as a JSON array, in the same order as the
+/// commits were provided.
+#[instrument]
+pub(crate) fn write_json(effects: &Effects, test_results: &TestResults) -> eyre::Result<()> {
+    let json_test_outputs: Vec<JsonTestOutput> = test_results
+        .test_outputs
+        .iter()
+        .map(|(commit_oid, test_output)| {
+            let TestOutput {
+                temp_dir: _,
+                result_path: _,
+                stdout_path,
+                stderr_path,
+                test_status,
+                duration,
+                interrupted: _,
+            } = test_output;
+            JsonTestOutput {
+                commit_oid: SerializedNonZeroOid(*commit_oid),
+                status: test_status.get_name(),
+                exit_code: test_status.get_exit_code(),
+                cached: test_status.is_cached(),
+                duration_ms: duration.and_then(|duration| u64::try_from(duration.as_millis()).ok()),
+                stdout: stdout_path,
+                stderr: stderr_path,
+            }
+        })
+        .collect();
+    writeln!(
+        effects.get_output_stream(),
+        "{}",
+        serde_json::to_string_pretty(&json_test_outputs)?
+    )?;
+    Ok(())
+}
+
+/// Write the results of the test run in the Test Anything Protocol (TAP)
+/// format. Results are numbered in the order that the commits were provided,
+/// regardless of the order in which they finished.
+#[instrument]
+pub(crate) fn write_tap(
+    effects: &Effects,
+    repo: &Repo,
+    test_results: &TestResults,
+) -> eyre::Result<()> {
+    let mut output = effects.get_output_stream();
+    writeln!(output, "1..{}", test_results.test_outputs.len())?;
+    for (i, (commit_oid, test_output)) in test_results.test_outputs.iter().enumerate() {
+        let commit = repo.find_commit_or_fail(*commit_oid)?;
+        let description = format!(
+            "{} {}",
+            commit.get_short_oid()?,
+            commit.get_summary()?.to_str_lossy()
+        );
+        let test_number = i + 1;
+        let test_status = &test_output.test_status;
+        match test_status.get_category() {
+            TestStatusCategory::Passed => {
+                writeln!(output, "ok {test_number} - {description}")?;
+            }
+
+            TestStatusCategory::Failed => {
+                writeln!(output, "not ok {test_number} - {description}")?;
+                writeln!(output, "# commit: {commit_oid}")?;
+                writeln!(output, "# status: {}", test_status.get_name())?;
+                if let Some(exit_code) = test_status.get_exit_code() {
+                    writeln!(output, "# exit code: {exit_code}")?;
+                }
+            }
+
+            TestStatusCategory::Skipped => {
+                writeln!(
+                    output,
+                    "ok {test_number} - {description} # SKIP {}",
+                    test_status.get_name()
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write the first failing commit(s) found by a search, one per line, in the
+/// form `culprit <full-oid>`. Nothing is written if no failing commit was found.
+#[instrument]
+pub(crate) fn write_porcelain(effects: &Effects, test_results: &TestResults) -> eyre::Result<()> {
+    let mut output = effects.get_output_stream();
+    for commit_oid in test_results.search_bounds.failure.iter().sorted() {
+        writeln!(output, "culprit {commit_oid}")?;
+    }
+    Ok(())
+}
+
+/// Write the results of the test run as CSV, with one row per commit, in the
+/// same order as the commits were provided.
+#[instrument]
+pub(crate) fn write_csv(
+    effects: &Effects,
+    repo: &Repo,
+    test_results: &TestResults,
+) -> eyre::Result<()> {
+    let mut output = effects.get_output_stream();
+    writeln!(
+        output,
+        "oid,short_oid,subject,status,exit_code,cached,duration_ms"
+    )?;
+    for (commit_oid, test_output) in &test_results.test_outputs {
+        let commit = repo.find_commit_or_fail(*commit_oid)?;
+        let TestOutput {
+            temp_dir: _,
+            result_path: _,
+            stdout_path: _,
+            stderr_path: _,
+            test_status,
+            duration,
+            interrupted: _,
+        } = test_output;
+        let fields = [
+            commit_oid.to_string(),
+            commit.get_short_oid()?,
+            commit.get_summary()?.to_str_lossy().into_owned(),
+            test_status.get_name().to_string(),
+            test_status
+                .get_exit_code()
+                .map(|exit_code| exit_code.to_string())
+                .unwrap_or_default(),
+            test_status.is_cached().to_string(),
+            duration
+                .map(|duration| duration.as_millis().to_string())
+                .unwrap_or_default(),
+        ];
+        writeln!(
+            output,
+            "{}",
+            fields.iter().map(|field| csv_escape(field)).join(",")
+        )?;
+    }
+    Ok(())
+}
+
+/// Escape a field for inclusion in a CSV row. Fields containing separators,
+/// quotes, or line breaks are quoted, with any quotes inside them doubled.
+fn csv_escape(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escape a string for inclusion in XML text or attribute values. Characters
+/// which are not allowed in XML documents at all (such as most control
+/// characters) are dropped.
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write the results of the test run to `path` as a JUnit-style XML report,
+/// with one test case per commit.
+#[instrument]
+pub(crate) fn write_junit(
+    path: &Path,
+    command: &str,
+    test_results: &TestResults,
+) -> eyre::Result<()> {
+    let mut num_failures = 0;
+    let mut num_skipped = 0;
+    let mut total_duration = Duration::ZERO;
+    let mut test_cases = String::new();
+    for (commit_oid, test_output) in &test_results.test_outputs {
+        let TestOutput {
+            temp_dir: _,
+            result_path: _,
+            stdout_path: _,
+            stderr_path,
+            test_status,
+            duration,
+            interrupted: _,
+        } = test_output;
+        let duration = duration.unwrap_or_default();
+        total_duration += duration;
+
+        writeln!(
+            test_cases,
+            r#"    <testcase name="{}" classname="{}" time="{:.3}">"#,
+            commit_oid,
+            xml_escape(command),
+            duration.as_secs_f64(),
+        )?;
+        match test_status.get_category() {
+            TestStatusCategory::Failed => {
+                num_failures += 1;
+                let stderr = read_test_output(stderr_path)
+                    .wrap_err_with(|| format!("Reading stderr file {stderr_path:?}"))?;
+                writeln!(
+                    test_cases,
+                    r#"      <failure message="{}">{}</failure>"#,
+                    test_status.get_name(),
+                    xml_escape(&String::from_utf8_lossy(&stderr)),
+                )?;
+            }
+
+            TestStatusCategory::Skipped => {
+                num_skipped += 1;
+                writeln!(
+                    test_cases,
+                    r#"      <skipped message="{}"/>"#,
+                    test_status.get_name(),
+                )?;
+            }
+
+            TestStatusCategory::Passed => {}
+        }
+        writeln!(test_cases, "    </testcase>")?;
+    }
+
+    let mut report = String::new();
+    writeln!(report, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(report, "<testsuites>")?;
+    writeln!(
+        report,
+        r#"  <testsuite name="{}" tests="{}" failures="{}" skipped="{}" time="{:.3}">"#,
+        xml_escape(command),
+        test_results.test_outputs.len(),
+        num_failures,
+        num_skipped,
+        total_duration.as_secs_f64(),
+    )?;
+    report.push_str(&test_cases);
+    writeln!(report, "  </testsuite>")?;
+    writeln!(report, "</testsuites>")?;
+
+    std::fs::write(path, report).wrap_err_with(|| format!("Writing JUnit report to {path:?}"))?;
+    Ok(())
+}
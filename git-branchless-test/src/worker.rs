@@ -7,6 +7,8 @@ use crossbeam::channel::Sender;
 use lib::core::effects::ProgressHandle;
 use tracing::{debug, warn};
 
+use crate::interrupt::is_interrupted;
+
 pub(crate) type WorkerId = usize;
 
 pub trait Job: Clone + Debug + Eq + Hash {}
@@ -16,6 +18,9 @@ impl<T: Clone + Debug + Eq + Hash> Job for T {}
 pub(crate) enum JobResult<J: Job, Output> {
     Done(J, Output),
     Error(WorkerId, J, String),
+    /// The job was accepted after the user interrupted testing, so it wasn't
+    /// run.
+    Cancelled(J),
 }
 
 #[derive(Debug)]
@@ -58,6 +63,15 @@ impl<J: Job> WorkQueue<J> {
         self.cond_var.notify_all();
     }
 
+    /// Remove all jobs which haven't yet been accepted by a worker, and return
+    /// them.
+    pub fn clear(&self) -> Vec<J> {
+        let mut state = self.state.lock().unwrap();
+        let jobs = state.jobs.drain(..).collect();
+        self.cond_var.notify_all();
+        jobs
+    }
+
     pub fn close(&self) {
         let mut state = self.state.lock().unwrap();
         state.jobs.clear();
@@ -112,6 +126,7 @@ pub(crate) fn worker<J: Job, Output, Context>(
     result_tx: Sender<JobResult<J, Output>>,
     setup: impl Fn() -> eyre::Result<Context>,
     f: impl Fn(J, &Context) -> eyre::Result<Output>,
+    teardown: impl Fn(&Context),
 ) {
     debug!(?worker_id, "Worker spawned");
 
@@ -139,8 +154,18 @@ pub(crate) fn worker<J: Job, Output, Context>(
         Ok(should_terminate)
     };
 
+    let mut has_run_job = false;
     while let Some(job) = work_queue.pop_blocking() {
+        if is_interrupted() {
+            debug!(?worker_id, ?job, "Worker cancelling job due to interrupt");
+            if result_tx.send(JobResult::Cancelled(job)).is_err() {
+                break;
+            }
+            continue;
+        }
+
         debug!(?worker_id, ?job, "Worker accepted job");
+        has_run_job = true;
         let job_result = run_job(job.clone());
         debug!(?worker_id, ?job, "Worker finished job");
         match job_result {
@@ -157,5 +182,9 @@ pub(crate) fn worker<J: Job, Output, Context>(
             }
         }
     }
+    if has_run_job {
+        debug!(?worker_id, "Worker tearing down");
+        teardown(&context);
+    }
     debug!(?worker_id, "Worker exiting");
 }
@@ -166,6 +166,7 @@ fn test_test_cached_results() -> eyre::Result<()> {
         ✓ Passed (cached): 1b0d484 Revert "create test3.txt"
         Ran command on 3 commits: exit 0:
         3 passed, 0 failed, 0 skipped
+        1/3 results from cache (33%)
         hint: there was 1 cached test result
         hint: to clear these cached results, run: git test clean "stack() | @"
         hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
@@ -185,6 +186,7 @@ fn test_test_cached_results() -> eyre::Result<()> {
         ✓ Passed (cached): 1b0d484 Revert "create test3.txt"
         Ran command on 3 commits: exit 0:
         3 passed, 0 failed, 0 skipped
+        3/3 results from cache (100%)
         hint: there were 3 cached test results
         hint: to clear these cached results, run: git test clean "stack() | @"
         hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
@@ -258,6 +260,7 @@ fn test_test_verbosity() -> eyre::Result<()> {
         <no output>
         Ran command on 1 commit: bash test.sh 10:
         1 passed, 0 failed, 0 skipped
+        1/1 result from cache (100%)
         hint: there was 1 cached test result
         hint: to clear these cached results, run: git test clean "stack() | @"
         hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
@@ -321,6 +324,7 @@ fn test_test_verbosity() -> eyre::Result<()> {
         <no output>
         Ran command on 1 commit: bash test.sh 15:
         1 passed, 0 failed, 0 skipped
+        1/1 result from cache (100%)
         hint: there was 1 cached test result
         hint: to clear these cached results, run: git test clean "stack() | @"
         hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
@@ -330,57 +334,82 @@ fn test_test_verbosity() -> eyre::Result<()> {
     Ok(())
 }
 
+#[cfg(unix)] // Paths don't match on Windows.
 #[test]
-fn test_test_show() -> eyre::Result<()> {
+fn test_test_max_output_bytes() -> eyre::Result<()> {
     let git = make_git()?;
-    git.init_repo()?;
 
+    git.init_repo()?;
     git.detach_head()?;
-    git.commit_file("test1", 1)?;
     git.commit_file("test2", 2)?;
 
+    write_test_script(&git)?;
+    git.run(&["config", "branchless.test.maxOutputBytes", "29"])?;
+
     {
-        let (stdout, _stderr) = git.branchless("test", &["run", "-x", "echo hi", "."])?;
+        let (stdout, _stderr) = git.branchless("test", &["run", "-x", "bash test.sh 10", "-v"])?;
         insta::assert_snapshot!(stdout, @r###"
         branchless: running command: <git-executable> diff --quiet
         Calling Git for on-disk rebase...
         branchless: running command: <git-executable> rebase --continue
         Using command execution strategy: working-copy
         branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: 96d1c37 create test2.txt
-        Ran command on 1 commit: echo hi:
+        ✓ Passed: fe65c1f create test2.txt
+        Stdout: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__10/stdout
+        This is line 1
+        This is line 2
+        <output truncated>
+        Stderr: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__10/stderr
+        <no output>
+        Ran command on 1 commit: bash test.sh 10:
         1 passed, 0 failed, 0 skipped
         "###);
     }
 
-    {
-        let (stdout, stderr) = git.branchless("test", &["show", "-x", "echo hi"])?;
-        insta::assert_snapshot!(stderr, @"");
-        insta::assert_snapshot!(stdout, @r###"
-        No cached test data for 62fc20d create test1.txt
-        ✓ Passed (cached): 96d1c37 create test2.txt
-        hint: to see more detailed output, re-run with -v/--verbose
-        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
-        "###);
-    }
+    Ok(())
+}
+
+#[cfg(unix)] // Paths don't match on Windows.
+#[test]
+fn test_test_compress_output() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test2", 2)?;
+
+    write_test_script(&git)?;
+    git.run(&["config", "branchless.test.compressOutput", "true"])?;
 
     {
-        let (stdout, stderr) = git.branchless("test", &["clean"])?;
-        insta::assert_snapshot!(stderr, @"");
+        let (stdout, _stderr) = git.branchless("test", &["run", "-x", "bash test.sh 2", "-v"])?;
         insta::assert_snapshot!(stdout, @r###"
-        Cleaning results for 62fc20d create test1.txt
-        Cleaning results for 96d1c37 create test2.txt
-        Cleaned 2 cached test results.
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: fe65c1f create test2.txt
+        Stdout: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__2/stdout.gz
+        This is line 1
+        This is line 2
+        Stderr: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__2/stderr.gz
+        <no output>
+        Ran command on 1 commit: bash test.sh 2:
+        1 passed, 0 failed, 0 skipped
         "###);
     }
 
     {
-        let (stdout, stderr) = git.branchless("test", &["show", "-x", "echo hi"])?;
-        insta::assert_snapshot!(stderr, @"");
+        let (stdout, _stderr) = git.branchless("test", &["show", "-x", "bash test.sh 2", "-v"])?;
         insta::assert_snapshot!(stdout, @r###"
-        No cached test data for 62fc20d create test1.txt
-        No cached test data for 96d1c37 create test2.txt
-        hint: to see more detailed output, re-run with -v/--verbose
+        ✓ Passed (cached): fe65c1f create test2.txt
+        Stdout: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__2/stdout.gz
+        This is line 1
+        This is line 2
+        Stderr: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__2/stderr.gz
+        <no output>
+        hint: to see more detailed output, re-run with -vv/--verbose --verbose
         hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
@@ -388,150 +417,220 @@ fn test_test_show() -> eyre::Result<()> {
     Ok(())
 }
 
+#[cfg(unix)] // Hard link counts aren't available on Windows.
 #[test]
-fn test_test_command_alias() -> eyre::Result<()> {
+fn test_test_dedupe_output() -> eyre::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    git.run(&["config", "branchless.test.dedupeOutput", "true"])?;
+    git.branchless(
+        "test",
+        &[
+            "run",
+            "--strategy",
+            "worktree",
+            "--exec",
+            "echo hello",
+            "stack()",
+        ],
+    )?;
+
+    // The identical stdout of each commit is stored once. The empty stderr
+    // isn't stored at all.
+    let outputs_dir = git.repo_path.join(".git/branchless/test/outputs");
+    let outputs = std::fs::read_dir(&outputs_dir)?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(std::fs::read_to_string(outputs[0].path())?, "hello\n");
+    assert_eq!(outputs[0].metadata()?.nlink(), 4);
+
+    git.branchless("test", &["clean", "--all"])?;
+    assert_eq!(std::fs::read_dir(&outputs_dir)?.count(), 0);
+
+    Ok(())
+}
+
+#[cfg(unix)] // Paths don't match on Windows.
+#[test]
+fn test_test_output_context_lines() -> eyre::Result<()> {
     let git = make_git()?;
+
     git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test2", 2)?;
+
+    write_test_script(&git)?;
+    let long_command = "bash test.sh 15";
+    git.branchless("test", &["run", "-x", long_command])?;
 
     {
-        let (stdout, _stderr) = git.branchless_with_options(
+        let (stdout, _stderr) = git.branchless(
             "test",
-            &["run"],
-            &GitRunOptions {
-                expected_exit_code: 1,
-                ..Default::default()
-            },
+            &["show", "-x", long_command, "-v", "--context", "2"],
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        Could not determine test command to run. No test command was provided with -c/--command or
-        -x/--exec, and the configuration value 'branchless.test.alias.default' was not set.
-
-        To configure a default test command, run: git config branchless.test.alias.default <command>
-        To run a specific test command, run: git test run -x <command>
-        To run a specific command alias, run: git test run -c <alias>
+        ✓ Passed (cached): fe65c1f create test2.txt
+        Stdout: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__15/stdout
+        This is line 1
+        This is line 2
+        <11 more lines>
+        This is line 14
+        This is line 15
+        Stderr: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__15/stderr
+        <no output>
         "###);
     }
 
-    git.run(&["config", "branchless.test.alias.foo", "echo foo"])?;
+    git.run(&["config", "branchless.test.outputContextLines", "7"])?;
     {
-        let (stdout, _stderr) = git.branchless_with_options(
-            "test",
-            &["run"],
-            &GitRunOptions {
-                expected_exit_code: 1,
-                ..Default::default()
-            },
-        )?;
+        let (stdout, _stderr) = git.branchless("test", &["show", "-x", long_command, "-v"])?;
         insta::assert_snapshot!(stdout, @r###"
-        Could not determine test command to run. No test command was provided with -c/--command or
-        -x/--exec, and the configuration value 'branchless.test.alias.default' was not set.
-
-        To configure a default test command, run: git config branchless.test.alias.default <command>
-        To run a specific test command, run: git test run -x <command>
-        To run a specific command alias, run: git test run -c <alias>
-
-        These are the currently-configured command aliases:
-        - branchless.test.alias.foo = "echo foo"
+        ✓ Passed (cached): fe65c1f create test2.txt
+        Stdout: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__15/stdout
+        This is line 1
+        This is line 2
+        This is line 3
+        This is line 4
+        This is line 5
+        This is line 6
+        This is line 7
+        <1 more lines>
+        This is line 9
+        This is line 10
+        This is line 11
+        This is line 12
+        This is line 13
+        This is line 14
+        This is line 15
+        Stderr: <repo-path>/.git/branchless/test/48bb2464c55090a387ed70b3d229705a94856efb/bash__test.sh__15/stderr
+        <no output>
         "###);
     }
 
+    git.run(&["config", "branchless.test.outputContextLines", "1000000"])?;
     {
         let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "-c", "nonexistent"],
+            &["show", "-x", long_command, "-v"],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        The test command alias "nonexistent" was not defined.
-
-        To create it, run: git config branchless.test.alias.nonexistent <command>
-        Or use the -x/--exec flag instead to run a test command without first creating an alias.
-
-        These are the currently-configured command aliases:
-        - branchless.test.alias.foo = "echo foo"
+        The number of lines of context for the test output (1000000) cannot be more than 10000.
         "###);
     }
 
-    git.run(&["config", "branchless.test.alias.default", "echo default"])?;
+    Ok(())
+}
+
+#[test]
+fn test_test_show() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
     {
-        let (stdout, _stderr) = git.branchless("test", &["run"])?;
+        let (stdout, _stderr) = git.branchless("test", &["run", "-x", "echo hi", "."])?;
         insta::assert_snapshot!(stdout, @r###"
         branchless: running command: <git-executable> diff --quiet
         Calling Git for on-disk rebase...
         branchless: running command: <git-executable> rebase --continue
         Using command execution strategy: working-copy
         branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: f777ecc create initial.txt
-        Ran command on 1 commit: echo default:
+        ✓ Passed: 96d1c37 create test2.txt
+        Ran command on 1 commit: echo hi:
         1 passed, 0 failed, 0 skipped
         "###);
     }
 
     {
-        let (stdout, _stderr) = git.branchless("test", &["run", "-c", "foo"])?;
+        let (stdout, stderr) = git.branchless("test", &["show", "-x", "echo hi"])?;
+        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: f777ecc create initial.txt
-        Ran command on 1 commit: echo foo:
-        1 passed, 0 failed, 0 skipped
+        No cached test data for 62fc20d create test1.txt
+        ✓ Passed (cached): 96d1c37 create test2.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
 
     {
-        let (stdout, _stderr) = git.branchless_with_options(
-            "test",
-            &["run", "-c", "foo bar baz"],
-            &GitRunOptions {
-                expected_exit_code: 1,
-                ..Default::default()
-            },
-        )?;
+        let (stdout, stderr) = git.branchless("test", &["clean"])?;
+        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        The test command alias "foo bar baz" was not defined.
-
-        To create it, run: git config branchless.test.alias.foo bar baz <command>
-        Or use the -x/--exec flag instead to run a test command without first creating an alias.
+        Cleaning results for 62fc20d create test1.txt
+        Cleaning results for 96d1c37 create test2.txt
+        Cleaned 2 cached test results.
+        "###);
+    }
 
-        These are the currently-configured command aliases:
-        - branchless.test.alias.foo = "echo foo"
-        - branchless.test.alias.default = "echo default"
+    {
+        let (stdout, stderr) = git.branchless("test", &["show", "-x", "echo hi"])?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        No cached test data for 62fc20d create test1.txt
+        No cached test data for 96d1c37 create test2.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
 
     Ok(())
 }
 
-#[cfg(unix)] // Paths don't match on Windows.
 #[test]
-fn test_test_worktree_strategy() -> eyre::Result<()> {
+fn test_test_show_filters() -> eyre::Result<()> {
     let git = make_git()?;
     git.init_repo()?;
 
+    git.detach_head()?;
     git.commit_file("test1", 1)?;
-    git.write_file_txt("test1", "Updated contents\n")?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    git.branchless_with_options(
+        "test",
+        &[
+            "run",
+            "--strategy",
+            "worktree",
+            "-x",
+            "if test -f test3.txt; then exit 125; fi; ! test -f test2.txt",
+            "stack()",
+        ],
+        &GitRunOptions {
+            expected_exit_code: 1,
+            ..Default::default()
+        },
+    )?;
 
     {
-        let (stdout, stderr) = git.branchless_with_options(
+        let (stdout, stderr) = git.branchless(
             "test",
-            &["run", "--strategy", "working-copy", "-x", "echo hello", "@"],
-            &GitRunOptions {
-                expected_exit_code: 1,
-                ..Default::default()
-            },
+            &[
+                "show",
+                "--failed-only",
+                "-x",
+                "if test -f test3.txt; then exit 125; fi; ! test -f test2.txt",
+            ],
         )?;
         insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        This operation would modify the working copy, but you have uncommitted changes
-        in your working copy which might be overwritten as a result.
-        Commit your changes and then try again.
+        X Failed (cached, exit code 1): 96d1c37 create test2.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
 
@@ -539,128 +638,151 @@ fn test_test_worktree_strategy() -> eyre::Result<()> {
         let (stdout, stderr) = git.branchless(
             "test",
             &[
-                "run",
-                "--strategy",
-                "worktree",
+                "show",
+                "--passed-only",
                 "-x",
-                "echo hello",
-                "-vv",
-                "@",
+                "if test -f test3.txt; then exit 125; fi; ! test -f test2.txt",
             ],
         )?;
         insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        Using command execution strategy: worktree
-        ✓ Passed: 62fc20d create test1.txt
-        Stdout: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stdout
-        hello
-        Stderr: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stderr
-        <no output>
-        Ran command on 1 commit: echo hello:
-        1 passed, 0 failed, 0 skipped
-        "###);
+        ✓ Passed (cached): 62fc20d create test1.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
+        "###);
     }
 
     {
         let (stdout, stderr) = git.branchless(
             "test",
             &[
-                "run",
-                "--strategy",
-                "worktree",
+                "show",
+                "--skipped-only",
                 "-x",
-                "echo hello",
-                "-vv",
-                "@",
+                "if test -f test3.txt; then exit 125; fi; ! test -f test2.txt",
             ],
         )?;
         insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        Using command execution strategy: worktree
-        ✓ Passed (cached): 62fc20d create test1.txt
-        Stdout: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stdout
-        hello
-        Stderr: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stderr
-        <no output>
-        Ran command on 1 commit: echo hello:
-        1 passed, 0 failed, 0 skipped
-        hint: there was 1 cached test result
-        hint: to clear these cached results, run: git test clean "@"
-        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        ! Exit code indicated to skip this commit (exit code 125): 70deb1e create test3.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
 
     Ok(())
 }
 
-#[cfg(unix)] // Paths don't match on Windows.
 #[test]
-fn test_test_config_strategy() -> eyre::Result<()> {
+fn test_test_clean_filters() -> eyre::Result<()> {
     let git = make_git()?;
     git.init_repo()?;
 
     git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
 
-    git.write_file(
-        "test.sh",
-        "#!/bin/sh
-echo hello
-",
+    git.branchless_with_options(
+        "test",
+        &[
+            "run",
+            "--strategy",
+            "worktree",
+            "-x",
+            "! test -f test2.txt",
+            "HEAD~ | HEAD",
+        ],
+        &GitRunOptions {
+            expected_exit_code: 1,
+            ..Default::default()
+        },
     )?;
-    git.commit_file("test2", 2)?;
 
-    git.write_file_txt("test1", "Updated contents\n")?;
+    {
+        let (stdout, stderr) =
+            git.branchless("test", &["clean", "--failed-only", "HEAD~ | HEAD"])?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Nothing to clean for 62fc20d create test1.txt
+        Cleaning failed results for 96d1c37 create test2.txt
+        Cleaned 1 cached test result.
+        "###);
+    }
 
-    git.run(&["config", "branchless.test.alias.default", "bash test.sh"])?;
-    git.run(&["config", "branchless.test.strategy", "working-copy"])?;
     {
-        let (stdout, stderr) = git.branchless_with_options(
+        let (stdout, stderr) = git.branchless(
             "test",
-            &["run", "@"],
-            &GitRunOptions {
-                expected_exit_code: 1,
-                ..Default::default()
-            },
+            &["show", "-x", "! test -f test2.txt", "HEAD~ | HEAD"],
         )?;
         insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        This operation would modify the working copy, but you have uncommitted changes
-        in your working copy which might be overwritten as a result.
-        Commit your changes and then try again.
+        ✓ Passed (cached): 62fc20d create test1.txt
+        No cached test data for 96d1c37 create test2.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
 
-    git.run(&["config", "branchless.test.strategy", "worktree"])?;
     {
-        let (stdout, stderr) = git.branchless("test", &["run", "-vv", "@"])?;
+        let (stdout, stderr) =
+            git.branchless("test", &["clean", "--passed-only", "HEAD~ | HEAD"])?;
         insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        Using command execution strategy: worktree
-        ✓ Passed: c82ebfa create test2.txt
-        Stdout: <repo-path>/.git/branchless/test/a3ae41e24abf7537423d8c72d07df7af456de6dd/bash__test.sh/stdout
-        hello
-        Stderr: <repo-path>/.git/branchless/test/a3ae41e24abf7537423d8c72d07df7af456de6dd/bash__test.sh/stderr
-        <no output>
-        Ran command on 1 commit: bash test.sh:
-        1 passed, 0 failed, 0 skipped
+        Cleaning passed results for 62fc20d create test1.txt
+        Nothing to clean for 96d1c37 create test2.txt
+        Cleaned 1 cached test result.
         "###);
     }
 
-    git.run(&["config", "branchless.test.strategy", "invalid-value"])?;
+    Ok(())
+}
+
+#[test]
+fn test_test_clean_all() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test2", 2)?;
+
+    git.branchless(
+        "test",
+        &["run", "--strategy", "worktree", "-x", "true", "@"],
+    )?;
+
     {
-        let (stdout, stderr) = git.branchless_with_options(
-            "test",
-            &["run", "-vv", "@"],
-            &GitRunOptions {
-                expected_exit_code: 1,
-                ..Default::default()
-            },
-        )?;
+        let (stdout, stderr) = git.branchless("test", &["clean", "--all", "--dry-run"])?;
         insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        Invalid value for config value branchless.test.strategy: invalid-value
-        Expected one of: working-copy, worktree
+        Would clean results for tree 48bb2464c55090a387ed70b3d229705a94856efb
+        Would clean 1 cached test result.
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless("test", &["show", "-x", "true", "@"])?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        ✓ Passed (cached): fe65c1f create test2.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless("test", &["clean", "--all"])?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Cleaning results for tree 48bb2464c55090a387ed70b3d229705a94856efb
+        Cleaned 1 cached test result.
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless("test", &["show", "-x", "true", "@"])?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        No cached test data for fe65c1f create test2.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
 
@@ -668,527 +790,4240 @@ echo hello
 }
 
 #[test]
-fn test_test_jobs_argument_handling() -> eyre::Result<()> {
+fn test_test_command_alias() -> eyre::Result<()> {
     let git = make_git()?;
-
-    if !git.supports_reference_transactions()? {
-        return Ok(());
-    }
     git.init_repo()?;
 
-    git.detach_head()?;
-    git.commit_file("test1", 1)?;
-    git.commit_file("test2", 2)?;
-    git.run(&["config", "branchless.test.alias.default", "exit 0"])?;
-
     {
-        let (stdout, stderr) = git.branchless_with_options(
+        let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--strategy", "working-copy", "--jobs", "0"],
+            &["run"],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
-        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        The --jobs option can only be used with --strategy worktree, but --strategy working-copy was provided instead.
+        Could not determine test command to run. No test command was provided with -c/--command or
+        -x/--exec, and the configuration value 'branchless.test.alias.default' was not set.
+
+        To configure a default test command, run: git config branchless.test.alias.default <command>
+        To run a specific test command, run: git test run -x <command>
+        To run a specific command alias, run: git test run -c <alias>
         "###);
     }
 
+    git.run(&["config", "branchless.test.alias.foo", "echo foo"])?;
     {
-        // `--jobs 1` is allowed for `--strategy working-copy`, since that's the default anyways.
-        let (stdout, _stderr) = git.branchless(
+        let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--strategy", "working-copy", "--jobs", "1"],
+            &["run"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: 62fc20d create test1.txt
-        ✓ Passed: 96d1c37 create test2.txt
-        Ran command on 2 commits: exit 0:
-        2 passed, 0 failed, 0 skipped
+        Could not determine test command to run. No test command was provided with -c/--command or
+        -x/--exec, and the configuration value 'branchless.test.alias.default' was not set.
+
+        To configure a default test command, run: git config branchless.test.alias.default <command>
+        To run a specific test command, run: git test run -x <command>
+        To run a specific command alias, run: git test run -c <alias>
+
+        These are the currently-configured command aliases:
+        - branchless.test.alias.foo = "echo foo"
         "###);
     }
 
     {
-        let (stdout, stderr) = git.branchless_with_options(
+        let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--strategy", "working-copy", "--jobs", "2"],
+            &["run", "-c", "nonexistent"],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
-        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        The --jobs option can only be used with --strategy worktree, but --strategy working-copy was provided instead.
+        The test command alias "nonexistent" was not defined.
+
+        To create it, run: git config branchless.test.alias.nonexistent <command>
+        Or use the -x/--exec flag instead to run a test command without first creating an alias.
+
+        These are the currently-configured command aliases:
+        - branchless.test.alias.foo = "echo foo"
         "###);
     }
 
+    git.run(&["config", "branchless.test.alias.default", "echo default"])?;
     {
-        let (stdout, stderr) = git.branchless("test", &["run", "--jobs", "2"])?;
-        insta::assert_snapshot!(stderr, @"");
+        let (stdout, _stderr) = git.branchless("test", &["run"])?;
         insta::assert_snapshot!(stdout, @r###"
-        Using command execution strategy: worktree
-        ✓ Passed (cached): 62fc20d create test1.txt
-        ✓ Passed (cached): 96d1c37 create test2.txt
-        Ran command on 2 commits: exit 0:
-        2 passed, 0 failed, 0 skipped
-        hint: there were 2 cached test results
-        hint: to clear these cached results, run: git test clean "stack() | @"
-        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: f777ecc create initial.txt
+        Ran command on 1 commit: echo default:
+        1 passed, 0 failed, 0 skipped
         "###);
     }
 
     {
-        let (stdout, stderr) = git.branchless(
-            "test",
-            &["run", "--exec", "true", "--interactive", "--jobs", "1"],
-        )?;
-        insta::assert_snapshot!(stderr, @r###"
-        Stopped at 96d1c37 (create test2.txt)
-        branchless: processing 1 update: ref HEAD
-        "###);
+        let (stdout, _stderr) = git.branchless("test", &["run", "-c", "foo"])?;
         insta::assert_snapshot!(stdout, @r###"
         branchless: running command: <git-executable> diff --quiet
         Calling Git for on-disk rebase...
         branchless: running command: <git-executable> rebase --continue
-        You are now at: 62fc20d create test1.txt
-        To mark this commit as passed,run:   exit 0
-        To mark this commit as failed, run:  exit 1
-        To mark this commit as skipped, run: exit 125
-        To abort testing entirely, run:      exit 127
-        You are now at: 96d1c37 create test2.txt
-        To mark this commit as passed,run:   exit 0
-        To mark this commit as failed, run:  exit 1
-        To mark this commit as skipped, run: exit 125
-        To abort testing entirely, run:      exit 127
+        Using command execution strategy: working-copy
         branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (interactive): 62fc20d create test1.txt
-        ✓ Passed (interactive): 96d1c37 create test2.txt
-        Ran command on 2 commits: true:
-        2 passed, 0 failed, 0 skipped
+        ✓ Passed: f777ecc create initial.txt
+        Ran command on 1 commit: echo foo:
+        1 passed, 0 failed, 0 skipped
         "###);
     }
 
     {
-        let (stdout, stderr) = git.branchless_with_options(
+        let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--exec", "true", "--interactive", "--jobs", "2"],
+            &["run", "-c", "foo bar baz"],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
-        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        The --jobs option cannot be used with the --interactive option.
-        "###);
-    }
+        The test command alias "foo bar baz" was not defined.
 
-    git.run(&["config", "branchless.test.jobs", "2"])?;
+        To create it, run: git config branchless.test.alias.foo bar baz <command>
+        Or use the -x/--exec flag instead to run a test command without first creating an alias.
 
-    {
-        let (stdout, stderr) = git.branchless("test", &["run", "--strategy", "working-copy"])?;
-        insta::assert_snapshot!(stderr, @r###"
-        Stopped at 96d1c37 (create test2.txt)
-        branchless: processing 1 update: ref HEAD
-        "###);
-        insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (cached): 62fc20d create test1.txt
-        ✓ Passed (cached): 96d1c37 create test2.txt
-        Ran command on 2 commits: exit 0:
-        2 passed, 0 failed, 0 skipped
-        hint: there were 2 cached test results
-        hint: to clear these cached results, run: git test clean "stack() | @"
-        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        These are the currently-configured command aliases:
+        - branchless.test.alias.foo = "echo foo"
+        - branchless.test.alias.default = "echo default"
         "###);
     }
 
     Ok(())
 }
 
+#[cfg(unix)] // Paths don't match on Windows.
 #[test]
-fn test_test_fix() -> eyre::Result<()> {
+fn test_test_worktree_strategy() -> eyre::Result<()> {
     let git = make_git()?;
     git.init_repo()?;
 
-    git.detach_head()?;
     git.commit_file("test1", 1)?;
-    git.commit_file("test2", 2)?;
-    git.commit_file("test3", 3)?;
+    git.write_file_txt("test1", "Updated contents\n")?;
 
     {
-        let stdout = git.smartlog()?;
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "working-copy", "-x", "echo hello", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        O f777ecc (master) create initial.txt
-        |
-        o 62fc20d create test1.txt
-        |
-        o 96d1c37 create test2.txt
-        |
-        @ 70deb1e create test3.txt
+        branchless: running command: <git-executable> diff --quiet
+        This operation would modify the working copy, but you have uncommitted changes
+        in your working copy which might be overwritten as a result.
+        Commit your changes and then try again.
         "###);
     }
 
-    git.write_file(
-        "test.sh",
-        r#"#!/bin/sh
-for i in *.txt; do
-    echo "Updated contents for file $i" >"$i"
-done
-"#,
-    )?;
     {
-        let (stdout, _stderr) = git.branchless("test", &["fix", "-x", "bash test.sh"])?;
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "echo hello",
+                "-vv",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (fixed): 62fc20d create test1.txt
-        ✓ Passed (fixed): 96d1c37 create test2.txt
-        ✓ Passed (fixed): 70deb1e create test3.txt
-        Ran command on 3 commits: bash test.sh:
-        3 passed, 0 failed, 0 skipped
-        Attempting rebase in-memory...
-        [1/3] Committed as: 300cb54 create test1.txt
-        [2/3] Committed as: 2ee3aea create test2.txt
-        [3/3] Committed as: 6f48e0a create test3.txt
-        branchless: processing 3 rewritten commits
-        branchless: running command: <git-executable> checkout 6f48e0a628753731739619f27107c57f5d0cc1e0
-        In-memory rebase succeeded.
-        Fixed 3 commits with bash test.sh:
-        62fc20d -> 300cb54 create test1.txt
-        96d1c37 -> 2ee3aea create test2.txt
-        70deb1e -> 6f48e0a create test3.txt
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Stdout: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stdout
+        hello
+        Stderr: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stderr
+        <no output>
+        Ran command on 1 commit: echo hello:
+        1 passed, 0 failed, 0 skipped
         "###);
     }
 
-    let original_log_output = {
-        let (stdout, _stderr) = git.run(&["log", "--patch"])?;
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "echo hello",
+                "-vv",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        commit 6f48e0a628753731739619f27107c57f5d0cc1e0
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 -0300
+        Using command execution strategy: worktree
+        ✓ Passed (cached): 62fc20d create test1.txt
+        Stdout: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stdout
+        hello
+        Stderr: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stderr
+        <no output>
+        Ran command on 1 commit: echo hello:
+        1 passed, 0 failed, 0 skipped
+        1/1 result from cache (100%)
+        hint: there was 1 cached test result
+        hint: to clear these cached results, run: git test clean "@"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        "###);
+    }
 
-            create test3.txt
+    Ok(())
+}
 
-        diff --git a/test3.txt b/test3.txt
-        new file mode 100644
-        index 0000000..95c32b2
-        --- /dev/null
-        +++ b/test3.txt
-        @@ -0,0 +1 @@
-        +Updated contents for file test3.txt
+#[test]
+fn test_test_du() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test2", 2)?;
 
-        commit 2ee3aea583d4da25fa7996b788f4f27cbf7b9fd8
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 -0200
+    // The sizes depend on the contents of the result files, which include
+    // timestamps, so they aren't stable between runs.
+    let redact_sizes = |stdout: &str| -> String {
+        let size_regex = regex::Regex::new(r"\d+(\.\d+)? (B|KiB|MiB|GiB)").unwrap();
+        size_regex.replace_all(stdout, "<size>").into_owned()
+    };
 
-            create test2.txt
+    {
+        let (stdout, _stderr) = git.branchless("test", &["du"])?;
+        insta::assert_snapshot!(redact_sizes(&stdout), @r###"
+        Cached test results: 0 commit trees, <size>
+        Testing worktrees: 0 worktrees, <size> (remove unused worktrees with: git test gc)
+        Locks: <size>
+        Total: <size>
+        "###);
+    }
 
-        diff --git a/test2.txt b/test2.txt
-        new file mode 100644
-        index 0000000..dce8610
-        --- /dev/null
-        +++ b/test2.txt
-        @@ -0,0 +1 @@
-        +Updated contents for file test2.txt
+    git.branchless(
+        "test",
+        &[
+            "run",
+            "--strategy",
+            "worktree",
+            "--jobs",
+            "1",
+            "-x",
+            "true",
+            "@",
+        ],
+    )?;
 
-        commit 300cb542f9b6474befd598bdbdd263d7d2b011a0
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 -0100
+    {
+        let (stdout, _stderr) = git.branchless("test", &["du"])?;
+        insta::assert_snapshot!(redact_sizes(&stdout), @r###"
+        Cached test results: 1 commit tree, <size>
+        - 48bb2464c55090a387ed70b3d229705a94856efb: <size>
+        Testing worktrees: 1 worktree, <size> (remove unused worktrees with: git test gc)
+        Locks: <size>
+        Total: <size>
+        "###);
+    }
 
-            create test1.txt
+    Ok(())
+}
 
-        diff --git a/initial.txt b/initial.txt
-        index 63af228..a48ef19 100644
-        --- a/initial.txt
-        +++ b/initial.txt
-        @@ -1 +1 @@
-        -initial contents
-        +Updated contents for file initial.txt
-        diff --git a/test1.txt b/test1.txt
-        new file mode 100644
-        index 0000000..4d62cad
-        --- /dev/null
-        +++ b/test1.txt
-        @@ -0,0 +1 @@
-        +Updated contents for file test1.txt
+#[test]
+fn test_test_cache_eviction() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
 
-        commit f777ecc9b0db5ed372b2615695191a8a17f79f24
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 +0000
+    git.branchless(
+        "test",
+        &["run", "--strategy", "worktree", "-x", "true", "master"],
+    )?;
+    let (master_tree_oid, _stderr) = git.run(&["rev-parse", "master^{tree}"])?;
 
-            create initial.txt
+    git.run(&["config", "branchless.test.cacheMaxAge", "0"])?;
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "true", "@"],
+        )?;
+        let size_regex = regex::Regex::new(r"\d+(\.\d+)? (B|KiB|MiB|GiB)").unwrap();
+        let stdout = size_regex
+            .replace_all(&stdout, "<size>")
+            .replace(master_tree_oid.trim(), "<master-tree-oid>");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: true:
+        1 passed, 0 failed, 0 skipped
+        Evicted 1 commit tree from the test cache, reclaiming <size>:
+        - <master-tree-oid>
+        "###);
+    }
 
-        diff --git a/initial.txt b/initial.txt
-        new file mode 100644
-        index 0000000..63af228
-        --- /dev/null
-        +++ b/initial.txt
-        @@ -0,0 +1 @@
-        +initial contents
+    {
+        // The tree of the draft commit is never evicted.
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "true", "@"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed (cached): 62fc20d create test1.txt
+        Ran command on 1 commit: true:
+        1 passed, 0 failed, 0 skipped
+        1/1 result from cache (100%)
+        hint: there was 1 cached test result
+        hint: to clear these cached results, run: git test clean "@"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
         "###);
-        stdout
-    };
+    }
 
-    let updated_smartlog = {
-        let stdout = git.smartlog()?;
+    git.run(&["config", "branchless.test.cacheMaxSize", "lots"])?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "true", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
         insta::assert_snapshot!(stdout, @r###"
-        O f777ecc (master) create initial.txt
-        |
-        o 300cb54 create test1.txt
-        |
-        o 2ee3aea create test2.txt
-        |
-        @ 6f48e0a create test3.txt
+        Invalid value for config value for branchless.test.cacheMaxSize (lots): expected a number of bytes, optionally followed by k, m, or g
         "###);
-        stdout
-    };
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_gc() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
 
-    // No changes should be made after the first invocation of the script, since
-    // it was idempotent.
     {
-        let (stdout, _stderr) = git.branchless("test", &["fix", "-x", "bash test.sh"])?;
+        let (stdout, _stderr) = git.branchless("test", &["gc"])?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: 300cb54 create test1.txt
-        ✓ Passed: 2ee3aea create test2.txt
-        ✓ Passed: 6f48e0a create test3.txt
-        Ran command on 3 commits: bash test.sh:
-        3 passed, 0 failed, 0 skipped
-        No commits to fix.
+        No testing worktrees to remove.
+        "###);
+    }
+
+    git.branchless(
+        "test",
+        &[
+            "run",
+            "--strategy",
+            "worktree",
+            "--jobs",
+            "1",
+            "-x",
+            "true",
+            "@",
+        ],
+    )?;
+    let worktree_dir = git
+        .repo_path
+        .join(".git/branchless/test/worktrees/testing-worktree-1");
+    assert!(worktree_dir.exists());
+
+    {
+        let (stdout, _stderr) = git.branchless("test", &["gc"])?;
+        assert!(
+            stdout.starts_with("Removed 1 testing worktree, reclaiming "),
+            "{stdout}"
+        );
+    }
+    assert!(!worktree_dir.exists());
+
+    {
+        let (stdout, _stderr) = git.run(&["worktree", "list", "--porcelain"])?;
+        assert!(!stdout.contains("testing-worktree-1"), "{stdout}");
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("test", &["gc"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        No testing worktrees to remove.
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_keep_worktrees() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--keep-worktrees", "-x", "exit 1", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The --keep-worktrees option can only be used with --strategy worktree.
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "1",
+                "--keep-worktrees",
+                "-x",
+                "exit 1",
+                "@",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        assert!(
+            stdout.contains(
+                "Kept testing worktrees for failed commits (run git test gc to remove them):"
+            ),
+            "{stdout}"
+        );
+        assert!(
+            stdout.contains("kept-worktree-62fc20d2a290daea0d52bdc2ed2ad4be6491010e"),
+            "{stdout}"
+        );
+    }
+    let worktrees_dir = git.repo_path.join(".git/branchless/test/worktrees");
+    let kept_worktree_dir =
+        worktrees_dir.join("kept-worktree-62fc20d2a290daea0d52bdc2ed2ad4be6491010e");
+    assert!(kept_worktree_dir.join("test1.txt").exists());
+    assert!(!worktrees_dir.join("testing-worktree-1").exists());
+
+    {
+        let (stdout, _stderr) = git.branchless("test", &["gc"])?;
+        assert!(
+            stdout.starts_with("Removed 1 testing worktree, reclaiming "),
+            "{stdout}"
+        );
+    }
+    assert!(!kept_worktree_dir.exists());
+
+    Ok(())
+}
+
+#[cfg(unix)] // Paths don't match on Windows.
+#[test]
+fn test_test_config_strategy() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    git.write_file(
+        "test.sh",
+        "#!/bin/sh
+echo hello
+",
+    )?;
+    git.commit_file("test2", 2)?;
+
+    git.write_file_txt("test1", "Updated contents\n")?;
+
+    git.run(&["config", "branchless.test.alias.default", "bash test.sh"])?;
+    git.run(&["config", "branchless.test.strategy", "working-copy"])?;
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        This operation would modify the working copy, but you have uncommitted changes
+        in your working copy which might be overwritten as a result.
+        Commit your changes and then try again.
+        "###);
+    }
+
+    git.run(&["config", "branchless.test.strategy", "worktree"])?;
+    {
+        let (stdout, stderr) = git.branchless("test", &["run", "-vv", "@"])?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: c82ebfa create test2.txt
+        Stdout: <repo-path>/.git/branchless/test/a3ae41e24abf7537423d8c72d07df7af456de6dd/bash__test.sh/stdout
+        hello
+        Stderr: <repo-path>/.git/branchless/test/a3ae41e24abf7537423d8c72d07df7af456de6dd/bash__test.sh/stderr
+        <no output>
+        Ran command on 1 commit: bash test.sh:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    git.run(&["config", "branchless.test.strategy", "invalid-value"])?;
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "-vv", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Invalid value for config value branchless.test.strategy: invalid-value
+        Expected one of: working-copy, worktree, docker, tmpfs
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_jobs_argument_handling() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    if !git.supports_reference_transactions()? {
+        return Ok(());
+    }
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.run(&["config", "branchless.test.alias.default", "exit 0"])?;
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "working-copy", "--jobs", "0"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        The --jobs option can only be used with --strategy worktree, but --strategy working-copy was provided instead.
+        "###);
+    }
+
+    {
+        // `--jobs 1` is allowed for `--strategy working-copy`, since that's the default anyways.
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--strategy", "working-copy", "--jobs", "1"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: 62fc20d create test1.txt
+        ✓ Passed: 96d1c37 create test2.txt
+        Ran command on 2 commits: exit 0:
+        2 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "working-copy", "--jobs", "2"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        The --jobs option can only be used with --strategy worktree, but --strategy working-copy was provided instead.
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless("test", &["run", "--jobs", "2"])?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed (cached): 62fc20d create test1.txt
+        ✓ Passed (cached): 96d1c37 create test2.txt
+        Ran command on 2 commits: exit 0:
+        2 passed, 0 failed, 0 skipped
+        2/2 results from cache (100%)
+        hint: there were 2 cached test results
+        hint: to clear these cached results, run: git test clean "stack() | @"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &["run", "--exec", "true", "--interactive", "--jobs", "1"],
+        )?;
+        insta::assert_snapshot!(stderr, @r###"
+        Stopped at 96d1c37 (create test2.txt)
+        branchless: processing 1 update: ref HEAD
+        "###);
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        You are now at: 62fc20d create test1.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        You are now at: 96d1c37 create test2.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (interactive): 62fc20d create test1.txt
+        ✓ Passed (interactive): 96d1c37 create test2.txt
+        Ran command on 2 commits: true:
+        2 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--exec", "true", "--interactive", "--jobs", "2"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        The --jobs option cannot be used with the --interactive option.
+        "###);
+    }
+
+    git.run(&["config", "branchless.test.jobs", "2"])?;
+
+    {
+        let (stdout, stderr) = git.branchless("test", &["run", "--strategy", "working-copy"])?;
+        insta::assert_snapshot!(stderr, @r###"
+        Stopped at 96d1c37 (create test2.txt)
+        branchless: processing 1 update: ref HEAD
+        "###);
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (cached): 62fc20d create test1.txt
+        ✓ Passed (cached): 96d1c37 create test2.txt
+        Ran command on 2 commits: exit 0:
+        2 passed, 0 failed, 0 skipped
+        2/2 results from cache (100%)
+        hint: there were 2 cached test results
+        hint: to clear these cached results, run: git test clean "stack() | @"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_fix() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    {
+        let stdout = git.smartlog()?;
+        insta::assert_snapshot!(stdout, @r###"
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        @ 70deb1e create test3.txt
+        "###);
+    }
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+for i in *.txt; do
+    echo "Updated contents for file $i" >"$i"
+done
+"#,
+    )?;
+    {
+        let (stdout, _stderr) = git.branchless("test", &["fix", "-x", "bash test.sh"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (fixed): 62fc20d create test1.txt
+        ✓ Passed (fixed): 96d1c37 create test2.txt
+        ✓ Passed (fixed): 70deb1e create test3.txt
+        Ran command on 3 commits: bash test.sh:
+        3 passed, 0 failed, 0 skipped
+        Attempting rebase in-memory...
+        [1/3] Committed as: 300cb54 create test1.txt
+        [2/3] Committed as: 2ee3aea create test2.txt
+        [3/3] Committed as: 6f48e0a create test3.txt
+        branchless: processing 3 rewritten commits
+        branchless: running command: <git-executable> checkout 6f48e0a628753731739619f27107c57f5d0cc1e0
+        In-memory rebase succeeded.
+        Fixed 3 commits with bash test.sh:
+        62fc20d -> 300cb54 create test1.txt
+        96d1c37 -> 2ee3aea create test2.txt
+        70deb1e -> 6f48e0a create test3.txt
+        "###);
+    }
+
+    let original_log_output = {
+        let (stdout, _stderr) = git.run(&["log", "--patch"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        commit 6f48e0a628753731739619f27107c57f5d0cc1e0
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 -0300
+
+            create test3.txt
+
+        diff --git a/test3.txt b/test3.txt
+        new file mode 100644
+        index 0000000..95c32b2
+        --- /dev/null
+        +++ b/test3.txt
+        @@ -0,0 +1 @@
+        +Updated contents for file test3.txt
+
+        commit 2ee3aea583d4da25fa7996b788f4f27cbf7b9fd8
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 -0200
+
+            create test2.txt
+
+        diff --git a/test2.txt b/test2.txt
+        new file mode 100644
+        index 0000000..dce8610
+        --- /dev/null
+        +++ b/test2.txt
+        @@ -0,0 +1 @@
+        +Updated contents for file test2.txt
+
+        commit 300cb542f9b6474befd598bdbdd263d7d2b011a0
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 -0100
+
+            create test1.txt
+
+        diff --git a/initial.txt b/initial.txt
+        index 63af228..a48ef19 100644
+        --- a/initial.txt
+        +++ b/initial.txt
+        @@ -1 +1 @@
+        -initial contents
+        +Updated contents for file initial.txt
+        diff --git a/test1.txt b/test1.txt
+        new file mode 100644
+        index 0000000..4d62cad
+        --- /dev/null
+        +++ b/test1.txt
+        @@ -0,0 +1 @@
+        +Updated contents for file test1.txt
+
+        commit f777ecc9b0db5ed372b2615695191a8a17f79f24
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 +0000
+
+            create initial.txt
+
+        diff --git a/initial.txt b/initial.txt
+        new file mode 100644
+        index 0000000..63af228
+        --- /dev/null
+        +++ b/initial.txt
+        @@ -0,0 +1 @@
+        +initial contents
+        "###);
+        stdout
+    };
+
+    let updated_smartlog = {
+        let stdout = git.smartlog()?;
+        insta::assert_snapshot!(stdout, @r###"
+        O f777ecc (master) create initial.txt
+        |
+        o 300cb54 create test1.txt
+        |
+        o 2ee3aea create test2.txt
+        |
+        @ 6f48e0a create test3.txt
+        "###);
+        stdout
+    };
+
+    // No changes should be made after the first invocation of the script, since
+    // it was idempotent.
+    {
+        let (stdout, _stderr) = git.branchless("test", &["fix", "-x", "bash test.sh"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: 300cb54 create test1.txt
+        ✓ Passed: 2ee3aea create test2.txt
+        ✓ Passed: 6f48e0a create test3.txt
+        Ran command on 3 commits: bash test.sh:
+        3 passed, 0 failed, 0 skipped
+        No commits to fix.
+        "###);
+    }
+    {
+        let (stdout, _stderr) = git.run(&["log", "--patch"])?;
+        assert_eq!(stdout, original_log_output);
+    }
+
+    {
+        let stdout = git.smartlog()?;
+        assert_eq!(stdout, updated_smartlog);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_fix_diff() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+for i in *.txt; do
+    echo "Updated contents for file $i" >"$i"
+done
+"#,
+    )?;
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["fix", "--diff", "--dry-run", "-x", "bash test.sh"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (fixed): 62fc20d create test1.txt
+        Ran command on 1 commit: bash test.sh:
+        1 passed, 0 failed, 0 skipped
+        Changes made to 62fc20d create test1.txt:
+        diff --git a/initial.txt b/initial.txt
+        index 63af228..a48ef19 100644
+        --- a/initial.txt
+        +++ b/initial.txt
+        @@ -1 +1 @@
+        -initial contents
+        +Updated contents for file initial.txt
+        diff --git a/test1.txt b/test1.txt
+        index 7432a8f..4d62cad 100644
+        --- a/test1.txt
+        +++ b/test1.txt
+        @@ -1 +1 @@
+        -test1 contents
+        +Updated contents for file test1.txt
+        Fixed 1 commit with bash test.sh:
+        62fc20d -> 300cb54 create test1.txt
+        "###);
+    }
+
+    {
+        let stdout = git.smartlog()?;
+        insta::assert_snapshot!(stdout, @r###"
+        O f777ecc (master) create initial.txt
+        |
+        @ 62fc20d create test1.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_fix_pathspec() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+for i in *.txt; do
+    echo "Updated contents for file $i" >"$i"
+done
+"#,
+    )?;
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["fix", "--pathspec", "test2*", "-x", "bash test.sh"],
+        )?;
+        assert!(
+            stdout.contains("Fixed 1 commit with bash test.sh:"),
+            "{stdout}"
+        );
+    }
+
+    {
+        let (stdout, _stderr) = git.run(&["rev-parse", "--short", "HEAD~"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        62fc20d
+        "###);
+        let (stdout, _stderr) = git.run(&["show", "HEAD:initial.txt"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        initial contents
+        "###);
+        let (stdout, _stderr) = git.run(&["show", "HEAD:test1.txt"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        test1 contents
+        "###);
+        let (stdout, _stderr) = git.run(&["show", "HEAD:test2.txt"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        Updated contents for file test2.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["fix", "--pathspec", "a**", "-x", "bash test.sh"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Invalid --pathspec pattern "a**": Pattern syntax error near position 1: recursive wildcards must form a single path component
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_fix_signed_commit() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+
+    // Git doesn't verify signatures when writing commit objects, so attach a
+    // placeholder signature to the commit.
+    let signed_commit_oid = {
+        let (commit_contents, _stderr) = git.run(&["cat-file", "commit", "HEAD"])?;
+        let commit_contents = commit_contents.replacen(
+            "\n\n",
+            "\ngpgsig -----BEGIN PGP SIGNATURE-----\n \n -----END PGP SIGNATURE-----\n\n",
+            1,
+        );
+        let (stdout, _stderr) = git.run_with_options(
+            &["hash-object", "-t", "commit", "-w", "--stdin"],
+            &GitRunOptions {
+                input: Some(commit_contents),
+                ..Default::default()
+            },
+        )?;
+        stdout.trim().to_owned()
+    };
+    git.run(&["checkout", &signed_commit_oid])?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+for i in *.txt; do
+    echo "Updated contents for file $i" >"$i"
+done
+"#,
+    )?;
+    {
+        let (stdout, _stderr) =
+            git.branchless("test", &["fix", "--dry-run", "-x", "bash test.sh", "@"])?;
+        assert!(
+            stdout.contains(
+                "Warning: 1 commit was signed, but signatures are not preserved when applying fixes:"
+            ),
+            "{stdout}"
+        );
+        assert!(
+            !stdout.contains("even though commit.gpgsign is set"),
+            "{stdout}"
+        );
+    }
+
+    git.run(&["config", "commit.gpgsign", "true"])?;
+    {
+        let (stdout, _stderr) =
+            git.branchless("test", &["fix", "--dry-run", "-x", "bash test.sh", "@"])?;
+        assert!(
+            stdout.contains("The rewritten commits will not be signed, even though commit.gpgsign is set. Sign them again before pushing them."),
+            "{stdout}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_fix_failure() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+for i in *.txt; do
+    if [[ "$i" == test2* ]]; then
+        echo "Failed on $i"
+        exit 1
+    fi
+    echo "Updated contents for file $i" >"$i"
+done
+"#,
+    )?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["fix", "-x", "bash test.sh"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (fixed): 62fc20d create test1.txt
+        X Failed (exit code 1): 96d1c37 create test2.txt
+        X Failed (exit code 1): 70deb1e create test3.txt
+        Ran command on 3 commits: bash test.sh:
+        1 passed, 2 failed, 0 skipped
+        "###);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_test_no_apply_descendants_as_patches() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+
+    git.write_file_txt("test1", "This file would conflict if applied as a patch\n")?;
+    git.write_file_txt("test2", "Updated contents for file test2.txt\n")?;
+    git.run(&["add", "."])?;
+    git.run(&["commit", "-m", "descendant commit"])?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+for i in *.txt; do
+    echo "Updated contents for file $i" >"$i"
+done
+"#,
+    )?;
+
+    {
+        let (stdout, _stderr) = git.run(&["log", "--patch"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        commit 75e728fb17f6952287302c3e76d88aa737dd99d1
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 +0000
+
+            descendant commit
+
+        diff --git a/test1.txt b/test1.txt
+        index 7432a8f..60d9cdb 100644
+        --- a/test1.txt
+        +++ b/test1.txt
+        @@ -1 +1 @@
+        -test1 contents
+        +This file would conflict if applied as a patch
+        diff --git a/test2.txt b/test2.txt
+        new file mode 100644
+        index 0000000..dce8610
+        --- /dev/null
+        +++ b/test2.txt
+        @@ -0,0 +1 @@
+        +Updated contents for file test2.txt
+
+        commit 62fc20d2a290daea0d52bdc2ed2ad4be6491010e
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 -0100
+
+            create test1.txt
+
+        diff --git a/test1.txt b/test1.txt
+        new file mode 100644
+        index 0000000..7432a8f
+        --- /dev/null
+        +++ b/test1.txt
+        @@ -0,0 +1 @@
+        +test1 contents
+
+        commit f777ecc9b0db5ed372b2615695191a8a17f79f24
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 +0000
+
+            create initial.txt
+
+        diff --git a/initial.txt b/initial.txt
+        new file mode 100644
+        index 0000000..63af228
+        --- /dev/null
+        +++ b/initial.txt
+        @@ -0,0 +1 @@
+        +initial contents
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("test", &["fix", "-x", "bash test.sh"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (fixed): 62fc20d create test1.txt
+        ✓ Passed (fixed): 75e728f descendant commit
+        Ran command on 2 commits: bash test.sh:
+        2 passed, 0 failed, 0 skipped
+        Attempting rebase in-memory...
+        [1/2] Committed as: 300cb54 create test1.txt
+        [2/2] Committed as: f15b423 descendant commit
+        branchless: processing 2 rewritten commits
+        branchless: running command: <git-executable> checkout f15b423404bbebfe4b09e305e074b525d008f44a
+        In-memory rebase succeeded.
+        Fixed 2 commits with bash test.sh:
+        62fc20d -> 300cb54 create test1.txt
+        75e728f -> f15b423 descendant commit
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.run(&["log", "--patch"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        commit f15b423404bbebfe4b09e305e074b525d008f44a
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 +0000
+
+            descendant commit
+
+        diff --git a/test2.txt b/test2.txt
+        new file mode 100644
+        index 0000000..dce8610
+        --- /dev/null
+        +++ b/test2.txt
+        @@ -0,0 +1 @@
+        +Updated contents for file test2.txt
+
+        commit 300cb542f9b6474befd598bdbdd263d7d2b011a0
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 -0100
+
+            create test1.txt
+
+        diff --git a/initial.txt b/initial.txt
+        index 63af228..a48ef19 100644
+        --- a/initial.txt
+        +++ b/initial.txt
+        @@ -1 +1 @@
+        -initial contents
+        +Updated contents for file initial.txt
+        diff --git a/test1.txt b/test1.txt
+        new file mode 100644
+        index 0000000..4d62cad
+        --- /dev/null
+        +++ b/test1.txt
+        @@ -0,0 +1 @@
+        +Updated contents for file test1.txt
+
+        commit f777ecc9b0db5ed372b2615695191a8a17f79f24
+        Author: Testy McTestface <test@example.com>
+        Date:   Thu Oct 29 12:34:56 2020 +0000
+
+            create initial.txt
+
+        diff --git a/initial.txt b/initial.txt
+        new file mode 100644
+        index 0000000..63af228
+        --- /dev/null
+        +++ b/initial.txt
+        @@ -0,0 +1 @@
+        +initial contents
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_fix_on_disk() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+for i in *.txt; do
+    echo "Updated contents for file $i" >"$i"
+done
+"#,
+    )?;
+    {
+        let (stdout, _stderr) =
+            git.branchless("test", &["fix", "--on-disk", "-x", "bash test.sh"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (fixed): 62fc20d create test1.txt
+        Ran command on 1 commit: bash test.sh:
+        1 passed, 0 failed, 0 skipped
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Fixed 1 commit with bash test.sh:
+        62fc20d -> 300cb54 create test1.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.run(&["show", "HEAD:test1.txt"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        Updated contents for file test1.txt
+        "###);
+    }
+
+    {
+        let stdout = git.smartlog()?;
+        insta::assert_snapshot!(stdout, @r###"
+        O f777ecc (master) create initial.txt
+        |
+        @ 300cb54 create test1.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_search_binary() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+    git.commit_file("test4", 4)?;
+    git.commit_file("test5", 5)?;
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--search",
+                "binary",
+                "--exec",
+                "! git grep -q 'test4'",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        Using test search strategy: binary
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: 70deb1e create test3.txt
+        X Failed (exit code 1): 355e173 create test4.txt
+        X Failed (exit code 1): f81d55c create test5.txt
+        Ran command on 3 commits: ! git grep -q 'test4':
+        1 passed, 2 failed, 0 skipped
+        Last passing commit:
+        - 70deb1e create test3.txt
+        First failing commit:
+        - 355e173 create test4.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_bisect_ref() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+    git.commit_file("test4", 4)?;
+    git.commit_file("test5", 5)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--bisect-ref",
+                "refs/branchless/bisect-bad",
+                "--exec",
+                "! git grep -q 'test4'",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The --bisect-ref option can only be used with --search or --bisect.
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--bisect",
+                "--bisect-ref",
+                "refs/branchless/bisect-bad",
+                "--exec",
+                "! git grep -q 'test4'",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        Using test search strategy: binary
+        ✓ Passed: 70deb1e create test3.txt
+        X Failed (exit code 1): 355e173 create test4.txt
+        X Failed (exit code 1): f81d55c create test5.txt
+        Ran command on 3 commits: ! git grep -q 'test4':
+        1 passed, 2 failed, 0 skipped
+        Last passing commit:
+        - 70deb1e create test3.txt
+        First failing commit:
+        - 355e173 create test4.txt
+        Updated refs/branchless/bisect-bad to point to the first failing commit: 355e173 create test4.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) =
+            git.run(&["log", "-1", "--format=%h", "refs/branchless/bisect-bad"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        355e173
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_porcelain() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+    git.commit_file("test4", 4)?;
+    git.commit_file("test5", 5)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--porcelain", "--exec", "true"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The --porcelain option can only be used with --search or --bisect, and not with --format.
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--bisect",
+                "--porcelain",
+                "--exec",
+                "! git grep -q 'test4'",
+            ],
+        )?;
+        let (culprit_oid, _stderr) = git.run(&["rev-parse", "HEAD~"])?;
+        assert_eq!(stdout, format!("culprit {culprit_oid}"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_perf_threshold() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+    git.commit_file("test4", 4)?;
+    git.commit_file("test5", 5)?;
+
+    {
+        // Use the number of test files as a stand-in for a benchmark result.
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--bisect",
+                "--perf-threshold",
+                "3.5",
+                "--exec",
+                "echo 'Running benchmark...'; ls test*.txt | wc -l",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        Using test search strategy: binary
+        ✓ Passed: 70deb1e create test3.txt
+        X Failed (measurement 4 exceeded threshold 3.5): 355e173 create test4.txt
+        X Failed (measurement 5 exceeded threshold 3.5): f81d55c create test5.txt
+        Ran command on 3 commits: echo 'Running benchmark...'; ls test*.txt | wc -l:
+        1 passed, 2 failed, 0 skipped
+        Last passing commit:
+        - 70deb1e create test3.txt
+        First failing commit:
+        - 355e173 create test4.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--perf-threshold",
+                "3.5",
+                "--exec",
+                "echo 'no measurement'",
+                "HEAD",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ! Exit code indicated to skip this commit (exit code 0, reason: no numeric measurement in output for --perf-threshold): f81d55c create test5.txt
+        Ran command on 1 commit: echo 'no measurement':
+        0 passed, 0 failed, 1 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_fail_on_output() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--fail-on-output",
+                "WARN(ING)?:",
+                "--exec",
+                "if [ -e test2.txt ]; then echo 'WARNING: deprecated' >&2; fi",
+                "stack()",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        X Failed (exit code 1, output matched WARN(ING)?:): 96d1c37 create test2.txt
+        Ran command on 2 commits: if [ -e test2.txt ]; then echo 'WARNING: deprecated' >&2; fi:
+        1 passed, 1 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--fail-on-output",
+                "WARN(",
+                "--exec",
+                "true",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Invalid regular expression for --fail-on-output: regex parse error:
+            WARN(
+                ^
+        error: unclosed group
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_extra_args() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--exec",
+                r#"test "$#" -eq 2 && test "$1" = "a b" && echo"#,
+                "--",
+                "a b",
+                "c",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: test "$#" -eq 2 && test "$1" = "a b" && echo "a b" "c":
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    git.run(&[
+        "config",
+        "branchless.test.alias.default",
+        r#"test "$#" -eq 1 && test "$@" = c"#,
+    ])?;
+    {
+        let (stdout, _stderr) =
+            git.branchless("test", &["run", "--strategy", "worktree", "--", "c"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: test "$#" -eq 1 && test "$@" = c "c":
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_cwd() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("subdir/test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--cwd",
+                "subdir",
+                "--exec",
+                "test -e test2.txt",
+                "stack()",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ! Failed to spawn command: directory subdir does not exist in this commit: 62fc20d create test1.txt
+        ✓ Passed: 3c71337 create subdir/test2.txt
+        ✓ Passed: 74827b6 create test3.txt
+        Ran command on 3 commits: test -e test2.txt:
+        2 passed, 0 failed, 1 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--cwd", "../subdir", "--exec", "true"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Invalid value for --cwd (../subdir): must be a relative path inside the repository, without `..` components
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_on_result() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    git.run(&[
+        "config",
+        "branchless.test.onResult",
+        r#"echo "$1 $2 $BRANCHLESS_TEST_EXIT_CODE" >>on-result.log"#,
+    ])?;
+    git.branchless_with_options(
+        "test",
+        &[
+            "run",
+            "--strategy",
+            "worktree",
+            "--exec",
+            "! test -e test2.txt",
+            "stack()",
+        ],
+        &GitRunOptions {
+            expected_exit_code: 1,
+            ..Default::default()
+        },
+    )?;
+    let on_result_log = std::fs::read_to_string(git.repo_path.join("on-result.log"))?;
+    insta::assert_snapshot!(on_result_log, @r###"
+    62fc20d2a290daea0d52bdc2ed2ad4be6491010e passed 0
+    96d1c37a3d4363611c49f7e52186e189a04c531f failed 1
+    "###);
+
+    // A failing hook doesn't affect the test results.
+    git.run(&["config", "branchless.test.onResult", "exit 1"])?;
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "--exec", "true", "stack()"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        ✓ Passed: 96d1c37 create test2.txt
+        Ran command on 2 commits: true:
+        2 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_group_failures() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--group",
+                "--exec",
+                "if test -f test2.txt; then echo 'missing dependency' >&2; exit 1; fi",
+                "stack()",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        X Failed (exit code 1) with identical output on 2 commits:
+        - 96d1c37 create test2.txt
+        - 70deb1e create test3.txt
+        Ran command on 3 commits: if test -f test2.txt; then echo 'missing dependency' >&2; exit 1; fi:
+        1 passed, 2 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_run_none() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    {
+        // Shouldn't time out.
+        let (stdout, _stderr) = git.branchless("test", &["run", "-x", "true", "none()"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        Ran command on 0 commits: true:
+        0 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_search_skip_indeterminate() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+    git.commit_file("test4", 4)?;
+    git.commit_file("test5", 5)?;
+    git.commit_file("test6", 6)?;
+    git.commit_file("test7", 7)?;
+
+    {
+        let (stdout, _stderr) =
+            git.branchless("test", &["run", "--search", "binary", "--exec", "exit 125"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        Using test search strategy: binary
+        branchless: running command: <git-executable> rebase --abort
+        ! Exit code indicated to skip this commit (exit code 125): 62fc20d create test1.txt
+        ! Exit code indicated to skip this commit (exit code 125): 96d1c37 create test2.txt
+        ! Exit code indicated to skip this commit (exit code 125): 70deb1e create test3.txt
+        ! Exit code indicated to skip this commit (exit code 125): 355e173 create test4.txt
+        ! Exit code indicated to skip this commit (exit code 125): f81d55c create test5.txt
+        ! Exit code indicated to skip this commit (exit code 125): 2831fb5 create test6.txt
+        ! Exit code indicated to skip this commit (exit code 125): c8933b3 create test7.txt
+        Ran command on 7 commits: exit 125:
+        0 passed, 0 failed, 7 skipped
+        There were no passing commits in the provided set.
+        There were no failing commits in the provided set.
+        "###);
+    }
+
+    // Confirm that we treat exit code 125 as indeterminate even when cached.
+    {
+        let (stdout, _stderr) =
+            git.branchless("test", &["run", "--search", "binary", "--exec", "exit 125"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        Using test search strategy: binary
+        branchless: running command: <git-executable> rebase --abort
+        ! Exit code indicated to skip this commit (exit code 125): 62fc20d create test1.txt
+        ! Exit code indicated to skip this commit (exit code 125): 96d1c37 create test2.txt
+        ! Exit code indicated to skip this commit (exit code 125): 70deb1e create test3.txt
+        ! Exit code indicated to skip this commit (exit code 125): 355e173 create test4.txt
+        ! Exit code indicated to skip this commit (exit code 125): f81d55c create test5.txt
+        ! Exit code indicated to skip this commit (exit code 125): 2831fb5 create test6.txt
+        ! Exit code indicated to skip this commit (exit code 125): c8933b3 create test7.txt
+        Ran command on 7 commits: exit 125:
+        0 passed, 0 failed, 7 skipped
+        There were no passing commits in the provided set.
+        There were no failing commits in the provided set.
+        "###);
+    }
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+if [[ "$(git log)" =~ 'test4' ]]; then
+    exit 125
+elif [[ "$(git log)" =~ 'test6' ]]; then
+    exit 1
+else
+    exit 0
+fi
+"#,
+    )?;
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--search", "linear", "--exec", "bash test.sh"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        Using test search strategy: linear
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: 62fc20d create test1.txt
+        ✓ Passed: 96d1c37 create test2.txt
+        ✓ Passed: 70deb1e create test3.txt
+        ! Exit code indicated to skip this commit (exit code 125): 355e173 create test4.txt
+        ! Exit code indicated to skip this commit (exit code 125): f81d55c create test5.txt
+        ! Exit code indicated to skip this commit (exit code 125): 2831fb5 create test6.txt
+        ! Exit code indicated to skip this commit (exit code 125): c8933b3 create test7.txt
+        Ran command on 7 commits: bash test.sh:
+        3 passed, 0 failed, 4 skipped
+        Last passing commit:
+        - 70deb1e create test3.txt
+        There were no failing commits in the provided set.
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--search", "binary", "--exec", "bash test.sh"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        Using test search strategy: binary
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (cached): 70deb1e create test3.txt
+        ! Exit code indicated to skip this commit (exit code 125): 355e173 create test4.txt
+        ! Exit code indicated to skip this commit (exit code 125): f81d55c create test5.txt
+        ! Exit code indicated to skip this commit (exit code 125): 2831fb5 create test6.txt
+        ! Exit code indicated to skip this commit (exit code 125): c8933b3 create test7.txt
+        Ran command on 5 commits: bash test.sh:
+        1 passed, 0 failed, 4 skipped
+        1/5 results from cache (20%)
+        Last passing commit:
+        - 70deb1e create test3.txt
+        There were no failing commits in the provided set.
+        hint: there was 1 cached test result
+        hint: to clear these cached results, run: git test clean "stack() | @"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_interactive() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    if !git.supports_reference_transactions()? {
+        return Ok(());
+    }
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--interactive"],
+            &GitRunOptions {
+                env: hashmap! {"SHELL".to_string() =>  "true".to_string()},
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        You are now at: 62fc20d create test1.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        You are now at: 96d1c37 create test2.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (interactive): 62fc20d create test1.txt
+        ✓ Passed (interactive): 96d1c37 create test2.txt
+        Ran command on 2 commits: true:
+        2 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--interactive"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                env: hashmap! {"SHELL".to_string() =>  "false".to_string()},
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        You are now at: 62fc20d create test1.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        You are now at: 96d1c37 create test2.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        branchless: running command: <git-executable> rebase --abort
+        X Failed (exit code 1, interactive): 62fc20d create test1.txt
+        X Failed (exit code 1, interactive): 96d1c37 create test2.txt
+        Ran command on 2 commits: false:
+        0 passed, 2 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--interactive", "-vv"],
+            &GitRunOptions {
+                env: hashmap! {"SHELL".to_string() =>  "bash".to_string()},
+                input: Some("echo hi; exit 0".to_string()),
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @r###"
+        Stopped at 96d1c37 (create test2.txt)
+        branchless: processing 1 update: ref HEAD
+        "###);
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        You are now at: 62fc20d create test1.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        hi
+        You are now at: 96d1c37 create test2.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (interactive): 62fc20d create test1.txt
+        ✓ Passed (interactive): 96d1c37 create test2.txt
+        Ran command on 2 commits: bash:
+        2 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--interactive", "-vv"],
+            &GitRunOptions {
+                env: hashmap! {"SHELL".to_string() =>  "bash".to_string()},
+                input: Some("echo hi; exit 0".to_string()),
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (cached, interactive): 62fc20d create test1.txt
+        ✓ Passed (cached, interactive): 96d1c37 create test2.txt
+        Ran command on 2 commits: bash:
+        2 passed, 0 failed, 0 skipped
+        2/2 results from cache (100%)
+        hint: there were 2 cached test results
+        hint: to clear these cached results, run: git test clean "stack() | @"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_interactive_resume() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    if !git.supports_reference_transactions()? {
+        return Ok(());
+    }
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    // Abort testing at the second commit.
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--interactive"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                env: hashmap! {"SHELL".to_string() =>  "bash".to_string()},
+                input: Some("exit 0\nexit 127\n".to_string()),
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        You are now at: 62fc20d create test1.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        You are now at: 96d1c37 create test2.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (interactive): 62fc20d create test1.txt
+        X Exit code indicated to abort command (exit code 127): 96d1c37 create test2.txt
+        Ran command on 2 commits: bash:
+        1 passed, 1 failed, 0 skipped
+        Aborted running commands with exit code 127 at commit: 96d1c37 create test2.txt
+        "###);
+    }
+
+    // The verdict for the first commit should be reused, and testing should
+    // resume from the commit where it was aborted.
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--interactive"],
+            &GitRunOptions {
+                env: hashmap! {"SHELL".to_string() =>  "bash".to_string()},
+                input: Some("exit 0\n".to_string()),
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Resuming interactive testing from commit: 96d1c37 create test2.txt (to start over, re-run with --no-cache)
+        You are now at: 96d1c37 create test2.txt
+        To mark this commit as passed,run:   exit 0
+        To mark this commit as failed, run:  exit 1
+        To mark this commit as skipped, run: exit 125
+        To abort testing entirely, run:      exit 127
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (cached, interactive): 62fc20d create test1.txt
+        ✓ Passed (interactive): 96d1c37 create test2.txt
+        Ran command on 2 commits: bash:
+        2 passed, 0 failed, 0 skipped
+        1/2 results from cache (50%)
+        hint: there was 1 cached test result
+        hint: to clear these cached results, run: git test clean "stack() | @"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_search_abort() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    if !git.supports_reference_transactions()? {
+        return Ok(());
+    }
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+if [[ "$(git log)" =~ 'test2' ]]; then
+    exit 127
+else
+    exit 0
+fi
+"#,
+    )?;
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--search", "linear", "-x", "bash test.sh"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @r###"
+        Stopped at 96d1c37 (create test2.txt)
+        branchless: processing 1 update: ref HEAD
+        "###);
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        Using test search strategy: linear
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: 62fc20d create test1.txt
+        X Exit code indicated to abort command (exit code 127): 96d1c37 create test2.txt
+        Ran command on 2 commits: bash test.sh:
+        1 passed, 1 failed, 0 skipped
+        Last passing commit:
+        - 62fc20d create test1.txt
+        There were no failing commits in the provided set.
+        Aborted running commands with exit code 127 at commit: 96d1c37 create test2.txt
+        "###);
+    }
+
+    // Check aborting when results are cached.
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--search", "linear", "-x", "bash test.sh"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        Using test search strategy: linear
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (cached): 62fc20d create test1.txt
+        X Exit code indicated to abort command (exit code 127): 96d1c37 create test2.txt
+        Ran command on 2 commits: bash test.sh:
+        1 passed, 1 failed, 0 skipped
+        1/2 results from cache (50%)
+        Last passing commit:
+        - 62fc20d create test1.txt
+        There were no failing commits in the provided set.
+        hint: there was 1 cached test result
+        hint: to clear these cached results, run: git test clean "stack() | @"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        Aborted running commands with exit code 127 at commit: 96d1c37 create test2.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)] // Paths don't match on Windows.
+#[test]
+fn test_test_sets_env_vars() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/sh
+echo "Commit is: $BRANCHLESS_TEST_COMMIT"
+echo "Tree is: $BRANCHLESS_TEST_TREE"
+echo "Subject is: $BRANCHLESS_TEST_COMMIT_SUBJECT"
+echo "Command is: $BRANCHLESS_TEST_COMMAND"
+"#,
+    )?;
+    {
+        let (stdout, _stderr) =
+            git.branchless("test", &["run", "--exec", "bash test.sh", "HEAD", "-vv"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: f777ecc create initial.txt
+        Stdout: <repo-path>/.git/branchless/test/d32758e20028dd1cffc2b359bc3766f80a258ee5/bash__test.sh/stdout
+        Commit is: f777ecc9b0db5ed372b2615695191a8a17f79f24
+        Tree is: d32758e20028dd1cffc2b359bc3766f80a258ee5
+        Subject is: create initial.txt
+        Command is: bash test.sh
+        Stderr: <repo-path>/.git/branchless/test/d32758e20028dd1cffc2b359bc3766f80a258ee5/bash__test.sh/stderr
+        <no output>
+        Ran command on 1 commit: bash test.sh:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_clean_env() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--clean-env",
+                "-x",
+                r#"test -z "$FOO" && test -n "$BRANCHLESS_TEST_COMMIT""#,
+                "@",
+            ],
+            &GitRunOptions {
+                env: hashmap! {"FOO".to_string() => "bar".to_string()},
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: test -z "$FOO" && test -n "$BRANCHLESS_TEST_COMMIT":
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    git.run(&["config", "branchless.test.env.passthrough", "PATH, FOO"])?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--clean-env",
+                "-x",
+                r#"test "$FOO" = bar"#,
+                "@",
+            ],
+            &GitRunOptions {
+                env: hashmap! {"FOO".to_string() => "bar".to_string()},
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: test "$FOO" = bar:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_clean_env_cached_separately() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                r#"test -z "$FOO""#,
+                "@",
+            ],
+            &GitRunOptions {
+                env: hashmap! {"FOO".to_string() => "bar".to_string()},
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        X Failed (exit code 1): 62fc20d create test1.txt
+        Ran command on 1 commit: test -z "$FOO":
+        0 passed, 1 failed, 0 skipped
+        "###);
+    }
+
+    // The failure without `--clean-env` shouldn't be reused.
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--clean-env",
+                "-x",
+                r#"test -z "$FOO""#,
+                "@",
+            ],
+            &GitRunOptions {
+                env: hashmap! {"FOO".to_string() => "bar".to_string()},
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: test -z "$FOO":
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)] // The error message differs on Windows.
+#[test]
+fn test_test_env_file() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    let env_file_path = git.repo_path.join("test.env");
+    std::fs::write(&env_file_path, "# Settings.\nFOO='bar baz'\n")?;
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--env-file",
+                env_file_path.to_str().unwrap(),
+                "-x",
+                r#"test "$FOO" = "bar baz""#,
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: test "$FOO" = "bar baz":
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    // Changing the environment file shouldn't reuse the cached result.
+    std::fs::write(&env_file_path, "FOO=qux\n")?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--env-file",
+                env_file_path.to_str().unwrap(),
+                "-x",
+                r#"test "$FOO" = "bar baz""#,
+                "@",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        X Failed (exit code 1): 62fc20d create test1.txt
+        Ran command on 1 commit: test "$FOO" = "bar baz":
+        0 passed, 1 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--env-file", "missing.env", "-x", "exit 0", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Could not read environment file missing.env: No such file or directory (os error 2)
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_revsets() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    if !git.supports_reference_transactions()? {
+        return Ok(());
+    }
+    git.init_repo()?;
+
+    git.detach_head()?;
+    let test1_oid = git.commit_file("test1", 1)?;
+    let test2_oid = git.commit_file("test2", 2)?;
+    let test3_oid = git.commit_file("test3", 3)?;
+    let test4_oid = git.commit_file("test4", 4)?;
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "query",
+            &["tests.passed()"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @r###"
+        Evaluation error for expression 'tests.passed()': there was no latest command run with `git test`; try running `git test` first
+        "###);
+        insta::assert_snapshot!(stdout, @"");
+    }
+
+    git.write_file(
+        "test.sh",
+        &format!(
+            r#"#!/bin/bash
+case "$(git rev-parse HEAD)" in
+    {test1_oid})
+        echo "Updated contents" >test1.txt
+        exit 0
+        ;;
+    {test2_oid})
+        exit 1
+        ;;
+    {test3_oid})
+        exit 125
+        ;;
+    {test4_oid})
+        exit 0
+        ;;
+    *)
+        exit 127
+        ;;
+esac
+"#
+        ),
+    )?;
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--exec", "bash test.sh"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @r###"
+        Stopped at 355e173 (create test4.txt)
+        branchless: processing 1 update: ref HEAD
+        "###);
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed (fixable): 62fc20d create test1.txt
+        X Failed (exit code 1): 96d1c37 create test2.txt
+        ! Exit code indicated to skip this commit (exit code 125): 70deb1e create test3.txt
+        ✓ Passed: 355e173 create test4.txt
+        Ran command on 4 commits: bash test.sh:
+        2 passed, 1 failed, 1 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("query", &["tests.passed()"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        62fc20d create test1.txt
+        355e173 create test4.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("query", &["tests.failed()"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        96d1c37 create test2.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("query", &["tests.fixable()"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        62fc20d create test1.txt
+        "###);
+    }
+
+    git.branchless("test", &["run", "--exec", "exit 0"])?;
+    {
+        let (stdout, _stderr) = git.branchless("query", &["tests.passed()"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        62fc20d create test1.txt
+        96d1c37 create test2.txt
+        70deb1e create test3.txt
+        355e173 create test4.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("query", &["tests.passed('bash test.sh')"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        62fc20d create test1.txt
+        355e173 create test4.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_no_cache() -> eyre::Result<()> {
+    // Tests the `--no-cache` flag of `git test run`.
+    let git = make_git()?;
+
+    if !git.supports_reference_transactions()? {
+        return Ok(());
+    }
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/bash
+    exit 0
+    "#,
+    )?;
+    {
+        let (stdout, _stderr) = git.branchless("test", &["run", "--exec", "bash test.sh"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        ✓ Passed: 62fc20d create test1.txt
+        ✓ Passed: 96d1c37 create test2.txt
+        Ran command on 2 commits: bash test.sh:
+        2 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    git.write_file(
+        "test.sh",
+        r#"#!/bin/bash
+    exit 1
+    "#,
+    )?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--exec", "bash test.sh", "--no-cache"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        X Failed (exit code 1): 62fc20d create test1.txt
+        X Failed (exit code 1): 96d1c37 create test2.txt
+        Ran command on 2 commits: bash test.sh:
+        0 passed, 2 failed, 0 skipped
+        Caching was disabled (--no-cache)
+        "###);
+    }
+
+    {
+        // The results of the `--no-cache` run should have overwritten the
+        // previously-cached results.
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--exec", "bash test.sh"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> diff --quiet
+        Calling Git for on-disk rebase...
+        branchless: running command: <git-executable> rebase --continue
+        Using command execution strategy: working-copy
+        branchless: running command: <git-executable> rebase --abort
+        X Failed (cached, exit code 1): 62fc20d create test1.txt
+        X Failed (cached, exit code 1): 96d1c37 create test2.txt
+        Ran command on 2 commits: bash test.sh:
+        0 passed, 2 failed, 0 skipped
+        2/2 results from cache (100%)
+        hint: there were 2 cached test results
+        hint: to clear these cached results, run: git test clean "stack() | @"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        "###);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_test_timeout() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    // Run twice: the second run should use the cached timeout result rather
+    // than waiting for the command again.
+    for _ in 0..2 {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--timeout",
+                "1",
+                "-x",
+                "sleep 60",
+                "@",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        X Timed out after 1s: 62fc20d create test1.txt
+        Ran command on 1 commit: sleep 60:
+        0 passed, 1 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--interactive", "--timeout", "1", "-x", "exit 0"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        The --timeout option cannot be used with the --interactive option.
+        "###);
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_test_memory_limit() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        // `ulimit -v` reports the address space limit in kibibytes.
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--memory-limit",
+                "1073741824",
+                "-x",
+                "test \"$(ulimit -v)\" = 1048576",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: test "$(ulimit -v)" = 1048576:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_test_wait_for_lock() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    // Simulate another `git test` process testing the same commit by holding
+    // the lock for a short while.
+    let command_dir = git
+        .repo_path
+        .join(".git/branchless/test")
+        .join("8108c01b1930423879f106c1ebf725fcbfedccda")
+        .join("exit__0");
+    std::fs::create_dir_all(&command_dir)?;
+    let mut lock_holder = std::process::Command::new("flock")
+        .arg(command_dir.join("pid.lock"))
+        .arg("sleep")
+        .arg("2")
+        .spawn()?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--wait=30",
+                "-x",
+                "exit 0",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+    lock_holder.wait()?;
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_test_reclaim_stale_lock() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    // Simulate a lock which was left locked after its owner died, by recording
+    // the PID of a process which has already exited and then holding the lock
+    // from another process.
+    let mut dead_process = std::process::Command::new("true").spawn()?;
+    let dead_pid = dead_process.id();
+    dead_process.wait()?;
+    let command_dir = git
+        .repo_path
+        .join(".git/branchless/test")
+        .join("8108c01b1930423879f106c1ebf725fcbfedccda")
+        .join("exit__0");
+    std::fs::create_dir_all(&command_dir)?;
+    let lock_path = command_dir.join("pid.lock");
+    std::fs::write(&lock_path, dead_pid.to_string())?;
+    let mut lock_holder = std::process::Command::new("flock")
+        .arg(&lock_path)
+        .arg("sleep")
+        .arg("5")
+        .spawn()?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "exit 0", "@"],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+    lock_holder.kill()?;
+    lock_holder.wait()?;
+
+    Ok(())
+}
+
+#[cfg(unix)] // Paths don't match on Windows.
+#[test]
+fn test_test_format_json() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    git.branchless(
+        "test",
+        &["run", "--strategy", "worktree", "-x", "echo hello", "@"],
+    )?;
+
+    {
+        // Use the cached result so that the duration is deterministic.
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "echo hello",
+                "--format",
+                "json",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        [
+          {
+            "commit_oid": "62fc20d2a290daea0d52bdc2ed2ad4be6491010e",
+            "status": "passed",
+            "exit_code": 0,
+            "cached": true,
+            "duration_ms": null,
+            "stdout": "<repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stdout",
+            "stderr": "<repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__hello/stderr"
+          }
+        ]
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_junit_report() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    // Run twice so that the report is produced from cached results, which
+    // have a deterministic duration.
+    for _ in 0..2 {
+        git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "echo oops >&2; exit 1",
+                "--junit",
+                "report.xml",
+                "@",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+    }
+
+    let report = std::fs::read_to_string(git.repo_path.join("report.xml"))?;
+    insta::assert_snapshot!(report, @r###"
+    <?xml version="1.0" encoding="UTF-8"?>
+    <testsuites>
+      <testsuite name="echo oops &gt;&amp;2; exit 1" tests="1" failures="1" skipped="0" time="0.000">
+        <testcase name="62fc20d2a290daea0d52bdc2ed2ad4be6491010e" classname="echo oops &gt;&amp;2; exit 1" time="0.000">
+          <failure message="failed">oops
+    </failure>
+        </testcase>
+      </testsuite>
+    </testsuites>
+    "###);
+
+    Ok(())
+}
+
+#[test]
+fn test_test_format_tap() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "2",
+                "--format",
+                "tap",
+                "-x",
+                "test ! -f test2.txt",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        1..2
+        ok 1 - 62fc20d create test1.txt
+        not ok 2 - 96d1c37 create test2.txt
+        # commit: 96d1c37a3d4363611c49f7e52186e189a04c531f
+        # status: failed
+        # exit code: 1
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_retries() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        // Fails on the first attempt only.
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--retries",
+                "2",
+                "-x",
+                r#"f="$(git rev-parse --git-common-dir)/attempted"; test -f "$f" || { touch "$f"; exit 1; }"#,
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed (flaky, 2 attempts): 62fc20d create test1.txt
+        Ran command on 1 commit: f="$(git rev-parse --git-common-dir)/attempted"; test -f "$f" || { touch "$f"; exit 1; }:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--retries",
+                "2",
+                "-x",
+                "exit 1",
+                "@",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        X Failed (exit code 1, 3 attempts): 62fc20d create test1.txt
+        Ran command on 1 commit: exit 1:
+        0 passed, 1 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_rerun_failures() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "test ! -f test2.txt"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        X Failed (exit code 1): 96d1c37 create test2.txt
+        Ran command on 2 commits: test ! -f test2.txt:
+        1 passed, 1 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--rerun-failures",
+                "-x",
+                "test ! -f test2.txt",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        X Failed (exit code 1): 96d1c37 create test2.txt
+        Ran command on 1 commit: test ! -f test2.txt:
+        0 passed, 1 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_notify_without_backend() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+    git.run(&["config", "branchless.test.notifyThreshold", "0"])?;
+
+    {
+        // The notification backend is most likely not available in the test
+        // environment, in which case the notification should be skipped
+        // without affecting the rest of the run.
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--notify",
+                "-x",
+                "exit 0",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_max_failures() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "1",
+                "--max-failures",
+                "1",
+                "-x",
+                "exit 1",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+
+        // The job for the next commit may have already been started by the
+        // time that the first failure is processed, but no jobs after that
+        // should be started.
+        assert!(
+            stdout.contains("X Failed (exit code 1): 62fc20d create test1.txt"),
+            "{stdout}"
+        );
+        assert!(!stdout.contains("create test3.txt"), "{stdout}");
+        assert!(
+            stdout.ends_with("Stopped running commands after 1 failure\n"),
+            "{stdout}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_setup_command() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--setup",
+                r#"echo "Setting up"; touch "$(git rev-parse --git-common-dir)/fixture""#,
+                "-x",
+                r#"test -f "$(git rev-parse --git-common-dir)/fixture""#,
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: test -f "$(git rev-parse --git-common-dir)/fixture":
+        1 passed, 0 failed, 0 skipped
+        "###);
+
+        let setup_output =
+            std::fs::read_to_string(git.repo_path.join(".git/branchless/test/setup"))?;
+        assert_eq!(setup_output, "Setting up\n");
+    }
+
+    git.run(&["config", "branchless.test.setup", "echo oops; exit 3"])?;
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "exit 0", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        Setup command failed with exit code 3: echo oops; exit 3
+        Setup output: <repo-path>/.git/branchless/test/setup
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_teardown_command() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+    git.run(&[
+        "config",
+        "branchless.test.teardown",
+        r#"touch "$(git rev-parse --git-common-dir)/teardown-$(basename "$PWD")"; exit 1"#,
+    ])?;
+
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "1",
+                "-x",
+                "exit 0",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    assert!(git
+        .repo_path
+        .join(".git")
+        .join("teardown-testing-worktree-1")
+        .exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_test_log() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, _stderr) = git.branchless("test", &["log"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        No cached test results for 62fc20d create test1.txt
+        "###);
+    }
+
+    git.branchless(
+        "test",
+        &["run", "--strategy", "worktree", "-x", "exit 0", "@"],
+    )?;
+    git.branchless_with_options(
+        "test",
+        &["run", "--strategy", "worktree", "-x", "exit 1", "@"],
+        &GitRunOptions {
+            expected_exit_code: 1,
+            ..Default::default()
+        },
+    )?;
+
+    {
+        let (stdout, _stderr) = git.branchless("test", &["log"])?;
+        // Redact the relative times, since they depend on how long the test
+        // took to run.
+        let stdout = stdout
+            .lines()
+            .map(|line| match (line.find(" ("), line.find(" ago)")) {
+                (Some(start), Some(end)) => {
+                    format!("{} (<time> ago){}", &line[..start], &line[end + 5..])
+                }
+                _ => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        insta::assert_snapshot!(stdout, @r###"
+        Cached test results for 62fc20d create test1.txt:
+        ✓ exit code 0 (<time> ago): exit 0
+        X exit code 1 (<time> ago): exit 1
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_configured_exit_codes() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    git.run(&["config", "branchless.test.skipExitCode", "3"])?;
+    git.run(&["config", "branchless.test.abortExitCode", "4"])?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "1",
+                "-x",
+                "if test -f test2.txt; then exit 4; else exit 3; fi",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ! Exit code indicated to skip this commit (exit code 3): 62fc20d create test1.txt
+        X Exit code indicated to abort command (exit code 4): 96d1c37 create test2.txt
+        Ran command on 2 commits: if test -f test2.txt; then exit 4; else exit 3; fi:
+        0 passed, 1 failed, 1 skipped
+        Aborted running commands with exit code 4 at commit: 96d1c37 create test2.txt
+        "###);
+    }
+
+    git.run(&["config", "branchless.test.skipExitCode", "0"])?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "exit 0"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Invalid value for config value branchless.test.skipExitCode (0): it cannot be the same as the success exit code.
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_configured_exit_code_lists() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    git.run(&["config", "branchless.test.successExitCodes", "5, 6"])?;
+    git.run(&["config", "branchless.test.skipExitCodes", "7"])?;
+    let command = "if test -f test2.txt; then exit 7; else exit 5; fi";
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "worktree", "-x", command],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        ! Exit code indicated to skip this commit (exit code 7): 96d1c37 create test2.txt
+        Ran command on 2 commits: if test -f test2.txt; then exit 7; else exit 5; fi:
+        1 passed, 0 failed, 1 skipped
+        "###);
+    }
+
+    // Cached results should be interpreted the same way.
+    {
+        let (stdout, _stderr) = git.branchless("test", &["show", "-x", command])?;
+        insta::assert_snapshot!(stdout, @r###"
+        ✓ Passed (cached): 62fc20d create test1.txt
+        ! Exit code indicated to skip this commit (exit code 7): 96d1c37 create test2.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
+        "###);
+    }
+
+    git.run(&["config", "branchless.test.skipExitCodes", "6"])?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "exit 0"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Invalid values for config values branchless.test.successExitCodes and branchless.test.skipExitCodes (6): an exit code cannot be in both.
+        "###);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_test_terminated_by_signal() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "kill -KILL $$", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ! Command terminated by signal SIGKILL (9): 62fc20d create test1.txt
+        Ran command on 1 commit: kill -KILL $$:
+        0 passed, 0 failed, 1 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)] // Paths don't match on Windows.
+#[test]
+fn test_test_combined_output() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--combined-output",
+                "-v",
+                "-x",
+                "echo out; echo err >&2; echo out2",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Output: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__out;__echo__err__>&2;__echo__out2/stdout
+        out
+        err
+        out2
+        Ran command on 1 commit: echo out; echo err >&2; echo out2:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["show", "-v", "-x", "echo out; echo err >&2; echo out2", "@"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        ✓ Passed (cached): 62fc20d create test1.txt
+        Output: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__out;__echo__err__>&2;__echo__out2/stdout
+        out
+        err
+        out2
+        hint: to see more detailed output, re-run with -vv/--verbose --verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
+        "###);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)] // Paths don't match on Windows.
+#[test]
+fn test_test_combined_output_max_output_bytes() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+    git.run(&["config", "branchless.test.maxOutputBytes", "8"])?;
+
+    // The limit applies to the output of both streams together, rather than
+    // to each of them separately.
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--combined-output",
+                "-v",
+                "-x",
+                "echo aaaa >&2; sleep 0.2; echo bbbb; sleep 0.2; echo cccc >&2",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Output: <repo-path>/.git/branchless/test/8108c01b1930423879f106c1ebf725fcbfedccda/echo__aaaa__>&2;__sleep__0.2;__echo__bbbb;__sleep__0.2;__echo__cccc__>&2/stdout
+        aaaa
+        bbb
+        <output truncated>
+        Ran command on 1 commit: echo aaaa >&2; sleep 0.2; echo bbbb; sleep 0.2; echo cccc >&2:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_stream() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--stream",
+                "-x",
+                "echo hello; echo goodbye >&2",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"goodbye");
+        insta::assert_snapshot!(stdout, @r###"
+        hello
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: echo hello; echo goodbye >&2:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "2",
+                "--stream",
+                "-x",
+                "exit 0",
+                "@",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The --stream option cannot be used with multiple jobs (2 were requested), since the output of the jobs would be interleaved.
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_cache_dir() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--cache-dir",
+                "cache",
+                "-x",
+                "exit 0",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+
+    let tree_dir = "8108c01b1930423879f106c1ebf725fcbfedccda";
+    assert!(git
+        .repo_path
+        .join("cache")
+        .join(tree_dir)
+        .join("exit__0")
+        .join("result")
+        .exists());
+    assert!(!git
+        .repo_path
+        .join(".git/branchless/test")
+        .join(tree_dir)
+        .exists());
+
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["show", "--cache-dir", "cache", "-x", "exit 0", "@"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        ✓ Passed (cached): 62fc20d create test1.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
+        "###);
+    }
+
+    git.run(&[
+        "config",
+        "branchless.test.cacheDir",
+        git.repo_path.join("cache").to_str().unwrap(),
+    ])?;
+    {
+        let (stdout, _stderr) = git.branchless("test", &["clean", "@"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        Cleaning results for 62fc20d create test1.txt
+        Cleaned 1 cached test result.
+        "###);
+    }
+    git.run(&["config", "--unset", "branchless.test.cacheDir"])?;
+
+    git.write_file_txt("not-a-dir", "contents")?;
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--cache-dir",
+                "not-a-dir.txt/cache",
+                "-x",
+                "exit 0",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The test cache directory not-a-dir.txt/cache is not writable: Not a directory (os error 20)
+        Falling back to the default test cache directory: <repo-path>/.git/branchless/test
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+    assert!(git
+        .repo_path
+        .join(".git/branchless/test")
+        .join(tree_dir)
+        .exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_test_worktree_dir() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+
+    let worktrees_dir = tempfile::tempdir()?;
+    git.run(&[
+        "config",
+        "branchless.test.worktreeDir",
+        worktrees_dir.path().to_str().unwrap(),
+    ])?;
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "exit 0", "@"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+    assert!(worktrees_dir.path().join("testing-worktree-1").exists());
+    assert!(!git
+        .repo_path
+        .join(".git/branchless/test/worktrees/testing-worktree-1")
+        .exists());
+
+    git.run(&["config", "branchless.test.worktreeDir", "worktrees"])?;
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--no-cache",
+                "-x",
+                "exit 0",
+                "@",
+            ],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The test worktree directory worktrees is inside the working copy at <repo-path>
+        Falling back to the default test worktree directory: <repo-path>/.git/branchless/test/worktrees
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        Caching was disabled (--no-cache)
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_skip_empty() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.run(&["commit", "--allow-empty", "-m", "empty commit"])?;
+    git.commit_file("test2", 2)?;
+
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--skip-empty",
+                "-x",
+                "exit 0",
+                "stack()",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        ! Skipped empty commit: 9e34524 empty commit
+        ✓ Passed: 1d0407b create test2.txt
+        Ran command on 3 commits: exit 0:
+        2 passed, 0 failed, 1 skipped
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_merge_parents() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+    git.detach_head()?;
+    let test1_oid = git.commit_file("test1", 1)?;
+    git.run(&["checkout", "master"])?;
+    git.commit_file("test2", 2)?;
+    git.run(&[
+        "merge",
+        "--no-ff",
+        "-m",
+        "merge test1",
+        &test1_oid.to_string(),
+    ])?;
+
+    {
+        let (stdout, stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--test-merge-parents",
+                "-x",
+                "test -f test1.txt && test -f test2.txt",
+                "HEAD",
+            ],
+        )?;
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 020e9b0 merge test1
+        ✓ Passed (cached): 8f6e1b8 merge test1 (from parent fe65c1f)
+        ✓ Passed (cached): b2d23e4 merge test1 (from parent 62fc20d)
+        Ran command on 3 commits: test -f test1.txt && test -f test2.txt:
+        3 passed, 0 failed, 0 skipped
+        2/3 results from cache (66%)
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_test_worktree_count() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+
+    git.branchless(
+        "test",
+        &[
+            "run",
+            "--strategy",
+            "worktree",
+            "--jobs",
+            "2",
+            "--worktree-count",
+            "1",
+            "-x",
+            "exit 0",
+            "stack()",
+        ],
+    )?;
+    let worktrees_dir = git.repo_path.join(".git/branchless/test/worktrees");
+    assert!(worktrees_dir.join("testing-worktree-1").exists());
+    assert!(!worktrees_dir.join("testing-worktree-2").exists());
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "2",
+                "--worktree-count",
+                "3",
+                "-x",
+                "exit 0",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The --worktree-count option must be between 1 and the number of jobs (2), but got: 3
         "###);
     }
-    {
-        let (stdout, _stderr) = git.run(&["log", "--patch"])?;
-        assert_eq!(stdout, original_log_output);
-    }
 
     {
-        let stdout = git.smartlog()?;
-        assert_eq!(stdout, updated_smartlog);
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "working-copy",
+                "--worktree-count",
+                "1",
+                "-x",
+                "exit 0",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The --worktree-count option can only be used with --strategy worktree.
+        "###);
     }
 
     Ok(())
 }
 
 #[test]
-fn test_test_fix_failure() -> eyre::Result<()> {
+fn test_test_docker_strategy_requires_image() -> eyre::Result<()> {
     let git = make_git()?;
     git.init_repo()?;
 
-    git.detach_head()?;
     git.commit_file("test1", 1)?;
-    git.commit_file("test2", 2)?;
-    git.commit_file("test3", 3)?;
 
-    git.write_file(
-        "test.sh",
-        r#"#!/bin/sh
-for i in *.txt; do
-    if [[ "$i" == test2* ]]; then
-        echo "Failed on $i"
-        exit 1
-    fi
-    echo "Updated contents for file $i" >"$i"
-done
-"#,
-    )?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["run", "--strategy", "docker", "-x", "exit 0", "@"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The docker execution strategy requires an image to run the tests in. Set one with: git config branchless.test.dockerImage <image>
+        "###);
+    }
 
+    git.run(&["config", "branchless.test.dockerImage", "alpine"])?;
     {
         let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["fix", "-x", "bash test.sh"],
+            &["run", "--strategy", "docker", "--interactive", "@"],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (fixed): 62fc20d create test1.txt
-        X Failed (exit code 1): 96d1c37 create test2.txt
-        X Failed (exit code 1): 70deb1e create test3.txt
-        Ran command on 3 commits: bash test.sh:
-        1 passed, 2 failed, 0 skipped
+        The --interactive option cannot be used with --strategy docker.
         "###);
     }
+
     Ok(())
 }
 
 #[test]
-fn test_test_no_apply_descendants_as_patches() -> eyre::Result<()> {
+fn test_test_tmpfs_strategy() -> eyre::Result<()> {
     let git = make_git()?;
     git.init_repo()?;
 
     git.detach_head()?;
     git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
 
-    git.write_file_txt("test1", "This file would conflict if applied as a patch\n")?;
-    git.write_file_txt("test2", "Updated contents for file test2.txt\n")?;
-    git.run(&["add", "."])?;
-    git.run(&["commit", "-m", "descendant commit"])?;
-
-    git.write_file(
-        "test.sh",
-        r#"#!/bin/sh
-for i in *.txt; do
-    echo "Updated contents for file $i" >"$i"
-done
-"#,
-    )?;
+    let tmpfs_dir = git.repo_path.join(".git").join("tmpfs");
+    git.run(&[
+        "config",
+        "branchless.test.tmpfsDir",
+        tmpfs_dir.to_str().unwrap(),
+    ])?;
 
     {
-        let (stdout, _stderr) = git.run(&["log", "--patch"])?;
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "tmpfs",
+                "--jobs",
+                "1",
+                "-x",
+                "test -f test1.txt && ! test -f test2.txt && ! test -e .git",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
         insta::assert_snapshot!(stdout, @r###"
-        commit 75e728fb17f6952287302c3e76d88aa737dd99d1
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 +0000
-
-            descendant commit
-
-        diff --git a/test1.txt b/test1.txt
-        index 7432a8f..60d9cdb 100644
-        --- a/test1.txt
-        +++ b/test1.txt
-        @@ -1 +1 @@
-        -test1 contents
-        +This file would conflict if applied as a patch
-        diff --git a/test2.txt b/test2.txt
-        new file mode 100644
-        index 0000000..dce8610
-        --- /dev/null
-        +++ b/test2.txt
-        @@ -0,0 +1 @@
-        +Updated contents for file test2.txt
-
-        commit 62fc20d2a290daea0d52bdc2ed2ad4be6491010e
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 -0100
+        Using command execution strategy: tmpfs
+        ✓ Passed: 62fc20d create test1.txt
+        X Failed (exit code 1): 96d1c37 create test2.txt
+        Ran command on 2 commits: test -f test1.txt && ! test -f test2.txt && ! test -e .git:
+        1 passed, 1 failed, 0 skipped
+        "###);
+    }
 
-            create test1.txt
+    // The tmpfs directory should be cleaned up once testing is done.
+    assert_eq!(std::fs::read_dir(&tmpfs_dir)?.count(), 0);
 
-        diff --git a/test1.txt b/test1.txt
-        new file mode 100644
-        index 0000000..7432a8f
-        --- /dev/null
-        +++ b/test1.txt
-        @@ -0,0 +1 @@
-        +test1 contents
+    // The working copy should not have been touched.
+    {
+        let stdout = git.smartlog()?;
+        insta::assert_snapshot!(stdout, @r###"
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        @ 96d1c37 create test2.txt
+        "###);
+    }
 
-        commit f777ecc9b0db5ed372b2615695191a8a17f79f24
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 +0000
+    Ok(())
+}
 
-            create initial.txt
+#[test]
+fn test_test_durations() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
 
-        diff --git a/initial.txt b/initial.txt
-        new file mode 100644
-        index 0000000..63af228
-        --- /dev/null
-        +++ b/initial.txt
-        @@ -0,0 +1 @@
-        +initial contents
-        "###);
-    }
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
 
     {
-        let (stdout, _stderr) = git.branchless("test", &["fix", "-x", "bash test.sh"])?;
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "if test -f test2.txt; then sleep 1; fi",
+            ],
+        )?;
+        // Redact the durations, since they depend on how long the command took
+        // to run.
+        let stdout = stdout
+            .lines()
+            .map(|line| match line.rfind(" (") {
+                Some(start) if line.ends_with("s)") => format!("{} (<duration>)", &line[..start]),
+                _ => match line.strip_prefix("Total time spent running the command: ") {
+                    Some(_) => "Total time spent running the command: <duration>".to_string(),
+                    None => line.to_string(),
+                },
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut settings = insta::Settings::clone_current();
+        settings.add_filter(
+            r"Finished in \S+ \(averaging \S+ per commit tested\)",
+            "Finished in <duration> (averaging <duration> per commit tested)",
+        );
+        let _guard = settings.bind_to_scope();
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (fixed): 62fc20d create test1.txt
-        ✓ Passed (fixed): 75e728f descendant commit
-        Ran command on 2 commits: bash test.sh:
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        ✓ Passed: 96d1c37 create test2.txt (<duration>)
+        Ran command on 2 commits: if test -f test2.txt; then sleep 1; fi:
         2 passed, 0 failed, 0 skipped
-        Attempting rebase in-memory...
-        [1/2] Committed as: 300cb54 create test1.txt
-        [2/2] Committed as: f15b423 descendant commit
-        branchless: processing 2 rewritten commits
-        branchless: running command: <git-executable> checkout f15b423404bbebfe4b09e305e074b525d008f44a
-        In-memory rebase succeeded.
-        Fixed 2 commits with bash test.sh:
-        62fc20d -> 300cb54 create test1.txt
-        75e728f -> f15b423 descendant commit
+        Total time spent running the command: <duration>
+        Slowest commit:
+        - 96d1c37 create test2.txt (<duration>)
+        Finished in <duration> (averaging <duration> per commit tested)
         "###);
     }
 
+    // The durations should be recorded in the cache.
     {
-        let (stdout, _stderr) = git.run(&["log", "--patch"])?;
-        insta::assert_snapshot!(stdout, @r###"
-        commit f15b423404bbebfe4b09e305e074b525d008f44a
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 +0000
-
-            descendant commit
-
-        diff --git a/test2.txt b/test2.txt
-        new file mode 100644
-        index 0000000..dce8610
-        --- /dev/null
-        +++ b/test2.txt
-        @@ -0,0 +1 @@
-        +Updated contents for file test2.txt
-
-        commit 300cb542f9b6474befd598bdbdd263d7d2b011a0
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 -0100
-
-            create test1.txt
+        let mut durations_ms = Vec::new();
+        for entry in std::fs::read_dir(git.repo_path.join(".git/branchless/test"))? {
+            let result_path = entry?
+                .path()
+                .join("if__test__-f__test2.txt;__then__sleep__1;__fi")
+                .join("result");
+            if result_path.exists() {
+                let result: serde_json::Value =
+                    serde_json::from_str(&std::fs::read_to_string(result_path)?)?;
+                durations_ms.push(result["duration_ms"].as_u64().unwrap());
+            }
+        }
+        durations_ms.sort_unstable();
+        assert_eq!(durations_ms.len(), 2);
+        assert!(durations_ms[1] >= 1000);
+    }
 
-        diff --git a/initial.txt b/initial.txt
-        index 63af228..a48ef19 100644
-        --- a/initial.txt
-        +++ b/initial.txt
-        @@ -1 +1 @@
-        -initial contents
-        +Updated contents for file initial.txt
-        diff --git a/test1.txt b/test1.txt
-        new file mode 100644
-        index 0000000..4d62cad
-        --- /dev/null
-        +++ b/test1.txt
-        @@ -0,0 +1 @@
-        +Updated contents for file test1.txt
+    Ok(())
+}
 
-        commit f777ecc9b0db5ed372b2615695191a8a17f79f24
-        Author: Testy McTestface <test@example.com>
-        Date:   Thu Oct 29 12:34:56 2020 +0000
+#[test]
+fn test_test_format_csv() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
 
-            create initial.txt
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.write_file_txt("test2", "test2 contents\n")?;
+    git.run(&["add", "."])?;
+    git.run(&["commit", "-m", r#"Update foo, bar, and "baz""#])?;
+
+    // Populate the cache first, so that the output below is produced from
+    // cached results, which have a deterministic duration.
+    git.branchless_with_options(
+        "test",
+        &["run", "--strategy", "worktree", "-x", "test ! -f test2.txt"],
+        &GitRunOptions {
+            expected_exit_code: 1,
+            ..Default::default()
+        },
+    )?;
 
-        diff --git a/initial.txt b/initial.txt
-        new file mode 100644
-        index 0000000..63af228
-        --- /dev/null
-        +++ b/initial.txt
-        @@ -0,0 +1 @@
-        +initial contents
+    {
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--format",
+                "csv",
+                "-x",
+                "test ! -f test2.txt",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        let (head_oid, _stderr) = git.run(&["rev-parse", "HEAD"])?;
+        let (head_short_oid, _stderr) = git.run(&["rev-parse", "--short", "HEAD"])?;
+        let stdout = stdout
+            .replace(head_oid.trim(), "<head-oid>")
+            .replace(head_short_oid.trim(), "<head-short-oid>");
+        insta::assert_snapshot!(stderr, @"");
+        insta::assert_snapshot!(stdout, @r###"
+        oid,short_oid,subject,status,exit_code,cached,duration_ms
+        62fc20d2a290daea0d52bdc2ed2ad4be6491010e,62fc20d,create test1.txt,passed,0,true,
+        <head-oid>,<head-short-oid>,"Update foo, bar, and ""baz""",failed,1,true,
         "###);
     }
 
@@ -1196,24 +5031,39 @@ done
 }
 
 #[test]
-fn test_test_forbid_on_disk_rebase() -> eyre::Result<()> {
+fn test_test_jobs_count_mode() -> eyre::Result<()> {
     let git = make_git()?;
-
     git.init_repo()?;
+
     git.detach_head()?;
     git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
 
+    git.run(&["config", "branchless.test.jobsCountMode", "invalid-value"])?;
     {
         let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["fix", "--on-disk", "-x", "exit 0"],
+            &["run", "--jobs", "0", "-x", "exit 0"],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        The --on-disk option cannot be provided for fixes. Use the --in-memory option instead.
+        Invalid value for config value branchless.test.jobsCountMode: invalid-value
+        Expected one of: physical, logical
+        "###);
+    }
+
+    git.run(&["config", "branchless.test.jobsCountMode", "logical"])?;
+    {
+        let (stdout, _stderr) = git.branchless("test", &["run", "--jobs", "0", "-x", "exit 0"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        ✓ Passed: 96d1c37 create test2.txt
+        Ran command on 2 commits: exit 0:
+        2 passed, 0 failed, 0 skipped
         "###);
     }
 
@@ -1221,66 +5071,58 @@ fn test_test_forbid_on_disk_rebase() -> eyre::Result<()> {
 }
 
 #[test]
-fn test_test_search_binary() -> eyre::Result<()> {
+fn test_test_cache_key_includes_command() -> eyre::Result<()> {
     let git = make_git()?;
-
     git.init_repo()?;
-    git.detach_head()?;
+
     git.commit_file("test1", 1)?;
-    git.commit_file("test2", 2)?;
-    git.commit_file("test3", 3)?;
-    git.commit_file("test4", 4)?;
-    git.commit_file("test5", 5)?;
 
     {
         let (stdout, _stderr) = git.branchless(
             "test",
-            &[
-                "run",
-                "--search",
-                "binary",
-                "--exec",
-                "! git grep -q 'test4'",
-            ],
+            &["run", "--strategy", "worktree", "-x", "echo a b", "@"],
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        Using test search strategy: binary
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: 70deb1e create test3.txt
-        X Failed (exit code 1): 355e173 create test4.txt
-        X Failed (exit code 1): f81d55c create test5.txt
-        Ran command on 3 commits: ! git grep -q 'test4':
-        1 passed, 2 failed, 0 skipped
-        Last passing commit:
-        - 70deb1e create test3.txt
-        First failing commit:
-        - 355e173 create test4.txt
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: echo a b:
+        1 passed, 0 failed, 0 skipped
         "###);
     }
 
-    Ok(())
-}
-
-#[test]
-fn test_test_run_none() -> eyre::Result<()> {
-    let git = make_git()?;
-    git.init_repo()?;
+    // Formerly, both commands mapped to the same cache directory.
+    {
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "echo a/b", "@"],
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: echo a/b:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
 
+    // A result stored for a different command should be treated as a cache
+    // miss.
+    let (tree_oid, _stderr) = git.run(&["rev-parse", "HEAD^{tree}"])?;
+    let tree_dir = git
+        .repo_path
+        .join(".git/branchless/test")
+        .join(tree_oid.trim());
+    std::fs::remove_dir_all(tree_dir.join("echo__a_sb"))?;
+    std::fs::rename(tree_dir.join("echo__a__b"), tree_dir.join("echo__a_sb"))?;
     {
-        // Shouldn't time out.
-        let (stdout, _stderr) = git.branchless("test", &["run", "-x", "true", "none()"])?;
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "echo a/b", "@"],
+        )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        Ran command on 0 commits: true:
-        0 passed, 0 failed, 0 skipped
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: echo a/b:
+        1 passed, 0 failed, 0 skipped
         "###);
     }
 
@@ -1288,133 +5130,149 @@ fn test_test_run_none() -> eyre::Result<()> {
 }
 
 #[test]
-fn test_test_search_skip_indeterminate() -> eyre::Result<()> {
+fn test_test_cache_invalidation() -> eyre::Result<()> {
     let git = make_git()?;
-
     git.init_repo()?;
-    git.detach_head()?;
+
     git.commit_file("test1", 1)?;
-    git.commit_file("test2", 2)?;
-    git.commit_file("test3", 3)?;
-    git.commit_file("test4", 4)?;
-    git.commit_file("test5", 5)?;
-    git.commit_file("test6", 6)?;
-    git.commit_file("test7", 7)?;
 
+    git.run(&["config", "branchless.test.alias.default", "exit 0"])?;
     {
-        let (stdout, _stderr) =
-            git.branchless("test", &["run", "--search", "binary", "--exec", "exit 125"])?;
+        let (stdout, _stderr) = git.branchless("test", &["run", "--strategy", "worktree", "@"])?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        Using test search strategy: binary
-        branchless: running command: <git-executable> rebase --abort
-        ! Exit code indicated to skip this commit (exit code 125): 62fc20d create test1.txt
-        ! Exit code indicated to skip this commit (exit code 125): 96d1c37 create test2.txt
-        ! Exit code indicated to skip this commit (exit code 125): 70deb1e create test3.txt
-        ! Exit code indicated to skip this commit (exit code 125): 355e173 create test4.txt
-        ! Exit code indicated to skip this commit (exit code 125): f81d55c create test5.txt
-        ! Exit code indicated to skip this commit (exit code 125): 2831fb5 create test6.txt
-        ! Exit code indicated to skip this commit (exit code 125): c8933b3 create test7.txt
-        Ran command on 7 commits: exit 125:
-        0 passed, 0 failed, 7 skipped
-        There were no passing commits in the provided set.
-        There were no failing commits in the provided set.
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        "###);
+    }
+    {
+        let (stdout, _stderr) = git.branchless("test", &["run", "--strategy", "worktree", "@"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed (cached): 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        1/1 result from cache (100%)
         "###);
     }
 
-    // Confirm that we treat exit code 125 as indeterminate even when cached.
+    // A result written in a different format should be ignored by every
+    // reader, and treated as a cache miss.
+    let (tree_oid, _stderr) = git.run(&["rev-parse", "HEAD^{tree}"])?;
+    let result_path = git
+        .repo_path
+        .join(".git/branchless/test")
+        .join(tree_oid.trim())
+        .join("exit__0")
+        .join("result");
+    let mut result: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&result_path)?)?;
+    result["schema_version"] = serde_json::Value::from(0);
+    std::fs::write(&result_path, serde_json::to_string(&result)?)?;
     {
-        let (stdout, _stderr) =
-            git.branchless("test", &["run", "--search", "binary", "--exec", "exit 125"])?;
+        let (stdout, _stderr) = git.branchless("query", &["tests.passed()"])?;
+        insta::assert_snapshot!(stdout, @"");
+    }
+    {
+        let (stdout, _stderr) = git.branchless("test", &["log", "@"])?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        Using test search strategy: binary
-        branchless: running command: <git-executable> rebase --abort
-        ! Exit code indicated to skip this commit (exit code 125): 62fc20d create test1.txt
-        ! Exit code indicated to skip this commit (exit code 125): 96d1c37 create test2.txt
-        ! Exit code indicated to skip this commit (exit code 125): 70deb1e create test3.txt
-        ! Exit code indicated to skip this commit (exit code 125): 355e173 create test4.txt
-        ! Exit code indicated to skip this commit (exit code 125): f81d55c create test5.txt
-        ! Exit code indicated to skip this commit (exit code 125): 2831fb5 create test6.txt
-        ! Exit code indicated to skip this commit (exit code 125): c8933b3 create test7.txt
-        Ran command on 7 commits: exit 125:
-        0 passed, 0 failed, 7 skipped
-        There were no passing commits in the provided set.
-        There were no failing commits in the provided set.
+        No cached test results for 62fc20d create test1.txt
+        "###);
+    }
+    {
+        let (stdout, _stderr) = git.branchless("test", &["run", "--strategy", "worktree", "@"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
         "###);
     }
 
-    git.write_file(
-        "test.sh",
-        r#"#!/bin/sh
-if [[ "$(git log)" =~ 'test4' ]]; then
-    exit 125
-elif [[ "$(git log)" =~ 'test6' ]]; then
-    exit 1
-else
-    exit 0
-fi
-"#,
-    )?;
+    Ok(())
+}
+
+#[test]
+fn test_test_multiple_commands() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
 
     {
-        let (stdout, _stderr) = git.branchless(
+        let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--search", "linear", "--exec", "bash test.sh"],
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "test -f test2.txt",
+                "-x",
+                "exit 0",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        Using test search strategy: linear
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: 62fc20d create test1.txt
+        Using command execution strategy: worktree
+        X Failed (exit code 1): 62fc20d create test1.txt
         ✓ Passed: 96d1c37 create test2.txt
-        ✓ Passed: 70deb1e create test3.txt
-        ! Exit code indicated to skip this commit (exit code 125): 355e173 create test4.txt
-        ! Exit code indicated to skip this commit (exit code 125): f81d55c create test5.txt
-        ! Exit code indicated to skip this commit (exit code 125): 2831fb5 create test6.txt
-        ! Exit code indicated to skip this commit (exit code 125): c8933b3 create test7.txt
-        Ran command on 7 commits: bash test.sh:
-        3 passed, 0 failed, 4 skipped
-        Last passing commit:
-        - 70deb1e create test3.txt
-        There were no failing commits in the provided set.
+        Ran command on 2 commits: test -f test2.txt:
+        1 passed, 1 failed, 0 skipped
+        Using command execution strategy: worktree
+        ✓ Passed: 96d1c37 create test2.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
+        Ran 2 commands on 2 commits:
+        1 passed, 1 failed, 0 skipped
         "###);
     }
 
+    git.run(&["config", "branchless.test.shortCircuit", "false"])?;
     {
-        let (stdout, _stderr) = git.branchless(
+        let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--search", "binary", "--exec", "bash test.sh"],
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "test -f test2.txt",
+                "-x",
+                "exit 0",
+            ],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        Using test search strategy: binary
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (cached): 70deb1e create test3.txt
-        ! Exit code indicated to skip this commit (exit code 125): 355e173 create test4.txt
-        ! Exit code indicated to skip this commit (exit code 125): f81d55c create test5.txt
-        ! Exit code indicated to skip this commit (exit code 125): 2831fb5 create test6.txt
-        ! Exit code indicated to skip this commit (exit code 125): c8933b3 create test7.txt
-        Ran command on 5 commits: bash test.sh:
-        1 passed, 0 failed, 4 skipped
-        Last passing commit:
-        - 70deb1e create test3.txt
-        There were no failing commits in the provided set.
+        Using command execution strategy: worktree
+        X Failed (cached) (exit code 1): 62fc20d create test1.txt
+        ✓ Passed (cached): 96d1c37 create test2.txt
+        Ran command on 2 commits: test -f test2.txt:
+        1 passed, 1 failed, 0 skipped
+        2/2 results from cache (100%)
+        hint: there were 2 cached test results
+        hint: to clear these cached results, run: git test clean "stack() | @"
+        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        ✓ Passed (cached): 96d1c37 create test2.txt
+        Ran command on 2 commits: exit 0:
+        2 passed, 0 failed, 0 skipped
+        1/2 results from cache (50%)
         hint: there was 1 cached test result
         hint: to clear these cached results, run: git test clean "stack() | @"
         hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        Ran 2 commands on 2 commits:
+        1 passed, 1 failed, 0 skipped
         "###);
     }
 
@@ -1422,140 +5280,96 @@ fi
 }
 
 #[test]
-fn test_test_interactive() -> eyre::Result<()> {
+fn test_test_skip_descendants_on_failure() -> eyre::Result<()> {
     let git = make_git()?;
-
-    if !git.supports_reference_transactions()? {
-        return Ok(());
-    }
     git.init_repo()?;
 
     git.detach_head()?;
     git.commit_file("test1", 1)?;
     git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
 
     {
         let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--interactive"],
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "1",
+                "--skip-descendants-on-failure",
+                "-x",
+                "! test -f test2.txt",
+            ],
             &GitRunOptions {
-                env: hashmap! {"SHELL".to_string() =>  "true".to_string()},
+                expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        You are now at: 62fc20d create test1.txt
-        To mark this commit as passed,run:   exit 0
-        To mark this commit as failed, run:  exit 1
-        To mark this commit as skipped, run: exit 125
-        To abort testing entirely, run:      exit 127
-        You are now at: 96d1c37 create test2.txt
-        To mark this commit as passed,run:   exit 0
-        To mark this commit as failed, run:  exit 1
-        To mark this commit as skipped, run: exit 125
-        To abort testing entirely, run:      exit 127
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (interactive): 62fc20d create test1.txt
-        ✓ Passed (interactive): 96d1c37 create test2.txt
-        Ran command on 2 commits: true:
-        2 passed, 0 failed, 0 skipped
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        X Failed (exit code 1): 96d1c37 create test2.txt
+        ! Skipped because an ancestor failed: 70deb1e create test3.txt
+        Ran command on 3 commits: ! test -f test2.txt:
+        1 passed, 1 failed, 1 skipped
         "###);
     }
 
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_test_interrupt() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    // Simulate pressing Ctrl-C while testing the second commit. The result for
+    // the first commit should still be reported, and the third commit
+    // shouldn't be tested.
+    let command = "if test -f test2.txt; then kill -INT $PPID; sleep 1; fi";
     {
         let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--interactive"],
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "1",
+                "-x",
+                command,
+            ],
             &GitRunOptions {
                 expected_exit_code: 1,
-                env: hashmap! {"SHELL".to_string() =>  "false".to_string()},
-                ..Default::default()
-            },
-        )?;
-        insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        You are now at: 62fc20d create test1.txt
-        To mark this commit as passed,run:   exit 0
-        To mark this commit as failed, run:  exit 1
-        To mark this commit as skipped, run: exit 125
-        To abort testing entirely, run:      exit 127
-        You are now at: 96d1c37 create test2.txt
-        To mark this commit as passed,run:   exit 0
-        To mark this commit as failed, run:  exit 1
-        To mark this commit as skipped, run: exit 125
-        To abort testing entirely, run:      exit 127
-        branchless: running command: <git-executable> rebase --abort
-        X Failed (exit code 1, interactive): 62fc20d create test1.txt
-        X Failed (exit code 1, interactive): 96d1c37 create test2.txt
-        Ran command on 2 commits: false:
-        0 passed, 2 failed, 0 skipped
-        "###);
-    }
-
-    {
-        let (stdout, stderr) = git.branchless_with_options(
-            "test",
-            &["run", "--interactive", "-vv"],
-            &GitRunOptions {
-                env: hashmap! {"SHELL".to_string() =>  "bash".to_string()},
-                input: Some("echo hi; exit 0".to_string()),
                 ..Default::default()
             },
         )?;
-        insta::assert_snapshot!(stderr, @r###"
-        Stopped at 96d1c37 (create test2.txt)
-        branchless: processing 1 update: ref HEAD
-        "###);
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        You are now at: 62fc20d create test1.txt
-        To mark this commit as passed,run:   exit 0
-        To mark this commit as failed, run:  exit 1
-        To mark this commit as skipped, run: exit 125
-        To abort testing entirely, run:      exit 127
-        hi
-        You are now at: 96d1c37 create test2.txt
-        To mark this commit as passed,run:   exit 0
-        To mark this commit as failed, run:  exit 1
-        To mark this commit as skipped, run: exit 125
-        To abort testing entirely, run:      exit 127
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (interactive): 62fc20d create test1.txt
-        ✓ Passed (interactive): 96d1c37 create test2.txt
-        Ran command on 2 commits: bash:
-        2 passed, 0 failed, 0 skipped
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        Ran command on 1 commit: if test -f test2.txt; then kill -INT $PPID; sleep 1; fi:
+        1 passed, 0 failed, 0 skipped
+        Interrupted; stopped running commands
         "###);
     }
 
+    // The interrupted commit's result shouldn't have been cached.
     {
-        let (stdout, _stderr) = git.branchless_with_options(
-            "test",
-            &["run", "--interactive", "-vv"],
-            &GitRunOptions {
-                env: hashmap! {"SHELL".to_string() =>  "bash".to_string()},
-                input: Some("echo hi; exit 0".to_string()),
-                ..Default::default()
-            },
-        )?;
+        let (stdout, _stderr) = git.branchless("test", &["show", "-x", command, "HEAD~2::"])?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (cached, interactive): 62fc20d create test1.txt
-        ✓ Passed (cached, interactive): 96d1c37 create test2.txt
-        Ran command on 2 commits: bash:
-        2 passed, 0 failed, 0 skipped
-        hint: there were 2 cached test results
-        hint: to clear these cached results, run: git test clean "stack() | @"
-        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        ✓ Passed (cached): 62fc20d create test1.txt
+        No cached test data for 96d1c37 create test2.txt
+        No cached test data for 70deb1e create test3.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
 
@@ -1563,123 +5377,79 @@ fn test_test_interactive() -> eyre::Result<()> {
 }
 
 #[test]
-fn test_test_search_abort() -> eyre::Result<()> {
+fn test_test_shuffle() -> eyre::Result<()> {
     let git = make_git()?;
-
-    if !git.supports_reference_transactions()? {
-        return Ok(());
-    }
     git.init_repo()?;
 
     git.detach_head()?;
     git.commit_file("test1", 1)?;
     git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
 
-    git.write_file(
-        "test.sh",
-        r#"#!/bin/sh
-if [[ "$(git log)" =~ 'test2' ]]; then
-    exit 127
-else
-    exit 0
-fi
-"#,
-    )?;
     {
-        let (stdout, stderr) = git.branchless_with_options(
+        // The results should be reported in the input order, regardless of
+        // the order in which the commits were tested.
+        let (stdout, stderr) = git.branchless(
             "test",
-            &["run", "--search", "linear", "-x", "bash test.sh"],
-            &GitRunOptions {
-                expected_exit_code: 1,
-                ..Default::default()
-            },
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "--jobs",
+                "1",
+                "--shuffle-seed",
+                "42",
+                "-x",
+                "exit 0",
+            ],
         )?;
-        insta::assert_snapshot!(stderr, @r###"
-        Stopped at 96d1c37 (create test2.txt)
-        branchless: processing 1 update: ref HEAD
-        "###);
+        insta::assert_snapshot!(stderr, @"");
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        Using test search strategy: linear
-        branchless: running command: <git-executable> rebase --abort
+        Using command execution strategy: worktree
+        Shuffling commits with seed: 42
         ✓ Passed: 62fc20d create test1.txt
-        X Exit code indicated to abort command (exit code 127): 96d1c37 create test2.txt
-        Ran command on 2 commits: bash test.sh:
-        1 passed, 1 failed, 0 skipped
-        Last passing commit:
-        - 62fc20d create test1.txt
-        There were no failing commits in the provided set.
-        Aborted running commands with exit code 127 at commit: 96d1c37 create test2.txt
+        ✓ Passed: 96d1c37 create test2.txt
+        ✓ Passed: 70deb1e create test3.txt
+        Ran command on 3 commits: exit 0:
+        3 passed, 0 failed, 0 skipped
         "###);
     }
 
-    // Check aborting when results are cached.
+    Ok(())
+}
+
+#[test]
+fn test_test_skip_reason() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.commit_file("test1", 1)?;
+
+    let command = r#"echo "needs network access" > "$BRANCHLESS_TEST_SKIP_REASON_FILE"; exit 125"#;
     {
         let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--search", "linear", "-x", "bash test.sh"],
+            &["run", "--strategy", "worktree", "-x", command, "@"],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        Using test search strategy: linear
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (cached): 62fc20d create test1.txt
-        X Exit code indicated to abort command (exit code 127): 96d1c37 create test2.txt
-        Ran command on 2 commits: bash test.sh:
-        1 passed, 1 failed, 0 skipped
-        Last passing commit:
-        - 62fc20d create test1.txt
-        There were no failing commits in the provided set.
-        hint: there was 1 cached test result
-        hint: to clear these cached results, run: git test clean "stack() | @"
-        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
-        Aborted running commands with exit code 127 at commit: 96d1c37 create test2.txt
+        Using command execution strategy: worktree
+        ! Exit code indicated to skip this commit (exit code 125, reason: needs network access): 62fc20d create test1.txt
+        Ran command on 1 commit: echo "needs network access" > "$BRANCHLESS_TEST_SKIP_REASON_FILE"; exit 125:
+        0 passed, 0 failed, 1 skipped
         "###);
     }
 
-    Ok(())
-}
-
-#[cfg(unix)] // Paths don't match on Windows.
-#[test]
-fn test_test_sets_env_vars() -> eyre::Result<()> {
-    let git = make_git()?;
-    git.init_repo()?;
-
-    git.write_file(
-        "test.sh",
-        r#"#!/bin/sh
-echo "Commit is: $BRANCHLESS_TEST_COMMIT"
-echo "Command is: $BRANCHLESS_TEST_COMMAND"
-"#,
-    )?;
-    {
-        let (stdout, _stderr) =
-            git.branchless("test", &["run", "--exec", "bash test.sh", "HEAD", "-vv"])?;
-        insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: f777ecc create initial.txt
-        Stdout: <repo-path>/.git/branchless/test/d32758e20028dd1cffc2b359bc3766f80a258ee5/bash__test.sh/stdout
-        Commit is: f777ecc9b0db5ed372b2615695191a8a17f79f24
-        Command is: bash test.sh
-        Stderr: <repo-path>/.git/branchless/test/d32758e20028dd1cffc2b359bc3766f80a258ee5/bash__test.sh/stderr
-        <no output>
-        Ran command on 1 commit: bash test.sh:
-        1 passed, 0 failed, 0 skipped
+    // The reason should be available from the cached result as well.
+    {
+        let (stdout, _stderr) = git.branchless("test", &["show", "-x", command, "@"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        ! Exit code indicated to skip this commit (exit code 125, reason: needs network access): 62fc20d create test1.txt
+        hint: to see more detailed output, re-run with -v/--verbose
+        hint: disable this hint by running: git config --global branchless.hint.testShowVerbose false
         "###);
     }
 
@@ -1687,211 +5457,235 @@ echo "Command is: $BRANCHLESS_TEST_COMMAND"
 }
 
 #[test]
-fn test_test_revsets() -> eyre::Result<()> {
+fn test_test_smartlog_status() -> eyre::Result<()> {
     let git = make_git()?;
-
-    if !git.supports_reference_transactions()? {
-        return Ok(());
-    }
     git.init_repo()?;
+    git.run(&["config", "branchless.commitDescriptors.testStatus", "true"])?;
 
     git.detach_head()?;
-    let test1_oid = git.commit_file("test1", 1)?;
-    let test2_oid = git.commit_file("test2", 2)?;
-    let test3_oid = git.commit_file("test3", 3)?;
-    let test4_oid = git.commit_file("test4", 4)?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
 
     {
-        let (stdout, stderr) = git.branchless_with_options(
-            "query",
-            &["tests.passed()"],
+        let (_stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "if test -e test3.txt; then exit 125; elif test -e test2.txt; then exit 1; fi",
+            ],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
-        insta::assert_snapshot!(stderr, @r###"
-        Evaluation error for expression 'tests.passed()': there was no latest command run with `git test`; try running `git test` first
-        "###);
-        insta::assert_snapshot!(stdout, @"");
     }
 
-    git.write_file(
-        "test.sh",
-        &format!(
-            r#"#!/bin/bash
-case "$(git rev-parse HEAD)" in
-    {test1_oid})
-        echo "Updated contents" >test1.txt
-        exit 0
-        ;;
-    {test2_oid})
-        exit 1
-        ;;
-    {test3_oid})
-        exit 125
-        ;;
-    {test4_oid})
-        exit 0
-        ;;
-    *)
-        exit 127
-        ;;
-esac
-"#
-        ),
-    )?;
-
     {
-        let (stdout, stderr) = git.branchless_with_options(
-            "test",
-            &["run", "--exec", "bash test.sh"],
-            &GitRunOptions {
-                expected_exit_code: 1,
-                ..Default::default()
-            },
-        )?;
-        insta::assert_snapshot!(stderr, @r###"
-        Stopped at 355e173 (create test4.txt)
-        branchless: processing 1 update: ref HEAD
-        "###);
+        let stdout = git.smartlog()?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (fixable): 62fc20d create test1.txt
-        X Failed (exit code 1): 96d1c37 create test2.txt
-        ! Exit code indicated to skip this commit (exit code 125): 70deb1e create test3.txt
-        ✓ Passed: 355e173 create test4.txt
-        Ran command on 4 commits: bash test.sh:
-        2 passed, 1 failed, 1 skipped
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d ✓ create test1.txt
+        |
+        o 96d1c37 X create test2.txt
+        |
+        @ 70deb1e ! create test3.txt
         "###);
     }
 
+    // Results for commands other than the latest one aren't shown.
     {
-        let (stdout, _stderr) = git.branchless("query", &["tests.passed()"])?;
+        git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "exit 0", "@"],
+        )?;
+        let stdout = git.smartlog()?;
         insta::assert_snapshot!(stdout, @r###"
-        62fc20d create test1.txt
-        355e173 create test4.txt
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        @ 70deb1e ✓ create test3.txt
         "###);
     }
 
+    // The configured exit codes are used to classify the results, as in `git
+    // test show`.
+    git.run(&["config", "branchless.test.successExitCodes", "3"])?;
     {
-        let (stdout, _stderr) = git.branchless("query", &["tests.failed()"])?;
+        git.branchless(
+            "test",
+            &["run", "--strategy", "worktree", "-x", "exit 3", "@"],
+        )?;
+        let stdout = git.smartlog()?;
         insta::assert_snapshot!(stdout, @r###"
-        96d1c37 create test2.txt
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        @ 70deb1e ✓ create test3.txt
         "###);
     }
 
+    Ok(())
+}
+
+#[test]
+fn test_test_commits_from() -> eyre::Result<()> {
+    let git = make_git()?;
+    git.init_repo()?;
+
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    git.write_file("commits", "# Commits to test\n62fc20d\n\n70deb1e\n")?;
     {
-        let (stdout, _stderr) = git.branchless("query", &["tests.fixable()"])?;
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "exit 0",
+                "--commits-from",
+                "commits",
+            ],
+        )?;
         insta::assert_snapshot!(stdout, @r###"
-        62fc20d create test1.txt
+        Using command execution strategy: worktree
+        ✓ Passed: 62fc20d create test1.txt
+        ✓ Passed: 70deb1e create test3.txt
+        Ran command on 2 commits: exit 0:
+        2 passed, 0 failed, 0 skipped
         "###);
     }
 
-    git.branchless("test", &["run", "--exec", "exit 0"])?;
     {
-        let (stdout, _stderr) = git.branchless("query", &["tests.passed()"])?;
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &[
+                "run",
+                "--strategy",
+                "worktree",
+                "-x",
+                "exit 0",
+                "--commits-from",
+                "-",
+            ],
+            &GitRunOptions {
+                input: Some("96d1c37\n".to_string()),
+                ..Default::default()
+            },
+        )?;
         insta::assert_snapshot!(stdout, @r###"
-        62fc20d create test1.txt
-        96d1c37 create test2.txt
-        70deb1e create test3.txt
-        355e173 create test4.txt
+        Using command execution strategy: worktree
+        ✓ Passed: 96d1c37 create test2.txt
+        Ran command on 1 commit: exit 0:
+        1 passed, 0 failed, 0 skipped
         "###);
     }
 
+    git.write_file("commits", "62fc20d\nfoo\n")?;
     {
-        let (stdout, _stderr) = git.branchless("query", &["tests.passed('bash test.sh')"])?;
-        insta::assert_snapshot!(stdout, @r###"
-        62fc20d create test1.txt
-        355e173 create test4.txt
+        let (stdout, stderr) = git.branchless_with_options(
+            "test",
+            &["run", "-x", "exit 0", "--commits-from", "commits"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stderr, @r###"
+        Invalid commit on line 2 of commits: foo
+        Evaluation error for expression 'foo': no commit, branch, or reference with the name 'foo' could be found
         "###);
+        insta::assert_snapshot!(stdout, @"");
     }
 
     Ok(())
 }
 
 #[test]
-fn test_test_no_cache() -> eyre::Result<()> {
-    // Tests the `--no-cache` flag of `git test run`.
+fn test_test_compare() -> eyre::Result<()> {
     let git = make_git()?;
-
-    if !git.supports_reference_transactions()? {
-        return Ok(());
-    }
     git.init_repo()?;
 
     git.detach_head()?;
     git.commit_file("test1", 1)?;
     git.commit_file("test2", 2)?;
 
-    git.write_file(
-        "test.sh",
-        r#"#!/bin/bash
-    exit 0
-    "#,
-    )?;
-    {
-        let (stdout, _stderr) = git.branchless("test", &["run", "--exec", "bash test.sh"])?;
-        insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed: 62fc20d create test1.txt
-        ✓ Passed: 96d1c37 create test2.txt
-        Ran command on 2 commits: bash test.sh:
-        2 passed, 0 failed, 0 skipped
-        "###);
-    }
-
-    git.write_file(
-        "test.sh",
-        r#"#!/bin/bash
-    exit 1
-    "#,
-    )?;
     {
         let (stdout, _stderr) = git.branchless_with_options(
             "test",
-            &["run", "--exec", "bash test.sh", "--no-cache"],
+            &[
+                "compare",
+                "--strategy",
+                "worktree",
+                "-x",
+                "exit 0",
+                "-x",
+                "test -f test2.txt",
+            ],
             &GitRunOptions {
                 expected_exit_code: 1,
                 ..Default::default()
             },
         )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        X Failed (exit code 1): 62fc20d create test1.txt
-        X Failed (exit code 1): 96d1c37 create test2.txt
-        Ran command on 2 commits: bash test.sh:
-        0 passed, 2 failed, 0 skipped
+        Using command execution strategy: worktree
+        Using command execution strategy: worktree
+        Command A: exit 0
+        Command B: test -f test2.txt
+        Results differ on 1 of 2 commits:
+        A B
+        ✓ X 62fc20d create test1.txt
         "###);
     }
 
     {
-        let (stdout, _stderr) = git.branchless("test", &["run", "--exec", "bash test.sh"])?;
+        let (stdout, _stderr) = git.branchless(
+            "test",
+            &[
+                "compare",
+                "--strategy",
+                "worktree",
+                "-x",
+                "exit 0",
+                "-x",
+                "true",
+            ],
+        )?;
         insta::assert_snapshot!(stdout, @r###"
-        branchless: running command: <git-executable> diff --quiet
-        Calling Git for on-disk rebase...
-        branchless: running command: <git-executable> rebase --continue
-        Using command execution strategy: working-copy
-        branchless: running command: <git-executable> rebase --abort
-        ✓ Passed (cached): 62fc20d create test1.txt
-        ✓ Passed (cached): 96d1c37 create test2.txt
-        Ran command on 2 commits: bash test.sh:
-        2 passed, 0 failed, 0 skipped
-        hint: there were 2 cached test results
-        hint: to clear these cached results, run: git test clean "stack() | @"
-        hint: disable this hint by running: git config --global branchless.hint.cleanCachedTestResults false
+        Using command execution strategy: worktree
+        Using command execution strategy: worktree
+        Command A: exit 0
+        Command B: true
+        No differences in results on 2 commits.
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "test",
+            &["compare", "-x", "exit 0"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Exactly two commands must be provided with --exec to compare.
         "###);
     }
 
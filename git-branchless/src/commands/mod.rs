@@ -11,6 +11,7 @@ mod wrap;
 
 use git_branchless_invoke::CommandContext;
 use lib::core::gc;
+use lib::core::navigation_history::NavigationDirection;
 use lib::core::rewrite::MergeConflictRemediation;
 use lib::util::ExitCode;
 
@@ -41,8 +42,20 @@ fn command_main(ctx: CommandContext, opts: Opts) -> eyre::Result<ExitCode> {
             reparent,
         )?,
 
+        Command::Back => git_branchless_navigation::navigate_history(
+            &effects,
+            &git_run_info,
+            NavigationDirection::Back,
+        )?,
+
         Command::BugReport => bug_report::bug_report(&effects, &git_run_info)?,
 
+        Command::Forward => git_branchless_navigation::navigate_history(
+            &effects,
+            &git_run_info,
+            NavigationDirection::Forward,
+        )?,
+
         Command::Switch { switch_options } => {
             git_branchless_navigation::switch(&effects, &git_run_info, &switch_options)?
         }
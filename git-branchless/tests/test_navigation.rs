@@ -156,6 +156,163 @@ fn test_next_ambiguous() -> eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_next_all_ambiguous() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.run(&["checkout", "HEAD^"])?;
+    git.commit_file("test3", 3)?;
+    git.run(&["checkout", "master"])?;
+
+    {
+        let (stdout, _stderr) = git.run_with_options(
+            &["next", "--all"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        Found multiple possible child commits to go to after traversing 1 child:
+          - 96d1c37 create test2.txt (oldest)
+          - 4838e49 create test3.txt (newest)
+        (Pass --oldest (-o), --newest (-n), or --interactive (-i) to select between ambiguous commits)
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("next", &["--all", "--newest"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 4838e49b08954becdd17c0900c1179c2c654c627
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |\
+        | o 96d1c37 create test2.txt
+        |
+        @ 4838e49 create test3.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_next_target() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.run(&["checkout", "HEAD^"])?;
+    git.commit_file("test3", 3)?;
+    git.run(&["checkout", "master"])?;
+
+    {
+        let (stdout, _stderr) = git.branchless("next", &["--all", "--target", "96d1c37"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 96d1c37a3d4363611c49f7e52186e189a04c531f
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |\
+        | @ 96d1c37 create test2.txt
+        |
+        o 4838e49 create test3.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "next",
+            &["--target", "4838e49"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The target 4838e49 create test3.txt is not a descendant of the current commit.
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_next_newest_by_author_date() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.run(&["checkout", "master"])?;
+    git.detach_head()?;
+    git.commit_file("test2", 2)?;
+    // Give `test2` an author date earlier than that of `test1`, while keeping
+    // its committer date later.
+    git.run_with_options(
+        &[
+            "commit",
+            "--amend",
+            "--no-edit",
+            "--date",
+            "Wed 29 Oct 12:34:56 2020 PDT -00",
+        ],
+        &GitRunOptions {
+            time: 2,
+            ..Default::default()
+        },
+    )?;
+    git.run(&["checkout", "master"])?;
+
+    {
+        let (stdout, _stderr) = git.branchless("next", &["--newest"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout a5d064da40775709e236127c6246610c5a43a9b4
+        O f777ecc (master) create initial.txt
+        |\
+        | o 62fc20d create test1.txt
+        |
+        @ a5d064d create test2.txt
+        "###);
+    }
+
+    git.run(&["checkout", "master"])?;
+    {
+        let (stdout, _stderr) = git.branchless("next", &["--newest", "--author-date"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 62fc20d2a290daea0d52bdc2ed2ad4be6491010e
+        O f777ecc (master) create initial.txt
+        |\
+        | @ 62fc20d create test1.txt
+        |
+        o a5d064d create test2.txt
+        "###);
+    }
+
+    git.run(&["config", "branchless.next.sortByAuthorDate", "true"])?;
+    git.run(&["checkout", "master"])?;
+    {
+        let (stdout, _stderr) = git.branchless("next", &["--oldest"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout a5d064da40775709e236127c6246610c5a43a9b4
+        O f777ecc (master) create initial.txt
+        |\
+        | o 62fc20d create test1.txt
+        |
+        @ a5d064d create test2.txt
+        "###);
+    }
+
+    Ok(())
+}
+
 #[test]
 #[cfg(unix)]
 fn test_next_ambiguous_interactive() -> eyre::Result<()> {
@@ -201,6 +358,46 @@ fn test_next_ambiguous_interactive() -> eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg(unix)]
+fn test_next_interactive_preview_diff() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.run(&["checkout", "master"])?;
+    git.detach_head()?;
+    git.commit_file("test2", 2)?;
+    git.run(&["checkout", "master"])?;
+
+    run_in_pty(
+        &git,
+        "next",
+        &["--interactive"],
+        &[
+            PtyAction::WaitUntilContains("> "),
+            PtyAction::Write("test2"),
+            PtyAction::WaitUntilContains("> test2"),
+            PtyAction::WaitUntilContains("+test2 contents"),
+            PtyAction::Write(CARRIAGE_RETURN),
+        ],
+    )?;
+
+    {
+        let stdout = git.smartlog()?;
+        insta::assert_snapshot!(stdout, @r###"
+        O f777ecc (master) create initial.txt
+        |\
+        | o 62fc20d create test1.txt
+        |
+        @ fe65c1f create test2.txt
+        "###);
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_next_on_master() -> eyre::Result<()> {
     let git = make_git()?;
@@ -268,6 +465,7 @@ fn test_next_no_more_children() -> eyre::Result<()> {
         let (stdout, _stderr) = git.branchless("next", &["3"])?;
         insta::assert_snapshot!(stdout, @r###"
         No more child commits to go to after traversing 2 children.
+        Moved forward 2 of 3 requested commits.
         branchless: running command: <git-executable> checkout 70deb1e28791d8e7dd5a1f0c871a51b91282562f
         :
         O 96d1c37 (master) create test2.txt
@@ -345,6 +543,47 @@ fn test_switch_pty() -> eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg(unix)]
+fn test_switch_pty_oid_query() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.run(&["checkout", "master"])?;
+    git.detach_head()?;
+    git.commit_file("test3", 3)?;
+
+    run_in_pty(
+        &git,
+        "switch",
+        &["-i"],
+        &[
+            PtyAction::WaitUntilContains("> "),
+            PtyAction::Write("96d1"),
+            PtyAction::WaitUntilContains("> 96d1"),
+            PtyAction::WaitUntilContains("> 96d1c37"),
+            PtyAction::Write(CARRIAGE_RETURN),
+        ],
+    )?;
+    {
+        let stdout = git.smartlog()?;
+        insta::assert_snapshot!(stdout, @r###"
+        O f777ecc (master) create initial.txt
+        |\
+        | o 62fc20d create test1.txt
+        | |
+        | @ 96d1c37 create test2.txt
+        |
+        o 98b9119 create test3.txt
+        "###);
+    }
+
+    Ok(())
+}
+
 #[test]
 #[cfg(unix)]
 fn test_switch_abort() -> eyre::Result<()> {
@@ -520,6 +759,55 @@ fn test_navigation_traverse_branches() -> eyre::Result<()> {
         "###);
     }
 
+    {
+        let (stdout, _stderr) = git.branchless("next", &["-b"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout f81d55c0d520ff8d02ef9294d95156dcb78a5255
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        o 96d1c37 (foo) create test2.txt
+        |
+        o 70deb1e create test3.txt
+        |
+        o 355e173 (bar) create test4.txt
+        |
+        @ f81d55c create test5.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_navigation_prev_branch_skips_unbranched_commits() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.run(&["branch", "foo"])?;
+    git.commit_file("test3", 3)?;
+    git.commit_file("test4", 4)?;
+
+    {
+        let (stdout, _stderr) = git.branchless("prev", &["--branch"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout foo
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        @ 96d1c37 (> foo) create test2.txt
+        |
+        o 70deb1e create test3.txt
+        |
+        o 355e173 create test4.txt
+        "###);
+    }
+
     Ok(())
 }
 
@@ -764,6 +1052,112 @@ fn test_navigation_force() -> eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_navigation_create_branch() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.run(&["checkout", "master"])?;
+
+    {
+        let (stdout, _stderr) = git.branchless("next", &["--create-branch", "foo"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 62fc20d2a290daea0d52bdc2ed2ad4be6491010e -b foo
+        O f777ecc (master) create initial.txt
+        |
+        @ 62fc20d (> foo) create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "next",
+            &["-c", "foo"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        A branch named foo already exists.
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_navigation_prev_parent() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    let test1_oid = git.commit_file("test1", 1)?;
+    git.run(&["checkout", "master"])?;
+    git.detach_head()?;
+    git.commit_file("test2", 2)?;
+    git.run(&[
+        "merge",
+        "--no-ff",
+        "-m",
+        "merge test1",
+        &test1_oid.to_string(),
+    ])?;
+
+    {
+        git.branchless("prev", &["--parent", "2"])?;
+        let (stdout, _stderr) = git.run(&["rev-parse", "HEAD"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        62fc20d2a290daea0d52bdc2ed2ad4be6491010e
+        "###);
+    }
+
+    git.run(&["checkout", "020e9b0"])?;
+    {
+        git.branchless("prev", &["-p", "1"])?;
+        let (stdout, _stderr) = git.run(&["rev-parse", "HEAD"])?;
+        insta::assert_snapshot!(stdout, @r###"
+        fe65c1fe15584744e649b2c79d4cf9b0d878f92e
+        "###);
+    }
+
+    git.run(&["checkout", "020e9b0"])?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "prev",
+            &["--parent", "3"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The current commit has no parent with index 3 (it has 2 parents).
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "next",
+            &["--parent", "1"],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        The --parent option can only be used with `git prev`.
+        "###);
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_navigation_switch_flags() -> eyre::Result<()> {
     let git = make_git()?;
@@ -971,3 +1365,208 @@ fn test_switch_detach() -> eyre::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_navigation_back_and_forward() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "back",
+            &[],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        No earlier position in the navigation history.
+        "###);
+    }
+
+    git.branchless("prev", &["2"])?;
+    {
+        let (stdout, _stderr) = git.branchless("back", &[])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 70deb1e28791d8e7dd5a1f0c871a51b91282562f
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        @ 70deb1e create test3.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "back",
+            &[],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        No earlier position in the navigation history.
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("forward", &[])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 62fc20d2a290daea0d52bdc2ed2ad4be6491010e
+        O f777ecc (master) create initial.txt
+        |
+        @ 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        o 70deb1e create test3.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "forward",
+            &[],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        No later position in the navigation history.
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("back", &[])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 70deb1e28791d8e7dd5a1f0c871a51b91282562f
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        @ 70deb1e create test3.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_navigation_forward_history_truncated() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    git.branchless("prev", &["2"])?;
+    git.branchless("back", &[])?;
+
+    // Navigating somewhere new after going back discards the positions which
+    // could have been gone forward to.
+    git.branchless("prev", &[])?;
+    {
+        let (stdout, _stderr) = git.branchless_with_options(
+            "forward",
+            &[],
+            &GitRunOptions {
+                expected_exit_code: 1,
+                ..Default::default()
+            },
+        )?;
+        insta::assert_snapshot!(stdout, @r###"
+        No later position in the navigation history.
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("back", &[])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 70deb1e28791d8e7dd5a1f0c871a51b91282562f
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        @ 70deb1e create test3.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("forward", &[])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 96d1c37a3d4363611c49f7e52186e189a04c531f
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        @ 96d1c37 create test2.txt
+        |
+        o 70deb1e create test3.txt
+        "###);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_navigation_back_after_unrecorded_checkout() -> eyre::Result<()> {
+    let git = make_git()?;
+
+    git.init_repo()?;
+    git.detach_head()?;
+    git.commit_file("test1", 1)?;
+    git.commit_file("test2", 2)?;
+    git.commit_file("test3", 3)?;
+
+    git.branchless("prev", &["2"])?;
+    git.run(&["checkout", "96d1c37"])?;
+
+    // The last recorded position was `test1`, so go back there first, rather
+    // than to the position before it.
+    {
+        let (stdout, _stderr) = git.branchless("back", &[])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 62fc20d2a290daea0d52bdc2ed2ad4be6491010e
+        O f777ecc (master) create initial.txt
+        |
+        @ 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        o 70deb1e create test3.txt
+        "###);
+    }
+
+    {
+        let (stdout, _stderr) = git.branchless("back", &[])?;
+        insta::assert_snapshot!(stdout, @r###"
+        branchless: running command: <git-executable> checkout 70deb1e28791d8e7dd5a1f0c871a51b91282562f
+        O f777ecc (master) create initial.txt
+        |
+        o 62fc20d create test1.txt
+        |
+        o 96d1c37 create test2.txt
+        |
+        @ 70deb1e create test3.txt
+        "###);
+    }
+
+    Ok(())
+}
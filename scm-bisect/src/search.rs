@@ -329,9 +329,23 @@ impl<G: SearchGraph> Search<G> {
     /// search. The caller is responsible for calling `notify` with the result.
     #[instrument]
     pub fn search(&self, strategy: Strategy) -> Result<LazySolution<G::Node>, Error<G>> {
+        self.search_with_pending(strategy, &HashSet::new())
+    }
+
+    /// Like `search`, but assume that the nodes in `pending` are currently
+    /// being tested by the caller. These nodes won't be suggested again. When
+    /// using `Strategy::Binary`, they also split the remaining nodes into
+    /// smaller ranges, so that the caller can search several ranges at once
+    /// rather than speculating deeper into a single range.
+    #[instrument]
+    pub fn search_with_pending(
+        &self,
+        strategy: Strategy,
+        pending: &HashSet<G::Node>,
+    ) -> Result<LazySolution<G::Node>, Error<G>> {
         let success_bounds = self.success_bounds()?;
         let failure_bounds = self.failure_bounds()?;
-        let nodes_to_search = {
+        let mut nodes_to_search = {
             let implied_success_nodes = self.graph.ancestors_all(success_bounds.clone())?;
             let implied_failure_nodes = self.graph.descendants_all(failure_bounds.clone())?;
             self.nodes
@@ -346,9 +360,18 @@ impl<G: SearchGraph> Search<G> {
                 .collect::<Vec<_>>()
         };
         let next_to_search: Box<dyn Iterator<Item = G::Node>> = match strategy {
-            Strategy::Linear => Box::new(nodes_to_search.into_iter()),
-            Strategy::LinearReverse => Box::new(nodes_to_search.into_iter().rev()),
-            Strategy::Binary => Box::new(make_binary_search_iter(&nodes_to_search)),
+            Strategy::Linear => {
+                nodes_to_search.retain(|node| !pending.contains(node));
+                Box::new(nodes_to_search.into_iter())
+            }
+            Strategy::LinearReverse => {
+                nodes_to_search.retain(|node| !pending.contains(node));
+                Box::new(nodes_to_search.into_iter().rev())
+            }
+            Strategy::Binary => {
+                let ranges = self.binary_search_ranges(nodes_to_search, pending)?;
+                Box::new(interleave_binary_search_iters(&ranges))
+            }
         };
 
         Ok(LazySolution {
@@ -409,6 +432,99 @@ impl<G: SearchGraph> Search<G> {
         self.nodes.insert(node, status);
         Ok(())
     }
+
+    /// Split `nodes` into ranges which can be bisected independently of each
+    /// other. Nodes which aren't related by ancestry (such as nodes on
+    /// different branches) are in different ranges, and each range is further
+    /// split at the nodes in `pending`, whose results aren't known yet. The
+    /// largest ranges are returned first.
+    ///
+    /// The ancestors of a node which is itself an ancestor of an
+    /// already-visited node are already accounted for, so `ancestors` is only
+    /// queried for the heads of `nodes` (visiting descendants first, assuming
+    /// `nodes` is in topological order). This takes O(n * h) time, where `h`
+    /// is the number of heads.
+    #[instrument]
+    fn binary_search_ranges(
+        &self,
+        nodes: Vec<G::Node>,
+        pending: &HashSet<G::Node>,
+    ) -> Result<Vec<Vec<G::Node>>, Error<G>> {
+        fn find_root(parents: &mut [usize], mut index: usize) -> usize {
+            while parents[index] != index {
+                parents[index] = parents[parents[index]];
+                index = parents[index];
+            }
+            index
+        }
+
+        let node_indexes: HashMap<G::Node, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.clone(), index))
+            .collect();
+        let mut parents = (0..nodes.len()).collect_vec();
+        let mut is_visited = vec![false; nodes.len()];
+        for (index, node) in nodes.iter().enumerate().rev() {
+            if is_visited[index] {
+                continue;
+            }
+            for ancestor in self.graph.ancestors(node.clone())? {
+                if let Some(ancestor_index) = node_indexes.get(&ancestor) {
+                    is_visited[*ancestor_index] = true;
+                    let root = find_root(&mut parents, index);
+                    let ancestor_root = find_root(&mut parents, *ancestor_index);
+                    parents[root] = ancestor_root;
+                }
+            }
+        }
+
+        let mut components: IndexMap<usize, Vec<G::Node>> = IndexMap::new();
+        for (index, node) in nodes.into_iter().enumerate() {
+            let root = find_root(&mut parents, index);
+            components.entry(root).or_default().push(node);
+        }
+
+        let mut ranges = Vec::new();
+        for component in components.into_values() {
+            let mut range = Vec::new();
+            for node in component {
+                if pending.contains(&node) {
+                    ranges.push(std::mem::take(&mut range));
+                } else {
+                    range.push(node);
+                }
+            }
+            ranges.push(range);
+        }
+        ranges.retain(|range| !range.is_empty());
+        ranges.sort_by_key(|range| std::cmp::Reverse(range.len()));
+        Ok(ranges)
+    }
+}
+
+/// Conduct a binary search on each of `ranges` at the same time, by taking
+/// nodes from each range in turn. The first nodes returned are the midpoints
+/// of each range.
+fn interleave_binary_search_iters<T: Clone>(ranges: &[Vec<T>]) -> impl Iterator<Item = T> {
+    let mut iters = ranges
+        .iter()
+        .map(|range| make_binary_search_iter(range))
+        .collect_vec();
+    let mut result = vec![];
+    loop {
+        let mut is_done = true;
+        for iter in iters.iter_mut() {
+            if let Some(node) = iter.next() {
+                result.push(node);
+                is_done = false;
+            }
+        }
+        if is_done {
+            break;
+        }
+    }
+    result.into_iter()
 }
 
 fn make_binary_search_iter<T: Clone>(nodes: &[T]) -> impl Iterator<Item = T> {
@@ -554,6 +670,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_search_pending() -> Result<(), Error<UsizeGraph>> {
+        let graph = UsizeGraph { max: 15 };
+        let nodes = 0..graph.max;
+        let mut search = Search::new(graph, nodes);
+
+        assert_eq!(
+            search
+                .search_with_pending(Strategy::Linear, &hashset! {0, 1})?
+                .into_eager(),
+            EagerSolution {
+                bounds: Default::default(),
+                next_to_search: vec![2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14],
+            }
+        );
+
+        // The midpoint is being tested, so the ranges on either side of it
+        // should be searched at the same time.
+        assert_eq!(
+            search
+                .search_with_pending(Strategy::Binary, &hashset! {7})?
+                .into_eager(),
+            EagerSolution {
+                bounds: Default::default(),
+                next_to_search: vec![3, 11, 1, 9, 5, 13, 0, 8, 4, 12, 2, 10, 6, 14],
+            }
+        );
+
+        search.notify(3, Status::Success)?;
+        assert_eq!(
+            search
+                .search_with_pending(Strategy::Binary, &hashset! {7, 11})?
+                .into_eager(),
+            EagerSolution {
+                bounds: Bounds {
+                    success: hashset! {3},
+                    failure: hashset! {},
+                },
+                next_to_search: vec![5, 9, 13, 4, 8, 12, 6, 10, 14],
+            }
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_search_inconsistent_notify() -> Result<(), Error<UsizeGraph>> {
         let graph = UsizeGraph { max: 7 };
@@ -703,6 +864,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_search_independent_ranges() -> Result<(), Error<TestGraph>> {
+        let graph = TestGraph {
+            nodes: hashmap! {
+                'a' => hashset! {'b'},
+                'b' => hashset! {'c'},
+                'c' => hashset! {},
+                'd' => hashset! {'e'},
+                'e' => hashset! {'f'},
+                'f' => hashset! {},
+            },
+        };
+        let search = Search::new(graph, 'a'..='f');
+
+        // The two branches are unrelated, so they should be bisected at the
+        // same time.
+        assert_eq!(
+            search.search(Strategy::Binary)?.into_eager(),
+            EagerSolution {
+                bounds: Default::default(),
+                next_to_search: vec!['b', 'e', 'a', 'd', 'c', 'f'],
+            }
+        );
+
+        Ok(())
+    }
+
     fn arb_strategy() -> impl ProptestStrategy<Value = Strategy> {
         prop_oneof![
             Just(Strategy::Linear),
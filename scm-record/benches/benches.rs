@@ -3,7 +3,8 @@ use std::{borrow::Cow, path::Path};
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 
 use scm_record::{
-    ChangeType, Event, EventSource, File, RecordState, Recorder, Section, SectionChangedLine,
+    ChangeType, Event, EventSource, File, KeyBindings, RecordState, Recorder, Section,
+    SectionChangedLine,
 };
 
 fn bench_record(c: &mut Criterion) {
@@ -34,7 +35,8 @@ fn bench_record(c: &mut Criterion) {
                     24,
                     [Event::ToggleItem, Event::ToggleItem, Event::QuitAccept],
                 );
-                let recorder = Recorder::new(record_state.clone(), event_source);
+                let recorder =
+                    Recorder::new(record_state.clone(), event_source, KeyBindings::default());
                 recorder
             },
             |recorder| recorder.run(),
@@ -5,7 +5,8 @@ use std::borrow::Cow;
 use std::path::Path;
 
 use scm_record::{
-    ChangeType, EventSource, File, RecordError, RecordState, Recorder, Section, SectionChangedLine,
+    ChangeType, EventSource, File, KeyBindings, RecordError, RecordState, Recorder, Section,
+    SectionChangedLine, SelectedContents,
 };
 
 fn main() {
@@ -91,7 +92,7 @@ fn main() {
     ];
     let record_state = RecordState { files };
 
-    let recorder = Recorder::new(record_state, EventSource::Crossterm);
+    let recorder = Recorder::new(record_state, EventSource::Crossterm, KeyBindings::default());
     let result = recorder.run();
     match result {
         Ok(result) => {
@@ -99,7 +100,14 @@ fn main() {
             for file in files {
                 println!("--- Path {:?} final lines: ---", file.path);
                 let (selected, _unselected) = file.get_selected_contents();
-                print!("{selected}");
+                match selected {
+                    SelectedContents::Unchanged => println!("<unchanged>"),
+                    SelectedContents::Binary {
+                        old_description,
+                        new_description,
+                    } => println!("<binary: {old_description:?} -> {new_description:?}>"),
+                    SelectedContents::Present { contents } => print!("{contents}"),
+                }
             }
         }
         Err(RecordError::Cancelled) => println!("Cancelled!\n"),
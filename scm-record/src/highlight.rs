@@ -0,0 +1,167 @@
+//! Syntax highlighting for changed lines, based on the file extension.
+//!
+//! Highlighting is only available when the `syntax-highlighting` feature is
+//! enabled. Otherwise, no lines are highlighted, and they are rendered in the
+//! plain style instead.
+
+use tui::style::Style;
+
+/// A run of text within a line, along with the style to render it in.
+pub(crate) type StyledSegment = (Style, String);
+
+#[cfg(feature = "syntax-highlighting")]
+mod syntect_impl {
+    use std::borrow::Cow;
+    use std::path::Path;
+
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::{FontStyle, Theme, ThemeSet};
+    use syntect::parsing::SyntaxSet;
+    use tui::style::{Color, Modifier, Style};
+
+    use super::StyledSegment;
+
+    const THEME_NAME: &str = "base16-ocean.dark";
+
+    pub(crate) struct Highlighter {
+        syntax_set: SyntaxSet,
+        theme: Theme,
+    }
+
+    impl Highlighter {
+        pub fn new() -> Self {
+            let mut theme_set = ThemeSet::load_defaults();
+            Self {
+                syntax_set: SyntaxSet::load_defaults_newlines(),
+                theme: theme_set
+                    .themes
+                    .remove(THEME_NAME)
+                    .expect("default theme should be available"),
+            }
+        }
+
+        /// Highlight the given lines of the file at `path`, in order. The lines
+        /// share a single parsing state, so constructs spanning multiple lines
+        /// (such as block comments) are highlighted correctly, as long as the
+        /// lines are given in the order that they appear in the file.
+        ///
+        /// Returns `None` if the file's type isn't recognized.
+        pub fn highlight_lines<'l>(
+            &self,
+            path: &Path,
+            lines: impl IntoIterator<Item = &'l str>,
+        ) -> Option<Vec<Vec<StyledSegment>>> {
+            let Self { syntax_set, theme } = self;
+            let extension = path.extension()?.to_str()?;
+            let syntax = syntax_set.find_syntax_by_extension(extension)?;
+
+            let mut highlight_lines = HighlightLines::new(syntax, theme);
+            lines
+                .into_iter()
+                .map(|line| {
+                    // The syntax definitions expect each line to end with a
+                    // newline, so add one for highlighting, and then strip it
+                    // from the result.
+                    let (line, added_newline) = if line.ends_with('\n') {
+                        (Cow::Borrowed(line), false)
+                    } else {
+                        (Cow::Owned(format!("{line}\n")), true)
+                    };
+                    let ranges = highlight_lines.highlight_line(&line, syntax_set).ok()?;
+                    let mut segments: Vec<StyledSegment> = ranges
+                        .into_iter()
+                        .map(|(style, text)| (convert_style(style), text.to_owned()))
+                        .collect();
+                    if added_newline {
+                        if let Some((_, text)) = segments.last_mut() {
+                            text.pop();
+                        }
+                        segments.retain(|(_, text)| !text.is_empty());
+                    }
+                    Some(segments)
+                })
+                .collect()
+        }
+    }
+
+    fn convert_style(style: syntect::highlighting::Style) -> Style {
+        let syntect::highlighting::Color { r, g, b, a: _ } = style.foreground;
+        let mut tui_style = Style::default().fg(Color::Rgb(r, g, b));
+        if style.font_style.contains(FontStyle::BOLD) {
+            tui_style = tui_style.add_modifier(Modifier::BOLD);
+        }
+        if style.font_style.contains(FontStyle::ITALIC) {
+            tui_style = tui_style.add_modifier(Modifier::ITALIC);
+        }
+        if style.font_style.contains(FontStyle::UNDERLINE) {
+            tui_style = tui_style.add_modifier(Modifier::UNDERLINED);
+        }
+        tui_style
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_highlight_lines_multiline_construct() {
+            let highlighter = Highlighter::new();
+            let segments = highlighter
+                .highlight_lines(
+                    Path::new("foo.rs"),
+                    ["/* start of comment", "end of comment */", "let x = 1;"],
+                )
+                .unwrap();
+            assert_eq!(segments.len(), 3);
+
+            // The second line is still inside the block comment, so it should be
+            // styled the same as the first line, rather than as code.
+            let comment_style = segments[0][0].0;
+            assert!(segments[1]
+                .iter()
+                .all(|(style, _text)| *style == comment_style));
+            assert_ne!(segments[2][0].0, comment_style);
+            assert_eq!(segments[2][0].1, "let");
+        }
+
+        #[test]
+        fn test_highlight_lines_unknown_extension() {
+            let highlighter = Highlighter::new();
+            assert_eq!(
+                highlighter.highlight_lines(Path::new("foo.unknown-extension"), ["foo"]),
+                None
+            );
+        }
+    }
+}
+
+#[cfg(feature = "syntax-highlighting")]
+pub(crate) use syntect_impl::Highlighter;
+
+#[cfg(not(feature = "syntax-highlighting"))]
+mod plain_impl {
+    use std::path::Path;
+
+    use super::StyledSegment;
+
+    /// Stand-in used when the `syntax-highlighting` feature is disabled. It
+    /// never highlights anything.
+    pub(crate) struct Highlighter;
+
+    impl Highlighter {
+        pub fn new() -> Self {
+            Self
+        }
+
+        pub fn highlight_lines<'l>(
+            &self,
+            _path: &Path,
+            _lines: impl IntoIterator<Item = &'l str>,
+        ) -> Option<Vec<Vec<StyledSegment>>> {
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "syntax-highlighting"))]
+pub(crate) use plain_impl::Highlighter;
@@ -0,0 +1,290 @@
+//! Word-level ("intraline") diffing of changed lines.
+//!
+//! When a removed line is replaced by a similar added line, only the words
+//! which actually differ between the two are emphasized, so that small edits
+//! are easier to spot.
+
+use std::ops::Range;
+
+use tui::style::Modifier;
+
+use crate::highlight::StyledSegment;
+use crate::{ChangeType, SectionChangedLine};
+
+/// Lines with more tokens than this are not diffed, since the diff takes
+/// quadratic time and space.
+const MAX_TOKENS: usize = 500;
+
+/// The minimum percentage of the (non-whitespace) text which must be shared
+/// between two lines for their differences to be emphasized. Below this, the
+/// lines are considered to be unrelated, and are highlighted in full instead.
+const MIN_SIMILARITY_PERCENT: usize = 50;
+
+/// The modifier applied to the differing parts of a line.
+const EMPHASIS_MODIFIER: Modifier = Modifier::BOLD.union(Modifier::UNDERLINED);
+
+/// The byte ranges of the parts of a line which differ from its paired line.
+pub(crate) type ChangedRanges = Vec<Range<usize>>;
+
+/// Pair up the removed lines in a section with the added lines which replace
+/// them. Each run of removed lines is paired, in order, with the run of added
+/// lines immediately following it. Returns the indexes of the paired lines.
+pub(crate) fn pair_changed_lines(lines: &[SectionChangedLine]) -> Vec<(usize, usize)> {
+    let mut result = Vec::new();
+    let mut line_idx = 0;
+    while line_idx < lines.len() {
+        let removed_start = line_idx;
+        while line_idx < lines.len() && lines[line_idx].change_type == ChangeType::Removed {
+            line_idx += 1;
+        }
+        let added_start = line_idx;
+        while line_idx < lines.len() && lines[line_idx].change_type == ChangeType::Added {
+            line_idx += 1;
+        }
+        result.extend((removed_start..added_start).zip(added_start..line_idx));
+    }
+    result
+}
+
+/// Compute the byte ranges of the words which differ between `old` and
+/// `new`. Returns `None` if the lines are too dissimilar for a word-level diff
+/// to be useful.
+pub(crate) fn diff_words(old: &str, new: &str) -> Option<(ChangedRanges, ChangedRanges)> {
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+    if old_tokens.len() > MAX_TOKENS || new_tokens.len() > MAX_TOKENS {
+        return None;
+    }
+
+    let (old_common, new_common) = longest_common_subsequence(&old_tokens, &new_tokens);
+    let (old_common_len, old_len) = count_non_whitespace(&old_tokens, &old_common);
+    let (new_common_len, new_len) = count_non_whitespace(&new_tokens, &new_common);
+    let total_len = old_len + new_len;
+    if total_len == 0
+        || (old_common_len + new_common_len) * 100 < total_len * MIN_SIMILARITY_PERCENT
+    {
+        return None;
+    }
+
+    Some((
+        changed_ranges(&old_tokens, &old_common),
+        changed_ranges(&new_tokens, &new_common),
+    ))
+}
+
+/// Split the given segments at the boundaries of `ranges`, and emphasize the
+/// parts which fall inside them.
+pub(crate) fn emphasize_segments(
+    segments: Vec<StyledSegment>,
+    ranges: &[Range<usize>],
+) -> Vec<StyledSegment> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    for (style, text) in segments {
+        let mut boundaries = vec![0, text.len()];
+        for range in ranges {
+            for point in [range.start, range.end] {
+                if offset < point && point < offset + text.len() {
+                    boundaries.push(point - offset);
+                }
+            }
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        for window in boundaries.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            let is_emphasized = ranges
+                .iter()
+                .any(|range| range.start <= offset + start && offset + end <= range.end);
+            let style = if is_emphasized {
+                style.add_modifier(EMPHASIS_MODIFIER)
+            } else {
+                style
+            };
+            result.push((style, text[start..end].to_owned()));
+        }
+        offset += text.len();
+    }
+    result
+}
+
+/// Split the line into words, runs of whitespace, and individual punctuation
+/// characters. Concatenating the tokens produces the original line.
+fn tokenize(line: &str) -> Vec<&str> {
+    #[derive(PartialEq, Eq)]
+    enum CharClass {
+        Word,
+        Whitespace,
+        Punctuation,
+    }
+
+    fn classify(c: char) -> CharClass {
+        if c.is_alphanumeric() || c == '_' {
+            CharClass::Word
+        } else if c.is_whitespace() {
+            CharClass::Whitespace
+        } else {
+            CharClass::Punctuation
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut token_start = 0;
+    let mut prev_class = None;
+    for (idx, c) in line.char_indices() {
+        let class = classify(c);
+        let continues_token = match &prev_class {
+            Some(prev_class) => *prev_class == class && class != CharClass::Punctuation,
+            None => true,
+        };
+        if !continues_token {
+            tokens.push(&line[token_start..idx]);
+            token_start = idx;
+        }
+        prev_class = Some(class);
+    }
+    if token_start < line.len() {
+        tokens.push(&line[token_start..]);
+    }
+    tokens
+}
+
+/// Determine which tokens of `old` and `new` belong to their longest common
+/// subsequence.
+fn longest_common_subsequence(old: &[&str], new: &[&str]) -> (Vec<bool>, Vec<bool>) {
+    // `lengths[i][j]` is the length of the longest common subsequence of
+    // `old[i..]` and `new[j..]`.
+    let mut lengths = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut old_common = vec![false; old.len()];
+    let mut new_common = vec![false; new.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            old_common[i] = true;
+            new_common[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    (old_common, new_common)
+}
+
+/// Returns the length of the common non-whitespace tokens and the length of
+/// all non-whitespace tokens.
+fn count_non_whitespace(tokens: &[&str], is_common: &[bool]) -> (usize, usize) {
+    tokens
+        .iter()
+        .zip(is_common)
+        .filter(|(token, _)| !token.trim().is_empty())
+        .fold((0, 0), |(common_len, len), (token, is_common)| {
+            (
+                common_len + if *is_common { token.len() } else { 0 },
+                len + token.len(),
+            )
+        })
+}
+
+/// Returns the byte ranges of the tokens which are not common, coalescing
+/// adjacent tokens into a single range.
+fn changed_ranges(tokens: &[&str], is_common: &[bool]) -> Vec<Range<usize>> {
+    let mut result: Vec<Range<usize>> = Vec::new();
+    let mut offset = 0;
+    for (token, is_common) in tokens.iter().zip(is_common) {
+        let token_range = offset..offset + token.len();
+        offset += token.len();
+        if *is_common {
+            continue;
+        }
+        match result.last_mut() {
+            Some(last_range) if last_range.end == token_range.start => {
+                last_range.end = token_range.end;
+            }
+            _ => result.push(token_range),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use tui::style::Style;
+
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("let foo_bar = baz(1);"),
+            vec!["let", " ", "foo_bar", " ", "=", " ", "baz", "(", "1", ")", ";"]
+        );
+        assert_eq!(tokenize(""), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_pair_changed_lines() {
+        let line = |change_type| SectionChangedLine {
+            is_toggled: false,
+            change_type,
+            line: Cow::Borrowed(""),
+        };
+        let lines = [
+            line(ChangeType::Removed),
+            line(ChangeType::Removed),
+            line(ChangeType::Added),
+            line(ChangeType::Added),
+            line(ChangeType::Added),
+            line(ChangeType::Removed),
+            line(ChangeType::Added),
+        ];
+        assert_eq!(pair_changed_lines(&lines), vec![(0, 2), (1, 3), (5, 6)]);
+    }
+
+    #[test]
+    fn test_diff_words() {
+        assert_eq!(
+            diff_words("let foo = bar(1);", "let foo = baz(1, 2);"),
+            Some((vec![10..13], vec![10..13, 15..18]))
+        );
+        assert_eq!(
+            diff_words("hello world", "hello world"),
+            Some((vec![], vec![]))
+        );
+        assert_eq!(diff_words("before text 1", "something else"), None);
+    }
+
+    #[test]
+    fn test_emphasize_segments() {
+        let plain = Style::default();
+        let emphasized = Style::default().add_modifier(EMPHASIS_MODIFIER);
+        assert_eq!(
+            emphasize_segments(
+                vec![(plain, "let foo".to_owned()), (plain, " = baz;".to_owned())],
+                &[4..7, 10..13],
+            ),
+            vec![
+                (plain, "let ".to_owned()),
+                (emphasized, "foo".to_owned()),
+                (plain, " = ".to_owned()),
+                (emphasized, "baz".to_owned()),
+                (plain, ";".to_owned()),
+            ]
+        );
+    }
+}
@@ -9,12 +9,15 @@
 )]
 #![allow(clippy::too_many_arguments, clippy::blocks_in_if_conditions)]
 
+mod highlight;
+mod intraline;
 mod render;
 mod types;
 mod ui;
 mod util;
 
 pub use types::{
-    ChangeType, File, FileMode, RecordError, RecordState, Section, SectionChangedLine,
+    ChangeType, File, FileMode, KeyBinding, KeyBindings, RecordError, RecordState, Section,
+    SectionChangedLine, SelectedContents,
 };
 pub use ui::{Event, EventSource, Recorder, TestingScreenshot};
@@ -4,6 +4,7 @@ use std::borrow::Cow;
 use std::io;
 use std::path::Path;
 
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use thiserror::Error;
 
 /// The state used to render the changes. This is passed into [`Recorder::new`]
@@ -51,24 +52,58 @@ pub struct File<'a> {
     /// The Unix file mode of the file, if available.
     ///
     /// This value is not directly modified by the UI; instead, construct a
-    /// [`Section::FileMode`] and use the [`FileState::get_file_mode`] function
-    /// to read a user-provided updated to the file mode function to read a
-    /// user-provided updated to the file mode
+    /// [`Section::FileMode`] and use the [`File::get_file_mode`] function to
+    /// read the user-selected file mode.
     pub file_mode: Option<FileMode>,
 
     /// The set of [`Section`]s inside the file.
     pub sections: Vec<Section<'a>>,
 }
 
-impl File<'_> {
+/// The contents of a file selected as part of the record operation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SelectedContents<'a> {
+    /// The file should be left as it was, since none of its changes apply.
+    Unchanged,
+
+    /// The file is binary, and the change to it applies in its entirety.
+    Binary {
+        /// A description of the old version of the file, if it existed.
+        old_description: Option<Cow<'a, str>>,
+
+        /// A description of the new version of the file, if it exists.
+        new_description: Option<Cow<'a, str>>,
+    },
+
+    /// The file has the given text contents.
+    Present {
+        /// The contents of the file.
+        contents: String,
+    },
+}
+
+impl<'a> File<'a> {
     /// An absent file.
     pub fn absent(path: Cow<Path>) -> Self {
         unimplemented!("FileState::absent for path {path:?}")
     }
 
-    /// A binary file.
-    pub fn binary(path: Cow<Path>) -> Self {
-        unimplemented!("FileState::binary for path {path:?}")
+    /// A binary file. Its changes can't be displayed, so the user can only
+    /// choose whether to include the entire change or not.
+    pub fn binary(
+        path: Cow<'a, Path>,
+        old_description: Option<Cow<'a, str>>,
+        new_description: Option<Cow<'a, str>>,
+    ) -> Self {
+        Self {
+            path,
+            file_mode: None,
+            sections: vec![Section::Binary {
+                is_toggled: false,
+                old_description,
+                new_description,
+            }],
+        }
     }
 
     /// Count the number of changed sections in this file.
@@ -82,17 +117,14 @@ impl File<'_> {
             .iter()
             .filter(|section| match section {
                 Section::Unchanged { .. } => false,
-                Section::Changed { .. } => true,
-                Section::FileMode { .. } => {
-                    unimplemented!("count_changed_sections for Section::FileMode")
-                }
+                Section::Changed { .. } | Section::FileMode { .. } | Section::Binary { .. } => true,
             })
             .count()
     }
 
     /// Get the new Unix file mode. If the user selected a
     /// [`Section::FileMode`], then returns that file mode. Otherwise, returns
-    /// the `file_mode` value that this [`FileState`] was constructed with.
+    /// the `file_mode` value that this [`File`] was constructed with.
     pub fn get_file_mode(&self) -> Option<FileMode> {
         let Self {
             path: _,
@@ -104,6 +136,7 @@ impl File<'_> {
             .find_map(|section| match section {
                 Section::Unchanged { .. }
                 | Section::Changed { .. }
+                | Section::Binary { .. }
                 | Section::FileMode {
                     is_toggled: false,
                     before: _,
@@ -122,14 +155,42 @@ impl File<'_> {
     /// Calculate the `(selected, unselected)` contents of the file. For
     /// example, the first value would be suitable for staging or committing,
     /// and the second value would be suitable for potentially recording again.
-    pub fn get_selected_contents(&self) -> (String, String) {
-        let mut acc_selected = String::new();
-        let mut acc_unselected = String::new();
+    ///
+    /// If the file contains a [`Section::Binary`], then the whole change to
+    /// the file is either selected or unselected, and the other value is
+    /// [`SelectedContents::Unchanged`].
+    ///
+    /// A [`Section::FileMode`] doesn't affect the contents of the file; use
+    /// [`File::get_file_mode`] to get the selected file mode instead.
+    pub fn get_selected_contents(&self) -> (SelectedContents<'a>, SelectedContents<'a>) {
         let Self {
             path: _,
             file_mode: _,
             sections,
         } = self;
+
+        let binary_section = sections.iter().find_map(|section| match section {
+            Section::Binary {
+                is_toggled,
+                old_description,
+                new_description,
+            } => Some((is_toggled, old_description, new_description)),
+            Section::Unchanged { .. } | Section::Changed { .. } | Section::FileMode { .. } => None,
+        });
+        if let Some((is_toggled, old_description, new_description)) = binary_section {
+            let contents = SelectedContents::Binary {
+                old_description: old_description.clone(),
+                new_description: new_description.clone(),
+            };
+            return if *is_toggled {
+                (contents, SelectedContents::Unchanged)
+            } else {
+                (SelectedContents::Unchanged, contents)
+            };
+        }
+
+        let mut acc_selected = String::new();
+        let mut acc_unselected = String::new();
         for section in sections {
             match section {
                 Section::Unchanged { lines } => {
@@ -159,12 +220,18 @@ impl File<'_> {
                     is_toggled: _,
                     before: _,
                     after: _,
-                } => {
-                    unimplemented!("get_selected_contents for Section::FileMode");
                 }
+                | Section::Binary { .. } => {}
             }
         }
-        (acc_selected, acc_unselected)
+        (
+            SelectedContents::Present {
+                contents: acc_selected,
+            },
+            SelectedContents::Present {
+                contents: acc_unselected,
+            },
+        )
     }
 }
 
@@ -203,6 +270,21 @@ pub enum Section<'a> {
         /// The new file mode.
         after: FileMode,
     },
+
+    /// This indicates that the file is binary, so its contents can't be
+    /// rendered. The user can only accept the entire change to the file or
+    /// not.
+    Binary {
+        /// Whether or not the change to the binary file was accepted.
+        is_toggled: bool,
+
+        /// A description of the old version of the file (such as its hash and
+        /// size), if it existed.
+        old_description: Option<Cow<'a, str>>,
+
+        /// A description of the new version of the file, if it exists.
+        new_description: Option<Cow<'a, str>>,
+    },
 }
 
 impl Section<'_> {
@@ -211,7 +293,7 @@ impl Section<'_> {
     pub fn is_editable(&self) -> bool {
         match self {
             Section::Unchanged { .. } => false,
-            Section::Changed { .. } | Section::FileMode { .. } => true,
+            Section::Changed { .. } | Section::FileMode { .. } | Section::Binary { .. } => true,
         }
     }
 }
@@ -239,3 +321,100 @@ pub struct SectionChangedLine<'a> {
     /// if any.
     pub line: Cow<'a, str>,
 }
+
+/// A key press which triggers an action. See [`KeyBindings`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct KeyBinding {
+    /// The key which is pressed.
+    pub code: KeyCode,
+
+    /// The modifier keys which are held while pressing the key.
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Constructor.
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    /// Whether or not the given key event triggers this binding.
+    pub(crate) fn matches(&self, key_event: &KeyEvent) -> bool {
+        let KeyEvent {
+            code,
+            modifiers,
+            kind,
+            state: _,
+        } = key_event;
+        if *kind != KeyEventKind::Press || *code != self.code {
+            return false;
+        }
+        match code {
+            // Terminals differ as to whether they report the Shift modifier
+            // for uppercase characters, so it's not considered.
+            KeyCode::Char(_) => {
+                (*modifiers - KeyModifiers::SHIFT) == (self.modifiers - KeyModifiers::SHIFT)
+            }
+            _ => *modifiers == self.modifiers,
+        }
+    }
+}
+
+/// The keys which trigger the user-configurable actions in the recorder. Each
+/// action can be bound to any number of keys. Actions which aren't listed here
+/// (such as scrolling and searching) always use their built-in keys.
+///
+/// Use [`KeyBindings::default`] for the standard bindings, and override the
+/// fields to customize them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyBindings {
+    /// Accept the selected changes and quit. Defaults to `c`.
+    pub quit_accept: Vec<KeyBinding>,
+
+    /// Discard the selected changes and quit. Defaults to `q`.
+    pub quit_cancel: Vec<KeyBinding>,
+
+    /// Focus the previous item. Defaults to the up arrow.
+    pub focus_prev: Vec<KeyBinding>,
+
+    /// Focus the next item. Defaults to the down arrow.
+    pub focus_next: Vec<KeyBinding>,
+
+    /// Focus the item containing the current item. Defaults to the left
+    /// arrow.
+    pub focus_outer: Vec<KeyBinding>,
+
+    /// Focus the first item inside the current item. Defaults to the right
+    /// arrow.
+    pub focus_inner: Vec<KeyBinding>,
+
+    /// Toggle the current item. Defaults to space.
+    pub toggle_item: Vec<KeyBinding>,
+
+    /// Toggle the current item and focus the next item. Defaults to enter.
+    pub toggle_item_and_advance: Vec<KeyBinding>,
+
+    /// Select all of the changes in the current file. Defaults to `a`.
+    pub select_all_in_file: Vec<KeyBinding>,
+
+    /// Deselect all of the changes in the current file. Defaults to `A`.
+    pub deselect_all_in_file: Vec<KeyBinding>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let key = |code| vec![KeyBinding::new(code, KeyModifiers::NONE)];
+        Self {
+            quit_accept: key(KeyCode::Char('c')),
+            quit_cancel: key(KeyCode::Char('q')),
+            focus_prev: key(KeyCode::Up),
+            focus_next: key(KeyCode::Down),
+            focus_outer: key(KeyCode::Left),
+            focus_inner: key(KeyCode::Right),
+            toggle_item: key(KeyCode::Char(' ')),
+            toggle_item_and_advance: key(KeyCode::Enter),
+            select_all_in_file: key(KeyCode::Char('a')),
+            deselect_all_in_file: key(KeyCode::Char('A')),
+        }
+    }
+}
@@ -3,11 +3,12 @@
 use std::any::Any;
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::cmp::min;
-use std::collections::{BTreeMap, HashMap};
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::Write;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
+use std::ops::RangeInclusive;
 use std::path::Path;
 use std::rc::Rc;
 use std::{io, panic};
@@ -28,8 +29,10 @@ use tui::widgets::{Block, Borders, Clear, Paragraph};
 use tui::{backend::CrosstermBackend, Terminal};
 use unicode_width::UnicodeWidthStr;
 
+use crate::highlight::{Highlighter, StyledSegment};
+use crate::intraline::{diff_words, emphasize_segments, pair_changed_lines};
 use crate::render::{centered_rect, Component, Rect, RectSize, Viewport};
-use crate::types::{ChangeType, RecordError, RecordState};
+use crate::types::{ChangeType, FileMode, KeyBindings, RecordError, RecordState};
 use crate::util::UsizeExt;
 use crate::{File, Section, SectionChangedLine};
 
@@ -103,42 +106,138 @@ pub enum Event {
     TakeScreenshot(TestingScreenshot),
     ScrollUp,
     ScrollDown,
+    ScrollLeft,
+    ScrollRight,
     PageUp,
     PageDown,
     FocusPrev,
     FocusPrevPage,
     FocusNext,
     FocusNextPage,
+    FocusPrevSection,
+    FocusNextSection,
     FocusInner,
     FocusOuter,
     ToggleItem,
     ToggleItemAndAdvance,
+    SelectAllInFile,
+    DeselectAllInFile,
+    ToggleExpand,
+    ToggleLineNumbers,
+    Undo,
+    Redo,
+    ToggleVisualMode,
+    StartSearch,
+    SearchInput(char),
+    SearchBackspace,
+    SearchAccept,
+    SearchCancel,
+    SearchNext,
+    SearchPrev,
 }
 
-impl From<crossterm::event::Event> for Event {
-    fn from(event: crossterm::event::Event) -> Self {
+impl Event {
+    /// Convert a terminal event into an `Event` while the user is typing a
+    /// search query. Most keys are interpreted as part of the query, rather
+    /// than as their usual commands.
+    fn from_search_input(event: crossterm::event::Event) -> Self {
         use crossterm::event::Event;
         match event {
             Event::Key(KeyEvent {
-                code: KeyCode::Char('q'),
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::QuitInterrupt,
+
+            Event::Key(KeyEvent {
+                code: KeyCode::Esc,
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::QuitCancel,
+            }) => Self::SearchCancel,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::CONTROL,
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::QuitInterrupt,
+            }) => Self::SearchAccept,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Char('c'),
+                code: KeyCode::Backspace,
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::QuitAccept,
+            }) => Self::SearchBackspace,
+
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::SearchInput(c),
+
+            _event => Self::None,
+        }
+    }
+}
+
+impl From<crossterm::event::Event> for Event {
+    fn from(event: crossterm::event::Event) -> Self {
+        Self::from_crossterm(event, &KeyBindings::default())
+    }
+}
+
+impl Event {
+    /// Convert a terminal event into an `Event`. Keys in `key_bindings` take
+    /// precedence over the built-in keys.
+    fn from_crossterm(event: crossterm::event::Event, key_bindings: &KeyBindings) -> Self {
+        if let crossterm::event::Event::Key(key_event) = &event {
+            let KeyBindings {
+                quit_accept,
+                quit_cancel,
+                focus_prev,
+                focus_next,
+                focus_outer,
+                focus_inner,
+                toggle_item,
+                toggle_item_and_advance,
+                select_all_in_file,
+                deselect_all_in_file,
+            } = key_bindings;
+            let bound_events = [
+                (quit_accept, Self::QuitAccept),
+                (quit_cancel, Self::QuitCancel),
+                (focus_prev, Self::FocusPrev),
+                (focus_next, Self::FocusNext),
+                (focus_outer, Self::FocusOuter),
+                (focus_inner, Self::FocusInner),
+                (toggle_item, Self::ToggleItem),
+                (toggle_item_and_advance, Self::ToggleItemAndAdvance),
+                (select_all_in_file, Self::SelectAllInFile),
+                (deselect_all_in_file, Self::DeselectAllInFile),
+            ];
+            for (bindings, bound_event) in bound_events {
+                if bindings.iter().any(|binding| binding.matches(key_event)) {
+                    return bound_event;
+                }
+            }
+        }
+        Self::from_built_in_keys(event)
+    }
+
+    /// Convert a terminal event into an `Event` for the actions which aren't
+    /// configurable with [`KeyBindings`].
+    fn from_built_in_keys(event: crossterm::event::Event) -> Self {
+        use crossterm::event::Event;
+        match event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::QuitInterrupt,
 
             Event::Key(KeyEvent {
                 code: KeyCode::Char('y'),
@@ -165,6 +264,19 @@ impl From<crossterm::event::Event> for Event {
                 modifiers: _,
             }) => Self::ScrollDown,
 
+            Event::Key(KeyEvent {
+                code: KeyCode::Left,
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::ScrollLeft,
+            Event::Key(KeyEvent {
+                code: KeyCode::Right,
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::ScrollRight,
+
             Event::Key(
                 KeyEvent {
                     code: KeyCode::PageUp,
@@ -195,59 +307,85 @@ impl From<crossterm::event::Event> for Event {
             ) => Self::PageDown,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Up,
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::FocusPrevPage,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::FocusNextPage,
+
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('f'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusPrev,
+            }) => Self::ToggleExpand,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Down,
+                code: KeyCode::Char('l'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusNext,
+            }) => Self::ToggleLineNumbers,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Left,
+                code: KeyCode::Char('u'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusOuter,
+            }) => Self::Undo,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::Redo,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Right,
+                code: KeyCode::Char('v'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusInner,
+            }) => Self::ToggleVisualMode,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Char('u'),
-                modifiers: KeyModifiers::CONTROL,
+                code: KeyCode::Char('['),
+                modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusPrevPage,
+            }) => Self::FocusPrevSection,
             Event::Key(KeyEvent {
-                code: KeyCode::Char('d'),
-                modifiers: KeyModifiers::CONTROL,
+                code: KeyCode::Char(']'),
+                modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusNextPage,
+            }) => Self::FocusNextSection,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Char(' '),
+                code: KeyCode::Char('/'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::ToggleItem,
+            }) => Self::StartSearch,
 
             Event::Key(KeyEvent {
-                code: KeyCode::Enter,
+                code: KeyCode::Char('n'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::ToggleItemAndAdvance,
+            }) => Self::SearchNext,
+
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('N'),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => Self::SearchPrev,
 
             _event => Self::None,
         }
@@ -286,11 +424,19 @@ impl EventSource {
         }
     }
 
-    fn next_event(&mut self) -> Result<Event, RecordError> {
+    fn next_event(
+        &mut self,
+        is_searching: bool,
+        key_bindings: &KeyBindings,
+    ) -> Result<Event, RecordError> {
         match self {
             EventSource::Crossterm => {
                 let event = crossterm::event::read().map_err(RecordError::ReadInput)?;
-                Ok(event.into())
+                if is_searching {
+                    Ok(Event::from_search_input(event))
+                } else {
+                    Ok(Event::from_crossterm(event, key_bindings))
+                }
             }
             EventSource::Testing {
                 width: _,
@@ -339,31 +485,113 @@ enum StateUpdate {
     QuitCancel,
     TakeScreenshot(TestingScreenshot),
     ScrollTo(isize),
+    ScrollHorizontallyTo(isize),
     SelectItem(SelectionKey),
     ToggleItem(SelectionKey),
     ToggleItemAndAdvance(SelectionKey, SelectionKey),
+    SetFileToggled(FileKey, bool),
+    ToggleExpand(FileKey),
+    ToggleLineNumbers,
+    Undo,
+    Redo,
+    SetVisualAnchor(Option<SelectionKey>),
+    ToggleRange(Vec<LineKey>),
+    SetSearchPrompt(Option<SearchPrompt>),
+    AcceptSearch {
+        query: String,
+        selection_key: SelectionKey,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+/// The number of columns to scroll by when scrolling horizontally.
+const HORIZONTAL_SCROLL_STEP: usize = 4;
+
+/// The maximum number of toggle actions which can be undone. Older actions are
+/// discarded.
+const MAX_UNDO_HISTORY_LENGTH: usize = 100;
+
+/// A toggle action which can be undone or redone. The toggled state of each of
+/// the changes in the affected files is recorded from before and after the
+/// action.
+#[derive(Clone, Debug)]
+struct ToggleHistoryEntry {
+    files: Vec<FileToggleHistory>,
+}
+
+#[derive(Clone, Debug)]
+struct FileToggleHistory {
+    file_key: FileKey,
+    before: Vec<bool>,
+    after: Vec<bool>,
 }
 
 /// UI component to record the user's changes.
 pub struct Recorder<'a> {
     state: RecordState<'a>,
     event_source: EventSource,
+    key_bindings: KeyBindings,
     use_unicode: bool,
     selection_key: SelectionKey,
     quit_dialog: Option<QuitDialog>,
+    scroll_offset_x: isize,
     scroll_offset_y: isize,
+    highlighter: Option<Highlighter>,
+    highlighted_files: HashSet<FileKey>,
+    highlighted_lines: HashMap<LineKey, Vec<StyledSegment>>,
+    search_prompt: Option<SearchPrompt>,
+    search_query: Option<String>,
+    collapsed_files: HashSet<FileKey>,
+    show_line_numbers: bool,
+    undo_stack: VecDeque<ToggleHistoryEntry>,
+    redo_stack: Vec<ToggleHistoryEntry>,
+
+    /// The item which was selected when visual mode was entered, if it's
+    /// active. The lines between it and the current selection are toggled
+    /// together.
+    visual_anchor: Option<SelectionKey>,
 }
 
 impl<'a> Recorder<'a> {
     /// Constructor.
-    pub fn new(state: RecordState<'a>, event_source: EventSource) -> Self {
+    ///
+    /// If the `syntax-highlighting` feature is enabled, the changed lines are
+    /// highlighted according to the extensions of their files' paths. When a
+    /// removed line is replaced by a similar added line, the words which differ
+    /// between them are emphasized. Files are only highlighted once they're
+    /// scrolled into view.
+    ///
+    /// When reading events from the terminal, `key_bindings` determines which
+    /// keys trigger which actions.
+    pub fn new(
+        state: RecordState<'a>,
+        event_source: EventSource,
+        key_bindings: KeyBindings,
+    ) -> Self {
         Self {
             state,
             event_source,
+            key_bindings,
             use_unicode: true,
             selection_key: SelectionKey::None,
             quit_dialog: None,
+            scroll_offset_x: 0,
             scroll_offset_y: 0,
+            highlighter: None,
+            highlighted_files: HashSet::new(),
+            highlighted_lines: HashMap::new(),
+            search_prompt: None,
+            search_query: None,
+            collapsed_files: HashSet::new(),
+            show_line_numbers: false,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            visual_anchor: None,
         }
     }
 
@@ -437,13 +665,14 @@ impl<'a> Recorder<'a> {
 
         loop {
             let app = self.make_app(None);
+            let term_width = usize::from(term.get_frame().size().width);
             let term_height = usize::from(term.get_frame().size().height);
 
             let mut drawn_rects: Option<HashMap<ComponentId, Rect>> = None;
             term.draw(|frame| {
                 drawn_rects = Some(Viewport::<ComponentId>::render_top_level(
                     frame,
-                    0,
+                    self.scroll_offset_x,
                     self.scroll_offset_y,
                     &app,
                 ));
@@ -456,6 +685,7 @@ impl<'a> Recorder<'a> {
             if debug {
                 let debug_info = AppDebugInfo {
                     term_height,
+                    scroll_offset_x: self.scroll_offset_x,
                     scroll_offset_y: self.scroll_offset_y,
                     selection_key: self.selection_key,
                     selection_key_y: self.selection_key_y(&drawn_rects, self.selection_key),
@@ -473,7 +703,7 @@ impl<'a> Recorder<'a> {
                 term.draw(|frame| {
                     Viewport::<ComponentId>::render_top_level(
                         frame,
-                        0,
+                        self.scroll_offset_x,
                         self.scroll_offset_y,
                         &debug_app,
                     );
@@ -481,7 +711,15 @@ impl<'a> Recorder<'a> {
                 .map_err(RecordError::RenderFrame)?;
             }
 
-            let event = self.event_source.next_event()?;
+            // Highlighting the newly-visible files changes how they're drawn,
+            // so redraw them before handling the next event.
+            if self.highlight_visible_files(term_height, &drawn_rects) {
+                continue;
+            }
+
+            let event = self
+                .event_source
+                .next_event(self.search_prompt.is_some(), &self.key_bindings)?;
             match self.handle_event(event, term_height, &drawn_rects)? {
                 StateUpdate::None => {}
                 StateUpdate::SetQuitDialog(quit_dialog) => {
@@ -500,27 +738,142 @@ impl<'a> Recorder<'a> {
                     self.scroll_offset_y = scroll_offset_y
                         .clamp(0, drawn_rects[&ComponentId::App].height.unwrap_isize() - 1);
                 }
+                StateUpdate::ScrollHorizontallyTo(scroll_offset_x) => {
+                    self.scroll_offset_x = scroll_offset_x.clamp(
+                        0,
+                        self.max_scroll_offset_x(term_width, term_height, &drawn_rects),
+                    );
+                }
                 StateUpdate::SelectItem(selection_key) => {
                     self.selection_key = selection_key;
                     self.scroll_offset_y =
                         self.ensure_in_viewport(term_height, &drawn_rects, selection_key);
                 }
                 StateUpdate::ToggleItem(selection_key) => {
-                    self.toggle_item(selection_key)?;
+                    self.record_toggle(Self::selection_file_key(selection_key), |this| {
+                        this.toggle_item(selection_key)
+                    })?;
                 }
                 StateUpdate::ToggleItemAndAdvance(selection_key, new_key) => {
-                    self.toggle_item(selection_key)?;
+                    self.record_toggle(Self::selection_file_key(selection_key), |this| {
+                        this.toggle_item(selection_key)
+                    })?;
                     self.selection_key = new_key;
                     self.scroll_offset_y =
                         self.ensure_in_viewport(term_height, &drawn_rects, selection_key);
                 }
+                StateUpdate::SetFileToggled(file_key, is_toggled) => {
+                    self.record_toggle(Some(file_key), |this| {
+                        this.set_file_toggled(file_key, is_toggled)
+                    })?;
+                }
+                StateUpdate::SetVisualAnchor(visual_anchor) => {
+                    self.visual_anchor = visual_anchor;
+                }
+                StateUpdate::ToggleRange(line_keys) => {
+                    let file_keys = line_keys
+                        .iter()
+                        .map(|line_key| FileKey {
+                            file_idx: line_key.file_idx,
+                        })
+                        .collect::<Vec<_>>();
+                    self.record_toggle(file_keys, |this| this.toggle_range(&line_keys))?;
+                    self.visual_anchor = None;
+                }
+                StateUpdate::Undo => {
+                    if let Some(entry) = self.undo_stack.pop_back() {
+                        for file in &entry.files {
+                            self.set_file_toggles(file.file_key, &file.before)?;
+                        }
+                        self.redo_stack.push(entry);
+                    }
+                }
+                StateUpdate::Redo => {
+                    if let Some(entry) = self.redo_stack.pop() {
+                        for file in &entry.files {
+                            self.set_file_toggles(file.file_key, &file.after)?;
+                        }
+                        self.undo_stack.push_back(entry);
+                    }
+                }
+                StateUpdate::ToggleExpand(file_key) => {
+                    if !self.collapsed_files.remove(&file_key) {
+                        self.collapsed_files.insert(file_key);
+                        // The selected section or line may no longer be
+                        // visible, so select the file itself instead, and
+                        // make sure that its header is on-screen.
+                        let selection_key = SelectionKey::File(file_key);
+                        self.selection_key = selection_key;
+                        self.scroll_offset_y = min(
+                            self.scroll_offset_y,
+                            self.selection_key_y(&drawn_rects, selection_key),
+                        );
+                    }
+                }
+                StateUpdate::ToggleLineNumbers => {
+                    self.show_line_numbers = !self.show_line_numbers;
+                }
+                StateUpdate::SetSearchPrompt(search_prompt) => {
+                    self.search_prompt = search_prompt;
+                }
+                StateUpdate::AcceptSearch {
+                    query,
+                    selection_key,
+                } => {
+                    self.search_prompt = None;
+                    self.search_query = Some(query);
+                    self.selection_key = selection_key;
+                    self.scroll_offset_y =
+                        self.ensure_in_viewport(term_height, &drawn_rects, selection_key);
+                }
             }
         }
 
         Ok(self.state)
     }
 
+    /// Calculate the highlighting for the expanded files which are currently
+    /// on-screen and which haven't already been highlighted, so that the cost
+    /// of highlighting is only paid for the files which the user actually
+    /// looks at. Returns whether any files were highlighted.
+    fn highlight_visible_files(
+        &mut self,
+        term_height: usize,
+        drawn_rects: &HashMap<ComponentId, Rect>,
+    ) -> bool {
+        let visible_y_range =
+            self.scroll_offset_y..self.scroll_offset_y + term_height.unwrap_isize();
+        let mut any_highlighted = false;
+        for (file_idx, file) in self.state.files.iter().enumerate() {
+            let file_key = FileKey { file_idx };
+            if self.highlighted_files.contains(&file_key)
+                || self.collapsed_files.contains(&file_key)
+            {
+                continue;
+            }
+            let is_visible =
+                match drawn_rects.get(&ComponentId::SelectableItem(SelectionKey::File(file_key))) {
+                    Some(rect) => {
+                        rect.y < visible_y_range.end
+                            && rect.y + rect.height.unwrap_isize() > visible_y_range.start
+                    }
+                    None => false,
+                };
+            if !is_visible {
+                continue;
+            }
+
+            let highlighter = self.highlighter.get_or_insert_with(Highlighter::new);
+            self.highlighted_lines
+                .extend(highlight_file_lines(highlighter, file_idx, file));
+            self.highlighted_files.insert(file_key);
+            any_highlighted = true;
+        }
+        any_highlighted
+    }
+
     fn make_app(&'a self, debug_info: Option<AppDebugInfo>) -> App<'a> {
+        let visual_range = self.visual_range();
         let file_views: Vec<FileView> = self
             .state
             .files
@@ -533,6 +886,7 @@ impl<'a> Recorder<'a> {
                     SelectionKey::None | SelectionKey::Section(_) | SelectionKey::Line(_) => false,
                     SelectionKey::File(selected_file_key) => file_key == selected_file_key,
                 };
+                let is_collapsed = self.collapsed_files.contains(&file_key);
                 FileView {
                     debug: debug_info.is_some(),
                     file_key,
@@ -544,7 +898,14 @@ impl<'a> Recorder<'a> {
                     },
                     is_header_selected: is_focused,
                     path: &file.path,
-                    section_views: {
+                    collapsed_summary: if is_collapsed {
+                        Some(self.file_summary(file_key).unwrap())
+                    } else {
+                        None
+                    },
+                    section_views: if is_collapsed {
+                        Vec::new()
+                    } else {
                         let mut section_views = Vec::new();
                         let total_num_sections = file
                             .sections
@@ -553,6 +914,7 @@ impl<'a> Recorder<'a> {
                             .count();
 
                         let mut line_num = 1;
+                        let mut new_line_num = 1;
                         let mut section_num = 0;
                         for (section_idx, section) in file.sections.iter().enumerate() {
                             let section_key = SectionKey {
@@ -565,7 +927,9 @@ impl<'a> Recorder<'a> {
                             }
                             section_views.push(SectionView {
                                 use_unicode: self.use_unicode,
+                                highlighted_lines: &self.highlighted_lines,
                                 section_key,
+                                visual_range: section_visual_range(&visual_range, section_key),
                                 tristate_box: TristateBox {
                                     use_unicode: self.use_unicode,
                                     id: ComponentId::TristateBox,
@@ -607,9 +971,22 @@ impl<'a> Recorder<'a> {
                                 section_num,
                                 total_num_sections,
                                 section,
+                                show_line_numbers: self.show_line_numbers,
                                 line_start_num: line_num,
+                                new_line_start_num: new_line_num,
                             });
 
+                            new_line_num += match section {
+                                Section::Unchanged { lines } => lines.len(),
+                                Section::Changed { lines } => lines
+                                    .iter()
+                                    .filter(|changed_line| match changed_line.change_type {
+                                        ChangeType::Added => true,
+                                        ChangeType::Removed => false,
+                                    })
+                                    .count(),
+                                Section::FileMode { .. } | Section::Binary { .. } => 0,
+                            };
                             line_num += match section {
                                 Section::Unchanged { lines } => lines.len(),
                                 Section::Changed { lines } => lines
@@ -619,7 +996,7 @@ impl<'a> Recorder<'a> {
                                         ChangeType::Removed => true,
                                     })
                                     .count(),
-                                Section::FileMode { .. } => 0,
+                                Section::FileMode { .. } | Section::Binary { .. } => 0,
                             };
                         }
                         section_views
@@ -631,6 +1008,7 @@ impl<'a> Recorder<'a> {
             debug_info: None,
             file_views,
             quit_dialog: self.quit_dialog.clone(),
+            search_prompt: self.search_prompt.clone(),
         }
     }
 
@@ -640,6 +1018,39 @@ impl<'a> Recorder<'a> {
         term_height: usize,
         drawn_rects: &HashMap<ComponentId, Rect>,
     ) -> Result<StateUpdate, RecordError> {
+        if let Some(SearchPrompt { query }) = &self.search_prompt {
+            match event {
+                Event::SearchInput(c) => {
+                    let mut query = query.clone();
+                    query.push(c);
+                    return Ok(StateUpdate::SetSearchPrompt(Some(SearchPrompt { query })));
+                }
+                Event::SearchBackspace => {
+                    let mut query = query.clone();
+                    return Ok(match query.pop() {
+                        Some(_) => StateUpdate::SetSearchPrompt(Some(SearchPrompt { query })),
+                        // Deleting past the start of the query cancels the
+                        // search, as in `vim`.
+                        None => StateUpdate::SetSearchPrompt(None),
+                    });
+                }
+                Event::SearchAccept => {
+                    if query.is_empty() {
+                        return Ok(StateUpdate::SetSearchPrompt(None));
+                    }
+                    let selection_key = self
+                        .find_search_match(query, SearchDirection::Forward)
+                        .unwrap_or(self.selection_key);
+                    return Ok(StateUpdate::AcceptSearch {
+                        query: query.clone(),
+                        selection_key,
+                    });
+                }
+                Event::SearchCancel => return Ok(StateUpdate::SetSearchPrompt(None)),
+                _ => {}
+            }
+        }
+
         let state_update = match (&self.quit_dialog, event) {
             (_, Event::None) => StateUpdate::None,
 
@@ -695,12 +1106,36 @@ impl<'a> Recorder<'a> {
                 Some(_),
                 Event::ScrollUp
                 | Event::ScrollDown
+                | Event::ScrollLeft
+                | Event::ScrollRight
                 | Event::PageUp
                 | Event::PageDown
                 | Event::FocusPrev
                 | Event::FocusNext
                 | Event::FocusPrevPage
-                | Event::FocusNextPage,
+                | Event::FocusNextPage
+                | Event::FocusPrevSection
+                | Event::FocusNextSection
+                | Event::SelectAllInFile
+                | Event::DeselectAllInFile
+                | Event::ToggleExpand
+                | Event::ToggleLineNumbers
+                | Event::Undo
+                | Event::Redo
+                | Event::ToggleVisualMode
+                | Event::StartSearch
+                | Event::SearchNext
+                | Event::SearchPrev,
+            ) => StateUpdate::None,
+
+            // These are only meaningful while the search prompt is open, which
+            // is handled above.
+            (
+                Some(_) | None,
+                Event::SearchInput(_)
+                | Event::SearchBackspace
+                | Event::SearchAccept
+                | Event::SearchCancel,
             ) => StateUpdate::None,
 
             (Some(_) | None, Event::TakeScreenshot(screenshot)) => {
@@ -712,6 +1147,14 @@ impl<'a> Recorder<'a> {
             (None, Event::ScrollDown) => {
                 StateUpdate::ScrollTo(self.scroll_offset_y.saturating_add(1))
             }
+            (None, Event::ScrollLeft) => StateUpdate::ScrollHorizontallyTo(
+                self.scroll_offset_x
+                    .saturating_sub(HORIZONTAL_SCROLL_STEP.unwrap_isize()),
+            ),
+            (None, Event::ScrollRight) => StateUpdate::ScrollHorizontallyTo(
+                self.scroll_offset_x
+                    .saturating_add(HORIZONTAL_SCROLL_STEP.unwrap_isize()),
+            ),
             (None, Event::PageUp) => StateUpdate::ScrollTo(
                 self.scroll_offset_y
                     .saturating_sub(term_height.unwrap_isize()),
@@ -738,10 +1181,47 @@ impl<'a> Recorder<'a> {
                 let selection_key = self.select_next_page(term_height, drawn_rects);
                 StateUpdate::SelectItem(selection_key)
             }
+            (None, Event::FocusPrevSection) => match self.select_prev_changed_section() {
+                Some(selection_key) => StateUpdate::SelectItem(selection_key),
+                None => StateUpdate::None,
+            },
+            (None, Event::FocusNextSection) => match self.select_next_changed_section() {
+                Some(selection_key) => StateUpdate::SelectItem(selection_key),
+                None => StateUpdate::None,
+            },
             (None, Event::FocusInner | Event::FocusOuter) => {
                 // TODO: implement
                 StateUpdate::None
             }
+            (None, Event::SelectAllInFile) => match self.selected_file_key() {
+                Some(file_key) => StateUpdate::SetFileToggled(file_key, true),
+                None => StateUpdate::None,
+            },
+            (None, Event::DeselectAllInFile) => match self.selected_file_key() {
+                Some(file_key) => StateUpdate::SetFileToggled(file_key, false),
+                None => StateUpdate::None,
+            },
+            (None, Event::ToggleExpand) => match self.selected_file_key() {
+                Some(file_key) => StateUpdate::ToggleExpand(file_key),
+                None => StateUpdate::None,
+            },
+            (None, Event::ToggleLineNumbers) => StateUpdate::ToggleLineNumbers,
+            (None, Event::Undo) => StateUpdate::Undo,
+            (None, Event::Redo) => StateUpdate::Redo,
+            (None, Event::ToggleVisualMode) => match self.visual_anchor {
+                Some(_) => StateUpdate::SetVisualAnchor(None),
+                None => StateUpdate::SetVisualAnchor(Some(self.selection_key)),
+            },
+            (None, Event::StartSearch) => StateUpdate::SetSearchPrompt(Some(SearchPrompt {
+                query: String::new(),
+            })),
+            (None, Event::SearchNext) => self.select_search_match(SearchDirection::Forward),
+            (None, Event::SearchPrev) => self.select_search_match(SearchDirection::Backward),
+            (None, Event::ToggleItem | Event::ToggleItemAndAdvance)
+                if self.visual_anchor.is_some() =>
+            {
+                StateUpdate::ToggleRange(self.visual_range())
+            }
             (None, Event::ToggleItem) => StateUpdate::ToggleItem(self.selection_key),
             (None, Event::ToggleItemAndAdvance) => {
                 let advanced_key = self.advance_to_next_of_kind();
@@ -751,6 +1231,27 @@ impl<'a> Recorder<'a> {
         Ok(state_update)
     }
 
+    /// The file containing the current selection, if any.
+    fn selected_file_key(&self) -> Option<FileKey> {
+        Self::selection_file_key(self.selection_key)
+    }
+
+    fn selection_file_key(selection_key: SelectionKey) -> Option<FileKey> {
+        match selection_key {
+            SelectionKey::None => None,
+            SelectionKey::File(file_key) => Some(file_key),
+            SelectionKey::Section(SectionKey {
+                file_idx,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                file_idx,
+                section_idx: _,
+                line_idx: _,
+            }) => Some(FileKey { file_idx }),
+        }
+    }
+
     fn first_selection_key(&self) -> SelectionKey {
         match self.state.files.iter().enumerate().next() {
             Some((file_idx, _)) => SelectionKey::File(FileKey { file_idx }),
@@ -775,7 +1276,13 @@ impl<'a> Recorder<'a> {
     fn all_selection_keys(&self) -> Vec<SelectionKey> {
         let mut result = Vec::new();
         for (file_idx, file) in self.state.files.iter().enumerate() {
-            result.push(SelectionKey::File(FileKey { file_idx }));
+            let file_key = FileKey { file_idx };
+            result.push(SelectionKey::File(file_key));
+            if self.collapsed_files.contains(&file_key) {
+                // The sections of collapsed files aren't rendered, so they
+                // can't be selected.
+                continue;
+            }
             for (section_idx, section) in file.sections.iter().enumerate() {
                 match section {
                     Section::Unchanged { .. } => {}
@@ -796,6 +1303,11 @@ impl<'a> Recorder<'a> {
                         is_toggled: _,
                         before: _,
                         after: _,
+                    }
+                    | Section::Binary {
+                        is_toggled: _,
+                        old_description: _,
+                        new_description: _,
                     } => {
                         result.push(SelectionKey::Section(SectionKey {
                             file_idx,
@@ -813,6 +1325,34 @@ impl<'a> Recorder<'a> {
         result
     }
 
+    /// The lines between the visual mode anchor and the current selection
+    /// (inclusive), in order, or nothing if visual mode isn't active.
+    fn visual_range(&self) -> Vec<LineKey> {
+        let visual_anchor = match self.visual_anchor {
+            Some(visual_anchor) => visual_anchor,
+            None => return Vec::new(),
+        };
+        let keys = self.all_selection_keys();
+        let position =
+            |selection_key: SelectionKey| keys.iter().position(|key| *key == selection_key);
+        let (start, end) = match (position(visual_anchor), position(self.selection_key)) {
+            (Some(anchor_index), Some(selection_index)) => (
+                min(anchor_index, selection_index),
+                max(anchor_index, selection_index),
+            ),
+            // The anchor may no longer be selectable (for example, if its file
+            // was collapsed).
+            _ => return Vec::new(),
+        };
+        keys[start..=end]
+            .iter()
+            .filter_map(|key| match key {
+                SelectionKey::Line(line_key) => Some(*line_key),
+                SelectionKey::None | SelectionKey::File(_) | SelectionKey::Section(_) => None,
+            })
+            .collect()
+    }
+
     fn find_selection(&self) -> (Vec<SelectionKey>, Option<usize>) {
         // FIXME: O(n) algorithm
         let keys = self.all_selection_keys();
@@ -890,6 +1430,36 @@ impl<'a> Recorder<'a> {
         keys[index]
     }
 
+    /// The first changed section after the current selection, if any.
+    fn select_next_changed_section(&self) -> Option<SelectionKey> {
+        let (keys, index) = self.find_selection();
+        let start = index.map(|index| index + 1).unwrap_or_default();
+        keys[start..]
+            .iter()
+            .copied()
+            .find(|key| self.is_changed_section(*key))
+    }
+
+    /// The last changed section before the current selection, if any.
+    fn select_prev_changed_section(&self) -> Option<SelectionKey> {
+        let (keys, index) = self.find_selection();
+        let end = index.unwrap_or(keys.len());
+        keys[..end]
+            .iter()
+            .rev()
+            .copied()
+            .find(|key| self.is_changed_section(*key))
+    }
+
+    fn is_changed_section(&self, selection_key: SelectionKey) -> bool {
+        match selection_key {
+            SelectionKey::Section(section_key) => {
+                matches!(self.section(section_key), Ok(Section::Changed { lines: _ }))
+            }
+            SelectionKey::None | SelectionKey::File(_) | SelectionKey::Line(_) => false,
+        }
+    }
+
     fn advance_to_next_of_kind(&self) -> SelectionKey {
         let (keys, index) = self.find_selection();
         let index = match index {
@@ -920,6 +1490,52 @@ impl<'a> Recorder<'a> {
             .unwrap_or(self.selection_key)
     }
 
+    fn select_search_match(&self, direction: SearchDirection) -> StateUpdate {
+        let search_match = self
+            .search_query
+            .as_ref()
+            .and_then(|query| self.find_search_match(query, direction));
+        match search_match {
+            Some(selection_key) => StateUpdate::SelectItem(selection_key),
+            None => StateUpdate::None,
+        }
+    }
+
+    /// Find the next changed line in the given direction (wrapping around)
+    /// which contains the search query.
+    fn find_search_match(&self, query: &str, direction: SearchDirection) -> Option<SelectionKey> {
+        let (keys, index) = self.find_selection();
+        let num_keys = keys.len();
+        let indices: Box<dyn Iterator<Item = usize>> = match direction {
+            SearchDirection::Forward => {
+                let start = index.map(|index| index + 1).unwrap_or(0);
+                Box::new((0..num_keys).map(move |offset| (start + offset) % num_keys))
+            }
+            SearchDirection::Backward => {
+                let start = index.unwrap_or(0) + num_keys;
+                Box::new((1..=num_keys).map(move |offset| (start - offset) % num_keys))
+            }
+        };
+        indices
+            .map(|index| keys[index])
+            .find(|key| self.line_contains(*key, query))
+    }
+
+    fn line_contains(&self, selection_key: SelectionKey, query: &str) -> bool {
+        let LineKey {
+            file_idx,
+            section_idx,
+            line_idx,
+        } = match selection_key {
+            SelectionKey::Line(line_key) => line_key,
+            SelectionKey::None | SelectionKey::File(_) | SelectionKey::Section(_) => return false,
+        };
+        match &self.state.files[file_idx].sections[section_idx] {
+            Section::Changed { lines } => lines[line_idx].line.contains(query),
+            Section::Unchanged { .. } | Section::FileMode { .. } | Section::Binary { .. } => false,
+        }
+    }
+
     fn selection_key_y(
         &self,
         drawn_rects: &HashMap<ComponentId, Rect>,
@@ -971,6 +1587,155 @@ impl<'a> Recorder<'a> {
         }
     }
 
+    /// The furthest that the view can be scrolled to the right, such that the
+    /// end of the longest line currently on-screen is aligned with the right
+    /// edge of the viewport.
+    fn max_scroll_offset_x(
+        &self,
+        term_width: usize,
+        term_height: usize,
+        drawn_rects: &HashMap<ComponentId, Rect>,
+    ) -> isize {
+        let visible_rows = self.scroll_offset_y..self.scroll_offset_y + term_height.unwrap_isize();
+        let max_line_end_x = drawn_rects
+            .iter()
+            .filter_map(|(id, rect)| match id {
+                ComponentId::SelectableItem(SelectionKey::Line(_))
+                    if visible_rows.contains(&rect.y) =>
+                {
+                    Some(rect.x + rect.width.unwrap_isize())
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or_default();
+        (max_line_end_x - term_width.unwrap_isize()).max(0)
+    }
+
+    /// Apply a toggle action which affects only the given files, and record it
+    /// so that it can be undone later.
+    fn record_toggle(
+        &mut self,
+        file_keys: impl IntoIterator<Item = FileKey>,
+        f: impl FnOnce(&mut Self) -> Result<(), RecordError>,
+    ) -> Result<(), RecordError> {
+        let file_keys: BTreeSet<FileKey> = file_keys.into_iter().collect();
+        let befores = file_keys
+            .iter()
+            .map(|file_key| self.file_toggles(*file_key))
+            .collect::<Result<Vec<_>, _>>()?;
+        f(self)?;
+
+        let mut files = Vec::new();
+        for (file_key, before) in file_keys.into_iter().zip(befores) {
+            let after = self.file_toggles(file_key)?;
+            if before != after {
+                files.push(FileToggleHistory {
+                    file_key,
+                    before,
+                    after,
+                });
+            }
+        }
+        if !files.is_empty() {
+            self.redo_stack.clear();
+            self.undo_stack.push_back(ToggleHistoryEntry { files });
+            if self.undo_stack.len() > MAX_UNDO_HISTORY_LENGTH {
+                self.undo_stack.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    /// The toggled state of each of the changes in the given file, in order.
+    fn file_toggles(&self, file_key: FileKey) -> Result<Vec<bool>, RecordError> {
+        let file = self.file(file_key)?;
+        let toggles = file
+            .sections
+            .iter()
+            .flat_map(|section| match section {
+                Section::Unchanged { .. } => Vec::new(),
+                Section::Changed { lines } => lines.iter().map(|line| line.is_toggled).collect(),
+                Section::FileMode {
+                    is_toggled,
+                    before: _,
+                    after: _,
+                }
+                | Section::Binary {
+                    is_toggled,
+                    old_description: _,
+                    new_description: _,
+                } => vec![*is_toggled],
+            })
+            .collect();
+        Ok(toggles)
+    }
+
+    /// Set the toggled state of each of the changes in the given file, as
+    /// returned by `file_toggles`.
+    fn set_file_toggles(&mut self, file_key: FileKey, toggles: &[bool]) -> Result<(), RecordError> {
+        self.visit_file(file_key, |file| {
+            let mut toggles = toggles.iter();
+            for section in file.sections.iter_mut() {
+                match section {
+                    Section::Unchanged { .. } => {}
+                    Section::Changed { lines } => {
+                        for line in lines {
+                            if let Some(is_toggled) = toggles.next() {
+                                line.is_toggled = *is_toggled;
+                            }
+                        }
+                    }
+                    Section::FileMode {
+                        is_toggled,
+                        before: _,
+                        after: _,
+                    }
+                    | Section::Binary {
+                        is_toggled,
+                        old_description: _,
+                        new_description: _,
+                    } => {
+                        if let Some(is_toggled_new) = toggles.next() {
+                            *is_toggled = *is_toggled_new;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    fn set_file_toggled(
+        &mut self,
+        file_key: FileKey,
+        is_toggled_new: bool,
+    ) -> Result<(), RecordError> {
+        self.visit_file(file_key, |file| {
+            for section in file.sections.iter_mut() {
+                match section {
+                    Section::Unchanged { .. } => {}
+                    Section::Changed { lines } => {
+                        for line in lines {
+                            line.is_toggled = is_toggled_new;
+                        }
+                    }
+                    Section::FileMode {
+                        is_toggled,
+                        before: _,
+                        after: _,
+                    }
+                    | Section::Binary {
+                        is_toggled,
+                        old_description: _,
+                        new_description: _,
+                    } => {
+                        *is_toggled = is_toggled_new;
+                    }
+                }
+            }
+        })
+    }
+
     fn toggle_item(&mut self, selection: SelectionKey) -> Result<(), RecordError> {
         match selection {
             SelectionKey::None => {}
@@ -980,25 +1745,7 @@ impl<'a> Recorder<'a> {
                     Tristate::Unchecked => true,
                     Tristate::Partial | Tristate::Checked => false,
                 };
-                self.visit_file(file_key, |file| {
-                    for section in file.sections.iter_mut() {
-                        match section {
-                            Section::Unchanged { .. } => {}
-                            Section::Changed { lines } => {
-                                for line in lines {
-                                    line.is_toggled = is_toggled_new;
-                                }
-                            }
-                            Section::FileMode {
-                                is_toggled,
-                                before: _,
-                                after: _,
-                            } => {
-                                *is_toggled = is_toggled_new;
-                            }
-                        }
-                    }
-                })?;
+                self.set_file_toggled(file_key, is_toggled_new)?;
             }
             SelectionKey::Section(section_key) => {
                 let tristate = self.section_tristate(section_key)?;
@@ -1017,20 +1764,40 @@ impl<'a> Recorder<'a> {
                         is_toggled,
                         before: _,
                         after: _,
+                    }
+                    | Section::Binary {
+                        is_toggled,
+                        old_description: _,
+                        new_description: _,
                     } => {
                         *is_toggled = is_focused_new;
                     }
                 })?;
             }
             SelectionKey::Line(line_key) => {
-                self.visit_line(line_key, |line| {
-                    line.is_toggled = !line.is_toggled;
+                self.visit_line_is_toggled(line_key, |is_toggled| {
+                    *is_toggled = !*is_toggled;
                 })?;
             }
         }
         Ok(())
     }
 
+    /// Toggle all of the given lines together. If they're all already
+    /// toggled, then they're all untoggled; otherwise, they're all toggled.
+    fn toggle_range(&mut self, line_keys: &[LineKey]) -> Result<(), RecordError> {
+        let mut is_all_toggled = true;
+        for line_key in line_keys {
+            is_all_toggled &= self.visit_line_is_toggled(*line_key, |is_toggled| *is_toggled)?;
+        }
+        for line_key in line_keys {
+            self.visit_line_is_toggled(*line_key, |is_toggled| {
+                *is_toggled = !is_all_toggled;
+            })?;
+        }
+        Ok(())
+    }
+
     fn file(&self, file_key: FileKey) -> Result<&File, RecordError> {
         let FileKey { file_idx } = file_key;
         match self.state.files.get(file_idx) {
@@ -1090,6 +1857,11 @@ impl<'a> Recorder<'a> {
                     is_toggled,
                     before: _,
                     after: _,
+                }
+                | Section::Binary {
+                    is_toggled,
+                    old_description: _,
+                    new_description: _,
                 } => {
                     seen_value = match (seen_value, is_toggled) {
                         (None, is_focused) => Some(*is_focused),
@@ -1132,6 +1904,45 @@ impl<'a> Recorder<'a> {
         }
     }
 
+    /// Summarize the changes to the file, for display when it's collapsed.
+    fn file_summary(&self, file_key: FileKey) -> Result<String, RecordError> {
+        let file = &self.state.files[file_key.file_idx];
+        let mut num_added = 0;
+        let mut num_removed = 0;
+        let mut num_sections = 0;
+        let mut num_selected_sections = 0;
+        for (section_idx, section) in file.sections.iter().enumerate() {
+            if !section.is_editable() {
+                continue;
+            }
+            num_sections += 1;
+            let section_key = SectionKey {
+                file_idx: file_key.file_idx,
+                section_idx,
+            };
+            match self.section_tristate(section_key)? {
+                Tristate::Unchecked => {}
+                Tristate::Partial | Tristate::Checked => num_selected_sections += 1,
+            }
+            if let Section::Changed { lines } = section {
+                for line in lines {
+                    match line.change_type {
+                        ChangeType::Added => num_added += 1,
+                        ChangeType::Removed => num_removed += 1,
+                    }
+                }
+            }
+        }
+        Ok(format!(
+            "+{num_added} -{num_removed}, {num_selected_sections}/{num_sections} {} selected",
+            if num_sections == 1 {
+                "section"
+            } else {
+                "sections"
+            }
+        ))
+    }
+
     fn section_tristate(&self, section_key: SectionKey) -> Result<Tristate, RecordError> {
         let mut seen_value = None;
         match self.section(section_key)? {
@@ -1150,6 +1961,11 @@ impl<'a> Recorder<'a> {
                 is_toggled,
                 before: _,
                 after: _,
+            }
+            | Section::Binary {
+                is_toggled,
+                old_description: _,
+                new_description: _,
             } => {
                 seen_value = match (seen_value, is_toggled) {
                     (None, is_toggled) => Some(*is_toggled),
@@ -1166,10 +1982,12 @@ impl<'a> Recorder<'a> {
         Ok(result)
     }
 
-    fn visit_line<T>(
+    /// Visit whether the given line is toggled. The file mode change of a
+    /// [`Section::FileMode`] is treated as its only line.
+    fn visit_line_is_toggled<T>(
         &mut self,
         line_key: LineKey,
-        f: impl FnOnce(&mut SectionChangedLine) -> T,
+        f: impl FnOnce(&mut bool) -> T,
     ) -> Result<T, RecordError> {
         let LineKey {
             file_idx,
@@ -1180,14 +1998,21 @@ impl<'a> Recorder<'a> {
         match section {
             Section::Changed { lines } => {
                 let line = &mut lines[line_idx];
-                Ok(f(line))
+                Ok(f(&mut line.is_toggled))
             }
-            section @ (Section::Unchanged { lines: _ }
-            | Section::FileMode {
-                is_toggled: _,
+            Section::FileMode {
+                is_toggled,
                 before: _,
                 after: _,
-            }) => Err(RecordError::Bug(format!(
+            }
+            | Section::Binary {
+                is_toggled,
+                old_description: _,
+                new_description: _,
+            } if line_idx == 0 => Ok(f(is_toggled)),
+            section @ (Section::Unchanged { lines: _ }
+            | Section::FileMode { .. }
+            | Section::Binary { .. }) => Err(RecordError::Bug(format!(
                 "Bad line key {line_key:?}, tried to index section {section:?}"
             ))),
         }
@@ -1201,6 +2026,7 @@ enum ComponentId {
     TristateBox,
     QuitDialog,
     QuitDialogButton(QuitDialogButtonId),
+    SearchPrompt,
 }
 
 #[derive(Clone, Debug)]
@@ -1267,6 +2093,7 @@ impl<Id: Clone + Debug + Eq + Hash> Component for TristateBox<Id> {
 #[derive(Clone, Debug)]
 struct AppDebugInfo {
     term_height: usize,
+    scroll_offset_x: isize,
     scroll_offset_y: isize,
     selection_key: SelectionKey,
     selection_key_y: isize,
@@ -1279,6 +2106,7 @@ struct App<'a> {
     debug_info: Option<AppDebugInfo>,
     file_views: Vec<FileView<'a>>,
     quit_dialog: Option<QuitDialog>,
+    search_prompt: Option<SearchPrompt>,
 }
 
 impl App<'_> {
@@ -1287,6 +2115,7 @@ impl App<'_> {
             debug_info: _,
             file_views,
             quit_dialog: _,
+            search_prompt: _,
         } = self;
         file_views.iter().map(|file_view| file_view.height()).sum()
     }
@@ -1304,6 +2133,7 @@ impl Component for App<'_> {
             debug_info,
             file_views,
             quit_dialog,
+            search_prompt,
         } = self;
 
         if let Some(debug_info) = debug_info {
@@ -1326,6 +2156,9 @@ impl Component for App<'_> {
         if let Some(quit_dialog) = quit_dialog {
             viewport.draw_component(0, 0, quit_dialog);
         }
+        if let Some(search_prompt) = search_prompt {
+            viewport.draw_component(0, 0, search_prompt);
+        }
     }
 }
 
@@ -1336,6 +2169,7 @@ struct FileView<'a> {
     tristate_box: TristateBox<ComponentId>,
     is_header_selected: bool,
     path: &'a Path,
+    collapsed_summary: Option<String>,
     section_views: Vec<SectionView<'a>>,
 }
 
@@ -1362,12 +2196,13 @@ impl Component for FileView<'_> {
             file_key: _,
             tristate_box,
             path,
+            collapsed_summary,
             section_views,
             is_header_selected,
         } = self;
 
         let tristate_box_rect = viewport.draw_component(x, y, tristate_box);
-        viewport.draw_span(
+        let path_rect = viewport.draw_span(
             x + tristate_box_rect.width.unwrap_isize() + 1,
             y,
             &Span::styled(
@@ -1379,6 +2214,16 @@ impl Component for FileView<'_> {
                 },
             ),
         );
+        if let Some(collapsed_summary) = collapsed_summary {
+            viewport.draw_span(
+                path_rect.x + path_rect.width.unwrap_isize() + 1,
+                y,
+                &Span::styled(
+                    format!("({collapsed_summary})"),
+                    Style::default().add_modifier(Modifier::DIM),
+                ),
+            );
+        }
         if *is_header_selected {
             highlight_line(viewport, y);
         }
@@ -1405,13 +2250,26 @@ enum SectionSelection {
 #[derive(Clone, Debug)]
 struct SectionView<'a> {
     use_unicode: bool,
+    highlighted_lines: &'a HashMap<LineKey, Vec<StyledSegment>>,
     section_key: SectionKey,
+
+    /// The lines of this section which are in the pending visual mode range,
+    /// if any.
+    visual_range: Option<RangeInclusive<usize>>,
     tristate_box: TristateBox<ComponentId>,
     selection: Option<SectionSelection>,
     section_num: usize,
     total_num_sections: usize,
     section: &'a Section<'a>,
+    show_line_numbers: bool,
+
+    /// The line number of the first line of this section in the old version
+    /// of the file.
     line_start_num: usize,
+
+    /// The line number of the first line of this section in the new version
+    /// of the file.
+    new_line_start_num: usize,
 }
 
 impl SectionView<'_> {
@@ -1421,7 +2279,7 @@ impl SectionView<'_> {
             + match self.section {
                 Section::Unchanged { lines } => lines.len(),
                 Section::Changed { lines } => lines.len(),
-                Section::FileMode { .. } => 1,
+                Section::FileMode { .. } | Section::Binary { .. } => 1,
             }
     }
 }
@@ -1436,13 +2294,17 @@ impl Component for SectionView<'_> {
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
         let Self {
             use_unicode,
+            highlighted_lines,
             section_key,
+            visual_range,
             tristate_box,
             selection,
             section_num,
             total_num_sections,
             section,
+            show_line_numbers,
             line_start_num,
+            new_line_start_num,
         } = self;
 
         let y = if !section.is_editable() {
@@ -1494,6 +2356,10 @@ impl Component for SectionView<'_> {
                             section_idx,
                             line_idx: *line_idx,
                         },
+                        line_numbers: show_line_numbers.then_some(LineNumbers {
+                            old: Some(line_start_num + line_idx),
+                            new: Some(new_line_start_num + line_idx),
+                        }),
                         inner: SectionLineViewInner::Unchanged {
                             line: line.as_ref(),
                             line_num: line_start_num + line_idx,
@@ -1523,6 +2389,10 @@ impl Component for SectionView<'_> {
                                 section_idx,
                                 line_idx: *line_idx,
                             },
+                            line_numbers: show_line_numbers.then_some(LineNumbers {
+                                old: Some(line_start_num + line_idx),
+                                new: Some(new_line_start_num + line_idx),
+                            }),
                             inner: SectionLineViewInner::Unchanged {
                                 line: line.as_ref(),
                                 line_num: line_start_num + line_idx,
@@ -1535,12 +2405,32 @@ impl Component for SectionView<'_> {
             }
 
             Section::Changed { lines } => {
+                let mut old_line_num = *line_start_num;
+                let mut new_line_num = *new_line_start_num;
                 for (line_idx, line) in lines.iter().enumerate() {
                     let SectionChangedLine {
                         is_toggled,
                         change_type,
                         line,
                     } = line;
+                    // Only one side of the diff has a line number for a
+                    // changed line.
+                    let line_numbers = match change_type {
+                        ChangeType::Added => {
+                            new_line_num += 1;
+                            LineNumbers {
+                                old: None,
+                                new: Some(new_line_num - 1),
+                            }
+                        }
+                        ChangeType::Removed => {
+                            old_line_num += 1;
+                            LineNumbers {
+                                old: Some(old_line_num - 1),
+                                new: None,
+                            }
+                        }
+                    };
                     let is_focused = match selection {
                         Some(SectionSelection::Line(selected_line_idx)) => {
                             line_idx == *selected_line_idx
@@ -1553,36 +2443,99 @@ impl Component for SectionView<'_> {
                         tristate: Tristate::from(*is_toggled),
                         is_focused,
                     };
+                    let line_key = LineKey {
+                        file_idx,
+                        section_idx,
+                        line_idx,
+                    };
                     let line_view = SectionLineView {
-                        line_key: LineKey {
-                            file_idx,
-                            section_idx,
-                            line_idx,
-                        },
+                        line_key,
+                        line_numbers: show_line_numbers.then_some(line_numbers),
                         inner: SectionLineViewInner::Changed {
                             tristate_box,
                             change_type: *change_type,
                             line: line.as_ref(),
+                            highlighted_segments: highlighted_lines
+                                .get(&line_key)
+                                .map(|segments| segments.as_slice()),
                         },
                     };
                     let y = y + line_idx.unwrap_isize();
                     viewport.draw_component(x + 2, y, &line_view);
+                    if matches!(visual_range, Some(visual_range) if visual_range.contains(&line_idx))
+                    {
+                        highlight_visual_range_line(viewport, y);
+                    }
                     if is_focused {
                         highlight_line(viewport, y);
                     }
                 }
             }
 
-            Section::FileMode { .. } => {
+            Section::FileMode {
+                is_toggled,
+                before,
+                after,
+            } => {
+                let is_focused = match selection {
+                    Some(SectionSelection::Line(selected_line_idx)) => *selected_line_idx == 0,
+                    Some(SectionSelection::Header) | None => false,
+                };
+                let line_view = SectionLineView {
+                    line_key: LineKey {
+                        file_idx,
+                        section_idx,
+                        line_idx: 0,
+                    },
+                    line_numbers: None,
+                    inner: SectionLineViewInner::FileMode {
+                        tristate_box: TristateBox {
+                            use_unicode: *use_unicode,
+                            id: ComponentId::TristateBox,
+                            tristate: Tristate::from(*is_toggled),
+                            is_focused,
+                        },
+                        before: *before,
+                        after: *after,
+                    },
+                };
+                viewport.draw_component(x + 2, y, &line_view);
+                if is_focused {
+                    highlight_line(viewport, y);
+                }
+            }
+
+            Section::Binary {
+                is_toggled,
+                old_description,
+                new_description,
+            } => {
+                let is_focused = match selection {
+                    Some(SectionSelection::Line(selected_line_idx)) => *selected_line_idx == 0,
+                    Some(SectionSelection::Header) | None => false,
+                };
                 let line_view = SectionLineView {
                     line_key: LineKey {
                         file_idx,
                         section_idx,
                         line_idx: 0,
                     },
-                    inner: SectionLineViewInner::FileMode,
+                    line_numbers: None,
+                    inner: SectionLineViewInner::Binary {
+                        tristate_box: TristateBox {
+                            use_unicode: *use_unicode,
+                            id: ComponentId::TristateBox,
+                            tristate: Tristate::from(*is_toggled),
+                            is_focused,
+                        },
+                        old_description: old_description.as_deref(),
+                        new_description: new_description.as_deref(),
+                    },
                 };
                 viewport.draw_component(x + 2, y, &line_view);
+                if is_focused {
+                    highlight_line(viewport, y);
+                }
             }
         }
     }
@@ -1598,13 +2551,46 @@ enum SectionLineViewInner<'a> {
         tristate_box: TristateBox<ComponentId>,
         change_type: ChangeType,
         line: &'a str,
+        highlighted_segments: Option<&'a [StyledSegment]>,
+    },
+    FileMode {
+        tristate_box: TristateBox<ComponentId>,
+        before: FileMode,
+        after: FileMode,
     },
-    FileMode,
+    Binary {
+        tristate_box: TristateBox<ComponentId>,
+        old_description: Option<&'a str>,
+        new_description: Option<&'a str>,
+    },
+}
+
+/// The line numbers of a line in the old and new versions of the file, as
+/// shown in the gutter.
+#[derive(Clone, Copy, Debug)]
+struct LineNumbers {
+    old: Option<usize>,
+    new: Option<usize>,
+}
+
+impl LineNumbers {
+    fn span(&self) -> Span<'static> {
+        let Self { old, new } = self;
+        let format_line_num = |line_num: &Option<usize>| match line_num {
+            Some(line_num) => format!("{line_num:5}"),
+            None => " ".repeat(5),
+        };
+        Span::styled(
+            format!("{} {} ", format_line_num(old), format_line_num(new)),
+            Style::default().add_modifier(Modifier::DIM),
+        )
+    }
 }
 
 #[derive(Clone, Debug)]
 struct SectionLineView<'a> {
     line_key: LineKey,
+    line_numbers: Option<LineNumbers>,
     inner: SectionLineViewInner<'a>,
 }
 
@@ -1616,8 +2602,25 @@ impl Component for SectionLineView<'_> {
     }
 
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
-        let Self { line_key: _, inner } = self;
+        let Self {
+            line_key: _,
+            line_numbers,
+            inner,
+        } = self;
+        let x = match line_numbers {
+            Some(line_numbers) => {
+                let rect = viewport.draw_span(x, y, &line_numbers.span());
+                x + rect.width.unwrap_isize()
+            }
+            None => x,
+        };
         match inner {
+            SectionLineViewInner::Unchanged { line, line_num: _ } if line_numbers.is_some() => {
+                // The line numbers are already in the gutter, so just align
+                // the text with that of the changed lines.
+                let style = Style::default().add_modifier(Modifier::DIM);
+                viewport.draw_span(x + 6, y, &Span::styled(*line, style));
+            }
             SectionLineViewInner::Unchanged { line, line_num } => {
                 let style = Style::default().add_modifier(Modifier::DIM);
                 // Pad the number in 5 columns because that will align the
@@ -1637,20 +2640,74 @@ impl Component for SectionLineView<'_> {
                 tristate_box,
                 change_type,
                 line,
+                highlighted_segments,
             } => {
                 let tristate_rect = viewport.draw_component(x, y, tristate_box);
                 let x = x + tristate_rect.width.unwrap_isize() + 1;
 
-                let (change_type_text, style) = match change_type {
-                    ChangeType::Added => ("+ ", Style::default().fg(Color::Green)),
-                    ChangeType::Removed => ("- ", Style::default().fg(Color::Red)),
+                let change_type_text = match change_type {
+                    ChangeType::Added => "+ ",
+                    ChangeType::Removed => "- ",
                 };
+                let style = changed_line_style(*change_type);
                 viewport.draw_span(x, y, &Span::styled(change_type_text, style));
                 let x = x + change_type_text.width().unwrap_isize();
-                viewport.draw_span(x, y, &Span::styled(*line, style));
+                match highlighted_segments {
+                    Some(segments) => {
+                        let mut x = x;
+                        for (segment_style, text) in segments.iter() {
+                            let rect = viewport.draw_span(
+                                x,
+                                y,
+                                &Span::styled(text.as_str(), *segment_style),
+                            );
+                            x += rect.width.unwrap_isize();
+                        }
+                    }
+                    None => {
+                        viewport.draw_span(x, y, &Span::styled(*line, style));
+                    }
+                }
             }
 
-            SectionLineViewInner::FileMode => unimplemented!("rendering file mode section"),
+            SectionLineViewInner::FileMode {
+                tristate_box,
+                before,
+                after,
+            } => {
+                let tristate_rect = viewport.draw_component(x, y, tristate_box);
+                let x = x + tristate_rect.width.unwrap_isize() + 1;
+                viewport.draw_span(
+                    x,
+                    y,
+                    &Span::styled(
+                        format!("File mode changed from {before:o} to {after:o}"),
+                        Style::default().fg(Color::Blue),
+                    ),
+                );
+            }
+
+            SectionLineViewInner::Binary {
+                tristate_box,
+                old_description,
+                new_description,
+            } => {
+                let tristate_rect = viewport.draw_component(x, y, tristate_box);
+                let x = x + tristate_rect.width.unwrap_isize() + 1;
+                let text = match (old_description, new_description) {
+                    (Some(old_description), Some(new_description)) => {
+                        format!("Binary file changed ({old_description} -> {new_description})")
+                    }
+                    (None, Some(new_description)) => {
+                        format!("Binary file added ({new_description})")
+                    }
+                    (Some(old_description), None) => {
+                        format!("Binary file removed ({old_description})")
+                    }
+                    (None, None) => "Binary file changed".to_string(),
+                };
+                viewport.draw_span(x, y, &Span::styled(text, Style::default().fg(Color::Blue)));
+            }
         }
     }
 }
@@ -1711,6 +2768,35 @@ impl Component for QuitDialog {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SearchPrompt {
+    query: String,
+}
+
+impl Component for SearchPrompt {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::SearchPrompt
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
+        let Self { query } = self;
+
+        // Draw the prompt over the bottom line of the viewport.
+        let viewport_rect = viewport.rect();
+        let rect = Rect {
+            x: viewport_rect.x,
+            y: viewport_rect.y + viewport_rect.height.unwrap_isize() - 1,
+            width: viewport_rect.width,
+            height: 1,
+        };
+        let tui_rect = viewport.translate_rect(rect);
+        viewport.draw_widget(tui_rect, Clear);
+        viewport.draw_span(rect.x, rect.y, &Span::raw(format!("/{query}")));
+    }
+}
+
 struct Button<'a, Id> {
     id: Id,
     label: Cow<'a, str>,
@@ -1809,10 +2895,89 @@ impl<Id: Clone + Debug + Eq + Hash> Component for Dialog<'_, Id> {
     }
 }
 
+/// Calculate the syntax highlighting for each changed line of the given file,
+/// so that it doesn't have to be recalculated on every render.
+fn highlight_file_lines(
+    highlighter: &Highlighter,
+    file_idx: usize,
+    file: &File,
+) -> HashMap<LineKey, Vec<StyledSegment>> {
+    // Highlight all of the lines of the file together, so that constructs
+    // spanning multiple lines are handled. The removed and added lines of a
+    // changed section are both included, since they're displayed in that
+    // order, although this means that the highlighting state may not match
+    // either version of the file exactly.
+    let all_lines = file.sections.iter().flat_map(|section| match section {
+        Section::Unchanged { lines } => lines.iter().map(|line| line.as_ref()).collect(),
+        Section::Changed { lines } => lines.iter().map(|line| line.line.as_ref()).collect(),
+        Section::FileMode { .. } | Section::Binary { .. } => Vec::new(),
+    });
+    let mut file_segments = highlighter
+        .highlight_lines(&file.path, all_lines)
+        .map(|segments| segments.into_iter());
+
+    let mut result = HashMap::new();
+    for (section_idx, section) in file.sections.iter().enumerate() {
+        let lines = match section {
+            Section::Changed { lines } => lines,
+            Section::Unchanged { lines } => {
+                // The unchanged lines are only highlighted for the sake of the
+                // parsing state, so skip over their results.
+                if let Some(file_segments) = file_segments.as_mut() {
+                    file_segments.by_ref().take(lines.len()).for_each(drop);
+                }
+                continue;
+            }
+            Section::FileMode { .. } | Section::Binary { .. } => continue,
+        };
+
+        let mut emphasized_ranges = HashMap::new();
+        for (old_line_idx, new_line_idx) in pair_changed_lines(lines) {
+            if let Some((old_ranges, new_ranges)) =
+                diff_words(&lines[old_line_idx].line, &lines[new_line_idx].line)
+            {
+                emphasized_ranges.insert(old_line_idx, old_ranges);
+                emphasized_ranges.insert(new_line_idx, new_ranges);
+            }
+        }
+
+        for (line_idx, line) in lines.iter().enumerate() {
+            let segments = file_segments.as_mut().and_then(|segments| segments.next());
+            let segments = match emphasized_ranges.get(&line_idx) {
+                Some(ranges) if !ranges.is_empty() => {
+                    let segments = segments.unwrap_or_else(|| {
+                        vec![(changed_line_style(line.change_type), line.line.to_string())]
+                    });
+                    Some(emphasize_segments(segments, ranges))
+                }
+                _ => segments,
+            };
+            if let Some(segments) = segments {
+                result.insert(
+                    LineKey {
+                        file_idx,
+                        section_idx,
+                        line_idx,
+                    },
+                    segments,
+                );
+            }
+        }
+    }
+    result
+}
+
+fn changed_line_style(change_type: ChangeType) -> Style {
+    match change_type {
+        ChangeType::Added => Style::default().fg(Color::Green),
+        ChangeType::Removed => Style::default().fg(Color::Red),
+    }
+}
+
 fn highlight_line<Id: Clone + Debug + Eq + Hash>(viewport: &mut Viewport<Id>, y: isize) {
     viewport.set_style(
         Rect {
-            x: 0,
+            x: viewport.rect().x,
             y,
             width: viewport.size().width,
             height: 1,
@@ -1821,26 +2986,68 @@ fn highlight_line<Id: Clone + Debug + Eq + Hash>(viewport: &mut Viewport<Id>, y:
     );
 }
 
+/// The indexes of the lines in the given section which are part of the visual
+/// mode range, if any.
+fn section_visual_range(
+    visual_range: &[LineKey],
+    section_key: SectionKey,
+) -> Option<RangeInclusive<usize>> {
+    let SectionKey {
+        file_idx,
+        section_idx,
+    } = section_key;
+    let mut line_idxs = visual_range
+        .iter()
+        .filter(|line_key| line_key.file_idx == file_idx && line_key.section_idx == section_idx)
+        .map(|line_key| line_key.line_idx);
+    let first = line_idxs.next()?;
+    let last = line_idxs.next_back().unwrap_or(first);
+    Some(first..=last)
+}
+
+fn highlight_visual_range_line<Id: Clone + Debug + Eq + Hash>(
+    viewport: &mut Viewport<Id>,
+    y: isize,
+) {
+    viewport.set_style(
+        Rect {
+            x: viewport.rect().x,
+            y,
+            width: viewport.size().width,
+            height: 1,
+        },
+        Style::default().bg(Color::DarkGray),
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
 
     use super::*;
+    use crate::KeyBinding;
 
     use assert_matches::assert_matches;
 
     #[test]
     fn test_event_source_testing() {
         let mut event_source = EventSource::testing(80, 24, [Event::QuitCancel]);
-        assert_matches!(event_source.next_event(), Ok(Event::QuitCancel));
-        assert_matches!(event_source.next_event(), Ok(Event::None));
+        let key_bindings = KeyBindings::default();
+        assert_matches!(
+            event_source.next_event(false, &key_bindings),
+            Ok(Event::QuitCancel)
+        );
+        assert_matches!(
+            event_source.next_event(false, &key_bindings),
+            Ok(Event::None)
+        );
     }
 
     #[test]
     fn test_quit_returns_error() {
         let state = RecordState::default();
         let event_source = EventSource::testing(80, 24, [Event::QuitCancel]);
-        let recorder = Recorder::new(state, event_source);
+        let recorder = Recorder::new(state, event_source, KeyBindings::default());
         assert_matches!(recorder.run(), Err(RecordError::Cancelled));
 
         let state = RecordState {
@@ -1851,7 +3058,49 @@ mod tests {
             }],
         };
         let event_source = EventSource::testing(80, 24, [Event::QuitAccept]);
-        let recorder = Recorder::new(state.clone(), event_source);
+        let recorder = Recorder::new(state.clone(), event_source, KeyBindings::default());
         assert_eq!(recorder.run().unwrap(), state);
     }
+
+    #[test]
+    fn test_key_bindings() {
+        let key_event =
+            |code, modifiers| crossterm::event::Event::Key(KeyEvent::new(code, modifiers));
+        let key_bindings = KeyBindings {
+            focus_next: vec![
+                KeyBinding::new(KeyCode::Char('j'), KeyModifiers::NONE),
+                KeyBinding::new(KeyCode::Char('n'), KeyModifiers::CONTROL),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            Event::from_crossterm(
+                key_event(KeyCode::Char('j'), KeyModifiers::NONE),
+                &key_bindings
+            ),
+            Event::FocusNext
+        );
+        assert_eq!(
+            Event::from_crossterm(
+                key_event(KeyCode::Char('n'), KeyModifiers::CONTROL),
+                &key_bindings
+            ),
+            Event::FocusNext
+        );
+        assert_eq!(
+            Event::from_crossterm(key_event(KeyCode::Down, KeyModifiers::NONE), &key_bindings),
+            Event::None
+        );
+        assert_eq!(
+            Event::from_crossterm(key_event(KeyCode::Up, KeyModifiers::NONE), &key_bindings),
+            Event::FocusPrev
+        );
+        assert_eq!(
+            Event::from_crossterm(
+                key_event(KeyCode::Char('A'), KeyModifiers::SHIFT),
+                &key_bindings
+            ),
+            Event::DeselectAllInFile
+        );
+    }
 }
@@ -2,8 +2,8 @@ use std::{borrow::Cow, path::Path};
 
 use assert_matches::assert_matches;
 use scm_record::{
-    ChangeType, Event, EventSource, File, RecordError, RecordState, Recorder, Section,
-    SectionChangedLine, TestingScreenshot,
+    ChangeType, Event, EventSource, File, KeyBindings, RecordError, RecordState, Recorder, Section,
+    SectionChangedLine, SelectedContents, TestingScreenshot,
 };
 
 fn example_contents() -> RecordState<'static> {
@@ -116,7 +116,7 @@ fn test_select_scroll_into_view() -> eyre::Result<()> {
         ],
     );
     let state = example_contents();
-    let recorder = Recorder::new(state, event_source);
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
     recorder.run()?;
     insta::assert_display_snapshot!(screenshot1, @r###"
     "(~) foo/bar                                                                     "
@@ -145,6 +145,52 @@ fn test_select_scroll_into_view() -> eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_scroll_horizontally() -> eyre::Result<()> {
+    let scrolled_once = TestingScreenshot::default();
+    let scrolled_to_end = TestingScreenshot::default();
+    let scrolled_back = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        20,
+        4,
+        [
+            Event::ScrollRight,
+            scrolled_once.event(),
+            // Scrolling should stop once the end of the longest visible line
+            // is on-screen.
+            Event::ScrollRight,
+            Event::ScrollRight,
+            scrolled_to_end.event(),
+            Event::ScrollLeft,
+            Event::ScrollLeft,
+            scrolled_back.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let state = example_contents();
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    recorder.run()?;
+    insta::assert_display_snapshot!(scrolled_once, @r###"
+    "foo/bar             "
+    "    1 this is some t"
+    "    2 this is some t"
+    "    3 this is some t"
+    "###);
+    insta::assert_display_snapshot!(scrolled_to_end, @r###"
+    "/bar                "
+    " 1 this is some text"
+    " 2 this is some text"
+    " 3 this is some text"
+    "###);
+    insta::assert_display_snapshot!(scrolled_back, @r###"
+    "(~) foo/bar         "
+    "        1 this is so"
+    "        2 this is so"
+    "        3 this is so"
+    "###);
+    Ok(())
+}
+
 #[test]
 fn test_quit_dialog_size() -> eyre::Result<()> {
     let expect_quit_dialog_to_be_centered = TestingScreenshot::default();
@@ -158,7 +204,7 @@ fn test_quit_dialog_size() -> eyre::Result<()> {
         ],
     );
     let state = example_contents();
-    let recorder = Recorder::new(state, event_source);
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
     let result = recorder.run();
     assert_matches!(result, Err(RecordError::Cancelled));
     insta::assert_display_snapshot!(expect_quit_dialog_to_be_centered, @r###"
@@ -235,7 +281,7 @@ fn test_quit_dialog_keyboard_navigation() -> eyre::Result<()> {
         ],
     );
     let state = example_contents();
-    let recorder = Recorder::new(state, event_source);
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
     assert_matches!(recorder.run(), Err(RecordError::Cancelled));
     insta::assert_display_snapshot!(expect_q_opens_quit_dialog, @r###"
     "(~) foo/bar                                                                     "
@@ -311,7 +357,7 @@ fn test_quit_dialog_buttons() -> eyre::Result<()> {
         ],
     );
     let state = example_contents();
-    let recorder = Recorder::new(state, event_source);
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
     assert_matches!(recorder.run(), Err(RecordError::Cancelled));
     insta::assert_display_snapshot!(expect_quit_button_focused_initially, @r###"
     "(~) foo/bar                                                                     "
@@ -365,6 +411,472 @@ fn test_quit_dialog_buttons() -> eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_search() -> eyre::Result<()> {
+    let prompt_screenshot = TestingScreenshot::default();
+    let first_match_screenshot = TestingScreenshot::default();
+    let second_match_screenshot = TestingScreenshot::default();
+    let prev_match_screenshot = TestingScreenshot::default();
+    let mut events = vec![Event::StartSearch];
+    events.extend("after text 2".chars().map(Event::SearchInput));
+    events.extend([
+        prompt_screenshot.event(),
+        Event::SearchAccept,
+        first_match_screenshot.event(),
+        Event::SearchNext,
+        second_match_screenshot.event(),
+        Event::SearchPrev,
+        prev_match_screenshot.event(),
+        Event::QuitAccept,
+    ]);
+    let event_source = EventSource::testing(80, 6, events);
+    let state = example_contents();
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    recorder.run()?;
+    insta::assert_display_snapshot!(prompt_screenshot, @r###"
+    "(~) foo/bar                                                                     "
+    "        1 this is some text                                                     "
+    "        2 this is some text                                                     "
+    "        3 this is some text                                                     "
+    "        ⋮                                                                       "
+    "/after text 2                                                                   "
+    "###);
+    insta::assert_display_snapshot!(first_match_screenshot, @r###"
+    "       20 this is some text                                                     "
+    "  [~] Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "    [×] - before text 2                                                         "
+    "    [×] + after text 1                                                          "
+    "    ( ) + after text 2                                                          "
+    "###);
+    insta::assert_display_snapshot!(second_match_screenshot, @r###"
+    "        2 Some leading text 2                                                   "
+    "  [×] Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "    [×] - before text 2                                                         "
+    "    [×] + after text 1                                                          "
+    "    (×) + after text 2                                                          "
+    "###);
+    insta::assert_display_snapshot!(prev_match_screenshot, @r###"
+    "    ( ) + after text 2                                                          "
+    "       23 this is some trailing text                                            "
+    "[×] baz                                                                         "
+    "        1 Some leading text 1                                                   "
+    "        2 Some leading text 2                                                   "
+    "  [×] Section 1/1                                                               "
+    "###);
+    Ok(())
+}
+
+#[test]
+fn test_collapse_files() -> eyre::Result<()> {
+    let first_file_collapsed = TestingScreenshot::default();
+    let both_files_collapsed = TestingScreenshot::default();
+    let second_file_expanded = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        80,
+        6,
+        [
+            Event::ToggleExpand,
+            first_file_collapsed.event(),
+            // The sections of the collapsed file should be skipped.
+            Event::FocusNext,
+            Event::ToggleExpand,
+            both_files_collapsed.event(),
+            Event::ToggleExpand,
+            second_file_expanded.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let state = example_contents();
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    recorder.run()?;
+    insta::assert_display_snapshot!(first_file_collapsed, @r###"
+    "(~) foo/bar (+2 -2, 1/1 section selected)                                       "
+    "[×] baz                                                                         "
+    "        1 Some leading text 1                                                   "
+    "        2 Some leading text 2                                                   "
+    "  [×] Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "###);
+    insta::assert_display_snapshot!(both_files_collapsed, @r###"
+    "(×) baz (+2 -2, 1/1 section selected)                                           "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "###);
+    insta::assert_display_snapshot!(second_file_expanded, @r###"
+    "(×) baz                                                                         "
+    "        1 Some leading text 1                                                   "
+    "        2 Some leading text 2                                                   "
+    "  [×] Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "    [×] - before text 2                                                         "
+    "###);
+    Ok(())
+}
+
+#[test]
+fn test_select_all_in_file() -> eyre::Result<()> {
+    let all_selected = TestingScreenshot::default();
+    let all_deselected = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        80,
+        6,
+        [
+            Event::FocusNext,
+            Event::SelectAllInFile,
+            all_selected.event(),
+            Event::DeselectAllInFile,
+            all_deselected.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let state = example_contents();
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    let state = recorder.run()?;
+    insta::assert_display_snapshot!(all_selected, @r###"
+    "       20 this is some text                                                     "
+    "  (×) Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "    [×] - before text 2                                                         "
+    "    [×] + after text 1                                                          "
+    "    [×] + after text 2                                                          "
+    "###);
+    insta::assert_display_snapshot!(all_deselected, @r###"
+    "       20 this is some text                                                     "
+    "  ( ) Section 1/1                                                               "
+    "    [ ] - before text 1                                                         "
+    "    [ ] - before text 2                                                         "
+    "    [ ] + after text 1                                                          "
+    "    [ ] + after text 2                                                          "
+    "###);
+
+    // Only the lines in the focused file should have been affected.
+    let is_toggled = |file: &File| -> Vec<bool> {
+        file.sections
+            .iter()
+            .flat_map(|section| match section {
+                Section::Changed { lines } => lines.iter().map(|line| line.is_toggled).collect(),
+                _ => Vec::new(),
+            })
+            .collect()
+    };
+    assert_eq!(
+        is_toggled(&state.files[0]),
+        vec![false, false, false, false]
+    );
+    assert_eq!(is_toggled(&state.files[1]), vec![true, true, true, true]);
+    Ok(())
+}
+
+#[test]
+fn test_toggle_line_numbers() -> eyre::Result<()> {
+    let line_numbers_shown = TestingScreenshot::default();
+    let line_numbers_hidden = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        80,
+        6,
+        [
+            Event::ToggleLineNumbers,
+            Event::FocusNext,
+            line_numbers_shown.event(),
+            Event::ToggleLineNumbers,
+            line_numbers_hidden.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let state = example_contents();
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    recorder.run()?;
+    insta::assert_display_snapshot!(line_numbers_shown, @r###"
+    "       20    20       this is some text                                         "
+    "  (~) Section 1/1                                                               "
+    "       21       [×] - before text 1                                             "
+    "       22       [×] - before text 2                                             "
+    "             21 [×] + after text 1                                              "
+    "             22 [ ] + after text 2                                              "
+    "###);
+    insta::assert_display_snapshot!(line_numbers_hidden, @r###"
+    "       20 this is some text                                                     "
+    "  (~) Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "    [×] - before text 2                                                         "
+    "    [×] + after text 1                                                          "
+    "    [ ] + after text 2                                                          "
+    "###);
+    Ok(())
+}
+
+#[test]
+fn test_undo_redo() -> eyre::Result<()> {
+    let undone = TestingScreenshot::default();
+    let redone = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        80,
+        6,
+        [
+            Event::ToggleItem,
+            Event::Undo,
+            undone.event(),
+            Event::Redo,
+            redone.event(),
+            Event::Undo,
+            // Toggling something new should discard the redo history.
+            Event::FocusNext,
+            Event::FocusNext,
+            Event::ToggleItem,
+            Event::Redo,
+            Event::QuitAccept,
+        ],
+    );
+    let state = example_contents();
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    let state = recorder.run()?;
+    insta::assert_display_snapshot!(undone, @r###"
+    "(~) foo/bar                                                                     "
+    "        1 this is some text                                                     "
+    "        2 this is some text                                                     "
+    "        3 this is some text                                                     "
+    "        ⋮                                                                       "
+    "       18 this is some text                                                     "
+    "###);
+    insta::assert_display_snapshot!(redone, @r###"
+    "( ) foo/bar                                                                     "
+    "        1 this is some text                                                     "
+    "        2 this is some text                                                     "
+    "        3 this is some text                                                     "
+    "        ⋮                                                                       "
+    "       18 this is some text                                                     "
+    "###);
+
+    let is_toggled = |file: &File| -> Vec<bool> {
+        file.sections
+            .iter()
+            .flat_map(|section| match section {
+                Section::Changed { lines } => lines.iter().map(|line| line.is_toggled).collect(),
+                _ => Vec::new(),
+            })
+            .collect()
+    };
+    assert_eq!(is_toggled(&state.files[0]), vec![false, true, true, false]);
+    assert_eq!(is_toggled(&state.files[1]), vec![true, true, true, true]);
+    Ok(())
+}
+
+#[test]
+fn test_visual_mode() -> eyre::Result<()> {
+    let screenshot = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        80,
+        6,
+        [
+            // Untoggle the first three lines, since they're all toggled.
+            Event::FocusNext,
+            Event::FocusNext,
+            Event::ToggleVisualMode,
+            Event::FocusNext,
+            Event::FocusNext,
+            Event::ToggleItem,
+            // Toggle the last two lines, since they're not all toggled.
+            Event::ToggleVisualMode,
+            Event::FocusNext,
+            Event::ToggleItem,
+            // Visual mode should have ended, so only the last line is toggled.
+            Event::ToggleItem,
+            screenshot.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let state = example_contents();
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    let state = recorder.run()?;
+    insta::assert_display_snapshot!(screenshot, @r###"
+    "       20 this is some text                                                     "
+    "  [~] Section 1/1                                                               "
+    "    [ ] - before text 1                                                         "
+    "    [ ] - before text 2                                                         "
+    "    [×] + after text 1                                                          "
+    "    ( ) + after text 2                                                          "
+    "###);
+
+    let is_toggled = |file: &File| -> Vec<bool> {
+        file.sections
+            .iter()
+            .flat_map(|section| match section {
+                Section::Changed { lines } => lines.iter().map(|line| line.is_toggled).collect(),
+                _ => Vec::new(),
+            })
+            .collect()
+    };
+    assert_eq!(is_toggled(&state.files[0]), vec![false, false, true, false]);
+    Ok(())
+}
+
+#[test]
+fn test_focus_changed_sections() -> eyre::Result<()> {
+    let first_section = TestingScreenshot::default();
+    let second_section = TestingScreenshot::default();
+    let back_to_first_section = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        80,
+        6,
+        [
+            Event::FocusNextSection,
+            first_section.event(),
+            Event::FocusNextSection,
+            second_section.event(),
+            // There are no more sections, so this should do nothing.
+            Event::FocusNextSection,
+            Event::FocusPrevSection,
+            back_to_first_section.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let state = example_contents();
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    recorder.run()?;
+    insta::assert_display_snapshot!(first_section, @r###"
+    "       20 this is some text                                                     "
+    "  (~) Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "    [×] - before text 2                                                         "
+    "    [×] + after text 1                                                          "
+    "    [ ] + after text 2                                                          "
+    "###);
+    insta::assert_display_snapshot!(second_section, @r###"
+    "        2 Some leading text 2                                                   "
+    "  (×) Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "    [×] - before text 2                                                         "
+    "    [×] + after text 1                                                          "
+    "    [×] + after text 2                                                          "
+    "###);
+    insta::assert_display_snapshot!(back_to_first_section, @r###"
+    "  (~) Section 1/1                                                               "
+    "    [×] - before text 1                                                         "
+    "    [×] - before text 2                                                         "
+    "    [×] + after text 1                                                          "
+    "    [ ] + after text 2                                                          "
+    "       23 this is some trailing text                                            "
+    "###);
+    Ok(())
+}
+
+#[test]
+fn test_file_mode_change() -> eyre::Result<()> {
+    let state = RecordState {
+        files: vec![File {
+            path: Cow::Borrowed(Path::new("foo")),
+            file_mode: Some(0o100644),
+            sections: vec![
+                Section::FileMode {
+                    is_toggled: false,
+                    before: 0o100644,
+                    after: 0o100755,
+                },
+                Section::Changed {
+                    lines: vec![SectionChangedLine {
+                        is_toggled: false,
+                        change_type: ChangeType::Added,
+                        line: Cow::Borrowed("hello"),
+                    }],
+                },
+            ],
+        }],
+    };
+    assert_eq!(state.files[0].get_file_mode(), Some(0o100644));
+
+    let screenshot = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        80,
+        5,
+        [
+            Event::FocusNext,
+            Event::FocusNext,
+            Event::ToggleItem,
+            screenshot.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    let state = recorder.run()?;
+    insta::assert_display_snapshot!(screenshot, @r###"
+    "[~] foo                                                                         "
+    "  [×] Section 1/2                                                               "
+    "    (×) File mode changed from 100644 to 100755                                 "
+    "  [ ] Section 2/2                                                               "
+    "    [ ] + hello                                                                 "
+    "###);
+
+    let file = &state.files[0];
+    assert_eq!(file.get_file_mode(), Some(0o100755));
+    assert_eq!(
+        file.get_selected_contents(),
+        (
+            SelectedContents::Present {
+                contents: "".to_string()
+            },
+            SelectedContents::Present {
+                contents: "hello".to_string()
+            }
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_binary_file() -> eyre::Result<()> {
+    let state = RecordState {
+        files: vec![File::binary(
+            Cow::Borrowed(Path::new("foo")),
+            Some(Cow::Borrowed("abc123 (4 bytes)")),
+            Some(Cow::Borrowed("def456 (8 bytes)")),
+        )],
+    };
+    let initial = TestingScreenshot::default();
+    let toggled = TestingScreenshot::default();
+    let event_source = EventSource::testing(
+        80,
+        3,
+        [
+            initial.event(),
+            Event::FocusNext,
+            Event::FocusNext,
+            Event::ToggleItem,
+            toggled.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
+    let state = recorder.run()?;
+    insta::assert_display_snapshot!(initial, @r###"
+    "( ) foo                                                                         "
+    "  [ ] Section 1/1                                                               "
+    "    [ ] Binary file changed (abc123 (4 bytes) -> def456 (8 bytes))              "
+    "###);
+    insta::assert_display_snapshot!(toggled, @r###"
+    "[×] foo                                                                         "
+    "  [×] Section 1/1                                                               "
+    "    (×) Binary file changed (abc123 (4 bytes) -> def456 (8 bytes))              "
+    "###);
+
+    let file = &state.files[0];
+    assert_eq!(
+        file.get_selected_contents(),
+        (
+            SelectedContents::Binary {
+                old_description: Some(Cow::Borrowed("abc123 (4 bytes)")),
+                new_description: Some(Cow::Borrowed("def456 (8 bytes)")),
+            },
+            SelectedContents::Unchanged,
+        )
+    );
+    Ok(())
+}
+
 #[test]
 fn test_enter_next() -> eyre::Result<()> {
     let state = RecordState {
@@ -422,7 +934,7 @@ fn test_enter_next() -> eyre::Result<()> {
             Event::ToggleItemAndAdvance,
         ],
     );
-    let recorder = Recorder::new(state, event_source);
+    let recorder = Recorder::new(state, event_source, KeyBindings::default());
     assert_matches!(recorder.run(), Err(RecordError::Cancelled));
     insta::assert_display_snapshot!(first_file_selected, @r###"
     "[×] foo                                                                         "